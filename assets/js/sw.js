@@ -0,0 +1,184 @@
+// Service worker of the app. It keeps the assets and the last version of
+// the pages for offline use, and queues the tasks created, toggled or
+// deleted while offline, which are sent to `/api/v1/sync` once the
+// connection is back. Every change gets an id, so sending it twice
+// (e.g. when the response is lost) applies it once.
+
+const CACHE = "todo-app-v1";
+// The queue is kept as a JSON response of the cache
+const QUEUE_URL = "/offline-queue";
+const SYNC_TAG = "sync-todos";
+
+self.addEventListener("install", () => self.skipWaiting());
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches
+            .keys()
+            .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+            .then(() => self.clients.claim())
+            .then(flush),
+    );
+});
+
+self.addEventListener("fetch", (event) => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (url.origin !== location.origin || url.pathname.startsWith("/api/")) return;
+
+    if (request.method === "GET") {
+        if (url.pathname.startsWith("/assets/")) {
+            event.respondWith(cacheFirst(request));
+        } else if (request.mode === "navigate") {
+            event.respondWith(networkFirst(request));
+        }
+        return;
+    }
+
+    // The pages of another user mustn't be shown offline
+    if (url.pathname === "/logout") {
+        event.respondWith(caches.delete(CACHE).then(() => fetch(request)));
+        return;
+    }
+
+    const mutation = offlineMutation(request.method, url.pathname);
+    if (mutation) {
+        const body = request.clone();
+        event.respondWith(
+            fetch(request).catch(async () => {
+                const form = new URLSearchParams(await body.text());
+                await enqueue(mutation(form));
+                await self.registration.sync?.register(SYNC_TAG).catch(() => {});
+                return offlineResponse(request.method === "DELETE");
+            }),
+        );
+    }
+});
+
+self.addEventListener("sync", (event) => {
+    if (event.tag === SYNC_TAG) event.waitUntil(flush());
+});
+
+// Sent by the pages when the browser is back online
+self.addEventListener("message", (event) => {
+    if (event.data === "sync") event.waitUntil(flush());
+});
+
+async function cacheFirst(request) {
+    const cached = await caches.match(request);
+    if (cached) return cached;
+
+    const response = await fetch(request);
+    if (response.ok) {
+        const cache = await caches.open(CACHE);
+        await cache.put(request, response.clone());
+    }
+    return response;
+}
+
+async function networkFirst(request) {
+    try {
+        const response = await fetch(request);
+        if (response.ok) {
+            const cache = await caches.open(CACHE);
+            await cache.put(request, response.clone());
+        }
+        return response;
+    } catch (error) {
+        const cached = await caches.match(request);
+        if (cached) return cached;
+        throw error;
+    }
+}
+
+// The change made by a request that can be queued, from its form fields
+function offlineMutation(method, pathname) {
+    const id = crypto.randomUUID();
+
+    if (method === "POST" && pathname === "/create") {
+        return (form) => ({
+            type: "create",
+            id,
+            title: form.get("title") ?? "",
+            description: form.get("description") ?? "",
+        });
+    }
+    if (method === "POST" && pathname === "/todo/quick-add") {
+        return (form) => ({ type: "quick_add", id, text: form.get("text") ?? "" });
+    }
+
+    const toggle = pathname.match(/^\/todo\/(\d+)\/toggle$/);
+    if (method === "PATCH" && toggle) {
+        return () => ({ type: "toggle", id, todo: Number(toggle[1]) });
+    }
+    const remove = pathname.match(/^\/todo\/(\d+)\/delete$/);
+    if (method === "DELETE" && remove) {
+        return () => ({ type: "delete", id, todo: Number(remove[1]) });
+    }
+
+    return null;
+}
+
+// Tells htmx to leave the page as it is (or to remove the row of a
+// deleted task) and shows a toast
+function offlineResponse(removesRow) {
+    const trigger = {
+        toast: { level: "info", text: "You are offline, the change will be saved once you are back online" },
+    };
+    const headers = { "HX-Trigger": JSON.stringify(trigger) };
+    if (!removesRow) headers["HX-Reswap"] = "none";
+
+    return new Response("", { status: 200, headers });
+}
+
+async function readQueue() {
+    const response = await caches.match(QUEUE_URL);
+    return response ? response.json() : [];
+}
+
+async function writeQueue(queue) {
+    const cache = await caches.open(CACHE);
+    await cache.put(QUEUE_URL, new Response(JSON.stringify(queue), { headers: { "Content-Type": "application/json" } }));
+}
+
+async function enqueue(mutation) {
+    const queue = await readQueue();
+    queue.push(mutation);
+    await writeQueue(queue);
+}
+
+// Only one flush at a time, the changes queued meanwhile go in the next one
+let flushing = null;
+
+function flush() {
+    flushing ??= sendQueue().finally(() => (flushing = null));
+    return flushing;
+}
+
+async function sendQueue() {
+    const queue = await readQueue();
+    if (queue.length === 0) return;
+
+    let results;
+    try {
+        const response = await fetch("/api/v1/sync", {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ mutations: queue }),
+        });
+        ({ results } = await response.json());
+    } catch {
+        // Still offline, or logged out: kept for the next time
+        return;
+    }
+
+    // The failed ones are sent again later
+    const done = new Set(results.filter((result) => result.status !== "failed").map((result) => result.id));
+    const remaining = (await readQueue()).filter((mutation) => !done.has(mutation.id));
+    await writeQueue(remaining);
+
+    const clients = await self.clients.matchAll({ type: "window" });
+    for (const client of clients) {
+        client.postMessage({ type: "synced", results });
+    }
+}
@@ -0,0 +1,81 @@
+//! Timings of the hot paths of the todo list, on a workspace with 10k
+//! todos: `cargo bench`. Compare the results before and after a change
+//! to pagination, caching or the queries.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rust_axum_askama_htmx::{
+    cli, db,
+    db::DbPools,
+    events::EventBus,
+    model::{PageStart, TodoFilter},
+    repo::{SqlRepo, TodoRepo},
+};
+use sqlx::sqlite::SqlitePoolOptions;
+use tokio::runtime::Runtime;
+
+/// Todos of the workspace being measured.
+const TODOS: u32 = 10_000;
+
+/// Same page size as the todo list.
+const PAGE_SIZE: i64 = 50;
+
+fn service(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    let (repo, workspace_id, user_id) = rt.block_on(async {
+        // A single connection that is never closed, as every
+        // connection to `sqlite::memory:` is a different database
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        db::migrate(&pool).await.unwrap();
+        let workspace_id = cli::seed_load(TODOS, &pool).await.unwrap();
+        let user_id: String = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+            .bind("load@localhost")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let repo = SqlRepo::new(DbPools::single(pool), EventBus::default());
+
+        (repo, workspace_id, user_id)
+    });
+
+    c.bench_function("get_all_todos (first page of 10k)", |b| {
+        b.to_async(&rt)
+            .iter(|| repo.get_all_todos(workspace_id, "", PageStart::First, PAGE_SIZE))
+    });
+
+    let filter = TodoFilter {
+        q: "report".to_string(),
+        tag: "work".to_string(),
+        status: "open".to_string(),
+        ..Default::default()
+    };
+    c.bench_function("get_filtered_todos (search in 10k)", |b| {
+        b.to_async(&rt)
+            .iter(|| repo.get_filtered_todos(workspace_id, &filter))
+    });
+
+    c.bench_function("add_todo", |b| {
+        b.to_async(&rt).iter(|| {
+            repo.add_todo(
+                user_id.clone(),
+                workspace_id,
+                "Write the weekly report".to_string(),
+                "Check the notes from last week".to_string(),
+                None,
+                1,
+                "work".to_string(),
+            )
+        })
+    });
+}
+
+criterion_group!(benches, service);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+use std::error::Error;
+
+use vergen::EmitBuilder;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Rebuild when a migration or an asset changes, as they are
+    // embedded with `sqlx::migrate!` and `rust-embed`
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-changed=assets");
+
+    // The commit and the time of the build, reported by `/healthchecker`
+    // and the footer. Outside of a git checkout the SHA is a placeholder
+    EmitBuilder::builder()
+        .build_timestamp()
+        .git_sha(true)
+        .emit()?;
+
+    Ok(())
+}
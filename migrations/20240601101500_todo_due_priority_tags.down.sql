@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+ALTER TABLE todos DROP COLUMN tags;
+
+ALTER TABLE todos DROP COLUMN priority;
+
+ALTER TABLE todos DROP COLUMN due_at;
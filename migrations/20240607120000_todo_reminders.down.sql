@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE todos DROP COLUMN reminder_sent_at;
+
+ALTER TABLE todos DROP COLUMN remind_at;
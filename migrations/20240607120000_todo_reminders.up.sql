@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+ALTER TABLE todos ADD COLUMN remind_at DATETIME;
+
+ALTER TABLE todos ADD COLUMN reminder_sent_at DATETIME;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "time_entries" (
+		id INTEGER PRIMARY KEY AUTOINCREMENT,
+		todo_id INTEGER NOT NULL,
+		user_id TEXT NOT NULL,
+		started_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		ended_at DATETIME,
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE,
+		FOREIGN KEY(user_id) REFERENCES users(id)
+    );
+
+CREATE INDEX time_entries_user_id_idx ON time_entries (user_id);
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "todo_versions" (
+		id INTEGER PRIMARY KEY NOT NULL,
+		todo_id INTEGER NOT NULL,
+		title VARCHAR(64) NOT NULL,
+		description VARCHAR(255) NOT NULL,
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX todo_versions_todo_id_idx ON todo_versions (todo_id);
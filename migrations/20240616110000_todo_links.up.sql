@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "todo_links" (
+		id INTEGER PRIMARY KEY NOT NULL,
+		todo_id INTEGER NOT NULL,
+		url VARCHAR(2048) NOT NULL,
+		title VARCHAR(255),
+		description VARCHAR(255),
+		fetched_at TIMESTAMP,
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX todo_links_todo_id_idx ON todo_links (todo_id);
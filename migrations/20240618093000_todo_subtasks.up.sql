@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "todo_subtasks" (
+		id INTEGER PRIMARY KEY NOT NULL,
+		todo_id INTEGER NOT NULL,
+		title VARCHAR(64) NOT NULL,
+		done BOOLEAN NOT NULL DEFAULT FALSE,
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX todo_subtasks_todo_id_idx ON todo_subtasks (todo_id);
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+ALTER TABLE users ADD COLUMN date_order TEXT;
+ALTER TABLE users ADD COLUMN clock TEXT;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "jobs" (
+		name VARCHAR(64) PRIMARY KEY NOT NULL,
+		next_run_at DATETIME NOT NULL,
+		last_started_at DATETIME,
+		last_finished_at DATETIME,
+		last_error TEXT
+    );
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+ALTER TABLE users ADD COLUMN email_verified_at DATETIME;
+
+CREATE TABLE
+    IF NOT EXISTS "user_tokens" (
+		token TEXT PRIMARY KEY NOT NULL,
+		user_id TEXT NOT NULL,
+		kind VARCHAR(16) NOT NULL,
+		expires_at DATETIME NOT NULL,
+		used_at DATETIME,
+		FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX user_tokens_user_id_idx ON user_tokens (user_id);
@@ -0,0 +1,11 @@
+-- Add down migration script here
+
+DROP INDEX todos_workspace_id_status_idx;
+
+DROP INDEX todos_workspace_id_created_at_idx;
+
+ALTER TABLE todos DROP COLUMN workspace_id;
+
+DROP TABLE IF EXISTS workspace_members;
+
+DROP TABLE IF EXISTS workspaces;
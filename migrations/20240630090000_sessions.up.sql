@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+-- Sessions of `SESSION_STORE=sqlite`, as the session store expects them
+CREATE TABLE
+    IF NOT EXISTS "tower_sessions" (
+		id TEXT PRIMARY KEY NOT NULL,
+		data BLOB NOT NULL,
+		expiry_date INTEGER NOT NULL
+    );
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE todos DROP COLUMN completed_at;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- When the todo was marked as done (NULL while it is open)
+ALTER TABLE todos ADD COLUMN completed_at DATETIME;
@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP INDEX audit_log_user_id_idx;
+
+DROP INDEX audit_log_created_at_idx;
+
+DROP TABLE IF EXISTS audit_log;
+
+ALTER TABLE users DROP COLUMN is_admin;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Address of the client of the request that made the event
+ALTER TABLE audit_log ADD COLUMN client_ip TEXT;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP INDEX notifications_user_id_idx;
+
+DROP TABLE IF EXISTS notifications;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- In-app notifications (workspace invites, reminders, mentions)
+CREATE TABLE
+    IF NOT EXISTS "notifications" (
+		id INTEGER PRIMARY KEY NOT NULL,
+		user_id TEXT NOT NULL,
+		kind TEXT NOT NULL,
+		message TEXT NOT NULL,
+		link TEXT NOT NULL,
+		-- The todo it is about, if any
+		todo_id INTEGER,
+		read_at DATETIME,
+		created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX notifications_user_id_idx ON notifications (user_id, created_at);
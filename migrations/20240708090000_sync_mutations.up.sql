@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+-- Ids given by the clients to the changes they made offline, so a change
+-- sent again on reconnect (e.g. after a lost response) is applied once
+CREATE TABLE
+    IF NOT EXISTS "sync_mutations" (
+		user_id TEXT NOT NULL,
+		client_id TEXT NOT NULL,
+		-- The todo it created or changed, once applied
+		todo_id INTEGER,
+		created_at DATETIME NOT NULL,
+		PRIMARY KEY (user_id, client_id),
+		FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX sync_mutations_created_at_idx ON sync_mutations (created_at);
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS todos_fts_insert;
+DROP TRIGGER IF EXISTS todos_fts_delete;
+DROP TRIGGER IF EXISTS todos_fts_update;
+DROP TABLE IF EXISTS "todos_fts";
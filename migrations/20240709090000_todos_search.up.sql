@@ -0,0 +1,29 @@
+-- Add up migration script here
+
+-- Full-text index of the titles and descriptions of the todos, for the
+-- search as you type. The triggers keep it in sync with the table
+CREATE VIRTUAL TABLE IF NOT EXISTS "todos_fts" USING fts5 (
+    title,
+    description,
+    content = 'todos',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER todos_fts_insert AFTER INSERT ON todos BEGIN
+    INSERT INTO todos_fts (rowid, title, description) VALUES (new.id, new.title, new.description);
+END;
+
+CREATE TRIGGER todos_fts_delete AFTER DELETE ON todos BEGIN
+    INSERT INTO todos_fts (todos_fts, rowid, title, description)
+    VALUES ('delete', old.id, old.title, old.description);
+END;
+
+CREATE TRIGGER todos_fts_update AFTER UPDATE OF title, description ON todos BEGIN
+    INSERT INTO todos_fts (todos_fts, rowid, title, description)
+    VALUES ('delete', old.id, old.title, old.description);
+    INSERT INTO todos_fts (rowid, title, description) VALUES (new.id, new.title, new.description);
+END;
+
+-- Indexes the existing todos
+INSERT INTO todos_fts (todos_fts) VALUES ('rebuild');
@@ -0,0 +1,32 @@
+-- Add up migration script here
+
+-- Preferences of the users, moved out of their table. The users without
+-- a row (or the settings left NULL) have the default ones
+CREATE TABLE
+    IF NOT EXISTS "user_settings" (
+		user_id TEXT PRIMARY KEY NOT NULL,
+		theme TEXT,
+		timezone TEXT,
+		date_order TEXT,
+		clock TEXT,
+		-- Todos loaded at once in the list
+		page_size INTEGER,
+		-- Sort of the todo list when none is chosen
+		default_sort TEXT,
+		-- How often the user gets the digest of their todos by email, NULL for never
+		digest TEXT,
+		FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX user_settings_digest_idx ON user_settings (digest) WHERE digest IS NOT NULL;
+
+INSERT INTO user_settings (user_id, theme, timezone, date_order, clock, digest)
+SELECT id, theme, timezone, date_order, clock, digest FROM users
+WHERE theme IS NOT NULL OR timezone IS NOT NULL OR date_order IS NOT NULL
+    OR clock IS NOT NULL OR digest IS NOT NULL;
+
+ALTER TABLE users DROP COLUMN theme;
+ALTER TABLE users DROP COLUMN timezone;
+ALTER TABLE users DROP COLUMN date_order;
+ALTER TABLE users DROP COLUMN clock;
+ALTER TABLE users DROP COLUMN digest;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Spacing of the rows of the todo list, `comfortable` when NULL
+ALTER TABLE user_settings ADD COLUMN density TEXT;
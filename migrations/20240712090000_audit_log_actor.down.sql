@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE audit_log DROP COLUMN actor_email;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS todos;
+
+DROP TABLE IF EXISTS users;
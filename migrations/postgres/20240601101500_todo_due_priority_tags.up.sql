@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+ALTER TABLE todos ADD COLUMN due_at TIMESTAMP;
+
+ALTER TABLE todos ADD COLUMN priority BIGINT NOT NULL DEFAULT(0);
+
+ALTER TABLE todos ADD COLUMN tags TEXT NOT NULL DEFAULT('');
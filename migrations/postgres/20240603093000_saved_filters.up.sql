@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "saved_filters" (
+		id BIGSERIAL PRIMARY KEY,
+		user_id TEXT NOT NULL,
+		name TEXT NOT NULL,
+		query TEXT NOT NULL DEFAULT(''),
+		tag TEXT NOT NULL DEFAULT(''),
+		status TEXT NOT NULL DEFAULT(''),
+		sort TEXT NOT NULL DEFAULT(''),
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(user_id) REFERENCES users(id),
+		UNIQUE(user_id, name)
+    );
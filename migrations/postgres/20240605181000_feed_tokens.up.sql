@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "feed_tokens" (
+		user_id TEXT PRIMARY KEY NOT NULL,
+		token TEXT NOT NULL UNIQUE,
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(user_id) REFERENCES users(id)
+    );
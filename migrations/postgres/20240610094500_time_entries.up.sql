@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "time_entries" (
+		id BIGSERIAL PRIMARY KEY,
+		todo_id BIGINT NOT NULL,
+		user_id TEXT NOT NULL,
+		started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		ended_at TIMESTAMP,
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE,
+		FOREIGN KEY(user_id) REFERENCES users(id)
+    );
+
+CREATE INDEX time_entries_user_id_idx ON time_entries (user_id);
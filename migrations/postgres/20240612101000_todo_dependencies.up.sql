@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "todo_dependencies" (
+		todo_id BIGINT NOT NULL,
+		blocked_by_id BIGINT NOT NULL,
+		PRIMARY KEY(todo_id, blocked_by_id),
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE,
+		FOREIGN KEY(blocked_by_id) REFERENCES todos(id) ON DELETE CASCADE,
+		CHECK(todo_id != blocked_by_id)
+    );
+
+CREATE INDEX todo_dependencies_blocked_by_id_idx ON todo_dependencies (blocked_by_id);
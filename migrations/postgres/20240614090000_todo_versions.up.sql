@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "todo_versions" (
+		id BIGSERIAL PRIMARY KEY,
+		todo_id BIGINT NOT NULL,
+		title TEXT NOT NULL,
+		description TEXT NOT NULL,
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX todo_versions_todo_id_idx ON todo_versions (todo_id);
@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "jobs" (
+		name VARCHAR(64) PRIMARY KEY NOT NULL,
+		next_run_at TIMESTAMP NOT NULL,
+		last_started_at TIMESTAMP,
+		last_finished_at TIMESTAMP,
+		last_error TEXT
+    );
@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+CREATE INDEX users_email_idx ON users (email);
+
+DROP INDEX todos_created_by_status_idx;
+
+DROP INDEX todos_created_by_created_at_idx;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+-- The todo list of a user, newest first, and its pages
+CREATE INDEX todos_created_by_created_at_idx ON todos (created_by, created_at);
+
+-- The counters of the stats and the status filter
+CREATE INDEX todos_created_by_status_idx ON todos (created_by, status);
+
+-- The UNIQUE constraint of `users.email` already creates a unique index,
+-- which the lookups by email use, so this one only slowed down the writes
+DROP INDEX users_email_idx;
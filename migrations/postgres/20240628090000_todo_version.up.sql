@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Incremented on each edit, so an edit made on an outdated copy is refused
+ALTER TABLE todos ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
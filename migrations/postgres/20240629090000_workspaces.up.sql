@@ -0,0 +1,43 @@
+-- Add up migration script here
+
+CREATE TABLE
+    IF NOT EXISTS "workspaces" (
+		id BIGSERIAL PRIMARY KEY,
+		name TEXT NOT NULL,
+		created_by TEXT NOT NULL,
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(created_by) REFERENCES users(id)
+    );
+
+CREATE TABLE
+    IF NOT EXISTS "workspace_members" (
+		workspace_id BIGINT NOT NULL,
+		user_id TEXT NOT NULL,
+		role TEXT NOT NULL DEFAULT('member'),
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		PRIMARY KEY(workspace_id, user_id),
+		FOREIGN KEY(workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE,
+		FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    );
+
+CREATE INDEX workspace_members_user_id_idx ON workspace_members (user_id);
+
+ALTER TABLE todos ADD COLUMN workspace_id BIGINT REFERENCES workspaces(id);
+
+-- Every existing user gets a workspace of their own, owning their todos
+INSERT INTO workspaces (name, created_by)
+SELECT username || '''s workspace', id FROM users;
+
+INSERT INTO workspace_members (workspace_id, user_id, role)
+SELECT id, created_by, 'owner' FROM workspaces;
+
+UPDATE todos SET workspace_id = (
+    SELECT workspaces.id FROM workspaces WHERE workspaces.created_by = todos.created_by
+);
+
+ALTER TABLE todos ALTER COLUMN workspace_id SET NOT NULL;
+
+-- The lists are now the todos of a workspace
+CREATE INDEX todos_workspace_id_created_at_idx ON todos (workspace_id, created_at);
+
+CREATE INDEX todos_workspace_id_status_idx ON todos (workspace_id, status);
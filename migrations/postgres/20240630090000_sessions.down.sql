@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DROP SCHEMA IF EXISTS "tower_sessions" CASCADE;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+
+-- Sessions of `SESSION_STORE=postgres`, as the session store expects them
+CREATE SCHEMA IF NOT EXISTS "tower_sessions";
+
+CREATE TABLE
+    IF NOT EXISTS "tower_sessions"."session" (
+		id TEXT PRIMARY KEY NOT NULL,
+		data BYTEA NOT NULL,
+		expiry_date TIMESTAMPTZ NOT NULL
+    );
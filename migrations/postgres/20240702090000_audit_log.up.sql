@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- Admins can search the audit log
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE
+    IF NOT EXISTS "audit_log" (
+		id BIGSERIAL PRIMARY KEY,
+		-- NULL for the failed logins of unknown emails
+		user_id TEXT,
+		email TEXT NOT NULL,
+		event TEXT NOT NULL,
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE SET NULL
+    );
+
+CREATE INDEX audit_log_created_at_idx ON audit_log (created_at);
+
+CREATE INDEX audit_log_user_id_idx ON audit_log (user_id, created_at);
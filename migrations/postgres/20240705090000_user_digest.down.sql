@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE users DROP COLUMN digest_sent_at;
+ALTER TABLE users DROP COLUMN digest;
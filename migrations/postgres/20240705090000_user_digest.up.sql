@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- How often the user gets the digest of their todos by email, NULL for never
+ALTER TABLE users ADD COLUMN digest TEXT;
+ALTER TABLE users ADD COLUMN digest_sent_at TIMESTAMP;
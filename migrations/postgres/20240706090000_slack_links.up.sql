@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+-- Slack users linked to an account, for the slash command
+CREATE TABLE
+    IF NOT EXISTS "slack_links" (
+		team_id TEXT NOT NULL,
+		slack_user_id TEXT NOT NULL,
+		user_id TEXT NOT NULL,
+		created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+		PRIMARY KEY (team_id, slack_user_id),
+		FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+    );
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+-- Names and UIDs given by the CalDAV clients to the todos they create,
+-- so they find them again under the same URL
+CREATE TABLE
+    IF NOT EXISTS "dav_resources" (
+		todo_id BIGINT PRIMARY KEY NOT NULL,
+		workspace_id BIGINT NOT NULL,
+		name TEXT NOT NULL,
+		uid TEXT NOT NULL,
+		UNIQUE (workspace_id, name),
+		FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+    );
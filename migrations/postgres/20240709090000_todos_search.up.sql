@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- Full-text index of the titles and descriptions of the todos, for the
+-- search as you type. The queries must use the same expression
+CREATE INDEX IF NOT EXISTS todos_search_idx ON todos
+USING GIN (to_tsvector('simple', title || ' ' || description));
@@ -0,0 +1,16 @@
+-- Add down migration script here
+
+ALTER TABLE users ADD COLUMN theme TEXT;
+ALTER TABLE users ADD COLUMN timezone TEXT;
+ALTER TABLE users ADD COLUMN date_order TEXT;
+ALTER TABLE users ADD COLUMN clock TEXT;
+ALTER TABLE users ADD COLUMN digest TEXT;
+
+UPDATE users SET
+    theme = (SELECT theme FROM user_settings WHERE user_id = users.id),
+    timezone = (SELECT timezone FROM user_settings WHERE user_id = users.id),
+    date_order = (SELECT date_order FROM user_settings WHERE user_id = users.id),
+    clock = (SELECT clock FROM user_settings WHERE user_id = users.id),
+    digest = (SELECT digest FROM user_settings WHERE user_id = users.id);
+
+DROP TABLE IF EXISTS user_settings;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+ALTER TABLE user_settings DROP COLUMN density;
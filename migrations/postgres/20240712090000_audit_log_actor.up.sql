@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- Email of the admin who made the event while impersonating the user
+ALTER TABLE audit_log ADD COLUMN actor_email TEXT;
@@ -0,0 +1,152 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use axum::{
+    extract::{Path, Request},
+    http::{header, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rust_embed::RustEmbed;
+
+/// Static assets, embedded in the binary so it can be deployed
+/// as a single file. Debug builds read them from disk instead,
+/// so changes to the CSS show up without recompiling.
+#[derive(RustEmbed)]
+#[folder = "assets/"]
+struct Embedded;
+
+/// Prefix of the URLs of the static assets.
+const PREFIX: &str = "/assets/";
+
+/// Versioned URLs never change their content, so they can be cached
+/// for good. Unversioned ones must be revalidated with the ETag.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+const REVALIDATE: &str = "no-cache";
+
+/// Content hashes of the assets, by path relative to the assets folder.
+static HASHES: OnceLock<HashMap<String, Hashes>> = OnceLock::new();
+
+/// The SHA-256 of an asset, as used in its URL and its `integrity` attribute.
+struct Hashes {
+    /// First 8 bytes, in hex.
+    version: String,
+    /// `sha256-` followed by the whole hash, in base64.
+    integrity: String,
+}
+
+fn hashes_of(name: &str) -> Option<&'static Hashes> {
+    let hashes = HASHES.get_or_init(|| {
+        Embedded::iter()
+            .filter_map(|name| {
+                let file = Embedded::get(&name)?;
+                let sha256 = file.metadata.sha256_hash();
+                let hashes = Hashes {
+                    version: sha256
+                        .iter()
+                        .take(8)
+                        .map(|byte| format!("{:02x}", byte))
+                        .collect(),
+                    integrity: format!("sha256-{}", STANDARD.encode(sha256)),
+                };
+
+                Some((name.into_owned(), hashes))
+            })
+            .collect()
+    });
+
+    hashes.get(name)
+}
+
+fn hash_of(name: &str) -> Option<&'static str> {
+    hashes_of(name).map(|hashes| hashes.version.as_str())
+}
+
+/// URL of an asset with the hash of its content as query,
+/// e.g. `/assets/css/main.css?v=3f2a…`. Unknown assets are left as they are.
+pub fn url(path: &str) -> String {
+    let hash = path.strip_prefix(PREFIX).and_then(hash_of);
+
+    match hash {
+        Some(hash) => format!("{}?v={}", path, hash),
+        None => path.to_string(),
+    }
+}
+
+/// Subresource integrity of an asset, e.g. `sha256-…`, so browsers
+/// refuse a script or a stylesheet that isn't the one bundled with
+/// the app. Empty for unknown assets.
+pub fn integrity(path: &str) -> String {
+    path.strip_prefix(PREFIX)
+        .and_then(hashes_of)
+        .map(|hashes| hashes.integrity.clone())
+        .unwrap_or_default()
+}
+
+/// Service of the embedded assets, to be nested under `/assets`.
+pub fn service() -> Router {
+    Router::new()
+        .route("/*path", get(serve_embedded))
+        .layer(middleware::from_fn(cache_middleware))
+}
+
+/// Handler that serves an embedded asset.
+async fn serve_embedded(Path(path): Path<String>) -> Response {
+    match Embedded::get(&path) {
+        Some(file) => (
+            [(header::CONTENT_TYPE, file.metadata.mimetype().to_string())],
+            file.data,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Handler of `/sw.js`, the service worker, served from the root
+/// so that its scope is the whole app. Always revalidated, as its
+/// URL can't carry the hash.
+pub async fn service_worker() -> Response {
+    let mut response = serve_embedded(Path("js/sw.js".to_string())).await;
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static(REVALIDATE));
+
+    response
+}
+
+/// Middleware of the assets service that sets the `Cache-Control` and
+/// `ETag` headers, and answers `304` when the browser has the same file.
+async fn cache_middleware(req: Request, next: Next) -> Response {
+    // The path is relative to `/assets`, as the service is nested there
+    let Some(hash) = hash_of(req.uri().path().trim_start_matches('/')) else {
+        return next.run(req).await;
+    };
+
+    let etag = HeaderValue::from_str(&format!("\"{}\"", hash)).unwrap();
+    let versioned = req
+        .uri()
+        .query()
+        .is_some_and(|query| query == format!("v={}", hash));
+    let cache_control = HeaderValue::from_static(if versioned { IMMUTABLE } else { REVALIDATE });
+
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|value| value == etag);
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        next.run(req).await
+    };
+
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        headers.insert(header::ETAG, etag);
+        headers.insert(header::CACHE_CONTROL, cache_control);
+    }
+
+    response
+}
@@ -0,0 +1,136 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use tracing::info;
+
+use crate::AppState;
+
+/// Names of the backup files, sorted by date: `backup-<date>.db`.
+const PREFIX: &str = "backup-";
+const EXTENSION: &str = ".db";
+
+/// A copy of the database in `BACKUP_DIR`.
+#[derive(Clone, Debug)]
+pub struct Backup {
+    pub name: String,
+    pub size: u64,
+    pub created_at: NaiveDateTime,
+}
+
+/// Backs up the database every `BACKUP_INTERVAL_HOURS` (a background
+/// job), when `BACKUP_DIR` is set.
+pub async fn scheduled(state: Arc<AppState>) -> Result<()> {
+    back_up(&state).await.map(|_| ())
+}
+
+/// Copies the database to a new file of `BACKUP_DIR` with `VACUUM INTO`,
+/// which takes a consistent snapshot without blocking the writes, then
+/// deletes the oldest backups beyond `BACKUP_KEEP`.
+pub async fn back_up(state: &AppState) -> Result<Backup> {
+    let dir = state
+        .config
+        .backup_dir
+        .as_deref()
+        .ok_or_else(|| anyhow!("the backups are disabled, BACKUP_DIR isn't set"))?;
+
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let name = format!(
+        "{}{}{}",
+        PREFIX,
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        EXTENSION
+    );
+    let path = dir.join(&name);
+
+    // Written under another name first, so a failed backup is never listed
+    let partial = dir.join(format!("{}.partial", name));
+    let _ = tokio::fs::remove_file(&partial).await;
+
+    sqlx::query("VACUUM INTO $1")
+        .bind(partial.to_string_lossy().into_owned())
+        .execute(&state.read_pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    tokio::fs::rename(&partial, &path)
+        .await
+        .with_context(|| format!("failed to move the backup to {}", path.display()))?;
+
+    let deleted = prune(dir, state.config.backup_keep).await?;
+
+    info!(deleted, "backed up the database to {}", path.display());
+
+    let backup = to_backup(name, &path).await?;
+
+    Ok(backup)
+}
+
+/// The backups of `BACKUP_DIR`, the newest first.
+pub async fn list_backups(dir: &Path) -> Result<Vec<Backup>> {
+    let mut backups = Vec::new();
+
+    for (name, path) in backup_files(dir).await?.into_iter().rev() {
+        backups.push(to_backup(name, &path).await?);
+    }
+
+    Ok(backups)
+}
+
+/// Deletes the oldest backups, keeping the last `keep`.
+/// Returns how many were deleted.
+async fn prune(dir: &Path, keep: usize) -> Result<usize> {
+    let files = backup_files(dir).await?;
+    let stale = files.len().saturating_sub(keep);
+
+    for (_, path) in &files[..stale] {
+        tokio::fs::remove_file(path)
+            .await
+            .with_context(|| format!("failed to delete {}", path.display()))?;
+    }
+
+    Ok(stale)
+}
+
+/// The backup files of `dir`, the oldest first.
+async fn backup_files(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+
+    let mut entries = match tokio::fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", dir.display())),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+
+        if name.starts_with(PREFIX) && name.ends_with(EXTENSION) {
+            files.push((name, entry.path()));
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// The size and date of the backup file at `path`.
+async fn to_backup(name: String, path: &Path) -> Result<Backup> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+
+    Ok(Backup {
+        name,
+        size: metadata.len(),
+        created_at: DateTime::<Utc>::from(modified).naive_utc(),
+    })
+}
@@ -0,0 +1,73 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use tower_sessions::ExpiredDeletion;
+use tracing::info;
+
+use crate::{
+    config::SessionStore,
+    service::{delete_stale_sync_mutations, delete_stale_user_tokens},
+    session, AppState,
+};
+
+/// How often the expired rows are deleted.
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long the ids of the changes synced by the clients are kept,
+/// longer than any client stays offline with changes to send.
+const SYNC_MUTATION_TTL: chrono::Duration = chrono::Duration::days(30);
+
+/// Rows deleted by `purge_expired` since the app started, exported
+/// as counters to Prometheus.
+#[derive(Debug, Default)]
+pub struct PurgedRows {
+    pub user_tokens: AtomicU64,
+    pub sync_mutations: AtomicU64,
+}
+
+/// Deletes the rows that are kept only until they expire (a background
+/// job), so the database doesn't grow without limit: the email tokens
+/// (verification and password reset) once used or expired, the ids of
+/// the changes synced by the offline clients once old, and the expired
+/// sessions when they are kept in the database. The JWTs aren't stored,
+/// and the other session stores expire them by themselves.
+pub async fn purge_expired(state: Arc<AppState>) -> Result<()> {
+    let now = Utc::now().naive_utc();
+
+    let user_tokens = delete_stale_user_tokens(now, &state.pool).await?;
+    let sync_mutations = delete_stale_sync_mutations(now - SYNC_MUTATION_TTL, &state.pool).await?;
+
+    if state.config.session_store == SessionStore::Database {
+        session::database_store(&state.pool)
+            .delete_expired()
+            .await
+            .context("failed to delete the expired sessions")?;
+    }
+
+    state
+        .purged
+        .user_tokens
+        .fetch_add(user_tokens, Ordering::Relaxed);
+    state
+        .purged
+        .sync_mutations
+        .fetch_add(sync_mutations, Ordering::Relaxed);
+
+    // Logged as fields, so the JSON logs can be aggregated as metrics
+    info!(
+        user_tokens,
+        sync_mutations,
+        "purged {} used or expired tokens and {} synced changes",
+        user_tokens,
+        sync_mutations
+    );
+
+    Ok(())
+}
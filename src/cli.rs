@@ -0,0 +1,335 @@
+use anyhow::{Context, Result};
+use chrono::{Duration, Utc};
+use clap::{Parser, Subcommand};
+use rand::{seq::SliceRandom, Rng};
+use uuid::Uuid;
+
+use crate::{
+    db::DbPool,
+    events::EventBus,
+    import::ImportedTodo,
+    model::User,
+    service::{
+        add_imported_todos, create_user, get_all_users, get_todo_stats, get_user_by_email,
+        get_user_workspaces, purge_orphaned_todos, set_user_admin, set_user_password,
+    },
+};
+
+/// Full stack Todo List app using Axum, Askama & HTMX.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Before starting the server, fill the workspace of `load@localhost`
+    /// (password demo1234) with N random todos, for load tests
+    #[arg(long, value_name = "N")]
+    pub seed_load: Option<u32>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the web server (default)
+    Serve,
+    /// Apply the pending database migrations and exit
+    Migrate,
+    /// Create a user account for the administrator, who can search the audit log
+    CreateAdmin {
+        email: String,
+        #[arg(long, default_value = "admin")]
+        username: String,
+        /// Password of the account; a random one is printed if missing
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Create demo users with random todos
+    Seed {
+        /// Number of demo users to create
+        #[arg(long, default_value_t = 1)]
+        users: u32,
+        /// Number of todos of each demo user
+        #[arg(long, default_value_t = 20)]
+        todos: u32,
+    },
+    /// Manage the user accounts
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// Maintenance of the todos
+    Todo {
+        #[command(subcommand)]
+        command: TodoCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// List the user accounts
+    List,
+    /// Set a new password for the account
+    ResetPassword {
+        email: String,
+        /// New password; a random one is printed if missing
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Make the account an administrator
+    Promote { email: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TodoCommand {
+    /// Delete the todos left behind by workspaces that no longer exist
+    PurgeDeleted,
+}
+
+const DEMO_PASSWORD: &str = "demo1234";
+
+/// Account whose workspace `--seed-load` fills.
+const LOAD_EMAIL: &str = "load@localhost";
+
+/// Todos inserted per transaction by `--seed-load`.
+const LOAD_BATCH_SIZE: u32 = 1000;
+
+const TITLES: &[&str] = &[
+    "Pay rent",
+    "Buy groceries",
+    "Read a book",
+    "Prepare the demo",
+    "Call the dentist",
+    "Renew the passport",
+    "Water the plants",
+    "Review the pull request",
+    "Book the flights",
+    "Clean the garage",
+    "Write the weekly report",
+    "Fix the bike",
+];
+const DESCRIPTIONS: &[&str] = &[
+    "",
+    "As soon as possible",
+    "Check the notes from last week",
+    "Ask for help if it takes too long",
+    "Milk, eggs and bread",
+];
+const TAGS: &[&str] = &["", "home", "work", "bills", "personal", "work,urgent"];
+
+pub async fn create_admin(
+    email: String,
+    username: String,
+    password: Option<String>,
+    pool: &DbPool,
+) -> Result<()> {
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+
+    // Nothing listens to the events of the CLI, no verification email is sent
+    let events = EventBus::default();
+
+    let user = create_user(
+        email.to_ascii_lowercase(),
+        password.clone(),
+        username,
+        &events,
+        pool,
+    )
+    .await?;
+
+    set_user_admin(&user.id, pool).await?;
+
+    println!("✅ Created the admin account {} ({})", user.email, user.id);
+    if generated {
+        println!("🔑 Password: {}", password);
+    }
+
+    Ok(())
+}
+
+/// Prints the user accounts, one per line.
+pub async fn list_users(pool: &DbPool) -> Result<()> {
+    let users = get_all_users(pool).await?;
+
+    for user in &users {
+        let mut flags = Vec::new();
+        if user.is_admin {
+            flags.push("admin");
+        }
+        if user.email_verified_at.is_none() {
+            flags.push("unverified");
+        }
+
+        println!(
+            "{}\t{}\t{}\t{}",
+            user.id,
+            user.email,
+            user.username,
+            flags.join(",")
+        );
+    }
+    println!("{} user(s)", users.len());
+
+    Ok(())
+}
+
+pub async fn reset_password(email: String, password: Option<String>, pool: &DbPool) -> Result<()> {
+    let user = find_user(&email, pool).await?;
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+
+    set_user_password(&user.id, &password, pool).await?;
+
+    println!("✅ Reset the password of {}", user.email);
+    if generated {
+        println!("🔑 Password: {}", password);
+    }
+
+    Ok(())
+}
+
+pub async fn promote(email: String, pool: &DbPool) -> Result<()> {
+    let user = find_user(&email, pool).await?;
+
+    set_user_admin(&user.id, pool).await?;
+
+    println!("✅ {} is now an admin", user.email);
+
+    Ok(())
+}
+
+pub async fn purge_deleted_todos(pool: &DbPool) -> Result<()> {
+    let count = purge_orphaned_todos(pool).await?;
+
+    println!("✅ Purged {} todo(s) of deleted workspaces", count);
+
+    Ok(())
+}
+
+/// The account with the email, an error if there is none.
+async fn find_user(email: &str, pool: &DbPool) -> Result<User> {
+    get_user_by_email(email, pool)
+        .await?
+        .with_context(|| format!("no user with the email {}", email))
+}
+
+/// Creates `users` demo users with `todos` random todos each. The first one
+/// is `demo@localhost` and the rest `demo2@localhost`, `demo3@localhost`...
+/// Users that already exist are left untouched, so seeding twice is harmless.
+pub async fn seed(users: u32, todos: u32, pool: &DbPool) -> Result<()> {
+    let events = EventBus::default();
+
+    for n in 1..=users {
+        let email = match n {
+            1 => "demo@localhost".to_string(),
+            n => format!("demo{}@localhost", n),
+        };
+
+        if get_user_by_email(&email, pool).await?.is_some() {
+            println!("The demo user {} already exists, skipping it", email);
+            continue;
+        }
+
+        let user = create_user(
+            email.clone(),
+            DEMO_PASSWORD.to_string(),
+            email.trim_end_matches("@localhost").to_string(),
+            &events,
+            pool,
+        )
+        .await?;
+
+        // The todos go to the workspace created along with the user
+        let workspace_id = personal_workspace(&user, pool).await?;
+
+        let count =
+            add_imported_todos(user.id, workspace_id, random_todos(todos), &events, pool).await?;
+
+        println!(
+            "✅ Created the demo user {} (password: {}) with {} todos",
+            email, DEMO_PASSWORD, count
+        );
+    }
+
+    Ok(())
+}
+
+/// Makes sure the workspace of `load@localhost` has at least `todos` todos,
+/// creating the account and the missing todos, so the server can be
+/// measured with a known amount of data. Returns the id of the workspace.
+pub async fn seed_load(todos: u32, pool: &DbPool) -> Result<i64> {
+    let events = EventBus::default();
+
+    let user = match get_user_by_email(LOAD_EMAIL, pool).await? {
+        Some(user) => user,
+        None => {
+            create_user(
+                LOAD_EMAIL.to_string(),
+                DEMO_PASSWORD.to_string(),
+                "load".to_string(),
+                &events,
+                pool,
+            )
+            .await?
+        }
+    };
+    let workspace_id = personal_workspace(&user, pool).await?;
+
+    let existing = get_todo_stats(workspace_id, Utc::now().naive_utc(), pool)
+        .await?
+        .total;
+    let mut missing = todos.saturating_sub(existing.try_into().unwrap_or(u32::MAX));
+
+    // Smaller transactions keep the database usable while seeding
+    while missing > 0 {
+        let batch = missing.min(LOAD_BATCH_SIZE);
+        add_imported_todos(
+            user.id.clone(),
+            workspace_id,
+            random_todos(batch),
+            &events,
+            pool,
+        )
+        .await?;
+        missing -= batch;
+    }
+
+    println!(
+        "✅ The load user {} (password: {}) has {} todos",
+        LOAD_EMAIL,
+        DEMO_PASSWORD,
+        existing.max(todos.into())
+    );
+
+    Ok(workspace_id)
+}
+
+/// The workspace created along with the user.
+async fn personal_workspace(user: &User, pool: &DbPool) -> Result<i64> {
+    let workspace = get_user_workspaces(&user.id, pool)
+        .await?
+        .into_iter()
+        .next()
+        .context("the user has no workspace")?;
+
+    Ok(workspace.id)
+}
+
+/// Todos with titles picked from a fixed list, due dates within a month
+/// from now (or none) and a third of them already done.
+fn random_todos(count: u32) -> Vec<ImportedTodo> {
+    let mut rng = rand::thread_rng();
+    let now = Utc::now().naive_utc();
+
+    (0..count)
+        .map(|_| ImportedTodo {
+            title: TITLES.choose(&mut rng).unwrap().to_string(),
+            description: DESCRIPTIONS.choose(&mut rng).unwrap().to_string(),
+            status: rng.gen_bool(1.0 / 3.0),
+            due_at: rng
+                .gen_bool(0.7)
+                .then(|| now + Duration::hours(rng.gen_range(-30 * 24..=30 * 24))),
+            priority: rng.gen_range(0..=3),
+            tags: TAGS.choose(&mut rng).unwrap().to_string(),
+        })
+        .collect()
+}
@@ -1,23 +1,755 @@
+use std::{
+    collections::HashMap,
+    env::VarError,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use axum::http::{HeaderValue, Method};
+use ipnet::IpNet;
+
+use crate::db::{redact_password, BACKEND};
+
+/// Shortest `JWT_SECRET` accepted.
+const MIN_JWT_SECRET_LENGTH: usize = 32;
+
+/// An address the server listens on, set with `LISTEN`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    /// A Unix domain socket (`unix:/path/to/socket`), e.g. for a
+    /// reverse proxy running on the same host.
+    Unix(PathBuf),
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(address) => write!(f, "{}", address),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Format of the logs, set with `LOG_FORMAT`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+/// How the emails are delivered, set with `MAIL_TRANSPORT`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MailTransport {
+    /// Through the SMTP server of `SMTP_URL` or `SMTP_HOST`.
+    Smtp,
+    /// Only written to the logs (development).
+    Log,
+    /// Dropped (tests).
+    None,
+}
+
+/// Where the sessions (flash messages, current workspace…) are kept,
+/// set with `SESSION_STORE`. Only the database and Redis stores share
+/// them between the instances of the app.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionStore {
+    /// In the memory of the app (default), lost on restarts.
+    Memory,
+    /// In the database of the app (`sqlite`, or `postgres` when built for it).
+    Database,
+    /// In the Redis server of `REDIS_URL` (requires the `redis` feature).
+    Redis,
+}
+
+/// Maximum lengths, in characters, of the text entered by users.
+/// Set with `MAX_TITLE_LENGTH` and `MAX_DESCRIPTION_LENGTH`.
+#[derive(Debug, Clone, Copy)]
+pub struct TextLimits {
+    pub title: usize,
+    pub description: usize,
+}
+
+impl Default for TextLimits {
+    fn default() -> Self {
+        Self {
+            title: 64,
+            description: 255,
+        }
+    }
+}
+
+/// Timeouts of the routes slower than the others, which would be cut
+/// short by `REQUEST_TIMEOUT`. Set with `EXPORT_TIMEOUT`, `IMPORT_TIMEOUT`
+/// and `SEARCH_TIMEOUT` (seconds).
+#[derive(Debug, Clone, Copy)]
+pub struct RouteTimeouts {
+    pub export: Duration,
+    pub import: Duration,
+    pub search: Duration,
+}
+
+impl Default for RouteTimeouts {
+    fn default() -> Self {
+        Self {
+            export: Duration::from_secs(120),
+            import: Duration::from_secs(120),
+            search: Duration::from_secs(60),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
+    pub host: IpAddr,
+    pub port: u16,
+    pub listen: Vec<ListenAddress>,
+    pub unix_socket_mode: u32,
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    pub http_redirect_port: Option<u16>,
+    pub request_timeout: Duration,
+    pub route_timeouts: RouteTimeouts,
+    /// Requests each of the export, import and search routes handles at
+    /// once, the others being turned away until they are done.
+    pub heavy_route_concurrency: usize,
+    pub trusted_proxies: Vec<IpNet>,
+    pub cors_allowed_origins: Vec<String>,
+    pub cors_allowed_methods: Vec<Method>,
     pub database_url: String,
+    pub run_migrations: bool,
+    pub db_connect_max_wait: Duration,
+    /// Connections of each pool of the database (SQLite writes go
+    /// through a single one regardless).
+    pub db_max_connections: u32,
+    /// How long a query waits for a connection of the pool before failing.
+    pub db_acquire_timeout: Duration,
+    /// How long an unused connection is kept open.
+    pub db_idle_timeout: Duration,
+    pub slow_query_threshold: Duration,
+    pub sqlite_busy_timeout: Duration,
     pub jwt_secret: String,
-    pub jwt_expires_in: String,
+    pub jwt_expires_in: Duration,
     pub jwt_maxage: i32,
+    pub url_signing_key: Option<String>,
+    pub feed_link_ttl: Duration,
+    pub app_url: String,
+    pub mail_transport: MailTransport,
+    pub smtp_url: Option<String>,
+    pub smtp_host: Option<String>,
+    pub smtp_port: Option<u16>,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+    pub mail_from: String,
+    pub log_format: LogFormat,
+    pub session_store: SessionStore,
+    pub redis_url: Option<String>,
+    pub sentry_dsn: Option<String>,
+    /// Enables the Slack slash command, whose requests it signs.
+    pub slack_signing_secret: Option<String>,
+    pub backup_dir: Option<PathBuf>,
+    pub backup_interval: Duration,
+    pub backup_keep: usize,
+    pub text_limits: TextLimits,
+    pub max_upload_size: usize,
+    pub todo_create_limit: usize,
+    pub login_attempt_limit: usize,
+    pub user_cache_ttl: Duration,
+    pub geoip_database: Option<PathBuf>,
+    /// Token the Prometheus scraper sends to read `/metrics`, which
+    /// is open to anyone without it.
+    pub metrics_token: Option<String>,
 }
 
 impl Config {
     pub fn init() -> Self {
-        let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-        let jwt_secret = std::env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let jwt_expires_in = std::env::var("JWT_EXPIRED_IN").expect("JWT_EXPIRED_IN must be set");
-        let jwt_maxage = std::env::var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
+        let source = Source::load();
+
+        let host = source.var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
+        let port = source.var("PORT").unwrap_or_else(|_| "8082".to_string());
+        let listen = source.var("LISTEN").ok();
+        let unix_socket_mode = source
+            .var("UNIX_SOCKET_MODE")
+            .unwrap_or_else(|_| "660".to_string());
+        let tls_cert = source.var("TLS_CERT").ok();
+        let tls_key = source.var("TLS_KEY").ok();
+        let http_redirect_port = source.var("HTTP_REDIRECT_PORT").ok();
+        let request_timeout = source
+            .var("REQUEST_TIMEOUT")
+            .unwrap_or_else(|_| "30".to_string());
+
+        if tls_cert.is_some() != tls_key.is_some() {
+            panic!("TLS_CERT and TLS_KEY must be set together");
+        }
+        let host = host
+            .parse::<IpAddr>()
+            .expect("HOST must be a valid IP address");
+        let port = port
+            .parse::<u16>()
+            .expect("PORT must be a number between 0 and 65535");
+
+        // `HOST` and `PORT` unless a list of addresses is given
+        let listen: Vec<_> = match &listen {
+            Some(listen) => split_list(listen).map(parse_listen_address).collect(),
+            None => vec![ListenAddress::Tcp(SocketAddr::new(host, port))],
+        };
+
+        if listen.is_empty() {
+            panic!("LISTEN must have at least one address");
+        }
+        if tls_cert.is_some()
+            && listen
+                .iter()
+                .any(|address| matches!(address, ListenAddress::Unix(_)))
+        {
+            panic!("TLS_CERT can't be used with Unix sockets, the reverse proxy must handle TLS");
+        }
+        let trusted_proxies = source.var("TRUSTED_PROXIES").unwrap_or_default();
+        let cors_allowed_origins = source.var("CORS_ALLOWED_ORIGINS").unwrap_or_default();
+        let cors_allowed_methods = source
+            .var("CORS_ALLOWED_METHODS")
+            .unwrap_or_else(|_| "GET,POST,PATCH,DELETE".to_string());
+        let database_url = source
+            .var("DATABASE_URL")
+            .expect("DATABASE_URL must be set");
+        let run_migrations = source
+            .var("RUN_MIGRATIONS")
+            .unwrap_or_else(|_| "true".to_string());
+        let db_connect_max_wait = source
+            .var("DB_CONNECT_MAX_WAIT")
+            .unwrap_or_else(|_| "30".to_string());
+        let jwt_secret = source.var("JWT_SECRET").expect("JWT_SECRET must be set");
+        let jwt_expires_in = source
+            .var("JWT_EXPIRED_IN")
+            .expect("JWT_EXPIRED_IN must be set");
+        let jwt_maxage = source.var("JWT_MAXAGE").expect("JWT_MAXAGE must be set");
+        let url_signing_key = source
+            .var("URL_SIGNING_KEY")
+            .ok()
+            .filter(|key| !key.is_empty());
+        let feed_link_ttl = source
+            .var("FEED_LINK_TTL")
+            .unwrap_or_else(|_| "90d".to_string());
+
+        // HS256 keys shorter than its 256 bits are easier to brute-force
+        if jwt_secret.len() < MIN_JWT_SECRET_LENGTH {
+            panic!(
+                "JWT_SECRET must be at least {} characters long",
+                MIN_JWT_SECRET_LENGTH
+            );
+        }
+        if url_signing_key
+            .as_ref()
+            .is_some_and(|key| key.len() < MIN_JWT_SECRET_LENGTH)
+        {
+            panic!(
+                "URL_SIGNING_KEY must be at least {} characters long",
+                MIN_JWT_SECRET_LENGTH
+            );
+        }
+        let app_url = source
+            .var("APP_URL")
+            .unwrap_or_else(|_| format!("http://localhost:{}", port));
+        let smtp_url = source.var("SMTP_URL").ok();
+        let smtp_host = source.var("SMTP_HOST").ok();
+        let smtp_port = source.var("SMTP_PORT").ok();
+        let smtp_username = source.var("SMTP_USERNAME").ok();
+        let smtp_password = source.var("SMTP_PASSWORD").ok();
+        let mail_transport = match source.var("MAIL_TRANSPORT").as_deref() {
+            Ok("smtp") => MailTransport::Smtp,
+            Ok("log") => MailTransport::Log,
+            Ok("none") => MailTransport::None,
+            Err(_) if smtp_url.is_some() || smtp_host.is_some() => MailTransport::Smtp,
+            Err(_) => MailTransport::Log,
+            Ok(other) => panic!("MAIL_TRANSPORT must be smtp, log or none, not {}", other),
+        };
+
+        if mail_transport == MailTransport::Smtp && smtp_url.is_none() && smtp_host.is_none() {
+            panic!("SMTP_URL or SMTP_HOST must be set to send the emails through SMTP");
+        }
+        if smtp_username.is_some() != smtp_password.is_some() {
+            panic!("SMTP_USERNAME and SMTP_PASSWORD must be set together");
+        }
+        let mail_from = source
+            .var("MAIL_FROM")
+            .unwrap_or_else(|_| "Todo List <noreply@localhost>".to_string());
+        let text_limits = TextLimits {
+            title: env_usize(&source, "MAX_TITLE_LENGTH", TextLimits::default().title),
+            description: env_usize(
+                &source,
+                "MAX_DESCRIPTION_LENGTH",
+                TextLimits::default().description,
+            ),
+        };
+        let route_timeouts = RouteTimeouts {
+            export: env_seconds(&source, "EXPORT_TIMEOUT", RouteTimeouts::default().export),
+            import: env_seconds(&source, "IMPORT_TIMEOUT", RouteTimeouts::default().import),
+            search: env_seconds(&source, "SEARCH_TIMEOUT", RouteTimeouts::default().search),
+        };
+        let heavy_route_concurrency = env_usize(&source, "HEAVY_ROUTE_CONCURRENCY", 4);
+        let max_upload_size = env_usize(&source, "MAX_UPLOAD_SIZE_KB", 2048);
+        let todo_create_limit = env_usize(&source, "TODO_CREATE_LIMIT", 30);
+        let login_attempt_limit = env_usize(&source, "LOGIN_ATTEMPT_LIMIT", 10);
+        let user_cache_ttl = source
+            .var("USER_CACHE_SECONDS")
+            .unwrap_or_else(|_| "30".to_string());
+        let db_max_connections = env_usize(&source, "DB_MAX_CONNECTIONS", 10);
+        let db_acquire_timeout = env_usize(&source, "DB_ACQUIRE_TIMEOUT", 30);
+        let db_idle_timeout = env_usize(&source, "DB_IDLE_TIMEOUT", 600);
+        let slow_query_threshold = env_usize(&source, "SLOW_QUERY_MS", 250);
+        let sqlite_busy_timeout = env_usize(&source, "SQLITE_BUSY_TIMEOUT_MS", 5000);
+        let log_format = match source.var("LOG_FORMAT").as_deref() {
+            Ok("json") => LogFormat::Json,
+            Ok("pretty") | Err(_) => LogFormat::Pretty,
+            Ok(other) => panic!("LOG_FORMAT must be json or pretty, not {}", other),
+        };
+        let redis_url = source.var("REDIS_URL").ok();
+        let sentry_dsn = source.var("SENTRY_DSN").ok().filter(|dsn| !dsn.is_empty());
+        let slack_signing_secret = source
+            .var("SLACK_SIGNING_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        let backup_dir = source
+            .var("BACKUP_DIR")
+            .ok()
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from);
+        let metrics_token = source
+            .var("METRICS_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+        let geoip_database = source
+            .var("GEOIP_DATABASE")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        let backup_interval = env_usize(&source, "BACKUP_INTERVAL_HOURS", 24);
+        let backup_keep = env_usize(&source, "BACKUP_KEEP", 7);
+
+        if backup_dir.is_some() && cfg!(feature = "postgres") {
+            panic!("BACKUP_DIR is only supported on SQLite, back up Postgres with pg_dump");
+        }
+        let session_store = match source.var("SESSION_STORE").as_deref() {
+            Ok("memory") | Err(_) => SessionStore::Memory,
+            Ok(name) if name == BACKEND => SessionStore::Database,
+            Ok("redis") => SessionStore::Redis,
+            Ok(other) => panic!(
+                "SESSION_STORE must be memory, {} or redis, not {}",
+                BACKEND, other
+            ),
+        };
+
+        if session_store == SessionStore::Redis {
+            if !cfg!(feature = "redis") {
+                panic!(
+                    "the app must be built with the `redis` feature to keep the sessions in Redis"
+                );
+            }
+            if redis_url.is_none() {
+                panic!("REDIS_URL must be set to keep the sessions in Redis");
+            }
+        }
 
         Self {
+            host,
+            port,
+            listen,
+            unix_socket_mode: u32::from_str_radix(&unix_socket_mode, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o777)
+                .expect("UNIX_SOCKET_MODE must be an octal file mode such as 660"),
+            tls_cert,
+            tls_key,
+            http_redirect_port: http_redirect_port.map(|port| {
+                port.parse::<u16>()
+                    .expect("HTTP_REDIRECT_PORT must be a number between 0 and 65535")
+            }),
+            request_timeout: Duration::from_secs(
+                request_timeout
+                    .parse::<u64>()
+                    .expect("REQUEST_TIMEOUT must be a number of seconds"),
+            ),
+            route_timeouts,
+            heavy_route_concurrency,
+            trusted_proxies: split_list(&trusted_proxies)
+                .map(|proxy| {
+                    // A single address is a network of its own
+                    proxy
+                        .parse::<IpNet>()
+                        .or_else(|_| proxy.parse::<IpAddr>().map(IpNet::from))
+                        .expect("TRUSTED_PROXIES must be a list of IP addresses or networks")
+                })
+                .collect(),
+            cors_allowed_origins: split_list(&cors_allowed_origins)
+                .inspect(|origin| {
+                    HeaderValue::from_str(origin)
+                        .expect("CORS_ALLOWED_ORIGINS must be a list of origins");
+                })
+                .map(str::to_string)
+                .collect(),
+            cors_allowed_methods: split_list(&cors_allowed_methods)
+                .map(|method| {
+                    method
+                        .parse::<Method>()
+                        .expect("CORS_ALLOWED_METHODS must be a list of http methods")
+                })
+                .collect(),
             database_url,
+            run_migrations: run_migrations
+                .parse::<bool>()
+                .expect("RUN_MIGRATIONS must be true or false"),
+            db_connect_max_wait: Duration::from_secs(
+                db_connect_max_wait
+                    .parse::<u64>()
+                    .expect("DB_CONNECT_MAX_WAIT must be a number of seconds"),
+            ),
+            db_max_connections: db_max_connections
+                .try_into()
+                .expect("DB_MAX_CONNECTIONS is too large"),
+            db_acquire_timeout: Duration::from_secs(db_acquire_timeout as u64),
+            db_idle_timeout: Duration::from_secs(db_idle_timeout as u64),
+            slow_query_threshold: Duration::from_millis(slow_query_threshold as u64),
+            sqlite_busy_timeout: Duration::from_millis(sqlite_busy_timeout as u64),
             jwt_secret,
-            jwt_expires_in,
-            jwt_maxage: jwt_maxage.parse::<i32>().unwrap(),
+            jwt_expires_in: parse_duration(&jwt_expires_in)
+                .expect("JWT_EXPIRED_IN must be a duration such as 90s, 60m, 12h or 7d"),
+            jwt_maxage: jwt_maxage
+                .parse::<i32>()
+                .ok()
+                .filter(|minutes| *minutes > 0)
+                .expect("JWT_MAXAGE must be a number of minutes"),
+            url_signing_key,
+            feed_link_ttl: parse_duration(&feed_link_ttl)
+                .expect("FEED_LINK_TTL must be a duration such as 12h or 90d"),
+            app_url: app_url.trim_end_matches('/').to_string(),
+            mail_transport,
+            smtp_url,
+            smtp_host,
+            smtp_port: smtp_port.map(|port| {
+                port.parse::<u16>()
+                    .expect("SMTP_PORT must be a number between 0 and 65535")
+            }),
+            smtp_username,
+            smtp_password,
+            mail_from,
+            log_format,
+            session_store,
+            redis_url,
+            sentry_dsn,
+            slack_signing_secret,
+            backup_dir,
+            backup_interval: Duration::from_secs(backup_interval as u64 * 60 * 60),
+            backup_keep,
+            text_limits,
+            max_upload_size: max_upload_size * 1024,
+            todo_create_limit,
+            login_attempt_limit,
+            user_cache_ttl: Duration::from_secs(
+                user_cache_ttl
+                    .parse::<u64>()
+                    .expect("USER_CACHE_SECONDS must be a number of seconds"),
+            ),
+            geoip_database,
+            metrics_token,
+        }
+    }
+
+    /// The longest a request can take, given the timeouts of the routes
+    /// (how long the in-flight requests are waited for on shutdown).
+    pub fn longest_request_timeout(&self) -> Duration {
+        let RouteTimeouts {
+            export,
+            import,
+            search,
+        } = self.route_timeouts;
+
+        [self.request_timeout, export, import, search]
+            .into_iter()
+            .max()
+            .unwrap_or(self.request_timeout)
+    }
+
+    /// The effective settings, one per line, without the secrets
+    /// (printed at startup).
+    pub fn summary(&self) -> String {
+        let unset = || "-".to_string();
+        let secret = |value: &Option<String>| value.as_ref().map_or_else(unset, |_| "***".into());
+        let url = |value: &Option<String>| value.as_deref().map_or_else(unset, redact_password);
+        let port = |value: Option<u16>| value.map_or_else(unset, |port| port.to_string());
+
+        let settings = [
+            ("HOST", self.host.to_string()),
+            ("PORT", self.port.to_string()),
+            (
+                "LISTEN",
+                self.listen
+                    .iter()
+                    .map(ListenAddress::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            ("UNIX_SOCKET_MODE", format!("{:o}", self.unix_socket_mode)),
+            ("TLS_CERT", self.tls_cert.clone().unwrap_or_else(unset)),
+            ("HTTP_REDIRECT_PORT", port(self.http_redirect_port)),
+            ("REQUEST_TIMEOUT", format!("{:?}", self.request_timeout)),
+            (
+                "EXPORT_TIMEOUT",
+                format!("{:?}", self.route_timeouts.export),
+            ),
+            (
+                "IMPORT_TIMEOUT",
+                format!("{:?}", self.route_timeouts.import),
+            ),
+            (
+                "SEARCH_TIMEOUT",
+                format!("{:?}", self.route_timeouts.search),
+            ),
+            (
+                "HEAVY_ROUTE_CONCURRENCY",
+                self.heavy_route_concurrency.to_string(),
+            ),
+            (
+                "TRUSTED_PROXIES",
+                self.trusted_proxies
+                    .iter()
+                    .map(IpNet::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            ("CORS_ALLOWED_ORIGINS", self.cors_allowed_origins.join(",")),
+            (
+                "CORS_ALLOWED_METHODS",
+                self.cors_allowed_methods
+                    .iter()
+                    .map(Method::as_str)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            ("DATABASE_URL", redact_password(&self.database_url)),
+            ("RUN_MIGRATIONS", self.run_migrations.to_string()),
+            (
+                "DB_CONNECT_MAX_WAIT",
+                format!("{:?}", self.db_connect_max_wait),
+            ),
+            ("DB_MAX_CONNECTIONS", self.db_max_connections.to_string()),
+            (
+                "DB_ACQUIRE_TIMEOUT",
+                format!("{:?}", self.db_acquire_timeout),
+            ),
+            ("DB_IDLE_TIMEOUT", format!("{:?}", self.db_idle_timeout)),
+            ("SLOW_QUERY_MS", format!("{:?}", self.slow_query_threshold)),
+            (
+                "SQLITE_BUSY_TIMEOUT_MS",
+                format!("{:?}", self.sqlite_busy_timeout),
+            ),
+            ("JWT_SECRET", "***".to_string()),
+            ("JWT_EXPIRED_IN", format!("{:?}", self.jwt_expires_in)),
+            ("JWT_MAXAGE", format!("{} minutes", self.jwt_maxage)),
+            ("URL_SIGNING_KEY", secret(&self.url_signing_key)),
+            ("FEED_LINK_TTL", format!("{:?}", self.feed_link_ttl)),
+            ("APP_URL", self.app_url.clone()),
+            ("MAIL_TRANSPORT", format!("{:?}", self.mail_transport)),
+            ("SMTP_URL", url(&self.smtp_url)),
+            ("SMTP_HOST", self.smtp_host.clone().unwrap_or_else(unset)),
+            ("SMTP_PORT", port(self.smtp_port)),
+            (
+                "SMTP_USERNAME",
+                self.smtp_username.clone().unwrap_or_else(unset),
+            ),
+            ("SMTP_PASSWORD", secret(&self.smtp_password)),
+            ("MAIL_FROM", self.mail_from.clone()),
+            ("LOG_FORMAT", format!("{:?}", self.log_format)),
+            ("SESSION_STORE", format!("{:?}", self.session_store)),
+            ("REDIS_URL", url(&self.redis_url)),
+            ("SENTRY_DSN", secret(&self.sentry_dsn)),
+            ("SLACK_SIGNING_SECRET", secret(&self.slack_signing_secret)),
+            (
+                "BACKUP_DIR",
+                self.backup_dir
+                    .as_ref()
+                    .map_or_else(unset, |dir| dir.display().to_string()),
+            ),
+            (
+                "BACKUP_INTERVAL_HOURS",
+                format!("{:?}", self.backup_interval),
+            ),
+            ("BACKUP_KEEP", self.backup_keep.to_string()),
+            ("MAX_TITLE_LENGTH", self.text_limits.title.to_string()),
+            (
+                "MAX_DESCRIPTION_LENGTH",
+                self.text_limits.description.to_string(),
+            ),
+            (
+                "MAX_UPLOAD_SIZE_KB",
+                (self.max_upload_size / 1024).to_string(),
+            ),
+            ("TODO_CREATE_LIMIT", self.todo_create_limit.to_string()),
+            ("LOGIN_ATTEMPT_LIMIT", self.login_attempt_limit.to_string()),
+            ("USER_CACHE_SECONDS", format!("{:?}", self.user_cache_ttl)),
+            (
+                "GEOIP_DATABASE",
+                self.geoip_database
+                    .as_ref()
+                    .map_or_else(unset, |path| path.display().to_string()),
+            ),
+            ("METRICS_TOKEN", secret(&self.metrics_token)),
+        ];
+
+        settings
+            .iter()
+            .map(|(name, value)| format!("  {} = {}", name, value))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The settings of `config.toml` (or the file of `CONFIG_FILE`), named
+/// as the env vars, which take precedence over them (e.g. `PORT = 8082`).
+/// Lists can be given as arrays (`CORS_ALLOWED_METHODS = ["GET", "POST"]`).
+#[derive(Default)]
+struct Source {
+    file: HashMap<String, String>,
+}
+
+impl Source {
+    /// Reads the config file. `config.toml` is optional, but
+    /// the file of `CONFIG_FILE` must exist.
+    fn load() -> Self {
+        let (path, required) = match std::env::var("CONFIG_FILE") {
+            Ok(path) => (path, true),
+            Err(_) => ("config.toml".to_string(), false),
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Self::default()
+            }
+            Err(e) => panic!("unable to read the config file {}: {}", path, e),
+        };
+
+        let table = text
+            .parse::<toml::Table>()
+            .unwrap_or_else(|e| panic!("{} must be a valid TOML file: {}", path, e));
+
+        let file = table
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    toml::Value::Array(items) => items
+                        .into_iter()
+                        .map(toml_string)
+                        .collect::<Option<Vec<_>>>()
+                        .map(|items| items.join(",")),
+                    value => toml_string(value),
+                };
+                let value = value.unwrap_or_else(|| {
+                    panic!(
+                        "{} of {} must be a string, a number or a boolean",
+                        key, path
+                    )
+                });
+
+                (key.to_ascii_uppercase(), value)
+            })
+            .collect();
+
+        Self { file }
+    }
+
+    /// The value of the env var, or else of the config file. In both,
+    /// `<NAME>_FILE` can give the path of a file holding the value instead
+    /// (e.g. `JWT_SECRET_FILE=/run/secrets/jwt_secret`), so that secrets
+    /// don't have to be passed through the environment.
+    fn var(&self, name: &str) -> Result<String, VarError> {
+        lookup(name, |key| std::env::var(key).ok())
+            .or_else(|| lookup(name, |key| self.file.get(key).cloned()))
+            .ok_or(VarError::NotPresent)
+    }
+}
+
+/// The value of `name`, or else the contents of the file of `<name>_FILE`.
+fn lookup(name: &str, get: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let file_var = format!("{}_FILE", name);
+
+    get(name).or_else(|| get(&file_var).map(|path| read_value_file(&file_var, &path)))
+}
+
+/// The contents of a file given by a `<NAME>_FILE` setting, without
+/// the surrounding whitespace (such as the final newline).
+fn read_value_file(var: &str, path: &str) -> String {
+    std::fs::read_to_string(path)
+        .map(|value| value.trim().to_string())
+        .unwrap_or_else(|e| panic!("unable to read the file of {} ({}): {}", var, path, e))
+}
+
+/// A value of the config file as the env vars would give it.
+fn toml_string(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(value) => Some(value),
+        toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+            Some(value.to_string())
         }
+        _ => None,
+    }
+}
+
+/// An address of `LISTEN`: `unix:/path/to/socket` or an IP address
+/// with its port (`0.0.0.0:8082`, `[::1]:8082`).
+fn parse_listen_address(address: &str) -> ListenAddress {
+    match address.strip_prefix("unix:") {
+        Some(_) if !cfg!(unix) => panic!("Unix sockets are only supported on Unix systems"),
+        Some(path) if !path.is_empty() => ListenAddress::Unix(PathBuf::from(path)),
+        Some(_) => panic!("LISTEN must give the path of the Unix socket after unix:"),
+        None => ListenAddress::Tcp(address.parse::<SocketAddr>().unwrap_or_else(|_| {
+            panic!(
+                "LISTEN must be a list of IP addresses with their port or unix:<path>, not {}",
+                address
+            )
+        })),
     }
 }
+
+/// A duration with its unit: `90s`, `60m`, `12h` or `7d`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit = match value.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    let amount = value[..value.len() - 1].parse::<u64>().ok()?;
+
+    Some(Duration::from_secs(amount * unit)).filter(|duration| !duration.is_zero())
+}
+
+/// A positive number from an env var, or the default if it isn't set.
+fn env_usize(source: &Source, name: &str, default: usize) -> usize {
+    match source.var(name) {
+        Ok(value) => match value.parse::<usize>() {
+            Ok(number) if number > 0 => number,
+            _ => panic!("{} must be a positive number", name),
+        },
+        Err(_) => default,
+    }
+}
+
+/// A positive number of seconds from an env var, or the default
+/// if it isn't set.
+fn env_seconds(source: &Source, name: &str, default: Duration) -> Duration {
+    match source.var(name) {
+        Ok(_) => Duration::from_secs(env_usize(source, name, 0) as u64),
+        Err(_) => default,
+    }
+}
+
+/// Items of a comma separated list, without blanks.
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
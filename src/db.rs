@@ -1,18 +1,211 @@
+use std::time::{Duration, Instant};
+
 use anyhow::{Context, Result};
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use log::LevelFilter;
+use reqwest::Url;
+use sqlx::{migrate::Migrator, pool::PoolOptions, ConnectOptions, Connection};
+
+#[cfg(feature = "sqlite")]
+use sqlx::sqlite::{SqliteJournalMode, SqliteSynchronous};
+
+use crate::config::Config;
+
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("the `sqlite` and `postgres` features are mutually exclusive");
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("either the `sqlite` or the `postgres` feature must be enabled");
+
+/// Database the app is built for, selected with the `sqlite`
+/// (default) or `postgres` feature.
+#[cfg(feature = "sqlite")]
+pub type Db = sqlx::Sqlite;
+#[cfg(feature = "postgres")]
+pub type Db = sqlx::Postgres;
+
+pub type DbPool = sqlx::Pool<Db>;
+
+/// The connections to the database: the service functions that only
+/// read go through `reader`, the others through `writer`. They are
+/// the same pool unless the database is SQLite.
+#[derive(Clone, Debug)]
+pub struct DbPools {
+    pub writer: DbPool,
+    pub reader: DbPool,
+}
+
+impl DbPools {
+    /// Reads and writes through the same pool (e.g. on an in-memory
+    /// SQLite database, that the connections of another pool can't see).
+    pub fn single(pool: DbPool) -> Self {
+        Self {
+            reader: pool.clone(),
+            writer: pool,
+        }
+    }
+
+    /// Closes both pools, waiting for their queries to finish.
+    pub async fn close(&self) {
+        self.writer.close().await;
+        self.reader.close().await;
+    }
+}
+
+/// Name of the database the app is built for, as in the env vars.
+#[cfg(feature = "sqlite")]
+pub const BACKEND: &str = "sqlite";
+#[cfg(feature = "postgres")]
+pub const BACKEND: &str = "postgres";
+
+/// Waits between connection attempts, doubled after each failure.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Migrations of the database, embedded in the binary.
+#[cfg(feature = "sqlite")]
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+#[cfg(feature = "postgres")]
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Create the pools of the database, with up to `DB_MAX_CONNECTIONS`
+/// connections each, waiting up to `DB_ACQUIRE_TIMEOUT` for one of them
+/// and closing them after `DB_IDLE_TIMEOUT` unused.
+/// SQLite databases are opened in WAL mode, with foreign keys enforced,
+/// and are written through a single connection, the reads going through
+/// read-only connections.
+/// Failed connections are retried with exponential backoff
+/// for up to `DB_CONNECT_MAX_WAIT` seconds.
+/// Pending migrations are applied unless `RUN_MIGRATIONS` is false;
+/// a changed migration that was already applied fails the startup.
+pub async fn connect(config: &Config) -> Result<DbPools> {
+    let pool_url = &config.database_url;
+    let display_url = redact_password(pool_url);
 
-const MAX_CONNECTIONS: u32 = 10;
+    #[cfg(feature = "sqlite")]
+    let options = pool_url
+        .parse::<sqlx::sqlite::SqliteConnectOptions>()
+        .with_context(|| format!("Error: 🔥 invalid database URL {}!", display_url))?
+        .create_if_missing(config.run_migrations)
+        // With the WAL journal the reads go on during a write, and the
+        // writes wait for each other (up to `SQLITE_BUSY_TIMEOUT_MS`)
+        // instead of failing with "database is locked". The normal
+        // synchronous mode is safe with WAL, syncing only on checkpoints
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .foreign_keys(true)
+        .busy_timeout(config.sqlite_busy_timeout);
+    // Timestamps are stored in UTC, like `CURRENT_TIMESTAMP` in SQLite
+    #[cfg(feature = "postgres")]
+    let options = pool_url
+        .parse::<sqlx::postgres::PgConnectOptions>()
+        .with_context(|| format!("Error: 🔥 invalid database URL {}!", display_url))?
+        .options([("timezone", "UTC")]);
 
-/// Create a new `SqlitePoolOptions` instance and set the
-/// maximum number of connections in the connection pool to 10.
-pub async fn connect(pool_url: &str) -> Result<SqlitePool> {
-    let pool = SqlitePoolOptions::new()
-        .max_connections(MAX_CONNECTIONS)
-        .connect(pool_url)
+    // Statements slower than `SLOW_QUERY_MS` are logged as warnings
+    let options = options.log_slow_statements(LevelFilter::Warn, config.slow_query_threshold);
+
+    // Each attempt opens a single connection, so it fails fast
+    // instead of waiting for the acquire timeout of the pool
+    let started_at = Instant::now();
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        match options.connect().await {
+            Ok(conn) => {
+                conn.close().await.ok();
+                break;
+            }
+            Err(e) if started_at.elapsed() + backoff <= config.db_connect_max_wait => {
+                eprintln!(
+                    "⏳ Unable to connect to database at {} ({}), retrying in {:?}…",
+                    display_url, e, backoff
+                );
+
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!(
+                        "Error: 🔥 unable to connect to database at {} after {:?}!",
+                        display_url,
+                        started_at.elapsed()
+                    )
+                })
+            }
+        }
+    }
+
+    // SQLite runs a single write at a time: the writes wait for the one
+    // connection of the writer in the order they came, rather than
+    // retrying against the busy timeout, while the reads go on in parallel
+    #[cfg(feature = "sqlite")]
+    let (writer_connections, reader_options) = (1, options.clone().read_only(true));
+    #[cfg(feature = "postgres")]
+    let writer_connections = config.db_max_connections;
+
+    let pool_options = PoolOptions::<Db>::new()
+        .acquire_timeout(config.db_acquire_timeout)
+        .idle_timeout(config.db_idle_timeout);
+
+    let writer = pool_options
+        .clone()
+        .max_connections(writer_connections)
+        .connect_with(options)
         .await
-        .context("Error: 🔥 unable to connect to database!")?;
+        .with_context(|| {
+            format!(
+                "Error: 🔥 unable to connect to database at {}!",
+                display_url
+            )
+        })?;
 
     println!("✅ Successfully connected to database!");
 
-    Ok(pool)
+    if config.run_migrations {
+        migrate(&writer).await?;
+    }
+
+    // Opened after the migrations, as a new database is created by the writer
+    #[cfg(feature = "sqlite")]
+    let pools = DbPools {
+        reader: pool_options
+            .max_connections(config.db_max_connections)
+            .connect_with(reader_options)
+            .await
+            .with_context(|| {
+                format!(
+                    "Error: 🔥 unable to connect to database at {}!",
+                    display_url
+                )
+            })?,
+        writer,
+    };
+    #[cfg(feature = "postgres")]
+    let pools = DbPools::single(writer);
+
+    Ok(pools)
+}
+
+/// Applies the pending migrations.
+pub async fn migrate(pool: &DbPool) -> Result<()> {
+    MIGRATOR
+        .run(pool)
+        .await
+        .context("Error: 🔥 unable to migrate the database!")?;
+
+    println!("✅ Database schema is up to date!");
+
+    Ok(())
+}
+
+/// Hides the password of the URL, so it can be logged.
+pub fn redact_password(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) if url.password().is_some() => {
+            let _ = url.set_password(Some("***"));
+            url.to_string()
+        }
+        _ => url.to_string(),
+    }
 }
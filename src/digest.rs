@@ -0,0 +1,122 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDateTime, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::Tz;
+use tracing::{error, info};
+
+use crate::{
+    handler::convert_datetime,
+    mailer::{DigestEmail, DigestItem},
+    model::{DigestFrequency, User, UserSettings},
+    service::{claim_digest, get_digest_recipients, get_digest_todos, release_digest},
+    AppState,
+};
+
+/// How often the users are checked for a due digest.
+pub const SCAN_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Local hour from which the digest of the day is sent.
+const SEND_HOUR: u32 = 7;
+
+/// Least time between two digests of a user, so a digest that was sent
+/// late (e.g. after a restart) doesn't make the next one be skipped.
+const MIN_GAP: chrono::Duration = chrono::Duration::hours(20);
+
+/// Emails the digest of the users who opted in (a background job).
+pub async fn scan(state: Arc<AppState>) -> Result<()> {
+    send_due(state, Utc::now().naive_utc()).await
+}
+
+/// Emails the digests due at `now`: the daily ones every morning and
+/// the weekly ones on Monday morning, in the timezone of every user
+/// (UTC if they have none). Like the reminders, every digest is claimed
+/// before it is sent and released again if sending fails.
+pub async fn send_due(state: Arc<AppState>, now: NaiveDateTime) -> Result<()> {
+    let users = get_digest_recipients(&state.read_pool).await?;
+
+    for (user, settings) in users {
+        let frequency = settings.digest();
+        let tz = settings
+            .timezone
+            .as_deref()
+            .and_then(|tzone| tzone.parse::<Tz>().ok())
+            .unwrap_or(Tz::UTC);
+        let local = Utc.from_utc_datetime(&now).with_timezone(&tz);
+
+        let due = local.hour() >= SEND_HOUR
+            && match frequency {
+                DigestFrequency::Off => false,
+                DigestFrequency::Daily => true,
+                DigestFrequency::Weekly => local.weekday() == Weekday::Mon,
+            };
+        if !due {
+            continue;
+        }
+
+        match claim_digest(&user.id, now, now - MIN_GAP, &state.pool).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                error!("failed to claim the digest of user {}: {}", user.id, e);
+                continue;
+            }
+        }
+
+        if let Err(e) = send_digest(&state, &user, &settings, tz, now).await {
+            error!("failed to send the digest of user {}: {:#}", user.id, e);
+            if let Err(e) = release_digest(&user.id, user.digest_sent_at, &state.pool).await {
+                error!("failed to release the digest of user {}: {}", user.id, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the digest of the user, unless there is nothing to tell.
+async fn send_digest(
+    state: &AppState,
+    user: &User,
+    settings: &UserSettings,
+    tz: Tz,
+    now: NaiveDateTime,
+) -> Result<()> {
+    let frequency = settings.digest();
+    let todos = get_digest_todos(&user.id, now + frequency.horizon(), &state.read_pool).await?;
+    if todos.is_empty() {
+        return Ok(());
+    }
+
+    let date_format = settings.date_format();
+    let mut overdue = Vec::new();
+    let mut upcoming = Vec::new();
+    for todo in todos {
+        let Some(due_at) = todo.due_at else {
+            continue;
+        };
+        let item = DigestItem {
+            title: todo.title,
+            due: convert_datetime(tz.name(), due_at, date_format)?,
+        };
+
+        if due_at < now {
+            overdue.push(item);
+        } else {
+            upcoming.push(item);
+        }
+    }
+
+    let email = DigestEmail {
+        username: user.username.clone(),
+        period: frequency.name().unwrap_or_default(),
+        overdue,
+        upcoming,
+        link: format!("{}/todo/list", state.config.app_url),
+    };
+    state.mailer.send_email(&user.email, &email).await?;
+
+    info!("digest sent to user {}", user.id);
+
+    Ok(())
+}
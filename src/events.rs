@@ -0,0 +1,275 @@
+use std::{collections::HashSet, future::Future, sync::Arc};
+
+use anyhow::Result;
+use chrono::Utc;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, error, warn};
+
+use crate::{
+    hub::TodoEvent,
+    mailer::VerificationEmail,
+    model::{NotificationKind, Todo, TokenKind},
+    AppState,
+};
+
+/// How many events a slow subscriber may fall behind before it skips some.
+const BUS_CAPACITY: usize = 256;
+
+/// Something that happened in the app, published by the service
+/// functions once it is saved in the database.
+#[derive(Clone, Debug)]
+pub enum DomainEvent {
+    UserRegistered {
+        user_id: String,
+        email: String,
+        username: String,
+    },
+    TodoCreated {
+        todo: Todo,
+    },
+    TodoUpdated {
+        todo: Todo,
+    },
+    TodoDeleted {
+        workspace_id: i64,
+        todo_id: i64,
+    },
+    TodosImported {
+        user_id: String,
+        count: usize,
+    },
+    MemberAdded {
+        workspace_id: i64,
+        user_id: String,
+    },
+    /// The reminder of a todo was emailed to its owner.
+    ReminderDue {
+        user_id: String,
+        todo_id: i64,
+        title: String,
+    },
+}
+
+/// Channel of the domain events. The side effects of the changes
+/// (live updates, emails…) subscribe to it instead of being
+/// triggered by the handlers.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<DomainEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(BUS_CAPACITY).0,
+        }
+    }
+}
+
+impl EventBus {
+    /// Publishes an event, which is dropped if nobody is subscribed.
+    pub fn publish(&self, event: DomainEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Starts the subscribers of the domain events of the app.
+pub fn start_subscribers(state: &Arc<AppState>) {
+    spawn_subscriber(state, "live_updates", live_updates);
+    spawn_subscriber(state, "verification_email", verification_email);
+    spawn_subscriber(state, "fragment_cache", evict_fragments);
+    spawn_subscriber(state, "notifications", notifications);
+}
+
+/// Runs `handle` on every event published on the bus of the app, in order.
+fn spawn_subscriber<F, Fut>(state: &Arc<AppState>, name: &'static str, handle: F)
+where
+    F: Fn(Arc<AppState>, DomainEvent) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let mut events = state.events.subscribe();
+    let state = state.clone();
+
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    debug!("{} handling {:?}", name, event);
+                    handle(state.clone(), event).await;
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("{} fell behind, {} events skipped", name, skipped);
+                }
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+}
+
+/// Pushes the changes of the todos to the WebSocket clients of their list.
+async fn live_updates(state: Arc<AppState>, event: DomainEvent) {
+    let event = match event {
+        DomainEvent::TodoCreated { todo } => TodoEvent::created(todo),
+        DomainEvent::TodoUpdated { todo } => TodoEvent::updated(todo),
+        DomainEvent::TodoDeleted {
+            workspace_id,
+            todo_id,
+        } => TodoEvent::deleted(workspace_id, todo_id),
+        _ => return,
+    };
+
+    state.hub.publish(event);
+}
+
+/// Evicts the cached rows of the changed todos, which won't match anymore.
+async fn evict_fragments(state: Arc<AppState>, event: DomainEvent) {
+    match event {
+        DomainEvent::TodoUpdated { todo } => state.fragments.invalidate(todo.id),
+        DomainEvent::TodoDeleted { todo_id, .. } => state.fragments.invalidate(todo_id),
+        _ => {}
+    }
+}
+
+/// Sends new users the link that verifies their email address.
+async fn verification_email(state: Arc<AppState>, event: DomainEvent) {
+    let DomainEvent::UserRegistered {
+        user_id,
+        email,
+        username,
+    } = event
+    else {
+        return;
+    };
+
+    let now = Utc::now().naive_utc();
+    let result = match state
+        .users
+        .create_user_token(&user_id, TokenKind::Verification, now)
+        .await
+    {
+        Ok(token) => {
+            let message = VerificationEmail {
+                username,
+                link: format!("{}/verify-email?token={}", state.config.app_url, token),
+            };
+            state.mailer.send_email(&email, &message).await
+        }
+        Err(e) => Err(e),
+    };
+
+    if let Err(e) = result {
+        error!(
+            "failed to send the verification email of user {}: {:#}",
+            user_id, e
+        );
+    }
+}
+
+/// Adds to the notification center of the users the invites to
+/// workspaces, the due reminders and the mentions in todos.
+async fn notifications(state: Arc<AppState>, event: DomainEvent) {
+    let result = match event {
+        DomainEvent::MemberAdded {
+            workspace_id,
+            user_id,
+        } => notify_invite(&state, workspace_id, &user_id).await,
+        DomainEvent::ReminderDue {
+            user_id,
+            todo_id,
+            title,
+        } => {
+            state
+                .notifications
+                .add_notification(
+                    &user_id,
+                    NotificationKind::Reminder,
+                    &format!("Reminder: {}", title),
+                    "/todo/list",
+                    Some(todo_id),
+                )
+                .await
+        }
+        DomainEvent::TodoCreated { todo } | DomainEvent::TodoUpdated { todo } => {
+            notify_mentions(&state, &todo).await
+        }
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        error!("failed to add a notification: {:#}", e);
+    }
+}
+
+async fn notify_invite(state: &AppState, workspace_id: i64, user_id: &str) -> Result<()> {
+    let Some(workspace) = state
+        .workspaces
+        .get_user_workspaces(user_id)
+        .await?
+        .into_iter()
+        .find(|workspace| workspace.id == workspace_id)
+    else {
+        // Removed again in the meantime
+        return Ok(());
+    };
+
+    state
+        .notifications
+        .add_notification(
+            user_id,
+            NotificationKind::Invite,
+            &format!("You were added to the workspace {}", workspace.name),
+            "/settings/workspace",
+            None,
+        )
+        .await
+}
+
+/// Notifies the members of the workspace of the todo mentioned as
+/// `@username` in its title or description, other than its author.
+async fn notify_mentions(state: &AppState, todo: &Todo) -> Result<()> {
+    let mentions = mentioned_usernames(&format!("{} {}", todo.title, todo.description));
+    if mentions.is_empty() {
+        return Ok(());
+    }
+
+    let members = state
+        .workspaces
+        .get_workspace_members(todo.workspace_id)
+        .await?;
+
+    for member in members {
+        if member.user_id == todo.created_by || !mentions.contains(&member.username.to_lowercase())
+        {
+            continue;
+        }
+
+        state
+            .notifications
+            .add_notification(
+                &member.user_id,
+                NotificationKind::Mention,
+                &format!("You were mentioned in \"{}\"", todo.title),
+                "/todo/list",
+                Some(todo.id),
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// The lowercased names after an `@` in the text.
+fn mentioned_usernames(text: &str) -> HashSet<String> {
+    text.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|name| {
+            name.trim_end_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|name| !name.is_empty())
+        .collect()
+}
@@ -0,0 +1,44 @@
+use moka::sync::Cache;
+
+/// Rows kept at most, the least used are evicted first.
+const CAPACITY: u64 = 10_000;
+
+/// Rendered rows of the todo list, kept in memory so that long lists
+/// aren't rendered again whole after every change. Each row is stored
+/// with the fingerprint of everything it shows and only reused while
+/// it matches; the rows of the changed todos are evicted as their
+/// domain events are published.
+#[derive(Clone)]
+pub struct FragmentCache(Cache<i64, (u64, String)>);
+
+impl Default for FragmentCache {
+    fn default() -> Self {
+        Self(Cache::new(CAPACITY))
+    }
+}
+
+impl FragmentCache {
+    /// The row of the todo `id`, rendered with `render` unless the one
+    /// cached has the same `fingerprint`.
+    pub fn get_or_render<E>(
+        &self,
+        id: i64,
+        fingerprint: u64,
+        render: impl FnOnce() -> Result<String, E>,
+    ) -> Result<String, E> {
+        if let Some((cached, html)) = self.0.get(&id) {
+            if cached == fingerprint {
+                return Ok(html);
+            }
+        }
+
+        let html = render()?;
+        self.0.insert(id, (fingerprint, html.clone()));
+
+        Ok(html)
+    }
+
+    pub fn invalidate(&self, id: i64) {
+        self.0.invalidate(&id);
+    }
+}
@@ -0,0 +1,62 @@
+use std::net::IpAddr;
+#[cfg(feature = "geoip")]
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::config::Config;
+
+/// Coarse location of the clients from their address, with a MaxMind
+/// City database (`GEOIP_DATABASE`, e.g. GeoLite2-City.mmdb) read into
+/// memory at startup. Only used to guess the timezone of the users who
+/// haven't chosen one and whose browser doesn't send it.
+#[derive(Clone, Default)]
+pub struct GeoIp {
+    #[cfg(feature = "geoip")]
+    reader: Option<Arc<maxminddb::Reader<Vec<u8>>>>,
+}
+
+impl GeoIp {
+    /// Reads the database of the config, if any.
+    #[cfg(feature = "geoip")]
+    pub fn new(config: &Config) -> Result<Self> {
+        use anyhow::Context;
+
+        let reader = config
+            .geoip_database
+            .as_ref()
+            .map(|path| {
+                maxminddb::Reader::open_readfile(path).with_context(|| {
+                    format!("Error: 🔥 failed to read the GeoIP database {:?}", path)
+                })
+            })
+            .transpose()?
+            .map(Arc::new);
+
+        Ok(Self { reader })
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    pub fn new(config: &Config) -> Result<Self> {
+        if config.geoip_database.is_some() {
+            anyhow::bail!("the app must be built with the `geoip` feature to use GEOIP_DATABASE");
+        }
+
+        Ok(Self {})
+    }
+
+    /// IANA timezone of the place the address is in, if it's known.
+    #[cfg(feature = "geoip")]
+    pub fn timezone(&self, ip: IpAddr) -> Option<String> {
+        use maxminddb::geoip2::City;
+
+        let city: City = self.reader.as_ref()?.lookup(ip).ok()?;
+
+        city.location?.time_zone.map(str::to_string)
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    pub fn timezone(&self, _ip: IpAddr) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_messages::Messages;
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{
+    mailer::{Email, PasswordResetEmail},
+    model::{AuditEvent, ForgotPasswordSchema, ResetPasswordSchema, TokenKind},
+    AppState,
+};
+
+use super::{audit, BaseContext, ForgotPasswordTemplate, HtmlTemplate, ResetPasswordTemplate};
+
+/// Minimum length of the passwords, as required by the forms.
+const MIN_PASSWORD_LENGTH: usize = 6;
+
+/// Struct for holding the token that comes in query params.
+#[derive(Debug, Deserialize)]
+pub struct TokenParams {
+    pub token: String,
+}
+
+/// Sends an email in the background, so the response doesn't wait
+/// for the SMTP server (nor tells by its delay if the user exists).
+fn send_in_background(
+    state: &Arc<AppState>,
+    to: String,
+    email: impl Email + Send + Sync + 'static,
+) {
+    let mailer = state.mailer.clone();
+
+    tokio::spawn(async move {
+        if let Err(e) = mailer.send_email(&to, &email).await {
+            error!("failed to send \"{}\" email: {:#}", email.subject(), e);
+        }
+    });
+}
+
+/// Handler of the link of the verification email.
+pub async fn verify_email_handler(
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Query(TokenParams { token }): Query<TokenParams>,
+) -> impl IntoResponse {
+    let now = Utc::now().naive_utc();
+
+    let result = match state
+        .users
+        .use_user_token(&token, TokenKind::Verification, now)
+        .await
+    {
+        Ok(Some(user_id)) => state
+            .users
+            .set_email_verified(&user_id, now)
+            .await
+            .map(|_| true),
+        Ok(None) => Ok(false),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(true) => messages.success("Your email address has been verified!!"),
+        Ok(false) => messages.error("The verification link is invalid or has expired"),
+        Err(e) => messages.error(format!("Something went wrong: {}", e)),
+    };
+
+    Redirect::to("/login")
+}
+
+/// Handler to serve the Forgotten Password Page template.
+pub async fn forgot_password_page_handler(ctx: BaseContext) -> impl IntoResponse {
+    HtmlTemplate(ForgotPasswordTemplate {
+        ctx: ctx.with_title("Forgotten password"),
+    })
+}
+
+/// Handle the `POST` request of the forgotten password form.
+/// The answer is the same whether the account exists or not.
+pub async fn forgot_password_handler(
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<ForgotPasswordSchema>,
+) -> impl IntoResponse {
+    let result = match state.users.get_user_by_email(&form_data.email).await {
+        Ok(Some(user)) => {
+            let now = Utc::now().naive_utc();
+            state
+                .users
+                .create_user_token(&user.id, TokenKind::PasswordReset, now)
+                .await
+                .map(|token| Some((user, token)))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(Some((user, token))) => {
+            let email = PasswordResetEmail {
+                username: user.username,
+                link: format!("{}/reset-password?token={}", state.config.app_url, token),
+                valid_minutes: TokenKind::PasswordReset.lifetime().num_minutes(),
+            };
+            send_in_background(&state, user.email, email);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            messages.error(format!("Something went wrong: {}", e));
+
+            return Redirect::to("/forgot-password");
+        }
+    }
+
+    messages.success(
+        "If there is an account with that email, we have sent it a link to reset the password",
+    );
+
+    Redirect::to("/login")
+}
+
+/// Handler to serve the Password Reset Page template (the link of the email).
+pub async fn reset_password_page_handler(
+    ctx: BaseContext,
+    Query(TokenParams { token }): Query<TokenParams>,
+) -> impl IntoResponse {
+    HtmlTemplate(ResetPasswordTemplate {
+        token,
+        ctx: ctx.with_title("Reset password"),
+    })
+}
+
+/// Handle the `POST` request of the password reset form.
+pub async fn reset_password_handler(
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<ResetPasswordSchema>,
+) -> Response {
+    if form_data.password.chars().count() < MIN_PASSWORD_LENGTH {
+        messages.error(format!(
+            "The password must have at least {} characters",
+            MIN_PASSWORD_LENGTH
+        ));
+
+        return Redirect::to(&format!("/reset-password?token={}", form_data.token)).into_response();
+    }
+
+    let now = Utc::now().naive_utc();
+
+    let result = match state
+        .users
+        .use_user_token(&form_data.token, TokenKind::PasswordReset, now)
+        .await
+    {
+        Ok(Some(user_id)) => state
+            .users
+            .set_user_password(&user_id, &form_data.password)
+            .await
+            .map(|_| Some(user_id)),
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(Some(user_id)) => {
+            if let Ok(Some(user)) = state.users.get_user_by_id(&user_id).await {
+                audit(&state, &user.email, AuditEvent::PasswordChanged).await;
+            }
+
+            messages.success("Your password has been changed, you can log in with it now");
+
+            Redirect::to("/login").into_response()
+        }
+        Ok(None) => {
+            messages.error("The password reset link is invalid or has expired");
+
+            Redirect::to("/forgot-password").into_response()
+        }
+        Err(e) => {
+            messages.error(format!("Something went wrong: {}", e));
+
+            Redirect::to("/forgot-password").into_response()
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_messages::Messages;
+use chrono::{Duration, Utc};
+use tower_sessions::Session;
+
+use crate::{
+    backup::{self, list_backups},
+    config::SessionStore,
+    db::DbPool,
+    model::{AuditEvent, AuditFilter, DateFormat, ImpersonateSchema, PoolStats, TokenClaims, User},
+    service::{count_active_sessions, get_jobs, get_ops_counts},
+    AppState,
+};
+
+use super::{
+    audit, audit_as, auth_handler::token_cookie, middleware::current_impersonator, render_error,
+    AuditLogTemplate, BaseContext, ErrorTemplate, HtmlTemplate, OpsTemplate, DATE_FORMAT_KEY,
+    TZONE_KEY, WORKSPACE_KEY,
+};
+
+/// Most entries shown by a search of the audit log.
+const AUDIT_LOG_LIMIT: i64 = 200;
+
+/// How long an admin can use the app as another user.
+const IMPERSONATION_TTL: Duration = Duration::hours(1);
+
+/// Response to the users who aren't admins.
+fn admins_only(what: &str) -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        render_error(
+            StatusCode::FORBIDDEN,
+            format!("Only the admins can see {}", what),
+        ),
+    )
+        .into_response()
+}
+
+/// Handler to serve the Audit Log page, searched by user
+/// and date range. Only the admins can see it.
+pub async fn audit_log_handler(
+    Extension(user): Extension<User>,
+    Query(filter): Query<AuditFilter>,
+    session: Session,
+    ctx: BaseContext,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !user.is_admin {
+        return admins_only("the audit log");
+    }
+
+    let entries = match state.users.search_audit_log(&filter, AUDIT_LOG_LIMIT).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                render_error(StatusCode::BAD_REQUEST, e.to_string()),
+            )
+                .into_response()
+        }
+    };
+
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    HtmlTemplate(AuditLogTemplate {
+        entries,
+        filter,
+        tzone,
+        date_format,
+        ctx: ctx.with_title("Audit Log"),
+    })
+    .into_response()
+}
+
+/// Handler to serve the Ops page: the connections of the database pool,
+/// the active sessions and WebSocket clients, the state of the background
+/// jobs, the last errors logged and how many users and todos there are.
+/// Only the admins can see it.
+pub async fn ops_handler(
+    Extension(user): Extension<User>,
+    session: Session,
+    ctx: BaseContext,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !user.is_admin {
+        return admins_only("the ops dashboard");
+    }
+
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    match ops_template(&state).await {
+        Ok(template) => HtmlTemplate(OpsTemplate {
+            tzone,
+            date_format,
+            ctx: ctx.with_title("Ops"),
+            ..template
+        })
+        .into_response(),
+        Err(e) => HtmlTemplate(ErrorTemplate {
+            link: "/".to_string(),
+            ..ErrorTemplate::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })
+        .into_response(),
+    }
+}
+
+/// Handle the `POST` request of the Ops page to back up the
+/// database now, as the `backup` job does.
+pub async fn backup_handler(
+    Extension(user): Extension<User>,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !user.is_admin {
+        return admins_only("the backups");
+    }
+
+    match backup::back_up(&state).await {
+        Ok(backup) => messages.success(format!("Backup {} created successfully!!", backup.name)),
+        Err(e) => messages.error(format!("Something went wrong: {:#}", e)),
+    };
+
+    Redirect::to("/admin/ops").into_response()
+}
+
+/// Handle the `POST` request of the Ops page to use the app as another
+/// user, with a JWT of theirs that names the admin in its `act` claim.
+pub async fn impersonate_handler(
+    Extension(user): Extension<User>,
+    session: Session,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<ImpersonateSchema>,
+) -> impl IntoResponse {
+    // Not from the account of another user, even of an admin
+    if !user.is_admin || current_impersonator().is_some() {
+        return admins_only("the accounts of other users");
+    }
+
+    let target = match state.users.get_user_by_email(form_data.email.trim()).await {
+        Ok(Some(target)) if target.id != user.id => target,
+        Ok(Some(_)) => {
+            messages.error("You are already using your own account");
+            return Redirect::to("/admin/ops").into_response();
+        }
+        Ok(None) => {
+            messages.error(format!(
+                "There is no user with the email {}",
+                form_data.email
+            ));
+            return Redirect::to("/admin/ops").into_response();
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            )
+                .into_response()
+        }
+    };
+
+    audit_as(
+        &state,
+        &target.email,
+        AuditEvent::ImpersonationStarted,
+        Some(user.email),
+    )
+    .await;
+
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: target.id,
+        iat: now.timestamp() as usize,
+        exp: (now + IMPERSONATION_TTL).timestamp() as usize,
+        act: Some(user.id),
+    };
+    let headers = token_cookie(
+        &state,
+        &claims,
+        time::Duration::seconds(IMPERSONATION_TTL.num_seconds()),
+    );
+
+    // The first workspace of the user
+    let _ = session.remove::<i64>(WORKSPACE_KEY).await;
+    messages.info(format!("You are now using the app as {}", target.username));
+
+    (headers, Redirect::to("/todo/list")).into_response()
+}
+
+/// Handle the `POST` request of the impersonation banner, which logs
+/// the admin back into their own account.
+pub async fn impersonate_stop_handler(
+    Extension(user): Extension<User>,
+    session: Session,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let Some(admin) = current_impersonator() else {
+        return (
+            StatusCode::BAD_REQUEST,
+            render_error(
+                StatusCode::BAD_REQUEST,
+                "You are not using the app as another user",
+            ),
+        )
+            .into_response();
+    };
+
+    audit(&state, &user.email, AuditEvent::ImpersonationStopped).await;
+
+    let now = Utc::now();
+    let claims = TokenClaims {
+        sub: admin.id,
+        iat: now.timestamp() as usize,
+        exp: (now + state.config.jwt_expires_in).timestamp() as usize,
+        act: None,
+    };
+    let headers = token_cookie(
+        &state,
+        &claims,
+        time::Duration::minutes(state.config.jwt_maxage.into()),
+    );
+
+    let _ = session.remove::<i64>(WORKSPACE_KEY).await;
+    messages.info(format!("You are back in your account, {}", admin.username));
+
+    (headers, Redirect::to("/admin/ops")).into_response()
+}
+
+/// Collects the figures of the Ops page.
+async fn ops_template(state: &AppState) -> Result<OpsTemplate> {
+    let now = Utc::now();
+
+    // The memory and Redis stores can't be counted
+    let sessions = match state.config.session_store {
+        SessionStore::Database => Some(count_active_sessions(now, &state.read_pool).await?),
+        SessionStore::Memory | SessionStore::Redis => None,
+    };
+
+    // Listed only when they are enabled
+    let backups = match &state.config.backup_dir {
+        Some(dir) => Some(list_backups(dir).await?),
+        None => None,
+    };
+
+    Ok(OpsTemplate {
+        read_pool: pool_stats(&state.read_pool),
+        write_pool: pool_stats(&state.pool),
+        session_store: format!("{:?}", state.config.session_store).to_lowercase(),
+        sessions,
+        ws_connections: state.hub.connections(),
+        jobs: get_jobs(&state.read_pool).await?,
+        errors: state.recent_errors.list(),
+        counts: get_ops_counts(&state.read_pool).await?,
+        backups,
+        now: now.naive_utc(),
+        ..Default::default()
+    })
+}
+
+/// The connections of `pool` and how many of them are in use.
+pub(super) fn pool_stats(pool: &DbPool) -> PoolStats {
+    let size = pool.size();
+    let idle = pool.num_idle() as u32;
+
+    PoolStats {
+        size,
+        idle,
+        in_use: size.saturating_sub(idle),
+        max: pool.options().get_max_connections(),
+    }
+}
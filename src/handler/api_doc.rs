@@ -0,0 +1,71 @@
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::model::{
+    BatchOperation, BatchRequest, BatchResponse, BatchResult, BatchStatus, BuildInfo,
+    BulkEditSchema, DatabaseHealth, HealthCheckResponse, LoginUserSchema, QuickAddSchema,
+    RegisterUserSchema, SyncMutation, SyncRequest, SyncResponse, SyncResult, SyncStatus,
+    SyncTodoRef, Todo, TodoEditSchema, TodoSchema,
+};
+
+use super::{auth_handler, batch_handler, sync_handler, todo_handler};
+
+/// OpenAPI spec of the app, served with Swagger UI at `/api/docs`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Rust/Axum+Askama+Htmx Todo App"),
+    paths(
+        super::health_checker_handler,
+        auth_handler::register_user_handler,
+        auth_handler::login_user_handler,
+        todo_handler::todo_add_handler,
+        todo_handler::todo_quick_add_handler,
+        todo_handler::todo_api_list_handler,
+        todo_handler::todo_patch_handler,
+        todo_handler::todo_toggle_handler,
+        todo_handler::todo_bulk_handler,
+        todo_handler::todo_delete_handler,
+        batch_handler::todo_batch_handler,
+        sync_handler::sync_handler,
+    ),
+    components(schemas(
+        HealthCheckResponse,
+        BuildInfo,
+        DatabaseHealth,
+        RegisterUserSchema,
+        LoginUserSchema,
+        Todo,
+        TodoSchema,
+        QuickAddSchema,
+        TodoEditSchema,
+        BulkEditSchema,
+        BatchRequest,
+        BatchOperation,
+        BatchResponse,
+        BatchResult,
+        BatchStatus,
+        SyncRequest,
+        SyncMutation,
+        SyncTodoRef,
+        SyncResponse,
+        SyncResult,
+        SyncStatus,
+    )),
+    modifiers(&SecurityAddon)
+)]
+pub struct ApiDoc;
+
+/// Adds the JWT cookie set on login as the security scheme.
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "token",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("token"))),
+        );
+    }
+}
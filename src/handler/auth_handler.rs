@@ -2,27 +2,24 @@ use std::sync::Arc;
 
 use axum::{
     extract::State,
-    http::{header::SET_COOKIE, HeaderMap},
+    http::{header::SET_COOKIE, HeaderMap, HeaderName, StatusCode},
     response::{AppendHeaders, IntoResponse, Redirect, Response},
-    Form,
+    Extension, Form,
 };
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use axum_messages::Messages;
-use jsonwebtoken::{encode, EncodingKey, Header};
 use time::Duration;
-use tokio::sync::RwLock;
 use tower_sessions::Session;
 
 use crate::{
-    handler::set_tzone_in_session,
-    model::{LoginUserSchema, RegisterUserSchema, TokenClaims},
-    service::{check_email_password, create_user, get_all_todos},
+    handler::{resolve_timezone, set_date_format_in_session, set_tzone_in_session},
+    model::{AuditEvent, LoginUserSchema, RegisterUserSchema, TokenClaims, User, UserSettings},
     AppState,
 };
 
 use super::{
-    get_messages, set_flag_in_session, Error404Template, Error500Template, HomeTemplate,
-    HtmlTemplate, LoginTemplate, RegisterTemplate, FROM_PROTECTED_KEY,
+    audit, middleware::record_user_id, set_flag_in_session, BaseContext, ErrorTemplate,
+    HomeTemplate, HtmlTemplate, LoginTemplate, RegisterTemplate, FROM_PROTECTED_KEY,
 };
 
 /* --------------------------------------- */
@@ -30,167 +27,163 @@ use super::{
 /* --------------------------------------- */
 
 /// Handler to serve the Home Page template.
-pub async fn home_handler(session: Session) -> impl IntoResponse {
-    let from_protected: bool = session
-        .get(FROM_PROTECTED_KEY)
-        .await
-        .unwrap()
-        .unwrap_or_default();
-
-    // println!("FP - home page: {}", from_protected.0);
-
+pub async fn home_handler(ctx: BaseContext) -> impl IntoResponse {
     HtmlTemplate(HomeTemplate {
-        title: "Home".to_string(),
-        from_protected,
-        ..Default::default()
+        ctx: ctx.with_title("Home"),
     })
 }
 
 /// Handler to serve the Register Page template.
-pub async fn register_page_handler(session: Session, messages: Messages) -> impl IntoResponse {
-    let from_protected: bool = session
-        .get(FROM_PROTECTED_KEY)
-        .await
-        .unwrap()
-        .unwrap_or_default();
-
-    let (messages_status, messages) = get_messages(messages);
-
+pub async fn register_page_handler(ctx: BaseContext) -> impl IntoResponse {
     HtmlTemplate(RegisterTemplate {
-        title: "Register".to_string(),
-        messages_status,
-        messages,
-        from_protected,
-        ..Default::default()
+        ctx: ctx.with_title("Register"),
     })
 }
 
 /// Handle the `POST` request of the user register form.
+#[utoipa::path(
+    post,
+    path = "/register",
+    tag = "auth",
+    request_body(content = RegisterUserSchema, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirect to the login page, or back on error"))
+)]
 pub async fn register_user_handler(
     messages: Messages,
-    State(state): State<Arc<RwLock<AppState>>>,
+    State(state): State<Arc<AppState>>,
     Form(form_data): Form<RegisterUserSchema>,
 ) -> impl IntoResponse {
     // println!("{:?}", form_data);
 
-    let result = create_user(
-        form_data.email,
-        form_data.password,
-        form_data.username,
-        &state.read().await.pool,
-    )
-    .await;
+    let result = state
+        .users
+        .create_user(form_data.email, form_data.password, form_data.username)
+        .await;
 
-    if let Err(err) = result {
-        let err = format!("Something went wrong: {}", err);
-        messages.error(err);
+    let user = match result {
+        Ok(user) => user,
+        Err(err) => {
+            let err = format!("Something went wrong: {}", err);
+            messages.error(err);
 
-        return Redirect::to("/register");
-    }
+            return Redirect::to("/register");
+        }
+    };
+
+    audit(&state, &user.email, AuditEvent::Register).await;
 
-    // println!("{:?}", result.unwrap());
+    // The verification email is sent by a subscriber of the
+    // `UserRegistered` event, the account can be used right away
 
-    messages.success("You have successfully registered!!");
+    messages.success("You have successfully registered!! Check your email to verify your address.");
 
     Redirect::to("/login")
 }
 
 /// Handler to serve the Login Page template.
-pub async fn login_page_handler(session: Session, messages: Messages) -> impl IntoResponse {
-    let from_protected: bool = session
-        .get(FROM_PROTECTED_KEY)
-        .await
-        .unwrap()
-        .unwrap_or_default();
-
-    let (messages_status, messages) = get_messages(messages);
-
+pub async fn login_page_handler(ctx: BaseContext) -> impl IntoResponse {
     HtmlTemplate(LoginTemplate {
-        title: "Login".to_string(),
-        messages_status,
-        messages,
-        from_protected,
-        ..Default::default()
+        ctx: ctx.with_title("Login"),
     })
 }
 
 /// Handle the `POST` request of the user login form.
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "auth",
+    params((
+        "X-Timezone" = Option<String>,
+        Header,
+        description = "IANA timezone of the client, used when the user has none saved (UTC by default)"
+    )),
+    request_body(content = LoginUserSchema, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Sets the `token` cookie and redirects to the todo list"))
+)]
 pub async fn login_user_handler(
     headers: HeaderMap,
     session: Session,
     messages: Messages,
-    State(state): State<Arc<RwLock<AppState>>>,
+    State(state): State<Arc<AppState>>,
     Form(form_data): Form<LoginUserSchema>,
 ) -> Response {
-    let tzone = headers["x-timezone"].to_str().unwrap().to_string();
-    set_tzone_in_session(&session, tzone).await;
-
-    let result = check_email_password(
-        form_data.email,
-        form_data.password,
-        &state.read().await.pool,
-    )
-    .await;
+    let result = state
+        .users
+        .check_email_password(form_data.email.clone(), form_data.password)
+        .await;
 
     if let Err(err) = result {
+        audit(&state, &form_data.email, AuditEvent::LoginFailed).await;
+
         let err = format!("Something went wrong: {}", err);
         messages.error(err);
 
         return Redirect::to("/login").into_response();
     }
 
-    let user_id = result.unwrap().id;
+    let user = result.unwrap();
+    record_user_id(&user.id);
+    audit(&state, &user.email, AuditEvent::LoginSucceeded).await;
+
+    // Without them, the ones of the browser and the default date format
+    let settings = state
+        .settings
+        .get_user_settings(&user.id)
+        .await
+        .unwrap_or_else(|_| UserSettings::new(&user.id));
+    let tzone = resolve_timezone(settings.timezone.as_deref(), &headers, &state.geoip);
+    set_tzone_in_session(&session, tzone).await;
+    set_date_format_in_session(&session, settings.date_format()).await;
+
+    let user_id = user.id;
 
     let now = chrono::Utc::now();
     let iat = now.timestamp() as usize;
-    let exp = (now + chrono::Duration::minutes(60)).timestamp() as usize;
+    let exp = (now + state.config.jwt_expires_in).timestamp() as usize;
     let claims = TokenClaims {
         sub: user_id.clone(),
         exp,
         iat,
+        act: None,
     };
 
-    let token = encode(
-        &Header::default(),
+    let headers = token_cookie(
+        &state,
         &claims,
-        &EncodingKey::from_secret(&state.read().await.config.jwt_secret.as_ref()),
-    )
-    .unwrap();
-
-    let cookie = Cookie::build(("token", token.to_owned()))
-        .path("/")
-        .max_age(Duration::hours(1))
-        .same_site(SameSite::Lax)
-        .http_only(true);
-
-    let headers = AppendHeaders([(SET_COOKIE, cookie.to_string())]);
+        Duration::minutes(state.config.jwt_maxage.into()),
+    );
 
-    let lock = state.read().await;
-    let result = get_all_todos(user_id, &lock.pool).await;
-    if let Err(e) = result {
-        return HtmlTemplate(Error500Template {
-            title: "Error 500".to_string(),
-            reason: e.to_string(),
-            link: "/".to_string(),
-            is_error: true,
-            ..Default::default()
-        })
-        .into_response();
-    }
-    drop(lock);
+    messages.success("You have successfully logged in!!");
 
-    let mut lock = state.write().await;
+    (headers, Redirect::to("/todo/list")).into_response()
+}
 
-    lock.todos = result.unwrap();
-    drop(lock);
+/// Header setting the `token` cookie to a JWT with the claims,
+/// kept by the browser for `max_age`.
+pub(super) fn token_cookie(
+    state: &AppState,
+    claims: &TokenClaims,
+    max_age: Duration,
+) -> AppendHeaders<[(HeaderName, String); 1]> {
+    let token = state.jwt_keys.encode(claims).unwrap();
 
-    messages.success("You have successfully logged in!!");
+    let cookie = Cookie::build(("token", token))
+        .path("/")
+        .max_age(max_age)
+        .same_site(SameSite::Lax)
+        .http_only(true);
 
-    (headers, Redirect::to("/todo/list")).into_response()
+    AppendHeaders([(SET_COOKIE, cookie.to_string())])
 }
 
 /// User Logout Handler.
-pub async fn logout_handler(session: Session, messages: Messages) -> impl IntoResponse {
+pub async fn logout_handler(
+    Extension(user): Extension<User>,
+    session: Session,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    audit(&state, &user.email, AuditEvent::Logout).await;
     set_flag_in_session(&session, false).await;
 
     let cookie = Cookie::build(("token", ""))
@@ -224,12 +217,9 @@ pub async fn handler_404(session: Session) -> impl IntoResponse {
         "/".to_string()
     };
 
-    HtmlTemplate(Error404Template {
-        title: "Error 404".to_string(),
-        reason: "Nothing to see here".to_string(),
+    HtmlTemplate(ErrorTemplate {
         link,
-        is_error: true,
-        ..Default::default()
+        ..ErrorTemplate::new(StatusCode::NOT_FOUND, "Nothing to see here")
     })
 }
 
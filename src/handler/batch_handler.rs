@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use tracing::error;
+
+use crate::{
+    config::TextLimits,
+    import::parse_tags,
+    model::{
+        BatchOperation, BatchRequest, BatchResponse, BatchResult, BatchStatus, User, Workspace,
+    },
+    service::{BatchError, BatchOutcome},
+    AppState,
+};
+
+use super::validate_todo;
+
+/// Most operations accepted in a batch.
+const MAX_BATCH_OPERATIONS: usize = 100;
+
+/// Handle the `POST` request with the todos to create, update and delete
+/// at once (e.g. by importers), applied in order in one transaction. If
+/// one of the operations fails, none is applied.
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/batch",
+    tag = "todos",
+    request_body = BatchRequest,
+    responses(
+        (status = 200, description = "All the operations were applied", body = BatchResponse),
+        (status = 400, description = "No operations, or more than 100", body = String),
+        (status = 422, description = "None was applied, the one that failed is marked", body = BatchResponse),
+    ),
+    security(("token" = []))
+)]
+pub async fn todo_batch_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BatchRequest>,
+) -> Response {
+    let mut operations = request.operations;
+    if operations.is_empty() || operations.len() > MAX_BATCH_OPERATIONS {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "A batch must have from 1 to {} operations",
+                MAX_BATCH_OPERATIONS
+            ),
+        )
+            .into_response();
+    }
+
+    // Nothing is written unless all of them are valid
+    let limits = state.config.text_limits;
+    for (index, operation) in operations.iter().enumerate() {
+        if let Err(reason) = validate(operation, limits) {
+            return failed(operations.len(), BatchError { index, reason });
+        }
+    }
+
+    // The todos created count towards the limit all at once, so a
+    // batch refused for being too fast doesn't use any of it
+    let creates: Vec<usize> = operations
+        .iter()
+        .enumerate()
+        .filter(|(_, operation)| matches!(operation, BatchOperation::Create { .. }))
+        .map(|(index, _)| index)
+        .collect();
+    let create_limit = state.config.todo_create_limit;
+    if let Some(&index) = creates.get(create_limit) {
+        let reason = format!("a batch can create up to {} tasks", create_limit);
+        return failed(operations.len(), BatchError { index, reason });
+    }
+    if let Some(&index) = creates.first() {
+        if let Err(wait) = state
+            .todo_create_limiter
+            .check_many(&user.id, creates.len())
+        {
+            let reason = format!(
+                "creating tasks too fast, wait {} seconds",
+                wait.as_secs() + 1
+            );
+            return failed(operations.len(), BatchError { index, reason });
+        }
+    }
+
+    // Stored as the forms store them
+    for operation in operations.iter_mut() {
+        match operation {
+            BatchOperation::Create { tags, .. }
+            | BatchOperation::Update {
+                tags: Some(tags), ..
+            } => *tags = parse_tags(tags),
+            _ => {}
+        }
+    }
+
+    match state
+        .todos
+        .apply_todo_batch(user.id, workspace.id, &operations)
+        .await
+    {
+        Ok(outcomes) => Json(BatchResponse {
+            applied: true,
+            results: outcomes.into_iter().map(applied).collect(),
+        })
+        .into_response(),
+        Err(e) => match e.downcast::<BatchError>() {
+            Ok(e) => failed(operations.len(), e),
+            Err(e) => {
+                error!(
+                    "failed to apply a batch of {} operations: {}",
+                    operations.len(),
+                    e
+                );
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        },
+    }
+}
+
+/// Rejects what the forms wouldn't accept. The fields left out
+/// of an update are kept, so they aren't checked.
+fn validate(operation: &BatchOperation, limits: TextLimits) -> Result<(), String> {
+    let (title, description, priority) = match operation {
+        BatchOperation::Create {
+            title,
+            description,
+            priority,
+            ..
+        } => (Some(title), Some(description), Some(*priority)),
+        BatchOperation::Update {
+            title,
+            description,
+            priority,
+            ..
+        } => (title.as_ref(), description.as_ref(), *priority),
+        BatchOperation::Delete { .. } => return Ok(()),
+    };
+
+    let errors = validate_todo(
+        title.map_or("", String::as_str),
+        description.map_or("", String::as_str),
+        limits,
+    );
+    let mut reasons = vec![errors.get("description")];
+    if title.is_some() {
+        reasons.insert(0, errors.get("title"));
+    }
+    if priority.is_some_and(|priority| !(0..=3).contains(&priority)) {
+        reasons.push("The priority must be from 0 to 3");
+    }
+
+    reasons.retain(|reason| !reason.is_empty());
+    if reasons.is_empty() {
+        return Ok(());
+    }
+
+    Err(reasons.join(" "))
+}
+
+fn applied(outcome: BatchOutcome) -> BatchResult {
+    let (status, todo) = match outcome {
+        BatchOutcome::Created(todo) => (BatchStatus::Created, Some(todo)),
+        BatchOutcome::Updated(todo) => (BatchStatus::Updated, Some(todo)),
+        BatchOutcome::Deleted(_) => (BatchStatus::Deleted, None),
+    };
+
+    BatchResult {
+        status,
+        todo,
+        error: None,
+    }
+}
+
+/// The results of a batch that wasn't applied because of `error`.
+fn failed(len: usize, error: BatchError) -> Response {
+    let results = (0..len)
+        .map(|index| {
+            if index == error.index {
+                BatchResult {
+                    status: BatchStatus::Failed,
+                    todo: None,
+                    error: Some(error.reason.clone()),
+                }
+            } else {
+                BatchResult {
+                    status: BatchStatus::Aborted,
+                    todo: None,
+                    error: None,
+                }
+            }
+        })
+        .collect();
+
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(BatchResponse {
+            applied: false,
+            results,
+        }),
+    )
+        .into_response()
+}
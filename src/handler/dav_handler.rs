@@ -0,0 +1,443 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+};
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderName, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::{NaiveDateTime, Utc};
+use tracing::error;
+
+use crate::{
+    ical,
+    model::{DavResource, Todo, TodoFilter, User, Workspace},
+    service::{TodoBlockedError, TodoConflictError},
+    AppState,
+};
+
+use super::{client_timezone, validate_todo, DavEntry, DavMultistatusTemplate, DavResponse};
+
+/// Methods of the CalDAV endpoint, announced to the clients by `OPTIONS`.
+const ALLOWED_METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, REPORT";
+
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Handler of `/dav/`, the principal and calendar home of the user,
+/// whose calendars are the workspaces they are a member of.
+pub async fn dav_home_handler(
+    method: Method,
+    headers: HeaderMap,
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, Response> {
+    match method.as_str() {
+        "OPTIONS" => Ok(options()),
+        "PROPFIND" => {
+            let mut responses = vec![DavResponse {
+                href: "/dav/".to_string(),
+                entry: DavEntry::Home(user.username.clone()),
+            }];
+
+            if depth(&headers) > 0 {
+                let workspaces = state
+                    .workspaces
+                    .get_user_workspaces(&user.id)
+                    .await
+                    .map_err(internal_error)?;
+
+                for workspace in workspaces {
+                    let calendar = Calendar::load(&state, workspace).await?;
+                    responses.push(calendar.response());
+                }
+            }
+
+            Ok(multistatus(responses))
+        }
+        _ => Ok(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    }
+}
+
+/// Handler of `/dav/:workspace_id/`, the calendar with the todos of a
+/// workspace. The `calendar-query` reports get all the todos, as
+/// their filters aren't applied.
+pub async fn dav_calendar_handler(
+    method: Method,
+    headers: HeaderMap,
+    Extension(user): Extension<User>,
+    Path(workspace_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<Response, Response> {
+    let calendar = Calendar::find(&state, &user, workspace_id).await?;
+    let now = Utc::now().naive_utc();
+
+    let responses = match method.as_str() {
+        "OPTIONS" => return Ok(options()),
+        "PROPFIND" => {
+            let mut responses = vec![calendar.response()];
+            if depth(&headers) > 0 {
+                responses.extend(
+                    calendar
+                        .todos
+                        .iter()
+                        .map(|todo| calendar.todo_response(todo, None)),
+                );
+            }
+
+            responses
+        }
+        "REPORT" if body.contains("calendar-multiget") => hrefs(&body)
+            .map(|href| {
+                let file_name = href.trim_end_matches('/').rsplit('/').next();
+                match file_name.and_then(|file_name| calendar.get(file_name)) {
+                    Some(todo) => calendar.todo_response(todo, Some(now)),
+                    None => DavResponse {
+                        href: href.to_string(),
+                        entry: DavEntry::NotFound,
+                    },
+                }
+            })
+            .collect(),
+        "REPORT" => calendar
+            .todos
+            .iter()
+            .map(|todo| calendar.todo_response(todo, Some(now)))
+            .collect(),
+        _ => return Ok(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    };
+
+    Ok(multistatus(responses))
+}
+
+/// Handler of `/dav/:workspace_id/:file_name`, a todo as an iCalendar
+/// object. The clients can create todos under the name they choose.
+pub async fn dav_todo_handler(
+    method: Method,
+    headers: HeaderMap,
+    Extension(user): Extension<User>,
+    Path((workspace_id, file_name)): Path<(i64, String)>,
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<Response, Response> {
+    let calendar = Calendar::find(&state, &user, workspace_id).await?;
+    let todo = calendar.get(&file_name);
+
+    match (method.as_str(), todo) {
+        ("OPTIONS", _) => Ok(options()),
+        ("PUT", _) => put_todo(&state, &user, &calendar, todo, &file_name, &headers, &body).await,
+        ("GET" | "HEAD", Some(todo)) => {
+            let data = ical::write_vtodo(todo, &calendar.uid(todo), Utc::now().naive_utc());
+
+            Ok((
+                [
+                    (header::CONTENT_TYPE, CALENDAR_CONTENT_TYPE.to_string()),
+                    (header::ETAG, etag(todo)),
+                ],
+                data,
+            )
+                .into_response())
+        }
+        ("PROPFIND", Some(todo)) => Ok(multistatus(vec![calendar.todo_response(todo, None)])),
+        ("DELETE", Some(todo)) => {
+            if preconditions_fail(&headers, Some(todo)) {
+                return Err(StatusCode::PRECONDITION_FAILED.into_response());
+            }
+            state
+                .todos
+                .remove_todo(todo.id, workspace_id)
+                .await
+                .map_err(internal_error)?;
+
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        ("GET" | "HEAD" | "PROPFIND" | "DELETE", None) => {
+            Err(StatusCode::NOT_FOUND.into_response())
+        }
+        _ => Ok(StatusCode::METHOD_NOT_ALLOWED.into_response()),
+    }
+}
+
+/// Creates or updates a todo from the iCalendar object sent by the
+/// client. Only the fields that can be edited in the app (title,
+/// description and status) are updated on the existing todos.
+async fn put_todo(
+    state: &AppState,
+    user: &User,
+    calendar: &Calendar,
+    todo: Option<&Todo>,
+    file_name: &str,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, Response> {
+    let Some(name) = file_name.strip_suffix(".ics") else {
+        return Err(bad_request("the name of the task must end with .ics"));
+    };
+    if preconditions_fail(headers, todo) {
+        return Err(StatusCode::PRECONDITION_FAILED.into_response());
+    }
+
+    let settings = state
+        .settings
+        .get_user_settings(&user.id)
+        .await
+        .map_err(internal_error)?;
+    let tz = client_timezone(settings.timezone.as_deref().unwrap_or_default());
+    let vtodo = ical::parse_vtodo(body, tz).map_err(|e| bad_request(&e.to_string()))?;
+
+    let errors = validate_todo(&vtodo.summary, &vtodo.description, state.config.text_limits);
+    if !errors.is_empty() {
+        return Err(bad_request(
+            format!("{} {}", errors.get("title"), errors.get("description")).trim(),
+        ));
+    }
+
+    let workspace_id = calendar.workspace.id;
+
+    let Some(todo) = todo else {
+        let todo = state
+            .todos
+            .add_todo(
+                user.id.clone(),
+                workspace_id,
+                vtodo.summary,
+                vtodo.description,
+                vtodo.due_at,
+                vtodo.priority,
+                vtodo.tags,
+            )
+            .await
+            .map_err(internal_error)?;
+
+        if vtodo.completed {
+            state
+                .todos
+                .toggle_todo(todo.id, workspace_id)
+                .await
+                .map_err(internal_error)?;
+        }
+
+        let uid = if vtodo.uid.is_empty() {
+            name.to_string()
+        } else {
+            vtodo.uid
+        };
+        state
+            .todos
+            .add_dav_resource(todo.id, workspace_id, name.to_string(), uid)
+            .await
+            .map_err(internal_error)?;
+
+        return Ok(StatusCode::CREATED.into_response());
+    };
+
+    // The version is checked again, in case it was edited meanwhile
+    let result = state
+        .todos
+        .update_todo(
+            vtodo.summary,
+            vtodo.description,
+            vtodo.completed,
+            todo.remind_at,
+            None,
+            todo.id,
+            Some(todo.version),
+            workspace_id,
+        )
+        .await;
+
+    match result {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(e) if e.is::<TodoConflictError>() => {
+            Err(StatusCode::PRECONDITION_FAILED.into_response())
+        }
+        Err(e) if e.is::<TodoBlockedError>() => {
+            Err((StatusCode::CONFLICT, e.to_string()).into_response())
+        }
+        Err(e) => Err(internal_error(e)),
+    }
+}
+
+/// The todos of a workspace, along with the names the clients
+/// gave to the ones they created.
+struct Calendar {
+    workspace: Workspace,
+    todos: Vec<Todo>,
+    resources: HashMap<i64, DavResource>,
+}
+
+impl Calendar {
+    /// The calendar of a workspace the user is a member of.
+    async fn find(state: &AppState, user: &User, workspace_id: i64) -> Result<Self, Response> {
+        let workspace = state
+            .workspaces
+            .get_user_workspaces(&user.id)
+            .await
+            .map_err(internal_error)?
+            .into_iter()
+            .find(|workspace| workspace.id == workspace_id)
+            .ok_or_else(|| StatusCode::NOT_FOUND.into_response())?;
+
+        Self::load(state, workspace).await
+    }
+
+    async fn load(state: &AppState, workspace: Workspace) -> Result<Self, Response> {
+        let todos = state
+            .todos
+            .get_filtered_todos(workspace.id, &TodoFilter::default())
+            .await
+            .map_err(internal_error)?;
+        let resources = state
+            .todos
+            .get_dav_resources(workspace.id)
+            .await
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|resource| (resource.todo_id, resource))
+            .collect();
+
+        Ok(Self {
+            workspace,
+            todos,
+            resources,
+        })
+    }
+
+    /// The todo served under that file name.
+    fn get(&self, file_name: &str) -> Option<&Todo> {
+        let name = file_name.strip_suffix(".ics")?;
+        let todo_id = match self
+            .resources
+            .values()
+            .find(|resource| resource.name == name)
+        {
+            Some(resource) => resource.todo_id,
+            None => name
+                .parse()
+                .ok()
+                .filter(|todo_id| !self.resources.contains_key(todo_id))?,
+        };
+
+        self.todos.iter().find(|todo| todo.id == todo_id)
+    }
+
+    fn href(&self, todo: &Todo) -> String {
+        match self.resources.get(&todo.id) {
+            Some(resource) => format!("/dav/{}/{}.ics", self.workspace.id, resource.name),
+            None => format!("/dav/{}/{}.ics", self.workspace.id, todo.id),
+        }
+    }
+
+    fn uid(&self, todo: &Todo) -> String {
+        match self.resources.get(&todo.id) {
+            Some(resource) => resource.uid.clone(),
+            None => format!("todo-{}", todo.id),
+        }
+    }
+
+    fn response(&self) -> DavResponse {
+        // Changes with every edit of a todo, so the clients know
+        // when they have to look for the changed ones
+        let mut hasher = DefaultHasher::new();
+        for todo in &self.todos {
+            (todo.id, todo.version).hash(&mut hasher);
+        }
+
+        DavResponse {
+            href: format!("/dav/{}/", self.workspace.id),
+            entry: DavEntry::Calendar(
+                self.workspace.name.clone(),
+                format!("{:x}", hasher.finish()),
+            ),
+        }
+    }
+
+    /// The properties of a todo, with its iCalendar object at `now` if given.
+    fn todo_response(&self, todo: &Todo, now: Option<NaiveDateTime>) -> DavResponse {
+        DavResponse {
+            href: self.href(todo),
+            entry: DavEntry::Todo(
+                etag(todo),
+                now.map(|now| ical::write_vtodo(todo, &self.uid(todo), now)),
+            ),
+        }
+    }
+}
+
+/// Changes with every edit of the todo.
+fn etag(todo: &Todo) -> String {
+    format!("\"{}-{}\"", todo.id, todo.version)
+}
+
+/// Whether the `If-Match` or `If-None-Match` headers of the client
+/// fail, so it doesn't overwrite a change it hasn't seen yet.
+fn preconditions_fail(headers: &HeaderMap, todo: Option<&Todo>) -> bool {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let matches = |tags: &str, todo: &Todo| {
+        tags.split(',')
+            .any(|tag| tag.trim() == "*" || tag.trim() == etag(todo))
+    };
+
+    match (
+        header(header::IF_MATCH),
+        header(header::IF_NONE_MATCH),
+        todo,
+    ) {
+        (Some(_), _, None) => true,
+        (Some(tags), _, Some(todo)) => !matches(tags, todo),
+        (None, Some(tags), Some(todo)) => matches(tags, todo),
+        _ => false,
+    }
+}
+
+/// The `href` elements of a `calendar-multiget` report.
+fn hrefs(body: &str) -> impl Iterator<Item = &str> {
+    body.split('<').filter_map(|element| {
+        let (tag, text) = element.split_once('>')?;
+        let tag = tag.trim();
+        (tag == "href" || tag.ends_with(":href")).then(|| text.trim())
+    })
+}
+
+/// `Depth` of a `PROPFIND`, `infinity` (the default) being taken as `1`.
+fn depth(headers: &HeaderMap) -> u8 {
+    match headers.get("depth").and_then(|value| value.to_str().ok()) {
+        Some("0") => 0,
+        _ => 1,
+    }
+}
+
+fn options() -> Response {
+    [
+        (header::ALLOW, ALLOWED_METHODS),
+        (HeaderName::from_static("dav"), "1, calendar-access"),
+    ]
+    .into_response()
+}
+
+fn multistatus(responses: Vec<DavResponse>) -> Response {
+    match (DavMultistatusTemplate { responses }).render() {
+        Ok(xml) => (
+            StatusCode::MULTI_STATUS,
+            [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+            xml,
+        )
+            .into_response(),
+        Err(e) => internal_error(e.into()),
+    }
+}
+
+fn bad_request(message: &str) -> Response {
+    (StatusCode::BAD_REQUEST, message.to_string()).into_response()
+}
+
+fn internal_error(e: anyhow::Error) -> Response {
+    error!("CalDAV request failed: {}", e);
+
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
@@ -0,0 +1,130 @@
+use std::any::Any;
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    BoxError,
+};
+use tower_sessions::Session;
+use tracing::error;
+
+use super::{ErrorPage, ErrorTemplate, ErrorToastTemplate, HtmlTemplate, FROM_PROTECTED_KEY};
+
+/// Middleware that answers the HTMX requests ending in an error page with
+/// a toast instead, so the page doesn't get swapped into a small target.
+/// Boosted requests are page navigations, so they still get the full page.
+pub async fn htmx_error_middleware(req: Request, next: Next) -> Response {
+    let headers = req.headers();
+    if !headers.contains_key("hx-request") || headers.contains_key("hx-boosted") {
+        return next.run(req).await;
+    }
+
+    let response = next.run(req).await;
+
+    let Some(error) = response.extensions().get::<ErrorPage>().cloned() else {
+        return response;
+    };
+
+    // HTMX doesn't swap error statuses, so the toast goes with a 200
+    (
+        [("HX-Retarget", "#toasts"), ("HX-Reswap", "beforeend")],
+        HtmlTemplate(ErrorToastTemplate {
+            status: error.status.as_u16(),
+            reason: error.reason,
+            reference_id: Default::default(),
+        }),
+    )
+        .into_response()
+}
+
+/// Middleware that replaces the bare `405` responses of the router
+/// (a known path with the wrong method) with the error page.
+pub async fn method_not_allowed_middleware(session: Session, req: Request, next: Next) -> Response {
+    let response = next.run(req).await;
+
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+
+    let from_protected: bool = session
+        .get(FROM_PROTECTED_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    let link = if from_protected {
+        "/todo/list".to_string()
+    } else {
+        "/".to_string()
+    };
+
+    // Keep the `Allow` header set by the router
+    let (parts, _) = response.into_parts();
+
+    (
+        parts,
+        HtmlTemplate(ErrorTemplate {
+            link,
+            ..ErrorTemplate::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "This method is not allowed here",
+            )
+        }),
+    )
+        .into_response()
+}
+
+/// Shows the error page when a handler panics, instead of dropping the connection.
+pub fn handle_panic(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("unknown panic");
+
+    error!("handler panicked: {}", message);
+
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        HtmlTemplate(ErrorTemplate {
+            link: "/".to_string(),
+            ..ErrorTemplate::unreported(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "The server failed while handling the request",
+            )
+        }),
+    )
+        .into_response()
+}
+
+/// Turns the errors of the middleware stack (the timeouts, and the
+/// heavy routes turning requests away) into the error page.
+pub async fn handle_timeout_error(err: BoxError) -> Response {
+    let (status, reason) = if err.is::<tower::timeout::error::Elapsed>() {
+        (
+            StatusCode::REQUEST_TIMEOUT,
+            "The request took too long to complete".to_string(),
+        )
+    } else if err.is::<tower::load_shed::error::Overloaded>() {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The server is busy, please try again in a moment".to_string(),
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Unhandled internal error: {}", err),
+        )
+    };
+
+    (
+        status,
+        HtmlTemplate(ErrorTemplate {
+            link: "/".to_string(),
+            ..ErrorTemplate::new(status, reason)
+        }),
+    )
+        .into_response()
+}
@@ -0,0 +1,194 @@
+use std::{fmt::Write, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+    Extension,
+};
+use chrono::Utc;
+use futures_util::{stream, StreamExt};
+use tower_sessions::Session;
+
+use crate::{
+    model::{DateFormat, Todo, TodoFilter, Workspace},
+    AppState,
+};
+
+use super::{convert_datetime, DATE_FORMAT_KEY, TZONE_KEY};
+
+/// Handler of `GET /todo/export.md`, the todos of the workspace matching
+/// the filter of the list as a Markdown checklist, the open ones first.
+/// The body is streamed: each section is read from the database once
+/// the previous one has been sent.
+pub async fn todo_export_handler(
+    Extension(workspace): Extension<Workspace>,
+    Query(filter): Query<TodoFilter>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    let title = format!(
+        "# {}\n\n_Exported on {}_\n",
+        escape(&workspace.name),
+        convert_datetime(&tzone, Utc::now().naive_utc(), date_format).unwrap_or_default()
+    );
+
+    let statuses = match filter.status.as_str() {
+        "open" => vec!["open"],
+        "done" => vec!["done"],
+        _ => vec!["open", "done"],
+    };
+    let file_name = format!("{}.md", file_stem(&workspace.name));
+    let workspace_id = workspace.id;
+
+    let sections = stream::iter(statuses).then(move |status| {
+        let state = state.clone();
+        let filter = TodoFilter {
+            status: status.to_string(),
+            ..filter.clone()
+        };
+        let tzone = tzone.clone();
+
+        async move { section(&state, workspace_id, &filter, &tzone, date_format).await }
+    });
+    let body = stream::once(async { anyhow::Ok(title) }).chain(sections);
+
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/markdown; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+}
+
+/// The todos with the status of the filter, with their checklists
+/// as nested items. Empty when there are none.
+async fn section(
+    state: &AppState,
+    workspace_id: i64,
+    filter: &TodoFilter,
+    tzone: &str,
+    date_format: DateFormat,
+) -> Result<String> {
+    let todos = state.todos.get_filtered_todos(workspace_id, filter).await?;
+    if todos.is_empty() {
+        return Ok(String::new());
+    }
+    let subtasks = state.todos.get_subtasks(workspace_id).await?;
+
+    let heading = if filter.status == "done" {
+        "Done"
+    } else {
+        "To do"
+    };
+    let mut markdown = format!("\n## {} ({})\n\n", heading, todos.len());
+
+    for todo in &todos {
+        write_item(&mut markdown, todo, tzone, date_format)?;
+
+        for subtask in subtasks.iter().filter(|s| s.todo_id == todo.id) {
+            writeln!(
+                markdown,
+                "  - [{}] {}",
+                if subtask.done { "x" } else { " " },
+                escape(&subtask.title)
+            )?;
+        }
+    }
+
+    Ok(markdown)
+}
+
+/// Writes a todo as a task list item with its due date (or completion
+/// date), priority and tags, followed by its description, which is
+/// Markdown already.
+fn write_item(
+    markdown: &mut String,
+    todo: &Todo,
+    tzone: &str,
+    date_format: DateFormat,
+) -> Result<()> {
+    let mut details = Vec::new();
+    match (todo.status, todo.completed_at, todo.due_at) {
+        (true, Some(completed_at), _) => details.push(format!(
+            "done {}",
+            convert_datetime(tzone, completed_at, date_format)?
+        )),
+        (false, _, Some(due_at)) => details.push(format!(
+            "due {}",
+            convert_datetime(tzone, due_at, date_format)?
+        )),
+        _ => {}
+    }
+    if todo.priority > 0 {
+        details.push(format!("{} priority", todo.priority_label()));
+    }
+    details.extend(todo.tag_list().iter().map(|tag| format!("`#{}`", tag)));
+
+    write!(
+        markdown,
+        "- [{}] {}",
+        if todo.status { "x" } else { " " },
+        escape(&todo.title)
+    )?;
+    if !details.is_empty() {
+        write!(markdown, " — {}", details.join(" · "))?;
+    }
+    writeln!(markdown)?;
+
+    // Indented, so it stays inside the item
+    for line in todo.description.lines() {
+        if line.trim().is_empty() {
+            writeln!(markdown)?;
+        } else {
+            writeln!(markdown, "  {}", line)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Escapes the characters that Markdown would read as formatting.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>#|~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Name of the downloaded file, from the name of the workspace.
+fn file_stem(name: &str) -> String {
+    let stem = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+
+    if stem.is_empty() {
+        "todos".to_string()
+    } else {
+        stem
+    }
+}
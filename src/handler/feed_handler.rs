@@ -0,0 +1,109 @@
+use std::sync::Arc;
+
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use chrono::Utc;
+
+use crate::{model::User, AppState};
+
+use super::{middleware::record_user_id, render_error, AtomFeedTemplate};
+
+/// Maximum number of entries in the feed.
+const FEED_ENTRIES: i64 = 50;
+
+/// Handler that redirects the logged-in user to a signed URL of their
+/// feed, valid for `FEED_LINK_TTL`, creating the feed token on first use.
+pub async fn feed_link_handler(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.users.get_or_create_feed_token(user.id).await {
+        Ok(token) => {
+            let url = state
+                .url_signer
+                .sign(&format!("/feed/{}.atom", token), state.config.feed_link_ttl);
+
+            Redirect::to(&url).into_response()
+        }
+        Err(e) => render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Handler to serve the Atom feed of the user owning the secret token,
+/// with the todos of their first workspace. The token and the signature
+/// of the URL (checked by `signed_url_middleware`) are the
+/// authentication, so feed readers can poll it until it expires.
+pub async fn feed_handler(
+    Path(file_name): Path<String>,
+    uri: Uri,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let Some(token) = file_name.strip_suffix(".atom") else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let user = match state.users.get_user_by_feed_token(token).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    record_user_id(&user.id);
+
+    let workspace = match state.workspaces.get_user_workspaces(&user.id).await {
+        Ok(workspaces) => workspaces.into_iter().next(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let todos = match workspace {
+        Some(workspace) => {
+            state
+                .todos
+                .get_recent_todos(workspace.id, FEED_ENTRIES)
+                .await
+        }
+        None => Ok(Vec::new()),
+    };
+
+    let todos = match todos {
+        Ok(todos) => todos,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let host = headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .unwrap_or("localhost");
+
+    let updated = todos
+        .first()
+        .map(|todo| todo.created_at)
+        .unwrap_or_else(|| Utc::now().naive_utc());
+
+    let feed = AtomFeedTemplate {
+        base_url: format!("http://{}", host),
+        token: token.to_string(),
+        self_url: uri.to_string(),
+        username: user.username,
+        updated,
+        todos,
+    };
+
+    match feed.render() {
+        Ok(xml) => (
+            [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+            xml,
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to render template. Error: {}", err),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_messages::Messages;
+
+use crate::{
+    model::{SavedFilterSchema, User},
+    AppState,
+};
+
+use super::{render_error, todo_handler::QueryParams};
+
+/// Handle the `POST` request to save the current filter under a name.
+pub async fn filter_save_handler(
+    Extension(user): Extension<User>,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<SavedFilterSchema>,
+) -> impl IntoResponse {
+    if form_data.name.trim() == "" {
+        return render_error(
+            StatusCode::BAD_REQUEST,
+            "You must enter a name for the filter",
+        )
+        .into_response();
+    }
+
+    let result = state
+        .todos
+        .add_saved_filter(user.id, form_data.name.trim().to_string(), form_data.filter)
+        .await;
+
+    match result {
+        Ok(saved_filter) => {
+            messages.success("Filter saved successfully!!");
+
+            Redirect::to(&format!("/todo/list?filter={}", saved_filter.id)).into_response()
+        }
+        Err(e) => {
+            let err = format!("Something went wrong: {}", e);
+            messages.error(err);
+
+            Redirect::to("/todo/list").into_response()
+        }
+    }
+}
+
+/// Handle the `DELETE` request to remove a saved filter.
+pub async fn filter_delete_handler(
+    Extension(user): Extension<User>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.todos.remove_saved_filter(id, user.id).await {
+        Ok(_) => {
+            messages.success("Filter successfully deleted!!");
+
+            Redirect::to("/todo/list").into_response()
+        }
+        Err(e) => render_error(StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    }
+}
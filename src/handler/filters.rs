@@ -0,0 +1,125 @@
+//! Custom filters of the templates. Askama finds them by name
+//! in this module, which is in scope of the template structs.
+
+use std::fmt::Display;
+
+use chrono::{NaiveDateTime, Utc};
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+
+use crate::model::DateFormat;
+
+use super::{convert_datetime, time_ago};
+
+/// Versioned URL of a static asset, so browsers fetch it again
+/// when its content changes: `{{ "/assets/css/main.css"|asset }}`.
+pub fn asset<T: Display>(path: T) -> askama::Result<String> {
+    Ok(crate::assets::url(&path.to_string()))
+}
+
+/// Value of the `integrity` attribute of a script or a stylesheet:
+/// `integrity="{{ "/assets/js/htmx.min.js"|integrity }}"`.
+pub fn integrity<T: Display>(path: T) -> askama::Result<String> {
+    Ok(crate::assets::integrity(&path.to_string()))
+}
+
+/// A UTC datetime from the database in the timezone and date format
+/// of the user: `{{ todo.created_at|localdatetime(tzone, date_format) }}`.
+pub fn localdatetime(
+    dt: &NaiveDateTime,
+    tzone: &str,
+    format: &DateFormat,
+) -> askama::Result<String> {
+    convert_datetime(tzone, *dt, *format).map_err(askama::Error::Fmt)
+}
+
+/// How long ago a UTC datetime from the database was, in the timezone and
+/// date format of the user (see `time_ago`), e.g. "2 h ago" or "yesterday":
+/// `{{ todo.created_at|timeago(tzone, date_format) }}`.
+pub fn timeago(dt: &NaiveDateTime, tzone: &str, format: &DateFormat) -> askama::Result<String> {
+    Ok(time_ago(tzone, *dt, Utc::now().naive_utc(), *format))
+}
+
+/// How far a UTC datetime is from now, e.g. "3 hours ago" or "in 2 days".
+pub fn relative_time(dt: &NaiveDateTime) -> askama::Result<String> {
+    Ok(relative_to(*dt, Utc::now().naive_utc()))
+}
+
+fn relative_to(dt: NaiveDateTime, now: NaiveDateTime) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+
+    let seconds = (dt - now).num_seconds();
+    let (amount, unit) = match seconds.abs() {
+        s if s < MINUTE => return "just now".to_string(),
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s if s < 30 * DAY => (s / DAY, "day"),
+        s if s < 365 * DAY => (s / (30 * DAY), "month"),
+        s => (s / (365 * DAY), "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+
+    if seconds > 0 {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
+
+/// The first `count` words of a text, ending in an ellipsis if it
+/// had more. The spacing between the words is kept.
+pub fn truncate_words<T: Display>(text: T, count: usize) -> askama::Result<String> {
+    let text = text.to_string();
+    let mut words = 0;
+    let mut in_word = false;
+
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            in_word = false;
+        } else if !in_word {
+            in_word = true;
+            words += 1;
+            if words > count {
+                return Ok(format!("{}…", text[..i].trim_end()));
+            }
+        }
+    }
+
+    Ok(text)
+}
+
+/// Renders a text written in Markdown as HTML: `{{ text|markdown_html|safe }}`.
+/// (`markdown` is the name of the Askama filter that needs comrak.)
+/// Raw HTML is escaped, images are left as their alt text and
+/// only the links to web pages and email addresses are kept.
+pub fn markdown_html<T: Display>(text: T) -> askama::Result<String> {
+    let text = text.to_string();
+    // Whether each open link is kept, so its end matches
+    let mut links = Vec::new();
+
+    let events =
+        Parser::new_ext(&text, Options::ENABLE_STRIKETHROUGH).filter_map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+            Event::Start(Tag::Link { ref dest_url, .. }) => {
+                let kept = is_safe_url(dest_url);
+                links.push(kept);
+                kept.then_some(event)
+            }
+            Event::End(TagEnd::Link) => links.pop().unwrap_or_default().then_some(event),
+            Event::Start(Tag::Image { .. }) | Event::End(TagEnd::Image) => None,
+            event => Some(event),
+        });
+
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+
+    Ok(output)
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let url = url.to_ascii_lowercase();
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
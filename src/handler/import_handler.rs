@@ -0,0 +1,147 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{multipart::Field, Multipart, State},
+    response::{IntoResponse, Redirect},
+    Extension,
+};
+use axum_messages::Messages;
+use tower_sessions::Session;
+
+use crate::{
+    import::{parse_export, ImportedTodo},
+    model::{User, Workspace},
+    sanitize::truncate,
+    AppState,
+};
+
+use super::{BaseContext, HtmlTemplate, ImportTemplate, IMPORT_KEY};
+
+/// Handler to serve the Import Page template (upload step).
+pub async fn import_page_handler(ctx: BaseContext, session: Session) -> impl IntoResponse {
+    // Starting over discards any pending preview
+    session
+        .remove::<Vec<ImportedTodo>>(IMPORT_KEY)
+        .await
+        .unwrap();
+
+    HtmlTemplate(ImportTemplate {
+        ctx: ctx.with_title("Import"),
+        ..Default::default()
+    })
+}
+
+/// Handle the `POST` request with the uploaded export file,
+/// rendering a preview of the todos that would be created.
+pub async fn import_preview_handler(
+    ctx: BaseContext,
+    session: Session,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut file: Option<(String, Vec<u8>)> = None;
+
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            match read_upload(field, state.config.max_upload_size).await {
+                Ok(data) => file = Some((file_name, data)),
+                Err(e) => {
+                    messages.error(format!("Something went wrong: {}", e));
+
+                    return Redirect::to("/settings/import").into_response();
+                }
+            }
+        }
+    }
+
+    let result = match file {
+        Some((file_name, data)) if !data.is_empty() => parse_export(&file_name, &data),
+        _ => Err(anyhow::anyhow!("you must select an export file.")),
+    };
+
+    // Long texts are cut to the limits, rather than rejecting the whole file
+    let limits = state.config.text_limits;
+    let result = result.map(|todos| {
+        todos
+            .into_iter()
+            .map(|todo| ImportedTodo {
+                title: truncate(&todo.title, limits.title),
+                description: truncate(&todo.description, limits.description),
+                ..todo
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let preview = match result {
+        Ok(preview) => preview,
+        Err(e) => {
+            messages.error(format!("Something went wrong: {}", e));
+
+            return Redirect::to("/settings/import").into_response();
+        }
+    };
+
+    session.insert(IMPORT_KEY, &preview).await.unwrap();
+
+    HtmlTemplate(ImportTemplate {
+        preview,
+        ctx: ctx.with_title("Import"),
+    })
+    .into_response()
+}
+
+/// Reads an uploaded file chunk by chunk, stopping as soon as it
+/// is larger than `max_size` (`MAX_UPLOAD_SIZE_KB`).
+async fn read_upload(mut field: Field<'_>, max_size: usize) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+
+    while let Some(chunk) = field.chunk().await? {
+        if data.len() + chunk.len() > max_size {
+            anyhow::bail!("the file can't be larger than {} KB.", max_size / 1024);
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    Ok(data)
+}
+
+/// Handle the `POST` request confirming the previewed import,
+/// which creates all the todos in a single transaction.
+pub async fn import_confirm_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    session: Session,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let preview: Vec<ImportedTodo> = session
+        .remove(IMPORT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    if preview.is_empty() {
+        messages.error("Something went wrong: there is nothing to import.");
+
+        return Redirect::to("/settings/import").into_response();
+    }
+
+    match state
+        .todos
+        .add_imported_todos(user.id, workspace.id, preview)
+        .await
+    {
+        Ok(count) => {
+            messages.success(format!("{} tasks imported successfully!!", count));
+
+            Redirect::to("/todo/list").into_response()
+        }
+        Err(e) => {
+            messages.error(format!("Something went wrong: {}", e));
+
+            Redirect::to("/settings/import").into_response()
+        }
+    }
+}
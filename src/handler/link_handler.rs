@@ -0,0 +1,99 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Form,
+};
+use tower_sessions::Session;
+use tracing::warn;
+
+use crate::{
+    link_preview,
+    model::{LinkSchema, User, Workspace},
+    repo::TodoRepo,
+    AppState,
+};
+
+use super::{
+    render_error, retarget_body,
+    todo_handler::{todo_changed_response, QueryParams},
+    FlashMessage,
+};
+
+/// Handle the `POST` request to attach a URL to a Todo.
+/// The preview of the page is fetched in a background task.
+pub async fn link_add_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<LinkSchema>,
+) -> impl IntoResponse {
+    let url = match link_preview::parse_url(&form_data.url) {
+        Ok(url) => url.to_string(),
+        Err(e) => return retarget_body(render_error(StatusCode::BAD_REQUEST, e.to_string())),
+    };
+
+    match state.todos.add_link(id, url.clone(), workspace.id).await {
+        Ok(link_id) => {
+            tokio::spawn(fetch_preview(link_id, url, state.todos.clone()));
+
+            todo_changed_response(
+                id,
+                FlashMessage::success("Link attached successfully!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Handle the `DELETE` request to remove a link from a Todo.
+pub async fn link_delete_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.todos.remove_link(id, workspace.id).await {
+        Ok(todo_id) => {
+            todo_changed_response(
+                todo_id,
+                FlashMessage::success("Link successfully removed!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Fetches the preview of a link and stores it. Failed fetches are
+/// stored empty so the link is shown as a plain URL.
+async fn fetch_preview(link_id: i64, url: String, todos: Arc<dyn TodoRepo>) {
+    let preview = match link_preview::fetch(&url).await {
+        Ok(preview) => preview,
+        Err(e) => {
+            warn!("failed to fetch preview of {}: {:#}", url, e);
+            Default::default()
+        }
+    };
+
+    if let Err(e) = todos
+        .set_link_preview(link_id, preview.title, preview.description)
+        .await
+    {
+        warn!("failed to store preview of link #{}: {}", link_id, e);
+    }
+}
@@ -0,0 +1,18 @@
+use std::sync::OnceLock;
+
+use axum::{http::header, response::IntoResponse};
+use uuid::Uuid;
+
+/// Id of this run of the server, which changes on every restart.
+fn boot_id() -> &'static str {
+    static BOOT_ID: OnceLock<String> = OnceLock::new();
+
+    BOOT_ID.get_or_init(|| Uuid::new_v4().to_string())
+}
+
+/// Handler polled by the pages in development (`dev` feature), which
+/// reload themselves when the answer changes, i.e. when `cargo watch`
+/// has restarted the server after an edit.
+pub async fn live_reload_handler() -> impl IntoResponse {
+    ([(header::CACHE_CONTROL, "no-store")], boot_id())
+}
@@ -0,0 +1,97 @@
+use std::{
+    fmt::Write,
+    sync::{atomic::Ordering, Arc},
+};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::{model::PoolStats, AppState};
+
+use super::admin_handler::pool_stats;
+
+/// Name, help text and value of a gauge of the pools.
+type Gauge = (&'static str, &'static str, fn(&PoolStats) -> u32);
+
+/// Content type of the text exposition format of Prometheus.
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Handler of the Prometheus endpoint: gauges of the connections of the
+/// database pools and counters of the rows purged by the cleanup job, in
+/// the text exposition format. When `METRICS_TOKEN` is set, the scraper
+/// must send it as a bearer token.
+pub async fn metrics_handler(headers: HeaderMap, State(state): State<Arc<AppState>>) -> Response {
+    if let Some(token) = &state.config.metrics_token {
+        let sent = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        // Compared through their hashes, so the time taken
+        // doesn't tell how much of the token is right
+        if Sha256::digest(sent) != Sha256::digest(token) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    let pools = [
+        ("read", pool_stats(&state.read_pool)),
+        ("write", pool_stats(&state.pool)),
+    ];
+    let gauges: [Gauge; 4] = [
+        (
+            "db_pool_connections",
+            "Open connections of the database pool.",
+            |stats| stats.size,
+        ),
+        (
+            "db_pool_idle_connections",
+            "Open connections of the database pool waiting for a query.",
+            |stats| stats.idle,
+        ),
+        (
+            "db_pool_in_use_connections",
+            "Connections of the database pool running a query.",
+            |stats| stats.in_use,
+        ),
+        (
+            "db_pool_max_connections",
+            "Most connections the database pool opens (DB_MAX_CONNECTIONS).",
+            |stats| stats.max,
+        ),
+    ];
+
+    let mut body = String::new();
+    for (name, help, value) in gauges {
+        let _ = writeln!(body, "# HELP todo_{} {}", name, help);
+        let _ = writeln!(body, "# TYPE todo_{} gauge", name);
+        for (pool, stats) in &pools {
+            let _ = writeln!(body, "todo_{}{{pool=\"{}\"}} {}", name, pool, value(stats));
+        }
+    }
+
+    let purged = [
+        ("user_tokens", &state.purged.user_tokens),
+        ("sync_mutations", &state.purged.sync_mutations),
+    ];
+    let _ = writeln!(
+        body,
+        "# HELP todo_purged_rows_total Used or expired rows deleted by the cleanup job."
+    );
+    let _ = writeln!(body, "# TYPE todo_purged_rows_total counter");
+    for (table, count) in purged {
+        let _ = writeln!(
+            body,
+            "todo_purged_rows_total{{table=\"{}\"}} {}",
+            table,
+            count.load(Ordering::Relaxed)
+        );
+    }
+
+    ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], body).into_response()
+}
@@ -1,24 +1,380 @@
-use std::sync::Arc;
+use std::{
+    cell::Cell,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
 
 use axum::{
-    extract::{Request, State},
-    http::header,
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 use axum_extra::extract::CookieJar;
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use tokio::sync::RwLock;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::Utc;
+use ipnet::IpNet;
+use sha2::{Digest, Sha256};
 use tower_sessions::Session;
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use super::{
+    audit_as, render_error, set_date_format_in_session, set_flag_in_session, set_theme_in_session,
+    set_tzone_in_session, THEME_KEY, WORKSPACE_KEY,
+};
+use crate::{
+    jwt::JwtKeys,
+    model::{AuditEvent, Theme, User, Workspace},
+    signed_url::SignedUrlError,
+    slack, AppState,
+};
+
+tokio::task_local! {
+    /// Id of the request being handled.
+    static REQUEST_ID: String;
+    /// Span of the request being handled, opened by the `TraceLayer`.
+    static REQUEST_SPAN: Span;
+    /// Address of the client of the request being handled.
+    static CLIENT_IP: Option<IpAddr>;
+    /// Theme of the pages rendered by the request being handled.
+    static THEME: Cell<Theme>;
+    /// Nonce of the inline scripts of the page being rendered.
+    static CSP_NONCE: String;
+    /// Admin impersonating the user of the request being handled.
+    static IMPERSONATOR: Option<User>;
+}
+
+/// Header holding the id set by `SetRequestIdLayer`.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Header selecting the workspace of the request, for clients without session.
+pub const WORKSPACE_HEADER: &str = "x-workspace";
+
+/// Header with the addresses a request went through, appended by each proxy.
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// Header with the address of the client, set by some proxies.
+const REAL_IP_HEADER: &str = "x-real-ip";
+
+/// Header with the method of a `POST` request that can't use it.
+const METHOD_OVERRIDE_HEADER: &str = "x-http-method-override";
+
+/// Largest form read to find its `_method` field (the default of `Form`).
+const FORM_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Challenge of the HTTP Basic authentication of the CalDAV endpoint.
+const DAV_CHALLENGE: &str = r#"Basic realm="Todo List", charset="UTF-8""#;
+
+/// Extension of the requests whose method was overridden, which
+/// come from pages without JavaScript and expect a full page back.
+#[derive(Clone, Copy, Debug)]
+pub struct MethodOverridden;
+
+/// Middleware that makes the id of the request available
+/// to the handlers, to show it in the error pages.
+pub async fn request_id_middleware(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+
+    // The layers below open spans of their own (e.g. the one of the
+    // sessions), so the request span is kept to record its fields
+    let request = REQUEST_ID.scope(request_id, next.run(req));
+    REQUEST_SPAN.scope(Span::current(), request).await
+}
+
+/// Span of the request being handled, where the fields known along the
+/// way are recorded. Outside of a request, the current span.
+fn request_span() -> Span {
+    REQUEST_SPAN
+        .try_with(Span::clone)
+        .unwrap_or_else(|_| Span::current())
+}
+
+/// Id of the request being handled, empty outside of a request.
+pub fn current_request_id() -> String {
+    REQUEST_ID.try_with(|id| id.clone()).unwrap_or_default()
+}
+
+/// Middleware that finds the address of the client, for the audit log
+/// and the rate limits, and records it in the request span. Behind the
+/// proxies of `TRUSTED_PROXIES` it's taken from their `X-Forwarded-For`
+/// (or `X-Real-IP`) header instead of the address of the proxy.
+pub async fn client_ip_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+    let client_ip = client_ip(peer, req.headers(), &state.config.trusted_proxies);
+
+    if let Some(ip) = client_ip {
+        Span::current().record("client_ip", display(ip));
+    }
+
+    CLIENT_IP.scope(client_ip, next.run(req)).await
+}
+
+/// Address of the client of the request being handled, if known.
+pub fn current_client_ip() -> Option<IpAddr> {
+    CLIENT_IP.try_with(|ip| *ip).ok().flatten()
+}
+
+/// The address of the client: the one of the connection unless it is a
+/// trusted proxy. Requests without one came through the Unix socket,
+/// which only the local reverse proxy can use.
+fn client_ip(
+    peer: Option<IpAddr>,
+    headers: &HeaderMap,
+    trusted_proxies: &[IpNet],
+) -> Option<IpAddr> {
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|proxy| proxy.contains(ip));
+
+    if peer.is_some_and(|peer| !is_trusted(&peer)) {
+        return peer;
+    }
+
+    let forwarded_for: Vec<&str> = headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|hop| !hop.is_empty())
+        .collect();
+
+    if forwarded_for.is_empty() {
+        return headers
+            .get(REAL_IP_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .or(peer);
+    }
+
+    // Each proxy appends the address it got the request from, so the
+    // client is the last one that isn't a trusted proxy: the ones before
+    // it could be made up by the client
+    let mut client_ip = peer;
+    for hop in forwarded_for.iter().rev() {
+        let Ok(ip) = hop.parse::<IpAddr>() else {
+            break;
+        };
+        client_ip = Some(ip);
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+
+    client_ip
+}
+
+/// Middleware that makes the theme stored in the session
+/// available to the templates of the request.
+pub async fn theme_middleware(session: Session, req: Request, next: Next) -> Response {
+    let theme: Theme = session.get(THEME_KEY).await.unwrap().unwrap_or_default();
+
+    THEME.scope(Cell::new(theme), next.run(req)).await
+}
+
+/// Theme of the request being handled, the system one outside of a request.
+pub fn current_theme() -> Theme {
+    THEME.try_with(Cell::get).unwrap_or_default()
+}
+
+/// Middleware that sets the security headers of the responses. The pages
+/// only run the scripts of the app, whose integrity is checked, and the
+/// inline ones that carry the nonce of the request (see `current_csp_nonce`).
+pub async fn security_headers_middleware(req: Request, next: Next) -> Response {
+    // The docs of the API come with their own inline scripts
+    let is_api = req.uri().path().starts_with("/api/");
+
+    let nonce = Uuid::new_v4().simple().to_string();
+    let mut response = CSP_NONCE.scope(nonce.clone(), next.run(req)).await;
+
+    // A 304 would replace the policy of the page kept by the browser,
+    // which has the nonce of an earlier request
+    let not_modified = response.status() == StatusCode::NOT_MODIFIED;
+
+    let headers = response.headers_mut();
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("same-origin"),
+    );
+    if !is_api && !not_modified && !headers.contains_key(header::CONTENT_SECURITY_POLICY) {
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_str(&content_security_policy(&nonce)).unwrap(),
+        );
+    }
+
+    response
+}
+
+/// Scripts from the app or with the nonce, and nothing from other origins.
+/// The styles can be inline, as htmx and SweetAlert2 add theirs.
+fn content_security_policy(nonce: &str) -> String {
+    format!(
+        "default-src 'self'; script-src 'self' 'nonce-{}'; style-src 'self' 'unsafe-inline'; \
+        img-src 'self' data:; object-src 'none'; base-uri 'self'; form-action 'self'; \
+        frame-ancestors 'none'",
+        nonce
+    )
+}
+
+/// Nonce of the request being handled, for the `nonce` attribute of the
+/// inline scripts. Empty outside of a request.
+pub fn current_csp_nonce() -> String {
+    CSP_NONCE.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Middleware of the routes opened through a signed URL (see `UrlSigner`),
+/// which refuses the requests whose signature is missing, wrong or expired.
+pub async fn signed_url_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match state.url_signer.verify(req.uri().path(), req.uri().query()) {
+        Ok(()) => next.run(req).await,
+        Err(SignedUrlError::Expired) => (
+            StatusCode::GONE,
+            render_error(StatusCode::GONE, "This link has expired"),
+        )
+            .into_response(),
+        Err(SignedUrlError::Invalid) => (
+            StatusCode::FORBIDDEN,
+            render_error(StatusCode::FORBIDDEN, "This link is not valid"),
+        )
+            .into_response(),
+    }
+}
+
+/// Middleware of the Slack slash command, which refuses the requests
+/// that aren't signed with `SLACK_SIGNING_SECRET`, or all of them when
+/// it isn't set.
+pub async fn slack_signature_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(secret) = state.config.slack_signing_secret.as_deref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // The signature covers the raw body, which is put back afterwards
+    let (parts, body) = req.into_parts();
+    let Ok(bytes) = to_bytes(body, FORM_BODY_LIMIT).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let signed = slack::verify_signature(
+        secret,
+        header(slack::TIMESTAMP_HEADER),
+        header(slack::SIGNATURE_HEADER),
+        &bytes,
+        Utc::now().timestamp(),
+    );
+    if !signed {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
+
+/// Middleware of the CalDAV endpoint (`/dav/`), whose clients send the
+/// email and password of the user on every request (HTTP Basic
+/// authentication). The failed attempts count towards the limit of
+/// login attempts of the client address (`LOGIN_ATTEMPT_LIMIT`).
+pub async fn dav_auth_middleware(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let unauthorized = || {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, DAV_CHALLENGE)],
+        )
+            .into_response()
+    };
+
+    let credentials = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+    let Some((email, password)) = credentials.as_deref().and_then(|c| c.split_once(':')) else {
+        return unauthorized();
+    };
+
+    let client_ip = current_client_ip().map(|ip| ip.to_string());
+    if let Some(wait) = client_ip
+        .as_deref()
+        .and_then(|ip| state.login_limiter.wait_time(ip))
+    {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, (wait.as_secs() + 1).to_string())],
+        )
+            .into_response();
+    }
+
+    match state
+        .users
+        .check_email_password(email.to_string(), password.to_string())
+        .await
+    {
+        Ok(user) => {
+            record_user_id(&user.id);
+            req.extensions_mut().insert(user);
 
-use super::{set_flag_in_session, Error401Template, HtmlTemplate};
-use crate::{model::TokenClaims, service::get_user_by_id, AppState};
+            next.run(req).await
+        }
+        Err(_) => {
+            if let Some(client_ip) = client_ip {
+                let _ = state.login_limiter.check(&client_ip);
+            }
+
+            unauthorized()
+        }
+    }
+}
+
+/// Id of the user logged in with the `token` cookie, if any.
+/// For routes that are public but behave differently for users.
+pub fn user_id_from_cookie(cookie_jar: &CookieJar, jwt_keys: &JwtKeys) -> Option<String> {
+    let token = cookie_jar.get("token")?.value();
+
+    jwt_keys.decode(token).ok().map(|claims| claims.sub)
+}
 
 /// Middleware to manage authorization.
 pub async fn auth_middleware(
     cookie_jar: CookieJar,
     session: Session,
-    State(state): State<Arc<RwLock<AppState>>>,
+    State(state): State<Arc<AppState>>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, Response> {
@@ -45,49 +401,28 @@ pub async fn auth_middleware(
     } else {
         set_flag_in_session(&session, false).await;
 
-        Err(HtmlTemplate(Error401Template {
-            title: "Error 401".to_string(),
-            reason: "You are not logged in, please provide token".to_string(),
-            is_error: true,
-            ..Default::default()
-        })
+        Err(render_error(
+            StatusCode::UNAUTHORIZED,
+            "You are not logged in, please provide token",
+        )
         .into_response())?
     };
 
-    let claims = if let Ok(clm) = decode::<TokenClaims>(
-        &token,
-        &DecodingKey::from_secret(&state.read().await.config.jwt_secret.as_ref()),
-        &Validation::default(),
-    ) {
-        clm.claims
+    let claims = if let Ok(clm) = state.jwt_keys.decode(&token) {
+        clm
     } else {
         set_flag_in_session(&session, false).await;
 
-        Err(HtmlTemplate(Error401Template {
-            title: "Error 401".to_string(),
-            reason: "Invalid token".to_string(),
-            is_error: true,
-            ..Default::default()
-        })
-        .into_response())?
+        Err(render_error(StatusCode::UNAUTHORIZED, "Invalid token").into_response())?
     };
 
     let user_id = &claims.sub;
-    let lock = state.read().await;
-    let pool = &lock.pool;
-    let result = get_user_by_id(user_id, pool).await;
-    drop(lock);
+    let result = state.users.get_user_by_id(user_id).await;
 
     if let Err(e) = result.clone() {
         set_flag_in_session(&session, false).await;
 
-        Err(HtmlTemplate(Error401Template {
-            title: "Error 401".to_string(),
-            reason: e,
-            is_error: true,
-            ..Default::default()
-        })
-        .into_response())?
+        Err(render_error(StatusCode::UNAUTHORIZED, e).into_response())?
     };
 
     let user = if let Some(u) = result.unwrap() {
@@ -95,18 +430,317 @@ pub async fn auth_middleware(
     } else {
         set_flag_in_session(&session, false).await;
 
-        Err(HtmlTemplate(Error401Template {
-            title: "Error 401".to_string(),
-            reason: "The user belonging to this token no longer exists".to_string(),
-            is_error: true,
-            ..Default::default()
-        })
+        Err(render_error(
+            StatusCode::UNAUTHORIZED,
+            "The user belonging to this token no longer exists",
+        )
         .into_response())?
     };
 
+    // Before anything else can fail, so that every log of the request has it
+    record_user_id(&user.id);
+
+    // An admin using the app as the user, as long as they are still an admin
+    let impersonator = match &claims.act {
+        Some(admin_id) => match state.users.get_user_by_id(admin_id).await {
+            Ok(Some(admin)) if admin.is_admin => {
+                request_span().record("impersonator_id", admin_id);
+                Some(admin)
+            }
+            _ => {
+                set_flag_in_session(&session, false).await;
+
+                Err(render_error(
+                    StatusCode::UNAUTHORIZED,
+                    "The impersonation is no longer allowed, please log in again",
+                )
+                .into_response())?
+            }
+        },
+        None => None,
+    };
+
     set_flag_in_session(&session, true).await;
 
+    // Loaded once, for the handlers and the base context of the pages
+    let settings = match state.settings.get_user_settings(&user.id).await {
+        Ok(settings) => settings,
+        Err(e) => {
+            Err(render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())?
+        }
+    };
+
+    // The theme saved in the account wins over the one of the session,
+    // so it follows the user to other browsers
+    if settings.theme.is_some() {
+        let theme = settings.theme();
+        set_theme_in_session(&session, theme).await;
+        let _ = THEME.try_with(|current| current.set(theme));
+    }
+
+    // Same for the timezone (the one of the session came from the browser)
+    // and the date format
+    if let Some(tzone) = settings.timezone.clone() {
+        set_tzone_in_session(&session, tzone).await;
+    }
+    set_date_format_in_session(&session, settings.date_format()).await;
+
+    // The todos are those of the workspace chosen in the switcher, or in the
+    // `X-Workspace` header by other clients (the first one of the user
+    // otherwise), which the user must be a member of
+    let workspaces = match state.workspaces.get_user_workspaces(&user.id).await {
+        Ok(workspaces) => workspaces,
+        Err(e) => {
+            Err(render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response())?
+        }
+    };
+
+    let selected: Option<i64> = match req.headers().get(WORKSPACE_HEADER) {
+        // An invalid id matches no workspace
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|id| id.parse().ok())
+                .unwrap_or_default(),
+        ),
+        None => session.get(WORKSPACE_KEY).await.unwrap(),
+    };
+    let workspace = match selected {
+        Some(id) => workspaces.iter().find(|workspace| workspace.id == id),
+        None => workspaces.first(),
+    };
+
+    let workspace = if let Some(w) = workspace {
+        w.clone()
+    } else {
+        // Back to the first workspace on the next request
+        let _ = session.remove::<i64>(WORKSPACE_KEY).await;
+
+        Err((
+            StatusCode::FORBIDDEN,
+            render_error(
+                StatusCode::FORBIDDEN,
+                "You are not a member of this workspace",
+            ),
+        )
+            .into_response())?
+    };
+
+    request_span().record("workspace_id", workspace.id);
+
+    // What an admin changes as the user goes to the audit log of the user
+    let audited = impersonator
+        .as_ref()
+        .filter(|_| !req.method().is_safe())
+        .map(|admin| (user.email.clone(), admin.email.clone()));
+
     req.extensions_mut().insert(user);
+    req.extensions_mut().insert(settings);
+    req.extensions_mut().insert(workspace);
+    req.extensions_mut().insert::<Vec<Workspace>>(workspaces);
+
+    let response = IMPERSONATOR.scope(impersonator, next.run(req)).await;
+
+    if let Some((email, actor_email)) = audited {
+        audit_as(
+            &state,
+            &email,
+            AuditEvent::ImpersonatedChange,
+            Some(actor_email),
+        )
+        .await;
+    }
+
+    Ok(response)
+}
+
+/// Admin impersonating the user of the request being handled, if any.
+pub fn current_impersonator() -> Option<User> {
+    IMPERSONATOR.try_with(Clone::clone).ok().flatten()
+}
+
+/// Records the user making the request in its span, so the logs can be
+/// filtered per user. The auth middleware records it on the protected
+/// routes, the other routes that identify the user call it themselves.
+pub fn record_user_id(user_id: &str) {
+    request_span().record("user_id", user_id);
+}
+
+/// Middleware that limits how many todos a user can create per minute
+/// (`TODO_CREATE_LIMIT`), so a script can't flood the database.
+/// It goes after the auth middleware, which provides the user.
+pub async fn todo_create_limit_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(user) = req.extensions().get::<User>() else {
+        return next.run(req).await;
+    };
+
+    if let Err(wait) = state.todo_create_limiter.check(&user.id) {
+        let seconds = wait.as_secs() + 1;
+
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, seconds.to_string())],
+            render_error(StatusCode::TOO_MANY_REQUESTS, format!(
+                    "You are creating tasks too fast, please wait {} seconds before adding another one",
+                    seconds
+                )),
+        )
+            .into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Middleware that limits how many times a client can try to log in per
+/// minute (`LOGIN_ATTEMPT_LIMIT`), to slow down the guessing of passwords.
+/// It goes after the client IP middleware, which provides the address.
+pub async fn login_limit_middleware(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(client_ip) = current_client_ip() else {
+        return next.run(req).await;
+    };
+
+    if let Err(wait) = state.login_limiter.check(&client_ip.to_string()) {
+        let seconds = wait.as_secs() + 1;
+
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, seconds.to_string())],
+            render_error(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!(
+                    "Too many login attempts, please wait {} seconds before trying again",
+                    seconds
+                ),
+            ),
+        )
+            .into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Middleware that tags the pages with an `ETag`, the hash of their
+/// contents, and answers with a `304 Not Modified` when the client
+/// already has the same page (`If-None-Match`), so reloading an
+/// unchanged list doesn't download it again. The page is still
+/// rendered, as it depends on much more than the todos (subtasks,
+/// timers, saved filters, flash messages…).
+pub async fn conditional_get_middleware(req: Request, next: Next) -> Response {
+    if req.method() != Method::GET {
+        return next.run(req).await;
+    }
+
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+    let response = next.run(req).await;
+
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+
+    // The nonce changes on every request, so it isn't part of the tag.
+    // The page kept by the browser goes on using its own, as no policy
+    // is sent along with a 304
+    let nonce = current_csp_nonce();
+    let hash = if nonce.is_empty() {
+        Sha256::digest(&bytes)
+    } else {
+        Sha256::digest(String::from_utf8_lossy(&bytes).replace(&nonce, ""))
+    };
+    let etag = format!("\"{:x}\"", hash);
+    parts
+        .headers
+        .insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    // Kept by the browser, but checked with the server every time
+    parts.headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-cache"),
+    );
+
+    let not_modified = if_none_match
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+        });
+
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_LENGTH);
+        parts.headers.remove(header::CONTENT_TYPE);
+
+        return Response::from_parts(parts, Body::empty());
+    }
+
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Middleware that turns `POST` requests into the `PATCH`, `PUT` or
+/// `DELETE` given in the `_method` field of their form (or in the
+/// `X-HTTP-Method-Override` header), for the forms sent without
+/// JavaScript. It must wrap the router, as routes are matched by method.
+pub async fn method_override_middleware(mut req: Request, next: Next) -> Response {
+    if req.method() != Method::POST {
+        return next.run(req).await;
+    }
+
+    let mut method = req
+        .headers()
+        .get(METHOD_OVERRIDE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let is_form = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| {
+            value
+                .as_bytes()
+                .starts_with(b"application/x-www-form-urlencoded")
+        });
+
+    if method.is_none() && is_form {
+        let (parts, body) = req.into_parts();
+        let Ok(bytes) = to_bytes(body, FORM_BODY_LIMIT).await else {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                render_error(StatusCode::PAYLOAD_TOO_LARGE, "The form is too large"),
+            )
+                .into_response();
+        };
+
+        // The names of the methods don't need to be decoded
+        method = bytes
+            .split(|byte| *byte == b'&')
+            .find_map(|pair| pair.strip_prefix(b"_method="))
+            .map(|value| String::from_utf8_lossy(value).into_owned());
+
+        req = Request::from_parts(parts, Body::from(bytes));
+    }
+
+    let method = method.and_then(|method| method.to_ascii_uppercase().parse::<Method>().ok());
+    if let Some(method) = method {
+        if [Method::PATCH, Method::PUT, Method::DELETE].contains(&method) {
+            *req.method_mut() = method;
+            req.extensions_mut().insert(MethodOverridden);
+        }
+    }
 
-    Ok::<Response, _>(next.run(req).await)
+    next.run(req).await
 }
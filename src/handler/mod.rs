@@ -1,29 +1,127 @@
+mod account_handler;
+mod admin_handler;
+mod api_doc;
 mod auth_handler;
+mod batch_handler;
+mod dav_handler;
+mod error_handler;
+mod export_handler;
+mod feed_handler;
+mod filter_handler;
+mod filters;
+mod import_handler;
+mod link_handler;
+#[cfg(feature = "dev")]
+mod live_reload_handler;
+mod metrics_handler;
 mod middleware;
+mod notification_handler;
+mod profile_handler;
+mod slack_handler;
+mod subtask_handler;
+mod sync_handler;
+mod theme_handler;
 mod todo_handler;
+mod workspace_handler;
+mod ws_handler;
 
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Write},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+pub use account_handler::{
+    forgot_password_handler, forgot_password_page_handler, reset_password_handler,
+    reset_password_page_handler, verify_email_handler,
+};
+pub use admin_handler::{
+    audit_log_handler, backup_handler, impersonate_handler, impersonate_stop_handler, ops_handler,
+};
+pub use api_doc::ApiDoc;
 pub use auth_handler::{
     handler_404, home_handler, login_page_handler, login_user_handler, logout_handler,
     register_page_handler, register_user_handler,
 };
-use chrono::{Local, NaiveDateTime, TimeZone};
+pub use batch_handler::todo_batch_handler;
+use chrono::{NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
-pub use middleware::auth_middleware;
+pub use dav_handler::{dav_calendar_handler, dav_home_handler, dav_todo_handler};
+pub use error_handler::{
+    handle_panic, handle_timeout_error, htmx_error_middleware, method_not_allowed_middleware,
+};
+pub use export_handler::todo_export_handler;
+pub use feed_handler::{feed_handler, feed_link_handler};
+pub use filter_handler::{filter_delete_handler, filter_save_handler};
+pub use import_handler::{import_confirm_handler, import_page_handler, import_preview_handler};
+pub use link_handler::{link_add_handler, link_delete_handler};
+#[cfg(feature = "dev")]
+pub use live_reload_handler::live_reload_handler;
+pub use metrics_handler::metrics_handler;
+pub use middleware::{
+    auth_middleware, client_ip_middleware, conditional_get_middleware, dav_auth_middleware,
+    login_limit_middleware, method_override_middleware, request_id_middleware,
+    security_headers_middleware, signed_url_middleware, slack_signature_middleware,
+    theme_middleware, todo_create_limit_middleware, MethodOverridden, REQUEST_ID_HEADER,
+    WORKSPACE_HEADER,
+};
+pub use notification_handler::{
+    notification_bell_handler, notification_read_handler, notifications_page_handler,
+    notifications_read_all_handler,
+};
+pub use profile_handler::{profile_page_handler, profile_update_handler};
+pub use slack_handler::{slack_command_handler, slack_link_handler};
+pub use subtask_handler::{subtask_add_handler, subtask_delete_handler, subtask_toggle_handler};
+pub use sync_handler::sync_handler;
+pub use theme_handler::theme_handler;
 pub use todo_handler::{
-    todo_add_handler, todo_create_handler, todo_delete_handler, todo_edit_handler,
-    todo_list_handler, todo_patch_handler,
+    legacy_delete_redirect_handler, legacy_edit_redirect_handler, tag_suggest_handler,
+    todo_add_handler, todo_api_list_handler, todo_bulk_edit_handler, todo_bulk_handler,
+    todo_create_handler, todo_delete_handler, todo_dependency_add_handler,
+    todo_dependency_remove_handler, todo_edit_handler, todo_list_handler, todo_list_page_handler,
+    todo_patch_handler, todo_quick_add_handler, todo_revert_handler, todo_search_live_handler,
+    todo_stats_handler, todo_timer_start_handler, todo_timer_stop_handler, todo_toggle_handler,
+    TOTAL_COUNT_HEADER,
+};
+pub use workspace_handler::{
+    workspace_create_handler, workspace_member_add_handler, workspace_member_remove_handler,
+    workspace_page_handler, workspace_switch_handler,
 };
+pub use ws_handler::ws_handler;
 
+use anyhow::anyhow;
 use askama::Template;
 use axum::{
-    http::StatusCode,
+    async_trait,
+    extract::{FromRequestParts, State},
+    http::{request::Parts, HeaderMap, StatusCode},
     response::{Html, IntoResponse, Response},
     Json,
 };
-use axum_messages::Messages;
+use axum_messages::{Level, Messages};
 use tower_sessions::Session;
+use tracing::error;
 
-use crate::model::Todo;
+use crate::{
+    backup::Backup,
+    config::TextLimits,
+    fragments::FragmentCache,
+    geoip::GeoIp,
+    import::ImportedTodo,
+    model::{
+        AuditEntry, AuditEvent, AuditFilter, BuildInfo, ChecklistProgress, DatabaseHealth,
+        DateFormat, DigestFrequency, HealthCheckResponse, JobStatus, Notification, OpsCounts,
+        PoolStats, SavedFilter, Subtask, TagSuggestion, Theme, Todo, TodoFilter, TodoLink,
+        TodoStats, TodoVersion, TrackedTime, User, UserSettings, Workspace, WorkspaceMember,
+        PAGE_SIZES,
+    },
+    reporting::RecentError,
+    sanitize::plain_text,
+    service::ping_database,
+    AppState,
+};
 
 /* --------------------------------------- */
 /* ------------ region: Utils ------------ */
@@ -31,18 +129,63 @@ use crate::model::Todo;
 
 const FROM_PROTECTED_KEY: &str = "from_protected";
 const TZONE_KEY: &str = "time_zone";
+const IMPORT_KEY: &str = "import_preview";
+const THEME_KEY: &str = "theme";
+const DATE_FORMAT_KEY: &str = "date_format";
+const WORKSPACE_KEY: &str = "workspace";
+
+/// How long the health check waits for the database.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Handler to check the status of the app.
-pub async fn health_checker_handler() -> impl IntoResponse {
+/// Responds with `503` when the database is unreachable.
+#[utoipa::path(
+    get,
+    path = "/healthchecker",
+    tag = "health",
+    responses(
+        (status = 200, description = "The app is up", body = HealthCheckResponse),
+        (status = 503, description = "The database is unreachable", body = HealthCheckResponse),
+    )
+)]
+pub async fn health_checker_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     const MESSAGE: &str =
         "Full stack Web App using Rust's Axum framework, Askama, HTMX, JWT & SQLITE3";
 
-    let json_response = serde_json::json!({
-        "status": "success",
-        "message": MESSAGE
-    });
+    let ping =
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, ping_database(&state.read_pool)).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow!("timed out after {:?}", HEALTH_CHECK_TIMEOUT)),
+        };
 
-    Json(json_response)
+    let size = state.read_pool.size();
+    let idle = state.read_pool.num_idle() as u32;
+    let database = DatabaseHealth {
+        reachable: ping.is_ok(),
+        size,
+        idle,
+        in_use: size.saturating_sub(idle),
+    };
+    match ping {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(HealthCheckResponse {
+                status: "success".to_string(),
+                message: MESSAGE.to_string(),
+                build: BuildInfo::CURRENT,
+                database,
+            }),
+        ),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(HealthCheckResponse {
+                status: "error".to_string(),
+                message: format!("Database unreachable: {}", e),
+                build: BuildInfo::CURRENT,
+                database,
+            }),
+        ),
+    }
 }
 
 /// Set flag in session.
@@ -58,40 +201,310 @@ async fn set_tzone_in_session(session: &Session, tzone: String) {
     session.insert(TZONE_KEY, tzone).await.unwrap();
 }
 
-/// Format flash messages generated in redirects.
-fn get_messages(messages: Messages) -> (String, String) {
-    let mut messages = messages
+/// Set date format in session.
+async fn set_date_format_in_session(session: &Session, date_format: DateFormat) {
+    session.insert(DATE_FORMAT_KEY, date_format).await.unwrap();
+}
+
+/// Set the current workspace in session.
+async fn set_workspace_in_session(session: &Session, workspace_id: i64) {
+    session.insert(WORKSPACE_KEY, workspace_id).await.unwrap();
+}
+
+/// Timezone the dates are shown in: the one saved in the account,
+/// else the one the browser sends in `X-Timezone`, else the one of the
+/// place the client is in (with `GEOIP_DATABASE`), else UTC.
+fn resolve_timezone(preference: Option<&str>, headers: &HeaderMap, geoip: &GeoIp) -> String {
+    let known = |tzone: &String| tzone.parse::<Tz>().is_ok();
+
+    preference
+        .or_else(|| {
+            headers
+                .get("x-timezone")
+                .and_then(|value| value.to_str().ok())
+        })
+        .map(str::to_string)
+        .filter(known)
+        .or_else(|| {
+            geoip
+                .timezone(middleware::current_client_ip()?)
+                .filter(known)
+        })
+        .unwrap_or_else(|| "UTC".to_string())
+}
+
+/// Records an authentication event in the audit log, along with the
+/// address of the client and the admin impersonating the user, if any.
+/// A failure is only logged, it doesn't stop the request.
+async fn audit(state: &AppState, email: &str, event: AuditEvent) {
+    let impersonator = middleware::current_impersonator();
+
+    audit_as(state, email, event, impersonator.map(|admin| admin.email)).await
+}
+
+/// Same as `audit`, made by the admin with the email `actor_email`.
+async fn audit_as(state: &AppState, email: &str, event: AuditEvent, actor_email: Option<String>) {
+    let client_ip = middleware::current_client_ip();
+
+    if let Err(e) = state
+        .users
+        .add_audit_entry(email, event, client_ip, actor_email.as_deref())
+        .await
+    {
+        error!(
+            "failed to record the {} event of {}: {}",
+            event.name(),
+            email,
+            e
+        );
+    }
+}
+
+/// Set theme in session.
+async fn set_theme_in_session(session: &Session, theme: Theme) {
+    session.insert(THEME_KEY, theme).await.unwrap();
+}
+
+/// Makes HTMX swap a full page response (e.g. an error page) into the
+/// body, for requests that target a fragment of the page.
+fn retarget_body<T: IntoResponse>(response: T) -> Response {
+    (
+        [("HX-Retarget", "body"), ("HX-Reswap", "innerHTML")],
+        response,
+    )
+        .into_response()
+}
+
+/// Makes HTMX replace the open modal with the response,
+/// e.g. the same modal showing the validation errors.
+fn retarget_modal<T: IntoResponse>(response: T) -> Response {
+    (
+        [("HX-Retarget", "#modal"), ("HX-Reswap", "outerHTML")],
+        response,
+    )
+        .into_response()
+}
+
+/// `HX-Trigger` header that shows `message` as a toast, along with the
+/// `events` that other elements of the page listen to (e.g. `todoCreated`
+/// closes the modal). It replaces the redirect with a flash message, so
+/// the list isn't loaded again after every action.
+fn toast_trigger(message: FlashMessage, events: &[&str]) -> [(&'static str, String); 1] {
+    let mut triggers = serde_json::Map::new();
+    for event in events {
+        triggers.insert(event.to_string(), serde_json::Value::Null);
+    }
+    triggers.insert(
+        "toast".to_string(),
+        serde_json::json!({ "level": message.level_name(), "text": message.text }),
+    );
+
+    // Headers are read as Latin-1, so any other character is escaped
+    let mut header = String::new();
+    for c in serde_json::Value::Object(triggers).to_string().chars() {
+        if c.is_ascii() {
+            header.push(c);
+        } else {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                let _ = write!(header, "\\u{:04x}", unit);
+            }
+        }
+    }
+
+    [("HX-Trigger", header)]
+}
+
+/// Validation errors of a form by field name,
+/// shown next to the fields while keeping the input.
+#[derive(Default)]
+struct FieldErrors(Vec<(&'static str, String)>);
+
+impl FieldErrors {
+    fn add(&mut self, field: &'static str, message: impl Into<String>) {
+        self.0.push((field, message.into()));
+    }
+
+    /// Error of a field (empty if none).
+    fn get(&self, field: &str) -> &str {
+        self.0
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, message)| message.as_str())
+            .unwrap_or_default()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Checks the title and description of a todo against the limits of
+/// the config. The title is checked once its markup has been removed.
+fn validate_todo(title: &str, description: &str, limits: TextLimits) -> FieldErrors {
+    let mut errors = FieldErrors::default();
+
+    if plain_text(title).is_empty() {
+        errors.add("title", "You must enter a title for the Todo");
+    } else if title.chars().count() > limits.title {
+        errors.add(
+            "title",
+            format!("The title can't be longer than {} characters", limits.title),
+        );
+    }
+
+    if description.chars().count() > limits.description {
+        errors.add(
+            "description",
+            format!(
+                "The description can't be longer than {} characters",
+                limits.description
+            ),
+        );
+    }
+
+    errors
+}
+
+/// A flash message, rendered with the style of its level.
+struct FlashMessage {
+    level: Level,
+    text: String,
+}
+
+impl FlashMessage {
+    fn success(text: impl Into<String>) -> Self {
+        Self {
+            level: Level::Success,
+            text: text.into(),
+        }
+    }
+
+    fn error(text: impl Into<String>) -> Self {
+        Self {
+            level: Level::Error,
+            text: text.into(),
+        }
+    }
+
+    /// DaisyUI class of the alert.
+    fn alert_class(&self) -> &'static str {
+        match self.level {
+            Level::Success => "alert-success",
+            Level::Error => "alert-error",
+            Level::Warning => "alert-warning",
+            Level::Info | Level::Debug => "alert-info",
+        }
+    }
+
+    /// Name of the level, as sent in the `toast` events.
+    fn level_name(&self) -> &'static str {
+        match self.level {
+            Level::Success => "success",
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info | Level::Debug => "info",
+        }
+    }
+
+    /// Icon shown before the text (empty if none).
+    fn icon(&self) -> &'static str {
+        match self.level {
+            Level::Success => "/assets/img/check_mark.svg",
+            Level::Error => "/assets/img/error_mark.svg",
+            _ => "",
+        }
+    }
+}
+
+/// Get the flash messages generated in redirects.
+fn get_messages(messages: Messages) -> Vec<FlashMessage> {
+    messages
         .into_iter()
-        .map(|message| format!("{}: {}", message.level, message))
-        .collect::<Vec<_>>()
-        .join(", ");
-    let mut messages_status = "".to_string();
+        .map(|message| FlashMessage {
+            level: message.level,
+            text: message.message,
+        })
+        .collect()
+}
+
+/// Timezone of the client from its IANA name, UTC if the name is unknown.
+fn client_timezone(tzone: &str) -> Tz {
+    tzone.parse::<Tz>().unwrap_or(Tz::UTC)
+}
+
+/// convert_datetime converts a datetime from the database (UTC)
+/// to the client's timezone, formatted as the user chose
+/// (e.g. `17 Jun 2024 14:30 +0200`).
+///
+/// Fails only if the format can't be written.
+pub fn convert_datetime(
+    tzone: &str,
+    dt: NaiveDateTime,
+    format: DateFormat,
+) -> Result<String, fmt::Error> {
+    let dttz = Utc
+        .from_utc_datetime(&dt)
+        .with_timezone(&client_timezone(tzone));
 
-    if messages.len() != 0 && messages.contains("Success") {
-        messages_status = messages[..7].to_string();
-        messages = messages[9..].to_string();
-    } else if messages.len() != 0 && messages.contains("Error") {
-        messages_status = messages[..5].to_string();
-        messages = messages[7..].to_string();
+    let mut output = String::new();
+    write!(output, "{}", dttz.format(&format.pattern()))?;
+
+    Ok(output)
+}
+
+/// How long ago a datetime from the database (UTC) was, as the user
+/// would put it in their timezone: "just now", "5 min ago", "2 h ago"
+/// (the same day), "yesterday", "3 days ago" (the last week) and then
+/// the date in their format (e.g. `17 Jun 2024`). The datetimes ahead
+/// of `now`, from clocks out of sync, are "just now".
+pub fn time_ago(tzone: &str, dt: NaiveDateTime, now: NaiveDateTime, format: DateFormat) -> String {
+    let tz = client_timezone(tzone);
+    let local = Utc.from_utc_datetime(&dt).with_timezone(&tz);
+    let today = Utc.from_utc_datetime(&now).with_timezone(&tz).date_naive();
+
+    let minutes = (now - dt).num_minutes();
+    let days = (today - local.date_naive()).num_days();
+
+    match (minutes, days) {
+        (..=0, _) => "just now".to_string(),
+        (1..=59, _) => format!("{} min ago", minutes),
+        (_, ..=0) => format!("{} h ago", minutes / 60),
+        (_, 1) => "yesterday".to_string(),
+        (_, 2..=6) => format!("{} days ago", days),
+        _ => local.format(format.date_pattern()).to_string(),
     }
+}
+
+/// Formats a number of seconds as a short duration ("1h 05m", "12m").
+fn format_duration(seconds: i64) -> String {
+    let hours = seconds / 3600;
+    let minutes = (seconds % 3600) / 60;
 
-    (messages_status, messages)
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", seconds.max(0)),
+        (0, m) => format!("{}m", m),
+        (h, m) => format!("{}h {:02}m", h, m),
+    }
 }
 
-/// convert_datetime converts the datetime format from the
-/// database (UTC timestamp) to a string in RFC822Z format,
-/// taking the client's timezone (&str) and a datetime (NaiveDateTime).
-pub fn convert_datetime(tzone: &str, dt: NaiveDateTime) -> String {
-    let tz = tzone.parse::<Tz>().unwrap();
-    let converted = Local.from_utc_datetime(&dt);
-    let dttz = converted.with_timezone(&tz).to_rfc2822();
+/// Converts a UTC datetime from the database into the value of a
+/// `datetime-local` input in the client's timezone.
+fn to_datetime_local(tzone: &str, dt: NaiveDateTime) -> String {
+    Utc.from_utc_datetime(&dt)
+        .with_timezone(&client_timezone(tzone))
+        .format("%Y-%m-%dT%H:%M")
+        .to_string()
+}
 
-    // conversion to RFC822Z format
-    let chars = dttz.chars().collect::<Vec<_>>();
-    let first_part = chars[5..22].iter().collect::<String>();
-    let last_part = chars[25..].iter().collect::<String>();
+/// Parses the value of a `datetime-local` input in the client's
+/// timezone into a UTC datetime (`None` if empty or invalid).
+fn from_datetime_local(tzone: &str, value: &str) -> Option<NaiveDateTime> {
+    let tz = client_timezone(tzone);
+    let local = NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%dT%H:%M").ok()?;
 
-    format!("{}{}", first_part, last_part)
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|dt| dt.naive_utc())
 }
 
 /* --------------------------------------- */
@@ -106,144 +519,740 @@ pub fn convert_datetime(tzone: &str, dt: NaiveDateTime) -> String {
 /// by askama into valid HTML for axum to serve.
 struct HtmlTemplate<T>(T);
 
+/// Templates served with `HtmlTemplate`. The error pages tell their
+/// status and reason, so HTMX requests can get them as a toast instead.
+trait Page: Template {
+    fn error(&self) -> Option<ErrorPage> {
+        None
+    }
+}
+
+/// Error shown by a page, set as an extension of its response.
+#[derive(Clone)]
+struct ErrorPage {
+    status: StatusCode,
+    reason: String,
+}
+
 /// Allows us to convert Askama HTML templates into valid HTML
 /// for axum to serve in the response.
 impl<T> IntoResponse for HtmlTemplate<T>
 where
-    T: Template,
+    T: Page,
 {
     fn into_response(self) -> Response {
+        let error = self.0.error();
+
         // Attempt to render the template with askama
         match self.0.render() {
             // If we're able to successfully parse and aggregate the template, serve it
-            Ok(html) => Html(html).into_response(),
+            Ok(html) => {
+                let mut response = Html(html).into_response();
+                if let Some(error) = error {
+                    response.extensions_mut().insert(error);
+                }
+                response
+            }
             // If we're not, return an error or some bit of fallback HTML
-            Err(err) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to render template. Error: {}", err),
-            )
-                .into_response(),
+            Err(err) => {
+                error!("failed to render template: {}", err);
+
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("Failed to render template. Error: {}", err),
+                )
+                    .into_response()
+            }
         }
     }
 }
 
-/// Home page template
-#[derive(Default, Template)]
-#[template(path = "auth/home.html")]
-struct HomeTemplate {
+/// Data shared by every page that extends the base layout. The
+/// handlers take it as an extractor and only set the title:
+/// `HomeTemplate { ctx: ctx.with_title("Home"), ..Default::default() }`.
+pub(crate) struct BaseContext {
     title: String,
     username: String,
-    messages_status: String,
-    messages: String,
+    /// Workspaces of the switcher in the navbar, and the current one.
+    workspaces: Vec<Workspace>,
+    workspace_id: i64,
+    /// Whether the navbar links to the admin pages.
+    is_admin: bool,
+    /// Version shown in the footer, to identify it in support requests.
+    build: BuildInfo,
+    /// Email of the admin using the app as the user, shown in a banner.
+    impersonator: Option<String>,
+    /// Settings of the user, loaded by `auth_middleware` (the default
+    /// ones outside of the protected routes).
+    settings: UserSettings,
+    messages: Vec<FlashMessage>,
     from_protected: bool,
     is_error: bool,
+    theme: Theme,
+    /// Nonce of the inline scripts, allowed by the `Content-Security-Policy`.
+    nonce: String,
+}
+
+impl Default for BaseContext {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            username: String::new(),
+            workspaces: Vec::new(),
+            workspace_id: 0,
+            is_admin: false,
+            build: BuildInfo::CURRENT,
+            impersonator: middleware::current_impersonator().map(|admin| admin.email),
+            settings: UserSettings::default(),
+            messages: Vec::new(),
+            from_protected: false,
+            is_error: false,
+            theme: middleware::current_theme(),
+            nonce: middleware::current_csp_nonce(),
+        }
+    }
+}
+
+impl BaseContext {
+    fn with_title(self, title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            ..self
+        }
+    }
+
+    /// Value of the `data-theme` attribute, empty to follow the system.
+    fn data_theme(&self) -> &str {
+        self.theme.name().unwrap_or_default()
+    }
+
+    /// Name of the current workspace (empty outside of the protected routes).
+    fn workspace_name(&self) -> &str {
+        self.workspaces
+            .iter()
+            .find(|workspace| workspace.id == self.workspace_id)
+            .map(|workspace| workspace.name.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Whether the pages reload when the server restarts.
+    fn live_reload(&self) -> bool {
+        cfg!(feature = "dev")
+    }
+}
+
+/// Reads the login flag from the session, the flash messages and the user,
+/// their settings and workspaces set by `auth_middleware` on protected routes.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for BaseContext {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::from_request_parts(parts, state).await?;
+        let messages = Messages::from_request_parts(parts, state).await?;
+
+        let from_protected: bool = session
+            .get(FROM_PROTECTED_KEY)
+            .await
+            .unwrap()
+            .unwrap_or_default();
+
+        let user = parts.extensions.get::<User>();
+        let username = user.map(|user| user.username.clone()).unwrap_or_default();
+        let is_admin = user.is_some_and(|user| user.is_admin);
+        let workspaces = parts
+            .extensions
+            .get::<Vec<Workspace>>()
+            .cloned()
+            .unwrap_or_default();
+        let workspace_id = parts
+            .extensions
+            .get::<Workspace>()
+            .map(|workspace| workspace.id)
+            .unwrap_or_default();
+        let settings = parts
+            .extensions
+            .get::<UserSettings>()
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(Self {
+            username,
+            workspaces,
+            workspace_id,
+            is_admin,
+            settings,
+            messages: get_messages(messages),
+            from_protected,
+            ..Default::default()
+        })
+    }
+}
+
+/// Home page template
+#[derive(Default, Template)]
+#[template(path = "auth/home.html")]
+struct HomeTemplate {
+    ctx: BaseContext,
 }
 
 /// Register page template
 #[derive(Default, Template)]
 #[template(path = "auth/register.html")]
 struct RegisterTemplate {
-    title: String,
-    username: String,
-    messages_status: String,
-    messages: String,
-    from_protected: bool,
-    is_error: bool,
+    ctx: BaseContext,
 }
 
 /// Login page template
 #[derive(Default, Template)]
 #[template(path = "auth/login.html")]
 struct LoginTemplate {
-    title: String,
-    username: String,
-    messages_status: String,
-    messages: String,
-    from_protected: bool,
-    is_error: bool,
+    ctx: BaseContext,
+}
+
+/// Forgotten password page template
+#[derive(Default, Template)]
+#[template(path = "auth/forgot_password.html")]
+struct ForgotPasswordTemplate {
+    ctx: BaseContext,
+}
+
+/// Password reset page template
+#[derive(Default, Template)]
+#[template(path = "auth/reset_password.html")]
+struct ResetPasswordTemplate {
+    token: String,
+    ctx: BaseContext,
 }
 
 /// Todolist page template
 #[derive(Default, Template)]
 #[template(path = "todos/todo_list.html")]
 struct TodoListTemplate {
-    title: String,
     title_page: String,
+    todos: Vec<Todo>,
+    /// Cursor of the next page, `None` on the last one
+    next_cursor: Option<String>,
+    items: TodoItemsData,
+    filter: TodoFilter,
+    saved_filters: Vec<SavedFilter>,
+    selected_filter: i64,
+    ctx: BaseContext,
+}
+
+/// Data shown next to each todo in the list items
+/// (shared by the list page and the item fragments)
+#[derive(Default)]
+struct TodoItemsData {
+    tzone: String,
+    date_format: DateFormat,
+    tracked: HashMap<i64, TrackedTime>,
+    blocked: HashSet<i64>,
+    links: HashMap<i64, Vec<TodoLink>>,
+    subtasks: HashMap<i64, Vec<Subtask>>,
+    progress: HashMap<i64, ChecklistProgress>,
+    /// Rows already rendered, reused by the lists (none to always render them)
+    fragments: Option<FragmentCache>,
+}
+
+impl TodoItemsData {
+    /// Reuses the rows rendered before, for the lists.
+    fn with_fragments(self, fragments: &FragmentCache) -> Self {
+        Self {
+            fragments: Some(fragments.clone()),
+            ..self
+        }
+    }
+
+    /// The row of a todo in the list, taken from the cache while
+    /// nothing it shows has changed.
+    fn row(&self, todo: &Todo) -> askama::Result<String> {
+        let render = || {
+            TodoRowTemplate {
+                todo,
+                items: self,
+                oob: false,
+            }
+            .render()
+        };
+
+        match &self.fragments {
+            Some(fragments) => fragments.get_or_render(todo.id, self.row_fingerprint(todo), render),
+            None => render(),
+        }
+    }
+
+    /// Hash of everything the row of a todo shows, including the
+    /// texts that change with time (due in…, running timer).
+    fn row_fingerprint(&self, todo: &Todo) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        todo.hash(&mut hasher);
+        self.tzone.hash(&mut hasher);
+        self.date_format.hash(&mut hasher);
+        self.tracked.get(&todo.id).hash(&mut hasher);
+        self.is_blocked(&todo.id).hash(&mut hasher);
+        self.links_of(&todo.id).hash(&mut hasher);
+        self.subtasks_of(&todo.id).hash(&mut hasher);
+        self.progress_of(&todo.id).hash(&mut hasher);
+        todo.due_at
+            .map(|due_at| filters::relative_time(&due_at))
+            .transpose()
+            .ok()
+            .hash(&mut hasher);
+        filters::timeago(&todo.created_at, &self.tzone, &self.date_format)
+            .ok()
+            .hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// Time tracked on a todo (empty if none).
+    fn tracked_time(&self, id: &i64) -> String {
+        self.tracked
+            .get(id)
+            .map(|tracked| format_duration(tracked.seconds))
+            .unwrap_or_default()
+    }
+
+    /// Whether a todo has open blockers.
+    fn is_blocked(&self, id: &i64) -> bool {
+        self.blocked.contains(id)
+    }
+
+    /// Links attached to a todo.
+    fn links_of(&self, id: &i64) -> &[TodoLink] {
+        self.links.get(id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Checklist items of a todo.
+    fn subtasks_of(&self, id: &i64) -> &[Subtask] {
+        self.subtasks.get(id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Checklist progress of a todo (`None` without subtasks).
+    fn progress_of(&self, id: &i64) -> Option<&ChecklistProgress> {
+        self.progress.get(id)
+    }
+
+    /// Whether the timer of a todo is running.
+    fn is_running(&self, id: &i64) -> bool {
+        self.tracked.get(id).is_some_and(|tracked| tracked.running)
+    }
+}
+
+/// A row of the todo list, rendered on its own to be cached
+#[derive(Template)]
+#[template(path = "partials/todo_item_list.html")]
+struct TodoRowTemplate<'a> {
+    todo: &'a Todo,
+    items: &'a TodoItemsData,
+    /// Swaps the row out of band
+    oob: bool,
+}
+
+/// Rows of the next page of the todo list, returned to HTMX
+/// when the end of the list is scrolled into view
+#[derive(Default, Template)]
+#[template(path = "partials/todo_item_page.html")]
+struct TodoPageTemplate {
+    todos: Vec<Todo>,
+    next_cursor: Option<String>,
+    sort: String,
+    items: TodoItemsData,
+}
+
+/// A single todo row, returned to HTMX after creating or updating a todo
+#[derive(Default, Template)]
+#[template(path = "partials/todo_item_fragment.html")]
+struct TodoItemTemplate {
+    todo: Todo,
+    items: TodoItemsData,
+    /// Removes the "nothing to do" placeholder row
+    created: bool,
+}
+
+/// Rows of the todos changed by the bulk edit, swapped out of band
+/// (the ones moved to another workspace are removed)
+#[derive(Default, Template)]
+#[template(path = "partials/todo_bulk_rows.html")]
+struct TodoBulkRowsTemplate {
+    todos: Vec<Todo>,
+    items: TodoItemsData,
+    workspace_id: i64,
+}
+
+/// Modal that edits the todos checked in the list at once
+#[derive(Default, Template)]
+#[template(path = "partials/todo_bulk_edit_modal.html")]
+struct TodoBulkEditModalTemplate {
+    ids: Vec<i64>,
+    /// Workspaces the todos can be moved to.
+    workspaces: Vec<Workspace>,
+    workspace_id: i64,
+}
+
+/// Todos found by the search as you type, as `<li>` items
+#[derive(Default, Template)]
+#[template(path = "partials/todo_search_results.html")]
+struct TodoSearchResultsTemplate {
+    hits: Vec<SearchHit>,
+    /// Whether the text had any word, to say that nothing was found
+    searched: bool,
+}
+
+/// A todo found by the search, with its title split around the matches
+struct SearchHit {
+    todo: Todo,
+    title: Vec<Highlight>,
+}
+
+/// Part of a text, marked when it matches the search
+struct Highlight {
+    text: String,
+    matched: bool,
+}
+
+/// Options of the datalist of the tags input, each one being the tags
+/// typed before the last one followed by a suggestion
+#[derive(Default, Template)]
+#[template(path = "partials/tag_suggestions.html")]
+struct TagSuggestionsTemplate {
+    typed: String,
+    suggestions: Vec<TagSuggestion>,
+}
+
+/// Stats page template
+#[derive(Default, Template)]
+#[template(path = "todos/stats.html")]
+struct StatsTemplate {
+    stats: TodoStats,
+    open: i64,
+    total_tracked: String,
+    time_per_todo: Vec<(String, String)>,
+    ctx: BaseContext,
+}
+
+/// Import page template (upload and preview steps)
+#[derive(Default, Template)]
+#[template(path = "settings/import.html")]
+struct ImportTemplate {
+    preview: Vec<ImportedTodo>,
+    ctx: BaseContext,
+}
+
+/// Profile page template
+#[derive(Default, Template)]
+#[template(path = "settings/profile.html")]
+/// (the settings come from the base context)
+struct ProfileTemplate {
+    email: String,
+    email_verified: bool,
+    timezones: Vec<String>,
+    ctx: BaseContext,
+}
+
+/// Audit log page template, for the admins
+#[derive(Default, Template)]
+#[template(path = "settings/audit_log.html")]
+struct AuditLogTemplate {
+    entries: Vec<AuditEntry>,
+    filter: AuditFilter,
+    tzone: String,
+    date_format: DateFormat,
+    ctx: BaseContext,
+}
+
+/// Ops page template, for the admins
+#[derive(Default, Template)]
+#[template(path = "settings/ops.html")]
+struct OpsTemplate {
+    /// The same pool unless the database is SQLite.
+    read_pool: PoolStats,
+    write_pool: PoolStats,
+    session_store: String,
+    /// Active sessions, when they are kept in the database.
+    sessions: Option<i64>,
+    ws_connections: usize,
+    jobs: Vec<JobStatus>,
+    errors: Vec<RecentError>,
+    counts: OpsCounts,
+    /// The backups of `BACKUP_DIR`, when it is set.
+    backups: Option<Vec<Backup>>,
+    now: NaiveDateTime,
+    tzone: String,
+    date_format: DateFormat,
+    ctx: BaseContext,
+}
+
+/// Workspace page template
+#[derive(Default, Template)]
+#[template(path = "settings/workspace.html")]
+struct WorkspaceTemplate {
+    workspace: Workspace,
+    members: Vec<WorkspaceMember>,
+    ctx: BaseContext,
+}
+
+/// Notifications page template
+#[derive(Default, Template)]
+#[template(path = "settings/notifications.html")]
+struct NotificationsTemplate {
+    notifications: Vec<Notification>,
+    tzone: String,
+    date_format: DateFormat,
+    ctx: BaseContext,
+}
+
+/// Bell of the navbar with the count of unread notifications
+#[derive(Default, Template)]
+#[template(path = "partials/notification_bell.html")]
+struct NotificationBellTemplate {
+    unread: i64,
+}
+
+/// Atom feed template (served as `application/atom+xml`)
+#[derive(Template)]
+#[template(path = "feed/atom.xml")]
+struct AtomFeedTemplate {
+    base_url: String,
+    token: String,
+    /// Signed URL the feed was read from.
+    self_url: String,
     username: String,
+    updated: NaiveDateTime,
     todos: Vec<Todo>,
-    messages_status: String,
-    messages: String,
-    from_protected: bool,
-    is_error: bool,
+}
+
+/// What a resource of the CalDAV endpoint is, with its properties.
+enum DavEntry {
+    /// The calendar home of the user, with their username.
+    Home(String),
+    /// A workspace, as a calendar: its name and its `getctag`.
+    Calendar(String, String),
+    /// A todo: its `ETag` and, for the reports, its iCalendar object.
+    Todo(String, Option<String>),
+    /// A requested resource that doesn't exist.
+    NotFound,
+}
+
+struct DavResponse {
+    href: String,
+    entry: DavEntry,
+}
+
+/// WebDAV multistatus template of the CalDAV endpoint (served as `application/xml`)
+#[derive(Template)]
+#[template(path = "dav/multistatus.xml")]
+struct DavMultistatusTemplate {
+    responses: Vec<DavResponse>,
 }
 
 /// Todo creation todo dialog template
 #[derive(Default, Template)]
 #[template(path = "partials/todo_creation_modal.html")]
-struct TodoCreationModalTemplate;
+struct TodoCreationModalTemplate {
+    title: String,
+    description: String,
+    tags: String,
+    errors: FieldErrors,
+    limits: TextLimits,
+    /// Title of an open todo that the new one seems to duplicate.
+    similar: Option<String>,
+}
+
+/// Toggled checklist item along with the progress bar of its todo
+#[derive(Template)]
+#[template(path = "partials/subtask_toggle.html")]
+struct SubtaskToggleTemplate {
+    subtask: Subtask,
+    progress: ChecklistProgress,
+    oob: bool,
+}
 
 /// Todo update todo dialog template
 #[derive(Default, Template)]
 #[template(path = "partials/todo_update_modal.html")]
 struct TodoUpdateModalTemplate {
     todo: Todo,
-    datetime: String,
+    tzone: String,
+    date_format: DateFormat,
+    remind_at: String,
+    blockers: Vec<Todo>,
+    candidates: Vec<Todo>,
+    /// Previous versions, newest first
+    history: Vec<TodoVersion>,
+    errors: FieldErrors,
+    limits: TextLimits,
+    /// The edit was refused because the todo had been changed meanwhile
+    conflict: bool,
     is_error: bool,
     reason: String,
 }
 
-/// Error 400 page template
-#[derive(Default, Template)]
-#[template(path = "error/error_400.html")]
-struct Error400Template {
-    title: String,
-    username: String,
-    reason: String,
-    messages_status: String,
-    messages: String,
-    from_protected: bool,
-    is_error: bool,
-}
+/// Id of the current request, shown in the error pages so users
+/// can report it. It is filled in by `..Default::default()`.
+struct ReferenceId(String);
 
-/// Error 401 page template
-#[derive(Default, Template)]
-#[template(path = "error/error_401.html")]
-struct Error401Template {
-    title: String,
-    username: String,
-    reason: String,
-    messages_status: String,
-    messages: String,
-    from_protected: bool,
-    is_error: bool,
+impl Default for ReferenceId {
+    fn default() -> Self {
+        Self(middleware::current_request_id())
+    }
 }
 
-/// Error 404 page template
+/// Error page template, the same for every status
 #[derive(Default, Template)]
-#[template(path = "error/error_404.html")]
-struct Error404Template {
-    title: String,
-    username: String,
+#[template(path = "error/error.html")]
+struct ErrorTemplate {
+    status: StatusCode,
     reason: String,
     link: String,
-    messages_status: String,
-    messages: String,
-    from_protected: bool,
-    is_error: bool,
+    ctx: BaseContext,
+    reference_id: ReferenceId,
 }
 
-/// Error 500 page template
-#[derive(Default, Template)]
-#[template(path = "error/error_500.html")]
-struct Error500Template {
-    title: String,
-    username: String,
+impl ErrorTemplate {
+    /// Error page of `status`, linking back to the login page
+    /// for `401` and to the todo list otherwise. The server
+    /// errors are logged, which reports them when `SENTRY_DSN` is set.
+    fn new(status: StatusCode, reason: impl Into<String>) -> Self {
+        let page = Self::unreported(status, reason);
+
+        if status.is_server_error() {
+            error!(status = status.as_u16(), "{}", page.reason);
+        }
+
+        page
+    }
+
+    /// Same as `new`, for the errors that were already logged.
+    fn unreported(status: StatusCode, reason: impl Into<String>) -> Self {
+        let link = if status == StatusCode::UNAUTHORIZED {
+            "/login"
+        } else {
+            "/todo/list"
+        };
+
+        Self {
+            status,
+            reason: reason.into(),
+            link: link.to_string(),
+            ctx: BaseContext {
+                title: format!("Error {}", status.as_u16()),
+                is_error: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Heading and explanation shown under the status code.
+    fn summary(&self) -> (&str, &str) {
+        match self.status {
+            StatusCode::BAD_REQUEST => ("Bad Request", "Malformed request syntax."),
+            StatusCode::UNAUTHORIZED => {
+                ("Status Unauthorized", "Please provide valid credentials.")
+            }
+            StatusCode::FORBIDDEN => ("Forbidden", "You don't have access to this resource."),
+            StatusCode::NOT_FOUND => (
+                "Resource not found",
+                "The requested resource could not be resolved.",
+            ),
+            StatusCode::METHOD_NOT_ALLOWED => (
+                "Method not allowed",
+                "The resource does not support this request method.",
+            ),
+            StatusCode::GONE => ("Link expired", "Ask for a new link to this resource."),
+            StatusCode::TOO_MANY_REQUESTS => {
+                ("Too Many Requests", "Slow down, you are going too fast.")
+            }
+            status => (
+                status.canonical_reason().unwrap_or("Error"),
+                "An unexpected condition was encountered.",
+            ),
+        }
+    }
+
+    /// Text of the link back to the app.
+    fn link_label(&self) -> &str {
+        match self.link.as_str() {
+            "/" => "Go Back Home Page",
+            "/login" => "Go Login Page",
+            _ => "Go Todo List Page",
+        }
+    }
+}
+
+/// Renders the error page of `status`, e.g. `render_error(StatusCode::NOT_FOUND, e.to_string())`.
+/// For another link, use `ErrorTemplate { link, ..ErrorTemplate::new(status, reason) }`.
+fn render_error(status: StatusCode, reason: impl Into<String>) -> HtmlTemplate<ErrorTemplate> {
+    HtmlTemplate(ErrorTemplate::new(status, reason))
+}
+
+/// Compact error, shown as a toast to HTMX requests that fail
+#[derive(Template)]
+#[template(path = "partials/error_toast.html")]
+struct ErrorToastTemplate {
+    status: u16,
     reason: String,
-    link: String,
-    messages_status: String,
-    messages: String,
-    from_protected: bool,
-    is_error: bool,
+    reference_id: ReferenceId,
+}
+
+impl Page for HomeTemplate {}
+
+impl Page for RegisterTemplate {}
+
+impl Page for LoginTemplate {}
+
+impl Page for ForgotPasswordTemplate {}
+
+impl Page for ResetPasswordTemplate {}
+
+impl Page for TodoListTemplate {}
+
+impl Page for TodoItemTemplate {}
+
+impl Page for TodoPageTemplate {}
+
+impl Page for TodoSearchResultsTemplate {}
+
+impl Page for StatsTemplate {}
+
+impl Page for ImportTemplate {}
+
+impl Page for ProfileTemplate {}
+
+impl Page for WorkspaceTemplate {}
+
+impl Page for NotificationsTemplate {}
+
+impl Page for NotificationBellTemplate {}
+
+impl Page for AuditLogTemplate {}
+
+impl Page for OpsTemplate {}
+
+impl Page for TodoCreationModalTemplate {}
+
+impl Page for TodoBulkEditModalTemplate {}
+
+impl Page for TodoBulkRowsTemplate {}
+
+impl Page for TagSuggestionsTemplate {}
+
+impl Page for SubtaskToggleTemplate {}
+
+impl Page for TodoUpdateModalTemplate {}
+
+impl Page for ErrorToastTemplate {}
+
+impl Page for ErrorTemplate {
+    fn error(&self) -> Option<ErrorPage> {
+        Some(ErrorPage {
+            status: self.status,
+            reason: self.reason.clone(),
+        })
+    }
 }
 
 /* --------------------------------------- */
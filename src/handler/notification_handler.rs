@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use tower_sessions::Session;
+
+use crate::{
+    model::{DateFormat, User},
+    AppState,
+};
+
+use super::{
+    middleware::{record_user_id, user_id_from_cookie},
+    render_error, BaseContext, HtmlTemplate, NotificationBellTemplate, NotificationsTemplate,
+    DATE_FORMAT_KEY, TZONE_KEY,
+};
+
+/// Most notifications shown in the notification center.
+const NOTIFICATIONS_LIMIT: i64 = 50;
+
+/// Status that tells HTMX to stop polling.
+const STOP_POLLING: u16 = 286;
+
+/// Handler of the bell of the navbar, polled by HTMX, with the count
+/// of unread notifications. Once the user is logged out, it answers
+/// with nothing and stops the polling instead of an error.
+pub async fn notification_bell_handler(
+    cookie_jar: CookieJar,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let Some(user_id) = user_id_from_cookie(&cookie_jar, &state.jwt_keys) else {
+        return StatusCode::from_u16(STOP_POLLING).unwrap().into_response();
+    };
+    record_user_id(&user_id);
+
+    match state
+        .notifications
+        .count_unread_notifications(&user_id)
+        .await
+    {
+        Ok(unread) => HtmlTemplate(NotificationBellTemplate { unread }).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        )
+            .into_response(),
+    }
+}
+
+/// Handler to serve the Notifications page, with the last
+/// notifications of the user.
+pub async fn notifications_page_handler(
+    Extension(user): Extension<User>,
+    session: Session,
+    ctx: BaseContext,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let notifications = match state
+        .notifications
+        .get_notifications(&user.id, NOTIFICATIONS_LIMIT)
+        .await
+    {
+        Ok(notifications) => notifications,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            )
+                .into_response()
+        }
+    };
+
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    HtmlTemplate(NotificationsTemplate {
+        notifications,
+        tzone,
+        date_format,
+        ctx: ctx.with_title("Notifications"),
+    })
+    .into_response()
+}
+
+/// Handle the `POST` request to mark a notification as read,
+/// which then leads to the page it is about.
+pub async fn notification_read_handler(
+    Extension(user): Extension<User>,
+    Path(notification_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state
+        .notifications
+        .mark_notification_read(notification_id, &user.id, Utc::now().naive_utc())
+        .await
+    {
+        Ok(notification) => Redirect::to(&notification.link).into_response(),
+        Err(e) => (
+            StatusCode::NOT_FOUND,
+            render_error(StatusCode::NOT_FOUND, e.to_string()),
+        )
+            .into_response(),
+    }
+}
+
+/// Handle the `POST` request to mark all the notifications of the user as read.
+pub async fn notifications_read_all_handler(
+    Extension(user): Extension<User>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state
+        .notifications
+        .mark_all_notifications_read(&user.id, Utc::now().naive_utc())
+        .await
+    {
+        Ok(_) => Redirect::to("/notifications").into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_messages::Messages;
+use chrono_tz::{Tz, TZ_VARIANTS};
+use tower_sessions::Session;
+
+use crate::{
+    model::{DateFormat, ProfileSchema, User, UserSettings, PAGE_SIZES},
+    AppState,
+};
+
+use super::{
+    resolve_timezone, set_date_format_in_session, set_tzone_in_session, BaseContext, HtmlTemplate,
+    ProfileTemplate,
+};
+
+/// Handler to serve the Profile Page template.
+pub async fn profile_page_handler(
+    Extension(user): Extension<User>,
+    ctx: BaseContext,
+) -> impl IntoResponse {
+    HtmlTemplate(ProfileTemplate {
+        email: user.email,
+        email_verified: user.email_verified_at.is_some(),
+        timezones: TZ_VARIANTS.iter().map(|tz| tz.name().to_string()).collect(),
+        ctx: ctx.with_title("Profile"),
+    })
+}
+
+/// Handle the `POST` request of the profile form.
+pub async fn profile_update_handler(
+    Extension(settings): Extension<UserSettings>,
+    headers: HeaderMap,
+    session: Session,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<ProfileSchema>,
+) -> impl IntoResponse {
+    let timezone = Some(form_data.timezone.trim()).filter(|tzone| !tzone.is_empty());
+
+    if timezone.is_some_and(|tzone| tzone.parse::<Tz>().is_err()) {
+        messages.error("Unknown timezone");
+
+        return Redirect::to("/settings/profile");
+    }
+
+    let date_format = DateFormat {
+        order: form_data.date_order,
+        clock: form_data.clock,
+    };
+
+    let (date_order, clock) = date_format.names();
+    let page_size = Some(form_data.page_size).filter(|size| PAGE_SIZES.contains(size));
+    let default_sort = Some(form_data.default_sort)
+        .filter(|sort| matches!(sort.as_str(), "oldest" | "due" | "priority"));
+    let density = Some(form_data.density).filter(|density| density == "compact");
+
+    // The theme is saved by the toggle of the navbar
+    let settings = UserSettings {
+        timezone: timezone.map(str::to_string),
+        date_order: Some(date_order.to_string()),
+        clock: Some(clock.to_string()),
+        page_size,
+        default_sort,
+        density,
+        digest: form_data.digest.name().map(str::to_string),
+        ..settings
+    };
+
+    if let Err(e) = state.settings.save_user_settings(&settings).await {
+        messages.error(format!("Something went wrong: {}", e));
+
+        return Redirect::to("/settings/profile");
+    }
+
+    set_tzone_in_session(&session, resolve_timezone(timezone, &headers, &state.geoip)).await;
+    set_date_format_in_session(&session, date_format).await;
+
+    messages.success("Your profile has been saved");
+
+    Redirect::to("/settings/profile")
+}
@@ -0,0 +1,179 @@
+use std::{fmt::Write, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect},
+    Extension, Form, Json,
+};
+use axum_messages::Messages;
+use chrono::Utc;
+
+use crate::{
+    model::{TodoFilter, User},
+    quick_add,
+    slack::{escape, SlackMessage, SlashCommand},
+    AppState,
+};
+
+use super::{client_timezone, convert_datetime, validate_todo};
+
+/// How long the link sent to unknown Slack users can be opened.
+const LINK_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Most todos listed by `list`.
+const LIST_LIMIT: usize = 20;
+
+const USAGE: &str = "Usage: `add <task>` (e.g. `add pay rent tomorrow 5pm #bills !high`) or `list`";
+
+/// Handler of the Slack slash command (e.g. `/todo add …` or `/todo list`),
+/// run as the account linked to the Slack user. The signature of the
+/// request is checked by `slack_signature_middleware`.
+pub async fn slack_command_handler(
+    State(state): State<Arc<AppState>>,
+    Form(command): Form<SlashCommand>,
+) -> impl IntoResponse {
+    let user = match state
+        .users
+        .get_user_by_slack_id(&command.team_id, &command.user_id)
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            let url = state.url_signer.sign(
+                &format!(
+                    "/integrations/slack/link/{}/{}",
+                    command.team_id, command.user_id
+                ),
+                LINK_TTL,
+            );
+
+            return Json(SlackMessage::ephemeral(format!(
+                "Your Slack account isn't linked to the todo list yet: <{}{}|link it> (the link works for {} minutes).",
+                state.config.app_url,
+                url,
+                LINK_TTL.as_secs() / 60
+            )));
+        }
+        Err(e) => {
+            return Json(SlackMessage::ephemeral(format!(
+                "Something went wrong: {}",
+                e
+            )))
+        }
+    };
+
+    let text = command.text.trim();
+    let (action, args) = text.split_once(' ').unwrap_or((text, ""));
+
+    let result = match action {
+        "add" => add_todo(&state, &user, args.trim()).await,
+        "list" => list_todos(&state, &user).await,
+        _ => Ok(USAGE.to_string()),
+    };
+
+    Json(SlackMessage::ephemeral(
+        result.unwrap_or_else(|e| format!("Something went wrong: {}", e)),
+    ))
+}
+
+/// Adds a todo to the personal workspace of the user, parsed like
+/// the quick-add of the todo list.
+async fn add_todo(state: &AppState, user: &User, text: &str) -> Result<String> {
+    let workspace_id = personal_workspace_id(state, user).await?;
+    let settings = state.settings.get_user_settings(&user.id).await?;
+    let tz = client_timezone(settings.timezone.as_deref().unwrap_or_default());
+    let parsed = quick_add::parse(text, tz, Utc::now());
+
+    let errors = validate_todo(&parsed.title, "", state.config.text_limits);
+    if !errors.is_empty() {
+        return Ok(errors.get("title").to_string());
+    }
+
+    let todo = state
+        .todos
+        .add_todo(
+            user.id.clone(),
+            workspace_id,
+            parsed.title,
+            String::new(),
+            parsed.due_at,
+            parsed.priority,
+            parsed.tags.join(" "),
+        )
+        .await?;
+
+    Ok(format!("Added *{}*", escape(&todo.title)))
+}
+
+/// Lists the open todos of the personal workspace of the user,
+/// the ones due first.
+async fn list_todos(state: &AppState, user: &User) -> Result<String> {
+    let workspace_id = personal_workspace_id(state, user).await?;
+    let settings = state.settings.get_user_settings(&user.id).await?;
+    let date_format = settings.date_format();
+    let tzone = settings.timezone.unwrap_or_default();
+
+    let filter = TodoFilter {
+        status: "open".to_string(),
+        sort: "due".to_string(),
+        ..Default::default()
+    };
+    let todos = state
+        .todos
+        .get_filtered_todos(workspace_id, &filter)
+        .await?;
+
+    if todos.is_empty() {
+        return Ok("Nothing to do 🎉".to_string());
+    }
+
+    let mut message = String::from("*Your tasks:*");
+    for todo in todos.iter().take(LIST_LIMIT) {
+        write!(message, "\n• {}", escape(&todo.title))?;
+        if let Some(due_at) = todo.due_at {
+            write!(
+                message,
+                " (due {})",
+                convert_datetime(&tzone, due_at, date_format)?
+            )?;
+        }
+    }
+    if todos.len() > LIST_LIMIT {
+        write!(message, "\n…and {} more", todos.len() - LIST_LIMIT)?;
+    }
+
+    Ok(message)
+}
+
+/// The workspace the user owns, where the commands add their todos.
+async fn personal_workspace_id(state: &AppState, user: &User) -> Result<i64> {
+    state
+        .workspaces
+        .get_user_workspaces(&user.id)
+        .await?
+        .first()
+        .map(|workspace| workspace.id)
+        .ok_or_else(|| anyhow!("you have no workspace"))
+}
+
+/// Handler of the link sent to an unknown Slack user, which links it
+/// to the logged-in account. The link is signed (see `UrlSigner`), so
+/// it can't be made up for another Slack user.
+pub async fn slack_link_handler(
+    Extension(user): Extension<User>,
+    Path((team_id, slack_user_id)): Path<(String, String)>,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state
+        .users
+        .link_slack_user(&team_id, &slack_user_id, &user.id)
+        .await
+    {
+        Ok(_) => messages.success("Your Slack account has been linked!!"),
+        Err(e) => messages.error(format!("Something went wrong: {}", e)),
+    };
+
+    Redirect::to("/settings/profile")
+}
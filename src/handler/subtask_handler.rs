@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension, Form,
+};
+use tower_sessions::Session;
+
+use crate::{
+    model::{SubtaskSchema, User, Workspace},
+    AppState,
+};
+
+use super::{
+    render_error, retarget_body,
+    todo_handler::{todo_changed_response, QueryParams},
+    FlashMessage, HtmlTemplate, SubtaskToggleTemplate,
+};
+
+/// Handle the `POST` request to add a checklist item to a Todo.
+pub async fn subtask_add_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<SubtaskSchema>,
+) -> impl IntoResponse {
+    if form_data.title.trim() == "" {
+        return retarget_body(render_error(
+            StatusCode::BAD_REQUEST,
+            "You must enter a title for the subtask",
+        ));
+    }
+
+    let result = state
+        .todos
+        .add_subtask(id, form_data.title.trim().to_string(), workspace.id)
+        .await;
+
+    match result {
+        Ok(_) => {
+            todo_changed_response(
+                id,
+                FlashMessage::success("Subtask added successfully!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Handle the `PATCH` request to check/uncheck a checklist item.
+/// Returns the item and, out of band, the progress bar of its Todo.
+pub async fn subtask_toggle_handler(
+    Extension(workspace): Extension<Workspace>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let result = match state.todos.toggle_subtask(id, workspace.id).await {
+        Ok(subtask) => state
+            .todos
+            .get_todo_checklist_progress(subtask.todo_id)
+            .await
+            .map(|progress| (subtask, progress)),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((subtask, progress)) => HtmlTemplate(SubtaskToggleTemplate {
+            subtask,
+            progress,
+            oob: true,
+        })
+        .into_response(),
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Handle the `DELETE` request to remove a checklist item.
+pub async fn subtask_delete_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.todos.remove_subtask(id, workspace.id).await {
+        Ok(todo_id) => {
+            todo_changed_response(
+                todo_id,
+                FlashMessage::success("Subtask successfully removed!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
@@ -0,0 +1,309 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Extension, Json};
+use chrono::Utc;
+use chrono_tz::Tz;
+use tower_sessions::Session;
+use tracing::error;
+
+use crate::{
+    config::TextLimits,
+    model::{
+        SyncMutation, SyncRequest, SyncResponse, SyncResult, SyncStatus, SyncTodoRef, Todo, User,
+        Workspace,
+    },
+    quick_add,
+    service::{TodoBlockedError, TodoConflictError},
+    AppState,
+};
+
+use super::{client_timezone, validate_todo, TZONE_KEY};
+
+/// Most changes applied per request, the next ones fail
+/// so that the client sends them again.
+const MAX_MUTATIONS: usize = 100;
+
+/// Why a change wasn't applied.
+enum SyncError {
+    Rejected(String),
+    Failed(String),
+}
+
+/// Handle the `POST` request with the changes that a client made while
+/// offline (queued by the service worker), applied in order. Each one is
+/// applied only once, however many times it is sent.
+#[utoipa::path(
+    post,
+    path = "/api/v1/sync",
+    tag = "sync",
+    request_body = SyncRequest,
+    responses(
+        (status = 200, description = "What became of each change", body = SyncResponse),
+    ),
+    security(("token" = []))
+)]
+pub async fn sync_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SyncRequest>,
+) -> Json<SyncResponse> {
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let tz = client_timezone(&tzone);
+
+    let mut results = Vec::with_capacity(request.mutations.len());
+    for (index, mutation) in request.mutations.iter().enumerate() {
+        let result = if index < MAX_MUTATIONS {
+            sync_mutation(&state, &user, &workspace, tz, mutation).await
+        } else {
+            result(
+                mutation,
+                SyncStatus::Failed,
+                None,
+                Some("too many changes at once".to_string()),
+            )
+        };
+        results.push(result);
+    }
+
+    Json(SyncResponse { results })
+}
+
+/// Applies a change unless it was already, recording its id.
+async fn sync_mutation(
+    state: &AppState,
+    user: &User,
+    workspace: &Workspace,
+    tz: Tz,
+    mutation: &SyncMutation,
+) -> SyncResult {
+    let client_id = mutation.id();
+    let now = Utc::now().naive_utc();
+
+    match state
+        .todos
+        .claim_sync_mutation(&user.id, client_id, now)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => {
+            let todo_id = state
+                .todos
+                .get_sync_mutation_todo(&user.id, client_id)
+                .await
+                .unwrap_or_default();
+            return result(mutation, SyncStatus::Duplicate, todo_id, None);
+        }
+        Err(e) => return result(mutation, SyncStatus::Failed, None, Some(e.to_string())),
+    }
+
+    match apply(state, user, workspace, tz, mutation).await {
+        Ok(todo_id) => {
+            if let Err(e) = state
+                .todos
+                .finish_sync_mutation(&user.id, client_id, todo_id)
+                .await
+            {
+                error!("failed to record the synced change {}: {}", client_id, e);
+            }
+            result(mutation, SyncStatus::Applied, Some(todo_id), None)
+        }
+        Err(e) => {
+            // So that it can be sent again
+            if let Err(e) = state.todos.release_sync_mutation(&user.id, client_id).await {
+                error!("failed to release the synced change {}: {}", client_id, e);
+            }
+            match e {
+                SyncError::Rejected(reason) => {
+                    result(mutation, SyncStatus::Rejected, None, Some(reason))
+                }
+                SyncError::Failed(reason) => {
+                    result(mutation, SyncStatus::Failed, None, Some(reason))
+                }
+            }
+        }
+    }
+}
+
+/// Applies a change, returning the id of the todo it created or changed.
+async fn apply(
+    state: &AppState,
+    user: &User,
+    workspace: &Workspace,
+    tz: Tz,
+    mutation: &SyncMutation,
+) -> Result<i64, SyncError> {
+    let limits = state.config.text_limits;
+
+    match mutation {
+        SyncMutation::Create {
+            title, description, ..
+        } => {
+            validate(title, description, limits)?;
+
+            create(
+                state,
+                user,
+                workspace,
+                quick_add::QuickAdd {
+                    title: title.clone(),
+                    ..Default::default()
+                },
+                description.clone(),
+            )
+            .await
+        }
+        SyncMutation::QuickAdd { text, .. } => {
+            let parsed = quick_add::parse(text, tz, Utc::now());
+
+            validate(&parsed.title, "", limits)?;
+
+            create(state, user, workspace, parsed, String::new()).await
+        }
+        SyncMutation::Update {
+            todo,
+            title,
+            description,
+            status,
+            version,
+            ..
+        } => {
+            validate(title, description, limits)?;
+
+            let todo = resolve(state, user, workspace, todo).await?;
+
+            state
+                .todos
+                .update_todo(
+                    title.clone(),
+                    description.clone(),
+                    *status,
+                    todo.remind_at,
+                    None,
+                    todo.id,
+                    *version,
+                    workspace.id,
+                )
+                .await
+                .map_err(todo_error)?;
+
+            Ok(todo.id)
+        }
+        SyncMutation::Toggle { todo, .. } => {
+            let todo = resolve(state, user, workspace, todo).await?;
+
+            state
+                .todos
+                .toggle_todo(todo.id, workspace.id)
+                .await
+                .map_err(todo_error)?;
+
+            Ok(todo.id)
+        }
+        SyncMutation::Delete { todo, .. } => {
+            let todo = resolve(state, user, workspace, todo).await?;
+
+            state
+                .todos
+                .remove_todo(todo.id, workspace.id)
+                .await
+                .map_err(todo_error)?;
+
+            Ok(todo.id)
+        }
+    }
+}
+
+/// Rejects the titles and descriptions that the forms wouldn't accept.
+fn validate(title: &str, description: &str, limits: TextLimits) -> Result<(), SyncError> {
+    let errors = validate_todo(title, description, limits);
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let reason = format!("{} {}", errors.get("title"), errors.get("description"));
+    Err(SyncError::Rejected(reason.trim().to_string()))
+}
+
+/// Creates a todo, within the limit of todos created per user.
+async fn create(
+    state: &AppState,
+    user: &User,
+    workspace: &Workspace,
+    parsed: quick_add::QuickAdd,
+    description: String,
+) -> Result<i64, SyncError> {
+    if let Err(wait) = state.todo_create_limiter.check(&user.id) {
+        return Err(SyncError::Failed(format!(
+            "creating tasks too fast, wait {} seconds",
+            wait.as_secs() + 1
+        )));
+    }
+
+    let todo = state
+        .todos
+        .add_todo(
+            user.id.clone(),
+            workspace.id,
+            parsed.title,
+            description,
+            parsed.due_at,
+            parsed.priority,
+            parsed.tags.join(" "),
+        )
+        .await
+        .map_err(|e| SyncError::Failed(e.to_string()))?;
+
+    Ok(todo.id)
+}
+
+/// The todo of a change, which must be in the workspace.
+async fn resolve(
+    state: &AppState,
+    user: &User,
+    workspace: &Workspace,
+    todo: &SyncTodoRef,
+) -> Result<Todo, SyncError> {
+    let todo_id = match todo {
+        SyncTodoRef::Id(id) => *id,
+        SyncTodoRef::Mutation(client_id) => state
+            .todos
+            .get_sync_mutation_todo(&user.id, client_id)
+            .await
+            .map_err(|e| SyncError::Failed(e.to_string()))?
+            .ok_or_else(|| {
+                SyncError::Rejected(format!("no todo was created by the change {}", client_id))
+            })?,
+    };
+
+    state
+        .todos
+        .get_todo_by_id(todo_id, workspace.id)
+        .await
+        .map_err(|e| SyncError::Rejected(e.to_string()))
+}
+
+/// The todos that are blocked or were changed meanwhile are rejected,
+/// the other errors can be retried.
+fn todo_error(e: anyhow::Error) -> SyncError {
+    if e.is::<TodoBlockedError>() || e.is::<TodoConflictError>() {
+        SyncError::Rejected(e.to_string())
+    } else {
+        SyncError::Failed(e.to_string())
+    }
+}
+
+fn result(
+    mutation: &SyncMutation,
+    status: SyncStatus,
+    todo_id: Option<i64>,
+    error: Option<String>,
+) -> SyncResult {
+    SyncResult {
+        id: mutation.id().to_string(),
+        status,
+        todo_id,
+        error,
+    }
+}
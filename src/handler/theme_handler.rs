@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Form,
+};
+use axum_extra::extract::CookieJar;
+use tower_sessions::Session;
+
+use crate::{model::ThemeSchema, AppState};
+
+use super::{
+    middleware::{record_user_id, user_id_from_cookie},
+    retarget_body, set_theme_in_session, ErrorTemplate, HtmlTemplate,
+};
+
+/// Handle the `POST` request of the theme toggle. The theme is applied
+/// in the browser right away, so there is nothing to swap on success.
+/// Logged in users also get it saved in their account.
+pub async fn theme_handler(
+    cookie_jar: CookieJar,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<ThemeSchema>,
+) -> Response {
+    set_theme_in_session(&session, form_data.theme).await;
+
+    if let Some(user_id) = user_id_from_cookie(&cookie_jar, &state.jwt_keys) {
+        record_user_id(&user_id);
+
+        if let Err(e) = state
+            .settings
+            .set_user_theme(&user_id, form_data.theme)
+            .await
+        {
+            return retarget_body(HtmlTemplate(ErrorTemplate {
+                link: "/".to_string(),
+                ..ErrorTemplate::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }));
+        }
+    }
+
+    StatusCode::NO_CONTENT.into_response()
+}
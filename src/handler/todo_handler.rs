@@ -1,248 +1,1338 @@
-use std::sync::Arc;
+use std::{cmp::Reverse, sync::Arc};
 
 use askama::filters::capitalize;
 use axum::{
-    extract::{Query, State},
-    response::{IntoResponse, Redirect},
-    Extension, Form,
+    extract::{Path, Query, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Form, Json,
 };
+use axum_extra::extract::Form as ExtraForm;
 use axum_messages::Messages;
+use chrono::Utc;
 use serde::Deserialize;
-use tokio::sync::RwLock;
 use tower_sessions::Session;
 
 use crate::{
-    model::{TodoEditSchema, TodoSchema, User},
-    service::{add_todo, get_todo_by_id, remove_todo, update_todo},
+    import::parse_tags,
+    model::{
+        BulkEditSchema, DateFormat, DependencySchema, Page, PageStart, QuickAddSchema, Todo,
+        TodoCursor, TodoEditSchema, TodoFilter, TodoSchema, User, UserSettings, Workspace,
+    },
+    quick_add,
+    repo::TodoRepo,
+    service::{search_terms, TodoBlockedError, TodoConflictError},
     AppState,
 };
 
 use super::{
-    convert_datetime, get_messages, Error400Template, Error404Template, Error500Template,
-    HtmlTemplate, TodoCreationModalTemplate, TodoListTemplate, TodoUpdateModalTemplate,
-    FROM_PROTECTED_KEY, TZONE_KEY,
+    client_timezone, format_duration, from_datetime_local, render_error, retarget_body,
+    retarget_modal, to_datetime_local, toast_trigger, validate_todo, BaseContext, ErrorTemplate,
+    FlashMessage, Highlight, HtmlTemplate, MethodOverridden, SearchHit, StatsTemplate,
+    TagSuggestionsTemplate, TodoBulkEditModalTemplate, TodoBulkRowsTemplate,
+    TodoCreationModalTemplate, TodoItemTemplate, TodoItemsData, TodoListTemplate, TodoPageTemplate,
+    TodoSearchResultsTemplate, TodoUpdateModalTemplate, DATE_FORMAT_KEY, TZONE_KEY,
 };
 
+/// Most todos shown by the search as you type.
+const LIVE_SEARCH_LIMIT: i64 = 20;
+
+/// Most tags suggested while typing them.
+const TAG_SUGGESTION_LIMIT: usize = 10;
+
+/// Most todos of a page of the JSON todo list.
+const MAX_API_PAGE_SIZE: i64 = 100;
+
+/// Header holding the number of todos of the JSON todo list.
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
 /// Struct for holding the todo_id (i64) that comes in query params.
 #[derive(Debug, Deserialize)]
 pub struct QueryParams {
     pub id: i64,
 }
 
+/// Struct for holding the cursor and the sort of the next page
+/// of the todo list.
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    pub after: String,
+    /// As in `TodoFilter::sort`, newest first when it's `""`.
+    #[serde(default)]
+    pub sort: String,
+}
+
+/// Struct for holding the paging of the JSON todo list, at most one
+/// of the cursors of its `Link` header.
+#[derive(Debug, Deserialize)]
+pub struct ApiPageParams {
+    pub after: Option<String>,
+    pub before: Option<String>,
+    /// The page size of the user by default.
+    pub limit: Option<i64>,
+}
+
+/// Struct for holding the text of the search as you type.
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    pub q: String,
+}
+
+/// Struct for holding the text of the tags input. The input itself
+/// sends it as `tags`.
+#[derive(Debug, Deserialize)]
+pub struct TagSuggestParams {
+    #[serde(default, alias = "tags")]
+    pub q: String,
+}
+
+/// Struct for holding the id of the saved filter selected in the sidebar.
+#[derive(Debug, Deserialize)]
+pub struct SelectedFilterParams {
+    pub filter: Option<i64>,
+}
+
 /// Handler to serve the Todo List Page template.
 pub async fn todo_list_handler(
     Extension(user): Extension<User>,
-    State(state): State<Arc<RwLock<AppState>>>,
-    messages: Messages,
+    Extension(workspace): Extension<Workspace>,
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<TodoFilter>,
+    Query(SelectedFilterParams { filter: selected }): Query<SelectedFilterParams>,
+    ctx: BaseContext,
     session: Session,
 ) -> impl IntoResponse {
-    let from_protected: bool = session
-        .get(FROM_PROTECTED_KEY)
+    let full_title = format!(
+        "{}'s Task List",
+        capitalize(&user.username).unwrap_or_else(|_| user.username.to_owned())
+    );
+
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
         .await
         .unwrap()
         .unwrap_or_default();
 
-    let (messages_status, messages) = get_messages(messages);
+    let saved_filters = match state.todos.get_saved_filters(user.id.clone()).await {
+        Ok(saved_filters) => saved_filters,
+        Err(e) => {
+            return HtmlTemplate(ErrorTemplate {
+                link: "/".to_string(),
+                ..ErrorTemplate::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            })
+            .into_response()
+        }
+    };
 
-    let full_title = format!(
-        "{}'s Task List",
-        capitalize(&user.username).unwrap_or_else(|_| user.username.to_owned())
-    );
+    let items =
+        match get_todo_items_data(user.id, workspace.id, tzone, date_format, &*state.todos).await {
+            Ok(items) => items,
+            Err(e) => {
+                return HtmlTemplate(ErrorTemplate {
+                    link: "/".to_string(),
+                    ..ErrorTemplate::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                })
+                .into_response()
+            }
+        };
+
+    // A selected saved filter takes precedence over the query string
+    let mut filter = match selected.and_then(|id| saved_filters.iter().find(|f| f.id == id)) {
+        Some(saved_filter) => TodoFilter::from(saved_filter),
+        None => filter,
+    };
+    if filter.sort.is_empty() {
+        filter.sort = ctx.settings.default_sort().to_string();
+    }
+
+    // Filtered lists are shown whole, the plain one (in any sort) a page at a time
+    let result = if filter.lists_all() {
+        state
+            .todos
+            .get_all_todos(
+                workspace.id,
+                &filter.sort,
+                PageStart::First,
+                ctx.settings.page_size(),
+            )
+            .await
+    } else {
+        state
+            .todos
+            .get_filtered_todos(workspace.id, &filter)
+            .await
+            .map(|items| Page {
+                items,
+                ..Default::default()
+            })
+    };
 
-    let lock = state.read().await;
-    let todos = lock.todos.clone();
-    drop(lock);
+    let page = match result {
+        Ok(page) => page,
+        Err(e) => {
+            return HtmlTemplate(ErrorTemplate {
+                link: "/".to_string(),
+                ..ErrorTemplate::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            })
+            .into_response()
+        }
+    };
 
     HtmlTemplate(TodoListTemplate {
-        title: full_title.to_owned(),
+        ctx: ctx.with_title(&full_title),
         title_page: full_title,
-        username: user.username,
-        todos,
-        messages_status,
-        messages,
-        from_protected,
-        ..Default::default()
+        todos: page.items,
+        next_cursor: page.next_cursor,
+        items: items.with_fragments(&state.fragments),
+        filter,
+        saved_filters,
+        selected_filter: selected.unwrap_or_default(),
     })
     .into_response()
 }
 
+/// Handler of the row at the end of the todo list, which HTMX
+/// replaces with the next page of todos when it is scrolled into view.
+pub async fn todo_list_page_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Extension(settings): Extension<UserSettings>,
+    State(state): State<Arc<AppState>>,
+    Query(PageParams { after, sort }): Query<PageParams>,
+    session: Session,
+) -> impl IntoResponse {
+    let Some(cursor) = TodoCursor::parse(&after) else {
+        return retarget_body(render_error(StatusCode::BAD_REQUEST, "Invalid page cursor"));
+    };
+
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    let result = match state
+        .todos
+        .get_all_todos(
+            workspace.id,
+            &sort,
+            PageStart::After(cursor),
+            settings.page_size(),
+        )
+        .await
+    {
+        Ok(page) => get_todo_items_data(user.id, workspace.id, tzone, date_format, &*state.todos)
+            .await
+            .map(|items| (page, items)),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((page, items)) => HtmlTemplate(TodoPageTemplate {
+            todos: page.items,
+            next_cursor: page.next_cursor,
+            sort,
+            items: items.with_fragments(&state.fragments),
+        })
+        .into_response(),
+        Err(e) => retarget_body(render_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
+    }
+}
+
+/// Loads the data shown next to each todo of the workspace in the list,
+/// with the time tracked by the user.
+async fn get_todo_items_data(
+    user_id: String,
+    workspace_id: i64,
+    tzone: String,
+    date_format: DateFormat,
+    todos: &dyn TodoRepo,
+) -> anyhow::Result<TodoItemsData> {
+    let mut items = TodoItemsData {
+        tzone,
+        date_format,
+        ..Default::default()
+    };
+
+    for tracked_time in todos.get_tracked_times(user_id).await? {
+        items.tracked.insert(tracked_time.todo_id, tracked_time);
+    }
+    items.blocked = todos
+        .get_blocked_todo_ids(workspace_id)
+        .await?
+        .into_iter()
+        .collect();
+    for link in todos.get_links(workspace_id).await? {
+        items.links.entry(link.todo_id).or_default().push(link);
+    }
+    for subtask in todos.get_subtasks(workspace_id).await? {
+        items
+            .subtasks
+            .entry(subtask.todo_id)
+            .or_default()
+            .push(subtask);
+    }
+    for progress in todos.get_checklist_progress(workspace_id).await? {
+        items.progress.insert(progress.todo_id, progress);
+    }
+
+    Ok(items)
+}
+
+/// Renders the row of a created/updated Todo for HTMX to swap in place,
+/// with the toast of `message` and the `trigger` event in `HX-Trigger`.
+#[allow(clippy::too_many_arguments)]
+async fn todo_item_response(
+    todo: Todo,
+    created: bool,
+    message: FlashMessage,
+    trigger: &'static str,
+    user_id: String,
+    tzone: String,
+    date_format: DateFormat,
+    todos: &dyn TodoRepo,
+) -> Response {
+    match get_todo_items_data(user_id, todo.workspace_id, tzone, date_format, todos).await {
+        Ok(items) => (
+            toast_trigger(message, &[trigger]),
+            HtmlTemplate(TodoItemTemplate {
+                todo,
+                items,
+                created,
+            }),
+        )
+            .into_response(),
+        Err(e) => retarget_body(render_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
+    }
+}
+
+/// Renders the row of the Todo `id` after an action on it (its timer,
+/// dependencies, links…), with the toast of `message`. Its `todoUpdated`
+/// event closes the modal the action may come from.
+pub(super) async fn todo_changed_response(
+    id: i64,
+    message: FlashMessage,
+    user_id: String,
+    workspace_id: i64,
+    session: &Session,
+    todos: &dyn TodoRepo,
+) -> Response {
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    match todos.get_todo_by_id(id, workspace_id).await {
+        Ok(todo) => {
+            todo_item_response(
+                todo,
+                false,
+                message,
+                "todoUpdated",
+                user_id,
+                tzone,
+                date_format,
+                todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
 /// Handler to show the Todo Create Modal template.
-pub async fn todo_create_handler() -> impl IntoResponse {
-    HtmlTemplate(TodoCreationModalTemplate)
+pub async fn todo_create_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    HtmlTemplate(TodoCreationModalTemplate {
+        limits: state.config.text_limits,
+        ..Default::default()
+    })
 }
 
 /// Handle the `POST` request to create a new Todo.
+#[utoipa::path(
+    post,
+    path = "/create",
+    tag = "todos",
+    request_body(content = TodoSchema, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Row of the created Todo, or the modal with the validation errors or asking to confirm a duplicate", content_type = "text/html"),
+    ),
+    security(("token" = []))
+)]
 pub async fn todo_add_handler(
     Extension(user): Extension<User>,
-    messages: Messages,
-    State(state): State<Arc<RwLock<AppState>>>,
+    Extension(workspace): Extension<Workspace>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
     Form(form_data): Form<TodoSchema>,
 ) -> impl IntoResponse {
-    if form_data.title.trim() == "" {
-        return HtmlTemplate(Error400Template {
-            title: "Error 400".to_string(),
-            reason: "You must enter at least one title for the Todo".to_string(),
-            is_error: true,
-            ..Default::default()
-        })
-        .into_response();
+    let limits = state.config.text_limits;
+    let tags = parse_tags(&form_data.tags);
+
+    let errors = validate_todo(&form_data.title, &form_data.description, limits);
+    if !errors.is_empty() {
+        return retarget_modal(HtmlTemplate(TodoCreationModalTemplate {
+            title: form_data.title,
+            description: form_data.description,
+            tags,
+            errors,
+            limits,
+            similar: None,
+        }));
     }
 
-    let lock = state.read().await;
+    if !form_data.confirmed {
+        match state
+            .todos
+            .find_similar_todo(workspace.id, &form_data.title)
+            .await
+        {
+            Ok(Some((_, similar))) => {
+                return retarget_modal(HtmlTemplate(TodoCreationModalTemplate {
+                    title: form_data.title,
+                    description: form_data.description,
+                    tags,
+                    limits,
+                    similar: Some(similar),
+                    ..Default::default()
+                }));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                return retarget_body(render_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    e.to_string(),
+                ))
+            }
+        }
+    }
+
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
 
-    match add_todo(user.id, form_data.title, form_data.description, &lock.pool).await {
+    match state
+        .todos
+        .add_todo(
+            user.id.clone(),
+            workspace.id,
+            form_data.title,
+            form_data.description,
+            None,
+            0,
+            tags,
+        )
+        .await
+    {
         Ok(todo) => {
-            drop(lock);
-            let mut lock = state.write().await;
-            lock.todos.insert(0, todo);
-            drop(lock);
+            todo_item_response(
+                todo,
+                true,
+                FlashMessage::success("Task created successfully!!"),
+                "todoCreated",
+                user.id,
+                tzone,
+                date_format,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
+    }
+}
 
-            messages.success("Task created successfully!!");
+/// Handle the `POST` request of the quick-add input, which parses
+/// strings like "pay rent tomorrow 5pm #bills !high" into a new Todo.
+#[utoipa::path(
+    post,
+    path = "/todo/quick-add",
+    tag = "todos",
+    request_body(content = QuickAddSchema, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Row of the created Todo", content_type = "text/html"),
+        (status = 400, description = "The text has no title", content_type = "text/html"),
+    ),
+    security(("token" = []))
+)]
+pub async fn todo_quick_add_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<QuickAddSchema>,
+) -> impl IntoResponse {
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+    let tz = client_timezone(&tzone);
+
+    let parsed = quick_add::parse(&form_data.text, tz, Utc::now());
 
-            Redirect::to("/todo/list").into_response()
+    let errors = validate_todo(&parsed.title, "", state.config.text_limits);
+    if !errors.is_empty() {
+        return retarget_body(render_error(
+            StatusCode::BAD_REQUEST,
+            errors.get("title").to_string(),
+        ));
+    }
+
+    match state
+        .todos
+        .add_todo(
+            user.id.clone(),
+            workspace.id,
+            parsed.title,
+            String::new(),
+            parsed.due_at,
+            parsed.priority,
+            parsed.tags.join(" "),
+        )
+        .await
+    {
+        Ok(todo) => {
+            todo_item_response(
+                todo,
+                true,
+                FlashMessage::success("Task created successfully!!"),
+                "todoCreated",
+                user.id,
+                tzone,
+                date_format,
+                &*state.todos,
+            )
+            .await
         }
-        Err(e) => HtmlTemplate(Error500Template {
-            title: "Error 500".to_string(),
-            reason: e.to_string(),
-            is_error: true,
-            link: "/todo/list".to_string(),
-            ..Default::default()
-        })
-        .into_response(),
+        Err(e) => retarget_body(render_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
     }
 }
 
 /// Handler to show the Todo Edit Modal template.
 pub async fn todo_edit_handler(
-    Query(QueryParams { id }): Query<QueryParams>,
+    Extension(workspace): Extension<Workspace>,
+    Path(id): Path<i64>,
     session: Session,
-    State(state): State<Arc<RwLock<AppState>>>,
+    State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    let lock = state.read().await;
-
-    let result = get_todo_by_id(id, &lock.pool).await;
-    drop(lock);
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
 
-    let mut lock = state.write().await;
-    if let Err(e) = result {
-        lock.todos.retain(|item| item.id != id);
-        drop(lock);
+    let result = match state.todos.get_todo_by_id(id, workspace.id).await {
+        Ok(todo) => update_modal(todo, workspace.id, &tzone, date_format, &state).await,
+        Err(e) => Err(e),
+    };
 
-        return HtmlTemplate(TodoUpdateModalTemplate {
+    match result {
+        Ok(modal) => HtmlTemplate(modal),
+        Err(e) => HtmlTemplate(TodoUpdateModalTemplate {
             is_error: true,
             reason: e.to_string(),
             ..Default::default()
-        });
+        }),
     }
+}
 
-    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
-    let todo = result.unwrap();
-    let datetime = convert_datetime(&tzone, todo.created_at);
+/// Builds the Todo Update Modal of a todo, with its blockers and history.
+async fn update_modal(
+    todo: Todo,
+    workspace_id: i64,
+    tzone: &str,
+    date_format: DateFormat,
+    state: &AppState,
+) -> anyhow::Result<TodoUpdateModalTemplate> {
+    let blockers = state.todos.get_blockers(todo.id).await?;
+    let candidates = state
+        .todos
+        .get_blocker_candidates(todo.id, workspace_id)
+        .await?;
+    let versions = state.todos.get_todo_versions(todo.id).await?;
+
+    let remind_at = todo
+        .remind_at
+        .map(|remind_at| to_datetime_local(tzone, remind_at))
+        .unwrap_or_default();
 
-    HtmlTemplate(TodoUpdateModalTemplate {
+    Ok(TodoUpdateModalTemplate {
         todo,
-        datetime,
+        tzone: tzone.to_string(),
+        date_format,
+        remind_at,
+        blockers,
+        candidates,
+        history: versions,
+        limits: state.config.text_limits,
         ..Default::default()
     })
 }
 
 /// Handle the `PATCH` request to edit a Todo.
+#[utoipa::path(
+    patch,
+    path = "/todo/{id}",
+    tag = "todos",
+    params(("id" = i64, Path, description = "Id of the Todo")),
+    request_body(content = TodoEditSchema, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Row of the updated Todo, or the modal with the validation errors", content_type = "text/html"),
+        (status = 404, description = "Todo not found", content_type = "text/html"),
+        (status = 409, description = "The Todo was changed since the given version, the modal shows the newer values", content_type = "text/html"),
+    ),
+    security(("token" = []))
+)]
 pub async fn todo_patch_handler(
-    Query(QueryParams { id }): Query<QueryParams>,
-    messages: Messages,
-    State(state): State<Arc<RwLock<AppState>>>,
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Path(id): Path<i64>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
     Form(form_data): Form<TodoEditSchema>,
 ) -> impl IntoResponse {
-    if form_data.title.trim() == "" {
-        return HtmlTemplate(Error400Template {
-            title: "Error 400".to_string(),
-            reason: "You must enter at least one title for the Todo".to_string(),
-            is_error: true,
-            ..Default::default()
-        })
-        .into_response();
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+    let remind_at = from_datetime_local(&tzone, &form_data.remind_at);
+    let tags = form_data.tags.as_deref().map(parse_tags);
+
+    let mut errors = validate_todo(
+        &form_data.title,
+        &form_data.description,
+        state.config.text_limits,
+    );
+
+    let result = if errors.is_empty() {
+        Some(
+            state
+                .todos
+                .update_todo(
+                    form_data.title.clone(),
+                    form_data.description.clone(),
+                    form_data.status,
+                    remind_at,
+                    tags.clone(),
+                    id,
+                    form_data.version,
+                    workspace.id,
+                )
+                .await,
+        )
+    } else {
+        None
+    };
+
+    if let Some(Err(e)) = &result {
+        if e.is::<TodoBlockedError>() {
+            errors.add("status", e.to_string());
+        }
     }
 
-    let lock = state.read().await;
+    // The todo was changed by someone else since the modal was opened
+    let conflict = matches!(&result, Some(Err(e)) if e.is::<TodoConflictError>());
 
-    let result = update_todo(
-        form_data.title.clone(),
-        form_data.description.clone(),
-        form_data.status,
-        id,
-        &lock.pool,
-    )
-    .await;
-    drop(lock);
+    // Show the modal again with the input of the user and the errors,
+    // or with the newer values of the todo after a conflict
+    if !errors.is_empty() || conflict {
+        let result = match state.todos.get_todo_by_id(id, workspace.id).await {
+            Ok(todo) => update_modal(todo, workspace.id, &tzone, date_format, &state).await,
+            Err(e) => Err(e),
+        };
 
-    let mut lock = state.write().await;
-    if let Err(e) = result {
-        lock.todos.retain(|item| item.id != id);
+        return match result {
+            Ok(modal) if conflict => (
+                StatusCode::CONFLICT,
+                retarget_modal(HtmlTemplate(TodoUpdateModalTemplate {
+                    conflict: true,
+                    ..modal
+                })),
+            )
+                .into_response(),
+            Ok(mut modal) => {
+                modal.todo.title = form_data.title;
+                modal.todo.description = form_data.description;
+                modal.todo.status = form_data.status;
+                if let Some(tags) = tags {
+                    modal.todo.tags = tags;
+                }
+                modal.remind_at = form_data.remind_at;
+                modal.errors = errors;
 
-        return HtmlTemplate(Error404Template {
-            title: "Error 404".to_string(),
-            reason: e.to_string(),
-            link: "/todo/list".to_string(),
-            is_error: true,
-            ..Default::default()
-        })
+                retarget_modal(HtmlTemplate(modal))
+            }
+            Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+        };
+    }
+
+    match result.expect("the todo is updated when the input is valid") {
+        Ok(todo) => {
+            todo_item_response(
+                todo,
+                false,
+                FlashMessage::success("Task successfully updated!!"),
+                "todoUpdated",
+                user.id,
+                tzone,
+                date_format,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Handle the `PATCH` request of the checkbox of a Todo, which
+/// only marks it as done or as open again.
+#[utoipa::path(
+    patch,
+    path = "/todo/{id}/toggle",
+    tag = "todos",
+    params(("id" = i64, Path, description = "Id of the Todo")),
+    responses(
+        (status = 200, description = "Row of the Todo, unchanged with an error toast if it is blocked", content_type = "text/html"),
+        (status = 404, description = "Todo not found", content_type = "text/html"),
+    ),
+    security(("token" = []))
+)]
+pub async fn todo_toggle_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Path(id): Path<i64>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let message = match state.todos.toggle_todo(id, workspace.id).await {
+        Ok(todo) if todo.status => FlashMessage::success("Task marked as done!!"),
+        Ok(_) => FlashMessage::success("Task marked as open!!"),
+        // The row is rendered again, so the checkbox is unchecked
+        Err(e) if e.is::<TodoBlockedError>() => FlashMessage::error(e.to_string()),
+        Err(e) => return retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    };
+
+    todo_changed_response(id, message, user.id, workspace.id, &session, &*state.todos).await
+}
+
+/// Handle the `GET` request of the todos of the workspace as JSON, newest
+/// first, a page at a time. The `Link` header holds the URLs of the next
+/// and previous pages (RFC 5988) and `X-Total-Count` the number of todos.
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos",
+    tag = "todos",
+    params(
+        ("after" = Option<String>, Query, description = "Cursor of the `next` link"),
+        ("before" = Option<String>, Query, description = "Cursor of the `prev` link"),
+        ("limit" = Option<i64>, Query, description = "Todos per page, up to 100 (the page size of the user by default)"),
+    ),
+    responses(
+        (status = 200, description = "A page of Todos", body = [Todo], headers(
+            ("Link" = String, description = "URLs of the `next` and `prev` pages"),
+            ("X-Total-Count" = i64, description = "Number of Todos of the workspace"),
+            ("ETag" = String, description = "Tag of the page, for `If-None-Match`"),
+        )),
+        (status = 304, description = "The page is the one of the `If-None-Match` tag"),
+        (status = 400, description = "Invalid cursor, or both of them"),
+    ),
+    security(("token" = []))
+)]
+pub async fn todo_api_list_handler(
+    Extension(workspace): Extension<Workspace>,
+    Extension(settings): Extension<UserSettings>,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ApiPageParams>,
+) -> Response {
+    let start = match (params.after.as_deref(), params.before.as_deref()) {
+        (None, None) => Some(PageStart::First),
+        (Some(after), None) => TodoCursor::parse(after).map(PageStart::After),
+        (None, Some(before)) => TodoCursor::parse(before).map(PageStart::Before),
+        (Some(_), Some(_)) => None,
+    };
+    let Some(start) = start else {
+        return (StatusCode::BAD_REQUEST, "Invalid page cursor").into_response();
+    };
+    let limit = params
+        .limit
+        .unwrap_or_else(|| settings.page_size())
+        .clamp(1, MAX_API_PAGE_SIZE);
+
+    let result = match state
+        .todos
+        .get_all_todos(workspace.id, "", start, limit)
+        .await
+    {
+        Ok(page) => state
+            .todos
+            .count_todos(workspace.id)
+            .await
+            .map(|total| (page, total)),
+        Err(e) => Err(e),
+    };
+    let (page, total) = match result {
+        Ok(result) => result,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    let url = format!("{}/api/v1/todos", state.config.app_url);
+    let links: Vec<String> = [
+        ("after", page.next_cursor, "next"),
+        ("before", page.prev_cursor, "prev"),
+    ]
+    .into_iter()
+    .filter_map(|(param, cursor, rel)| {
+        cursor.map(|cursor| format!("<{url}?{param}={cursor}&limit={limit}>; rel=\"{rel}\""))
+    })
+    .collect();
+
+    let mut response = (
+        [(
+            HeaderName::from_static(TOTAL_COUNT_HEADER),
+            total.to_string(),
+        )],
+        Json(page.items),
+    )
         .into_response();
+    if !links.is_empty() {
+        response.headers_mut().insert(
+            header::LINK,
+            HeaderValue::from_str(&links.join(", ")).unwrap(),
+        );
     }
 
-    let index = lock.todos.iter().position(|item| item.id == id).unwrap();
-    lock.todos[index].title = form_data.title;
-    lock.todos[index].description = form_data.description;
-    lock.todos[index].status = form_data.status;
-    drop(lock);
+    response
+}
 
-    messages.success("Task successfully updated!!");
+/// Handler to show the modal that edits the todos checked in the list,
+/// whose ids come as repeated `id` params.
+pub async fn todo_bulk_edit_handler(
+    Extension(workspace): Extension<Workspace>,
+    Extension(workspaces): Extension<Vec<Workspace>>,
+    ExtraForm(form_data): ExtraForm<BulkEditSchema>,
+) -> impl IntoResponse {
+    if form_data.ids.is_empty() {
+        return retarget_body(render_error(
+            StatusCode::BAD_REQUEST,
+            "Check the tasks to edit first",
+        ));
+    }
 
-    Redirect::to("/todo/list").into_response()
+    HtmlTemplate(TodoBulkEditModalTemplate {
+        ids: form_data.ids,
+        workspaces,
+        workspace_id: workspace.id,
+    })
+    .into_response()
 }
 
-/// Handle the `DELETE` request to remove a Todo.
-pub async fn todo_delete_handler(
+/// Handle the `POST` request of the bulk edit modal, which changes
+/// the status, priority, tags or workspace of all the checked todos.
+#[utoipa::path(
+    post,
+    path = "/todo/bulk",
+    tag = "todos",
+    request_body(content = BulkEditSchema, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Rows of the updated Todos, swapped out of band", content_type = "text/html"),
+        (status = 400, description = "No Todo checked, or an invalid priority or workspace", content_type = "text/html"),
+        (status = 409, description = "A Todo to mark as done is blocked, none was changed", content_type = "text/html"),
+    ),
+    security(("token" = []))
+)]
+pub async fn todo_bulk_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Extension(workspaces): Extension<Vec<Workspace>>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    ExtraForm(mut form_data): ExtraForm<BulkEditSchema>,
+) -> impl IntoResponse {
+    if form_data.ids.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            retarget_body(render_error(
+                StatusCode::BAD_REQUEST,
+                "Check the tasks to edit first",
+            )),
+        )
+            .into_response();
+    }
+    if form_data
+        .priority
+        .is_some_and(|priority| !(0..=3).contains(&priority))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            retarget_body(render_error(StatusCode::BAD_REQUEST, "Invalid priority")),
+        )
+            .into_response();
+    }
+    if form_data
+        .workspace_id
+        .is_some_and(|id| !workspaces.iter().any(|workspace| workspace.id == id))
+    {
+        return (
+            StatusCode::BAD_REQUEST,
+            retarget_body(render_error(
+                StatusCode::BAD_REQUEST,
+                "You are not a member of this workspace",
+            )),
+        )
+            .into_response();
+    }
+    form_data.tags = form_data.tags.as_deref().map(parse_tags);
+
+    let todos = match state
+        .todos
+        .bulk_update_todos(&form_data, workspace.id)
+        .await
+    {
+        Ok(todos) => todos,
+        Err(e) if e.is::<TodoBlockedError>() => {
+            return (
+                StatusCode::CONFLICT,
+                retarget_body(render_error(StatusCode::CONFLICT, e.to_string())),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::NOT_FOUND,
+                retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+            )
+                .into_response()
+        }
+    };
+
+    let tzone: String = session.get(TZONE_KEY).await.unwrap().unwrap_or_default();
+    let date_format: DateFormat = session
+        .get(DATE_FORMAT_KEY)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+
+    match get_todo_items_data(user.id, workspace.id, tzone, date_format, &*state.todos).await {
+        Ok(items) => (
+            toast_trigger(
+                FlashMessage::success(format!("{} task(s) updated!!", todos.len())),
+                &["todoUpdated"],
+            ),
+            HtmlTemplate(TodoBulkRowsTemplate {
+                todos,
+                items,
+                workspace_id: workspace.id,
+            }),
+        )
+            .into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            retarget_body(render_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.to_string(),
+            )),
+        )
+            .into_response(),
+    }
+}
+
+/// Struct for holding the version id that comes in query params.
+#[derive(Debug, Deserialize)]
+pub struct RevertParams {
+    pub id: i64,
+    pub version: i64,
+}
+
+/// Handle the `POST` request to restore a previous version of a Todo.
+pub async fn todo_revert_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(RevertParams { id, version }): Query<RevertParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.todos.revert_todo(id, version, workspace.id).await {
+        Ok(_) => {
+            todo_changed_response(
+                id,
+                FlashMessage::success("Task successfully reverted!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Struct for holding the dependency ids that come in query params.
+#[derive(Debug, Deserialize)]
+pub struct DependencyParams {
+    pub id: i64,
+    pub blocked_by: i64,
+}
+
+/// Handle the `POST` request to mark a Todo as blocked by another.
+pub async fn todo_dependency_add_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
     Query(QueryParams { id }): Query<QueryParams>,
-    messages: Messages,
-    State(state): State<Arc<RwLock<AppState>>>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<DependencySchema>,
 ) -> impl IntoResponse {
-    let lock = state.read().await;
+    match state
+        .todos
+        .add_dependency(id, form_data.blocked_by, workspace.id)
+        .await
+    {
+        Ok(_) => {
+            todo_changed_response(
+                id,
+                FlashMessage::success("Dependency added successfully!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::BAD_REQUEST, e.to_string())),
+    }
+}
 
-    match remove_todo(id, &lock.pool).await {
+/// Handle the `DELETE` request to remove a dependency of a Todo.
+pub async fn todo_dependency_remove_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(DependencyParams { id, blocked_by }): Query<DependencyParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state
+        .todos
+        .remove_dependency(id, blocked_by, workspace.id)
+        .await
+    {
         Ok(_) => {
-            drop(lock);
-            let mut lock = state.write().await;
-            lock.todos.retain(|item| item.id != id);
-            // lock.todos = lock
-            //     .todos
-            //     .clone()
-            //     .into_iter()
-            //     .filter(|item| item.id != id)
-            //     .collect();
-            drop(lock);
-
-            messages.success("Task successfully deleted!!");
-
-            Redirect::to("/todo/list").into_response()
+            todo_changed_response(
+                id,
+                FlashMessage::success("Dependency successfully removed!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
         }
-        Err(e) => {
-            drop(lock);
-            let mut lock = state.write().await;
-            lock.todos.retain(|item| item.id != id);
-            drop(lock);
-
-            HtmlTemplate(Error404Template {
-                title: "Error 404".to_string(),
-                reason: e.to_string(),
-                link: "/todo/list".to_string(),
-                is_error: true,
-                ..Default::default()
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Handle the `POST` request to start the timer of a Todo.
+pub async fn todo_timer_start_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state
+        .todos
+        .start_timer(id, user.id.clone(), workspace.id)
+        .await
+    {
+        Ok(_) => {
+            todo_changed_response(
+                id,
+                FlashMessage::success("Timer started!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Handle the `POST` request to stop the timer of a Todo.
+pub async fn todo_timer_stop_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    Query(QueryParams { id }): Query<QueryParams>,
+    session: Session,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.todos.stop_timer(id, user.id.clone()).await {
+        Ok(_) => {
+            todo_changed_response(
+                id,
+                FlashMessage::success("Timer stopped!!"),
+                user.id,
+                workspace.id,
+                &session,
+                &*state.todos,
+            )
+            .await
+        }
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Handler of the search as you type of the todo list, which returns the
+/// first todos matching the text as `<li>` items, with the matching
+/// words highlighted. Nothing when the text has no words.
+pub async fn todo_search_live_handler(
+    Extension(workspace): Extension<Workspace>,
+    Query(SearchParams { q }): Query<SearchParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let terms = search_terms(&q);
+
+    match state
+        .todos
+        .search_todos(workspace.id, &terms, LIVE_SEARCH_LIMIT)
+        .await
+    {
+        Ok(todos) => HtmlTemplate(TodoSearchResultsTemplate {
+            hits: todos
+                .into_iter()
+                .map(|todo| SearchHit {
+                    title: highlight(&todo.title, &terms),
+                    todo,
+                })
+                .collect(),
+            searched: !terms.is_empty(),
+        })
+        .into_response(),
+        Err(e) => retarget_body(render_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
+    }
+}
+
+/// Handler of `GET /tags/suggest`, which fills the datalist of the tags
+/// input of the modals. The last tag typed is completed with the tags of
+/// the workspace starting with it, the most used first, leaving out the
+/// ones typed already.
+pub async fn tag_suggest_handler(
+    Extension(workspace): Extension<Workspace>,
+    Query(TagSuggestParams { q }): Query<TagSuggestParams>,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let start = q
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace() || *c == ',')
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let (typed, prefix) = q.split_at(start);
+    let typed_tags = parse_tags(typed);
+
+    match state
+        .todos
+        .suggest_tags(
+            workspace.id,
+            prefix,
+            TAG_SUGGESTION_LIMIT + typed_tags.split_whitespace().count(),
+        )
+        .await
+    {
+        Ok(mut suggestions) => {
+            suggestions.retain(|s| !typed_tags.split_whitespace().any(|tag| tag == s.tag));
+            suggestions.truncate(TAG_SUGGESTION_LIMIT);
+
+            HtmlTemplate(TagSuggestionsTemplate {
+                typed: typed.to_string(),
+                suggestions,
             })
             .into_response()
         }
+        Err(e) => retarget_body(render_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            e.to_string(),
+        )),
     }
 }
 
+/// Splits a text into the words starting with one of the terms (the
+/// part that matches them) and the rest, as the index matches them.
+fn highlight(text: &str, terms: &[String]) -> Vec<Highlight> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut matched = vec![false; chars.len()];
+
+    for start in 0..chars.len() {
+        if start > 0 && chars[start - 1].is_alphanumeric() {
+            continue;
+        }
+        for term in terms {
+            let end = start + term.chars().count();
+            let is_match = chars
+                .get(start..end)
+                .is_some_and(|word| word.iter().flat_map(|c| c.to_lowercase()).eq(term.chars()));
+            if is_match {
+                matched[start..end].fill(true);
+            }
+        }
+    }
+
+    let mut parts: Vec<Highlight> = Vec::new();
+    for (c, matched) in chars.into_iter().zip(matched) {
+        match parts.last_mut() {
+            Some(part) if part.matched == matched => part.text.push(c),
+            _ => parts.push(Highlight {
+                text: c.to_string(),
+                matched,
+            }),
+        }
+    }
+
+    parts
+}
+
+/// Handler to serve the Stats Page template.
+pub async fn todo_stats_handler(
+    Extension(user): Extension<User>,
+    Extension(workspace): Extension<Workspace>,
+    ctx: BaseContext,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let stats = state
+        .todos
+        .get_todo_stats(workspace.id, Utc::now().naive_utc())
+        .await;
+    let tracked = state.todos.get_tracked_times(user.id).await;
+    let titles = state.todos.get_todo_titles(workspace.id).await;
+
+    let (stats, tracked, titles) = match (stats, tracked, titles) {
+        (Ok(stats), Ok(tracked), Ok(titles)) => (stats, tracked, titles),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            return render_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    };
+
+    let total_tracked = tracked.iter().map(|t| t.seconds).sum::<i64>();
+
+    // Todos with the most tracked time first
+    let mut time_per_todo = tracked
+        .into_iter()
+        .filter_map(|t| {
+            titles
+                .iter()
+                .find(|(id, _)| *id == t.todo_id)
+                .map(|(_, title)| (title.clone(), format_duration(t.seconds), t.seconds))
+        })
+        .collect::<Vec<_>>();
+    time_per_todo.sort_by_key(|t| Reverse(t.2));
+
+    HtmlTemplate(StatsTemplate {
+        open: stats.total - stats.done,
+        stats,
+        total_tracked: format_duration(total_tracked),
+        time_per_todo: time_per_todo
+            .into_iter()
+            .map(|(title, duration, _)| (title, duration))
+            .collect(),
+        ctx: ctx.with_title("Stats"),
+    })
+    .into_response()
+}
+
+/// Handle the `DELETE` request to remove a Todo.
+/// The row is removed by HTMX, only the toast is returned.
+/// The forms sent without JavaScript are redirected to the list.
+#[utoipa::path(
+    delete,
+    path = "/todo/{id}/delete",
+    tag = "todos",
+    params(("id" = i64, Path, description = "Id of the Todo")),
+    responses(
+        (status = 200, description = "Empty, with the toast of the deletion in `HX-Trigger`", content_type = "text/html"),
+        (status = 303, description = "Redirect to the list, for the forms sent without JavaScript"),
+        (status = 404, description = "Todo not found", content_type = "text/html"),
+    ),
+    security(("token" = []))
+)]
+pub async fn todo_delete_handler(
+    Extension(workspace): Extension<Workspace>,
+    Path(id): Path<i64>,
+    overridden: Option<Extension<MethodOverridden>>,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let result = state.todos.remove_todo(id, workspace.id).await;
+
+    if overridden.is_some() {
+        match result {
+            Ok(_) => messages.success("Task successfully deleted!!"),
+            Err(e) => messages.error(format!("Something went wrong: {}", e)),
+        };
+
+        return Redirect::to("/todo/list").into_response();
+    }
+
+    match result {
+        Ok(_) => (
+            toast_trigger(
+                FlashMessage::success("Task successfully deleted!!"),
+                &["todoDeleted"],
+            ),
+            Html(""),
+        )
+            .into_response(),
+        Err(e) => retarget_body(render_error(StatusCode::NOT_FOUND, e.to_string())),
+    }
+}
+
+/// Redirects the old `/edit?id=` URLs (e.g. from pages loaded before
+/// an upgrade) to the path of the Todo. The `308` status keeps the
+/// method, so `PATCH` requests reach the update handler.
+pub async fn legacy_edit_redirect_handler(
+    method: Method,
+    Query(QueryParams { id }): Query<QueryParams>,
+) -> impl IntoResponse {
+    if method == Method::GET {
+        Redirect::permanent(&format!("/todo/{}/edit", id))
+    } else {
+        Redirect::permanent(&format!("/todo/{}", id))
+    }
+}
+
+/// Redirects the old `/delete?id=` URLs to the path of the Todo.
+pub async fn legacy_delete_redirect_handler(
+    Query(QueryParams { id }): Query<QueryParams>,
+) -> impl IntoResponse {
+    Redirect::permanent(&format!("/todo/{}/delete", id))
+}
+
 /* REFERENCES 22-05-2024:
 https://www.youtube.com/@_noisecode/videos
 https://dev.to/pongsakornsemsuwan/rust-axum-extracting-query-param-of-vec-4pdm
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_messages::Messages;
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    model::{SwitchWorkspaceSchema, User, Workspace, WorkspaceMemberSchema, WorkspaceSchema},
+    AppState,
+};
+
+use super::{
+    render_error, set_workspace_in_session, BaseContext, ErrorTemplate, HtmlTemplate,
+    WorkspaceTemplate,
+};
+
+/// Struct for holding the id of the member that comes in query params.
+#[derive(Debug, Deserialize)]
+pub struct MemberParams {
+    pub user_id: String,
+}
+
+/// Handler to serve the Workspace Page template, with the members
+/// of the current workspace.
+pub async fn workspace_page_handler(
+    Extension(workspace): Extension<Workspace>,
+    ctx: BaseContext,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    match state.workspaces.get_workspace_members(workspace.id).await {
+        Ok(members) => HtmlTemplate(WorkspaceTemplate {
+            workspace,
+            members,
+            ctx: ctx.with_title("Workspace"),
+        })
+        .into_response(),
+        Err(e) => HtmlTemplate(ErrorTemplate {
+            link: "/".to_string(),
+            ..ErrorTemplate::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })
+        .into_response(),
+    }
+}
+
+/// Handle the `POST` request to create a workspace, which
+/// becomes the current one.
+pub async fn workspace_create_handler(
+    Extension(user): Extension<User>,
+    session: Session,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<WorkspaceSchema>,
+) -> impl IntoResponse {
+    if form_data.name.trim() == "" {
+        messages.error("You must enter a name for the workspace");
+
+        return Redirect::to("/settings/workspace");
+    }
+
+    match state
+        .workspaces
+        .create_workspace(form_data.name.trim().to_string(), user.id)
+        .await
+    {
+        Ok(workspace) => {
+            set_workspace_in_session(&session, workspace.id).await;
+
+            messages.success(format!(
+                "Workspace {} created successfully!!",
+                workspace.name
+            ));
+        }
+        Err(e) => {
+            messages.error(format!("Something went wrong: {}", e));
+        }
+    }
+
+    Redirect::to("/settings/workspace")
+}
+
+/// Handle the `POST` request of the workspace switcher in the navbar.
+pub async fn workspace_switch_handler(
+    Extension(workspaces): Extension<Vec<Workspace>>,
+    session: Session,
+    Form(form_data): Form<SwitchWorkspaceSchema>,
+) -> impl IntoResponse {
+    if !workspaces
+        .iter()
+        .any(|workspace| workspace.id == form_data.workspace_id)
+    {
+        return (
+            StatusCode::FORBIDDEN,
+            render_error(
+                StatusCode::FORBIDDEN,
+                "You are not a member of this workspace",
+            ),
+        )
+            .into_response();
+    }
+
+    set_workspace_in_session(&session, form_data.workspace_id).await;
+
+    Redirect::to("/todo/list").into_response()
+}
+
+/// Handle the `POST` request to add a member to the current workspace.
+/// Only its owners can add members.
+pub async fn workspace_member_add_handler(
+    Extension(workspace): Extension<Workspace>,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+    Form(form_data): Form<WorkspaceMemberSchema>,
+) -> impl IntoResponse {
+    if !workspace.is_owner() {
+        return (
+            StatusCode::FORBIDDEN,
+            render_error(
+                StatusCode::FORBIDDEN,
+                "Only the owners of the workspace can add members",
+            ),
+        )
+            .into_response();
+    }
+
+    match state
+        .workspaces
+        .add_workspace_member(workspace.id, form_data.email.trim().to_string())
+        .await
+    {
+        Ok(_) => messages.success("Member added successfully!!"),
+        Err(e) => messages.error(format!("Something went wrong: {}", e)),
+    };
+
+    Redirect::to("/settings/workspace").into_response()
+}
+
+/// Handle the `DELETE` request to remove a member of the current
+/// workspace. Only its owners can remove members.
+pub async fn workspace_member_remove_handler(
+    Extension(workspace): Extension<Workspace>,
+    Query(MemberParams { user_id }): Query<MemberParams>,
+    messages: Messages,
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    if !workspace.is_owner() {
+        return (
+            StatusCode::FORBIDDEN,
+            render_error(
+                StatusCode::FORBIDDEN,
+                "Only the owners of the workspace can remove members",
+            ),
+        )
+            .into_response();
+    }
+
+    match state
+        .workspaces
+        .remove_workspace_member(workspace.id, user_id)
+        .await
+    {
+        Ok(_) => {
+            messages.success("Member successfully removed!!");
+
+            Redirect::to("/settings/workspace").into_response()
+        }
+        Err(e) => render_error(StatusCode::NOT_FOUND, e.to_string()).into_response(),
+    }
+}
@@ -0,0 +1,111 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+    Extension,
+};
+use serde::Deserialize;
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+};
+
+use crate::{hub::TodoEvent, model::Workspace, AppState};
+
+/// Messages sent by the clients of the WebSocket channel.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum ClientMessage {
+    Join { list: i64 },
+    Leave { list: i64 },
+}
+
+/// Handle the upgrade of `/ws`. The connection is authenticated with
+/// the JWT by the auth middleware, like the rest of protected routes.
+pub async fn ws_handler(
+    Extension(workspaces): Extension<Vec<Workspace>>,
+    State(state): State<Arc<AppState>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, workspaces, state))
+}
+
+/// Multiplexes the rooms joined by a client over a single socket.
+/// Each joined room has a task forwarding its events to the socket.
+async fn handle_socket(mut socket: WebSocket, workspaces: Vec<Workspace>, state: Arc<AppState>) {
+    let (tx, mut rx) = mpsc::channel::<TodoEvent>(32);
+    let mut rooms: HashMap<i64, JoinHandle<()>> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            Some(event) = rx.recv() => {
+                let text = serde_json::to_string(&event).unwrap();
+
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            msg = socket.recv() => {
+                let text = match msg {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                let reply = match serde_json::from_str::<ClientMessage>(&text) {
+                    // Users can only join the lists of their workspaces
+                    Ok(ClientMessage::Join { list })
+                        if !workspaces.iter().any(|workspace| workspace.id == list) =>
+                    {
+                        Some(format!("You are not allowed to join the list {}", list))
+                    }
+                    Ok(ClientMessage::Join { list }) => {
+                        rooms.entry(list).or_insert_with_key(|list| {
+                            tokio::spawn(forward(state.hub.subscribe(*list), tx.clone()))
+                        });
+                        None
+                    }
+                    Ok(ClientMessage::Leave { list }) => {
+                        if let Some(task) = rooms.remove(&list) {
+                            task.abort();
+                        }
+                        None
+                    }
+                    Err(e) => Some(format!("Invalid message: {}", e)),
+                };
+
+                if let Some(reason) = reply {
+                    let error = serde_json::json!({ "event": "error", "reason": reason });
+
+                    if socket.send(Message::Text(error.to_string())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    for task in rooms.into_values() {
+        task.abort();
+    }
+}
+
+/// Forwards the events of a room to the socket of a client.
+/// Events missed by a lagging client are skipped.
+async fn forward(mut events: broadcast::Receiver<TodoEvent>, tx: mpsc::Sender<TodoEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
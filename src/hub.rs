@@ -0,0 +1,84 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::model::Todo;
+
+/// How many events a slow client may fall behind before it skips some.
+const ROOM_CAPACITY: usize = 64;
+
+/// A change to a todo list, pushed to the clients of its room.
+/// Lists are identified by the id of their workspace.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum TodoEvent {
+    Created { list: i64, todo: Todo },
+    Updated { list: i64, todo: Todo },
+    Deleted { list: i64, todo_id: i64 },
+}
+
+impl TodoEvent {
+    pub fn created(todo: Todo) -> Self {
+        Self::Created {
+            list: todo.workspace_id,
+            todo,
+        }
+    }
+
+    pub fn updated(todo: Todo) -> Self {
+        Self::Updated {
+            list: todo.workspace_id,
+            todo,
+        }
+    }
+
+    pub fn deleted(list: i64, todo_id: i64) -> Self {
+        Self::Deleted { list, todo_id }
+    }
+
+    fn list(&self) -> i64 {
+        match self {
+            Self::Created { list, .. }
+            | Self::Updated { list, .. }
+            | Self::Deleted { list, .. } => *list,
+        }
+    }
+}
+
+/// Rooms of the WebSocket channel, one broadcast channel per list.
+/// A room is created on the first subscription and dropped once
+/// an event finds it without subscribers.
+#[derive(Default)]
+pub struct Hub {
+    rooms: Mutex<HashMap<i64, broadcast::Sender<TodoEvent>>>,
+}
+
+impl Hub {
+    pub fn subscribe(&self, list: i64) -> broadcast::Receiver<TodoEvent> {
+        let mut rooms = self.rooms.lock().unwrap();
+
+        rooms
+            .entry(list)
+            .or_insert_with(|| broadcast::channel(ROOM_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub fn publish(&self, event: TodoEvent) {
+        let mut rooms = self.rooms.lock().unwrap();
+        let list = event.list();
+
+        if let Some(room) = rooms.get(&list) {
+            if room.send(event).is_err() {
+                rooms.remove(&list);
+            }
+        }
+    }
+
+    /// Clients connected to the WebSocket channel, in every room.
+    pub fn connections(&self) -> usize {
+        let rooms = self.rooms.lock().unwrap();
+
+        rooms.values().map(|room| room.receiver_count()).sum()
+    }
+}
@@ -0,0 +1,276 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+
+use crate::{import::normalize_tags, model::Todo, quick_add::DEFAULT_DUE_HOUR};
+
+/// Format of the date-times of iCalendar, followed by `Z` when in UTC.
+const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Longest line, in bytes, before it's folded (RFC 5545, section 3.1).
+const LINE_LIMIT: usize = 75;
+
+/// The fields of a todo read from an iCalendar `VTODO`.
+#[derive(Debug, Default)]
+pub struct VTodo {
+    /// Empty when the client didn't give one.
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub due_at: Option<NaiveDateTime>,
+    pub completed: bool,
+    pub priority: i64,
+    /// Space separated, as stored in the `tags` column.
+    pub tags: String,
+}
+
+/// Writes a todo as an iCalendar object with a single `VTODO`
+/// (RFC 5545), the dates in UTC.
+pub fn write_vtodo(todo: &Todo, uid: &str, now: NaiveDateTime) -> String {
+    let utc = |dt: NaiveDateTime| format!("{}Z", dt.format(DATETIME_FORMAT));
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//rust-axum-askama-htmx//Todo List//EN".to_string(),
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", escape(uid)),
+        format!("DTSTAMP:{}", utc(now)),
+        format!("CREATED:{}", utc(todo.created_at)),
+        format!("SEQUENCE:{}", todo.version),
+        format!("SUMMARY:{}", escape(&todo.title)),
+    ];
+
+    if !todo.description.is_empty() {
+        lines.push(format!("DESCRIPTION:{}", escape(&todo.description)));
+    }
+    if let Some(due_at) = todo.due_at {
+        lines.push(format!("DUE:{}", utc(due_at)));
+    }
+    // From 1 (highest) to 9 (lowest), 0 being undefined
+    match todo.priority {
+        3 => lines.push("PRIORITY:1".to_string()),
+        2 => lines.push("PRIORITY:5".to_string()),
+        1 => lines.push("PRIORITY:9".to_string()),
+        _ => {}
+    }
+    if !todo.tags.is_empty() {
+        let categories: Vec<_> = todo.tag_list().into_iter().map(escape).collect();
+        lines.push(format!("CATEGORIES:{}", categories.join(",")));
+    }
+    if todo.status {
+        lines.push("STATUS:COMPLETED".to_string());
+        if let Some(completed_at) = todo.completed_at {
+            lines.push(format!("COMPLETED:{}", utc(completed_at)));
+        }
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+
+    lines.push("END:VTODO".to_string());
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Reads the first `VTODO` of an iCalendar object. The date-times
+/// without a timezone are taken in `tz`, and the dates without a time
+/// at `DEFAULT_DUE_HOUR`, like in the quick add.
+pub fn parse_vtodo(data: &str, tz: Tz) -> Result<VTodo> {
+    let mut vtodo: Option<VTodo> = None;
+    // Components inside the VTODO, such as its alarms
+    let mut nested = 0;
+    let mut status = None;
+    let mut has_completed = false;
+    let mut categories = Vec::new();
+
+    for line in unfold(data) {
+        let Some(Property {
+            name,
+            params,
+            value,
+        }) = Property::parse(&line)
+        else {
+            continue;
+        };
+
+        let Some(todo) = vtodo.as_mut() else {
+            if name == "BEGIN" && value.eq_ignore_ascii_case("VTODO") {
+                vtodo = Some(VTodo::default());
+            }
+            continue;
+        };
+
+        match name.as_str() {
+            "BEGIN" => nested += 1,
+            "END" if nested > 0 => nested -= 1,
+            "END" => break,
+            _ if nested > 0 => {}
+            "UID" => todo.uid = unescape(value),
+            "SUMMARY" => todo.summary = unescape(value),
+            "DESCRIPTION" => todo.description = unescape(value),
+            "DUE" => todo.due_at = parse_datetime(value, &params, tz),
+            "STATUS" => status = Some(value.eq_ignore_ascii_case("COMPLETED")),
+            "COMPLETED" => has_completed = true,
+            "PRIORITY" => {
+                todo.priority = match value.trim().parse::<i64>() {
+                    Ok(1..=4) => 3,
+                    Ok(5) => 2,
+                    Ok(6..=9) => 1,
+                    _ => 0,
+                }
+            }
+            "CATEGORIES" => categories.extend(split_list(value)),
+            _ => {}
+        }
+    }
+
+    let mut vtodo = vtodo.ok_or_else(|| anyhow!("the calendar object has no VTODO"))?;
+    vtodo.completed = status.unwrap_or(has_completed);
+    vtodo.tags = normalize_tags(categories.iter().map(String::as_str));
+
+    Ok(vtodo)
+}
+
+/// Escapes the characters with a meaning in the text values.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+
+    unescaped
+}
+
+/// Splits a list value (e.g. of `CATEGORIES`) on its unescaped commas.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut escaped = false;
+
+    for c in value.chars() {
+        if c == ',' && !escaped {
+            items.push(String::new());
+            continue;
+        }
+        escaped = c == '\\' && !escaped;
+        items.last_mut().unwrap().push(c);
+    }
+
+    items.iter().map(|item| unescape(item)).collect()
+}
+
+/// Splits the long lines in lines of `LINE_LIMIT` bytes at most,
+/// the next ones starting with a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+
+    folded
+}
+
+/// Joins the folded lines back.
+fn unfold(data: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in data.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    lines
+}
+
+/// A content line, `NAME;PARAM=VALUE:value`.
+struct Property<'a> {
+    /// Uppercased.
+    name: String,
+    params: Vec<(String, String)>,
+    value: &'a str,
+}
+
+impl<'a> Property<'a> {
+    /// Splits a line into its parts. The parameters can have quoted colons.
+    fn parse(line: &'a str) -> Option<Self> {
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            (c == ':' && !quoted).then_some(i)
+        })?;
+
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim().to_ascii_uppercase();
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| {
+                (
+                    key.to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                )
+            })
+            .collect();
+
+        Some(Self {
+            name,
+            params,
+            value,
+        })
+    }
+}
+
+/// Reads a date-time in UTC (`…Z`), in the timezone of its `TZID`
+/// or else in `tz`, or a date.
+fn parse_datetime(value: &str, params: &[(String, String)], tz: Tz) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, DATETIME_FORMAT).ok();
+    }
+
+    let local = NaiveDateTime::parse_from_str(value, DATETIME_FORMAT)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()?
+                .and_hms_opt(DEFAULT_DUE_HOUR, 0, 0)
+        })?;
+    let tz = params
+        .iter()
+        .find(|(key, _)| key == "TZID")
+        .and_then(|(_, tzid)| tzid.parse::<Tz>().ok())
+        .unwrap_or(tz);
+
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|dt| dt.naive_utc())
+}
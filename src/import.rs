@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of todos accepted from a single export file.
+pub const MAX_IMPORTED_TODOS: usize = 1000;
+
+/// A todo mapped from an external export to the local schema,
+/// waiting to be confirmed in the preview step.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ImportedTodo {
+    pub title: String,
+    pub description: String,
+    pub status: bool,
+    pub due_at: Option<NaiveDateTime>,
+    pub priority: i64,
+    pub tags: String,
+}
+
+/// Parses an export file, detecting whether it is a Trello JSON export
+/// or a Todoist CSV export from its name and contents.
+pub fn parse_export(file_name: &str, data: &[u8]) -> Result<Vec<ImportedTodo>> {
+    // The name and the type of the file are given by the client,
+    // so the contents must be text for any of the formats
+    if data.contains(&0) || std::str::from_utf8(data).is_err() {
+        bail!("the file must be a CSV or JSON export, not a binary file.");
+    }
+
+    let is_json = file_name.to_lowercase().ends_with(".json")
+        || data
+            .iter()
+            .find(|b| !b.is_ascii_whitespace())
+            .is_some_and(|b| *b == b'{');
+
+    let todos = if is_json {
+        parse_trello_json(data)?
+    } else {
+        parse_todoist_csv(data)?
+    };
+
+    if todos.is_empty() {
+        bail!("the file does not contain any task.");
+    }
+
+    if todos.len() > MAX_IMPORTED_TODOS {
+        bail!(
+            "the file contains {} tasks, the maximum is {}.",
+            todos.len(),
+            MAX_IMPORTED_TODOS
+        );
+    }
+
+    Ok(todos)
+}
+
+/// Todoist CSV export: one row per task (`TYPE` = `task`), with the
+/// labels written as `@label` inside `CONTENT` and priorities from
+/// 4 (p1, highest) to 1 (p4, none).
+pub fn parse_todoist_csv(data: &[u8]) -> Result<Vec<ImportedTodo>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+
+    let headers = reader
+        .headers()
+        .context("invalid Todoist CSV file")?
+        .iter()
+        .enumerate()
+        .map(|(i, h)| (h.trim().to_uppercase(), i))
+        .collect::<HashMap<_, _>>();
+
+    let content_idx = *headers
+        .get("CONTENT")
+        .ok_or_else(|| anyhow!("invalid Todoist CSV file: missing CONTENT column."))?;
+
+    let mut todos = Vec::new();
+
+    for record in reader.records() {
+        let record = record.context("invalid Todoist CSV file")?;
+        let field = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|i| record.get(*i))
+                .unwrap_or_default()
+                .trim()
+        };
+
+        if !field("TYPE").eq_ignore_ascii_case("task") {
+            continue;
+        }
+
+        let content = record.get(content_idx).unwrap_or_default();
+        let (title, tags): (Vec<_>, Vec<_>) = content
+            .split_whitespace()
+            .partition(|w| !w.starts_with('@'));
+        let title = title.join(" ");
+
+        if title.is_empty() {
+            continue;
+        }
+
+        let priority = match field("PRIORITY") {
+            "4" => 3,
+            "3" => 2,
+            "2" => 1,
+            _ => 0,
+        };
+
+        todos.push(ImportedTodo {
+            title,
+            description: field("DESCRIPTION").to_string(),
+            status: false,
+            due_at: parse_date(field("DATE")),
+            priority,
+            tags: normalize_tags(tags.iter().map(|t| t.trim_start_matches('@'))),
+        });
+    }
+
+    Ok(todos)
+}
+
+#[derive(Deserialize)]
+struct TrelloBoard {
+    #[serde(default)]
+    cards: Vec<TrelloCard>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrelloCard {
+    name: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    closed: bool,
+    due: Option<String>,
+    #[serde(default)]
+    due_complete: bool,
+    #[serde(default)]
+    labels: Vec<TrelloLabel>,
+}
+
+#[derive(Deserialize)]
+struct TrelloLabel {
+    #[serde(default)]
+    name: String,
+}
+
+/// Trello board JSON export: every non-archived card becomes a todo,
+/// its labels become tags and `dueComplete` the status.
+pub fn parse_trello_json(data: &[u8]) -> Result<Vec<ImportedTodo>> {
+    let board: TrelloBoard = serde_json::from_slice(data).context("invalid Trello JSON file")?;
+
+    let todos = board
+        .cards
+        .into_iter()
+        .filter(|card| !card.closed && !card.name.trim().is_empty())
+        .map(|card| ImportedTodo {
+            title: card.name.trim().to_string(),
+            description: card.desc,
+            status: card.due_complete,
+            due_at: card.due.as_deref().and_then(parse_date),
+            priority: 0,
+            tags: normalize_tags(card.labels.iter().map(|l| l.name.as_str())),
+        })
+        .collect();
+
+    Ok(todos)
+}
+
+/// Accepts RFC 3339 timestamps and plain `YYYY-MM-DD` dates.
+fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.naive_utc())
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
+
+/// Reads the tags typed in a form, separated by spaces or commas,
+/// with or without `#`.
+pub fn parse_tags(input: &str) -> String {
+    normalize_tags(
+        input
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .map(|tag| tag.trim_start_matches('#')),
+    )
+}
+
+/// Lowercases tags, replaces inner whitespace by dashes and joins them
+/// space separated as stored in the `tags` column.
+pub fn normalize_tags<'a>(tags: impl Iterator<Item = &'a str>) -> String {
+    let mut normalized: Vec<String> = Vec::new();
+
+    for tag in tags {
+        let tag = tag
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    normalized.join(" ")
+}
@@ -0,0 +1,146 @@
+use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use tokio::{sync::watch, task::JoinSet};
+use tracing::{error, info};
+
+use crate::{
+    service::{claim_job, finish_job, get_job_next_run, register_job},
+    AppState,
+};
+
+/// How long a job waits before trying again when the database fails.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// A periodic task, run every `interval`.
+struct Job {
+    name: &'static str,
+    interval: Duration,
+    run: Box<dyn Fn(Arc<AppState>) -> JobFuture + Send + Sync>,
+}
+
+/// Runs the periodic work of the app (reminder emails, cleanups…)
+/// outside of the request handlers.
+///
+/// The schedule of every job is kept in the `jobs` table, so a restart
+/// doesn't run them again before they are due, and when several
+/// instances of the app share the database only one of them runs each
+/// job. The table also records the end and the error of the last runs.
+#[derive(Default)]
+pub struct JobRunner {
+    jobs: Vec<Job>,
+}
+
+/// The jobs started by [`JobRunner::start`].
+pub struct RunningJobs {
+    shutdown: watch::Sender<bool>,
+    tasks: JoinSet<()>,
+}
+
+impl JobRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a job, run every `interval`. The name identifies it in the
+    /// `jobs` table, so it must not change between versions of the app.
+    pub fn register<F, Fut>(mut self, name: &'static str, interval: Duration, run: F) -> Self
+    where
+        F: Fn(Arc<AppState>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.jobs.push(Job {
+            name,
+            interval,
+            run: Box::new(move |state| Box::pin(run(state))),
+        });
+
+        self
+    }
+
+    /// Spawns a task for every job.
+    pub fn start(self, state: Arc<AppState>) -> RunningJobs {
+        let (shutdown, stopped) = watch::channel(false);
+        let mut tasks = JoinSet::new();
+
+        for job in self.jobs {
+            tasks.spawn(run_job(job, state.clone(), stopped.clone()));
+        }
+
+        RunningJobs { shutdown, tasks }
+    }
+}
+
+impl RunningJobs {
+    /// Stops the jobs, waiting for the ones that are running to finish.
+    pub async fn shutdown(mut self) {
+        info!("stopping the background jobs…");
+        let _ = self.shutdown.send(true);
+
+        while self.tasks.join_next().await.is_some() {}
+    }
+}
+
+/// Runs a job whenever it is due, until the shutdown.
+async fn run_job(job: Job, state: Arc<AppState>, mut stopped: watch::Receiver<bool>) {
+    let pool = &state.pool;
+
+    if let Err(e) = register_job(job.name, Utc::now().naive_utc(), pool).await {
+        error!("failed to register job {}: {}", job.name, e);
+    }
+
+    loop {
+        let wait = match get_job_next_run(job.name, &state.read_pool).await {
+            Ok(next_run_at) => (next_run_at - Utc::now().naive_utc())
+                .to_std()
+                .unwrap_or_default(),
+            Err(e) => {
+                error!("failed to schedule job {}: {}", job.name, e);
+                RETRY_DELAY
+            }
+        };
+
+        if !sleep_until_stopped(wait, &mut stopped).await {
+            return;
+        }
+
+        let now = Utc::now().naive_utc();
+        let next_run_at = now + chrono::Duration::from_std(job.interval).unwrap();
+
+        match claim_job(job.name, now, next_run_at, pool).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                error!("failed to claim job {}: {}", job.name, e);
+                if !sleep_until_stopped(RETRY_DELAY, &mut stopped).await {
+                    return;
+                }
+                continue;
+            }
+        }
+
+        // A run isn't interrupted by the shutdown, it is waited for
+        let error = match (job.run)(state.clone()).await {
+            Ok(_) => None,
+            Err(e) => {
+                error!("job {} failed: {:#}", job.name, e);
+                Some(format!("{:#}", e))
+            }
+        };
+
+        if let Err(e) = finish_job(job.name, Utc::now().naive_utc(), error, pool).await {
+            error!("failed to record the run of job {}: {}", job.name, e);
+        }
+    }
+}
+
+/// Sleeps for `duration`, returning `false` if the jobs are stopped meanwhile.
+async fn sleep_until_stopped(duration: Duration, stopped: &mut watch::Receiver<bool>) -> bool {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => true,
+        _ = stopped.changed() => false,
+    }
+}
@@ -0,0 +1,30 @@
+use jsonwebtoken::{decode, encode, errors::Result, DecodingKey, EncodingKey, Header, Validation};
+
+use crate::model::TokenClaims;
+
+/// The keys signing and verifying the JWTs, prepared once from
+/// `JWT_SECRET` instead of on every request.
+pub struct JwtKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl JwtKeys {
+    pub fn new(secret: &str) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+        }
+    }
+
+    /// Signs a token with the claims.
+    pub fn encode(&self, claims: &TokenClaims) -> Result<String> {
+        encode(&Header::default(), claims, &self.encoding)
+    }
+
+    /// The claims of a token, if it was signed with the secret and
+    /// hasn't expired.
+    pub fn decode(&self, token: &str) -> Result<TokenClaims> {
+        decode::<TokenClaims>(token, &self.decoding, &Validation::default()).map(|data| data.claims)
+    }
+}
@@ -0,0 +1,186 @@
+mod assets;
+pub mod backup;
+pub mod cleanup;
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod digest;
+pub mod events;
+mod fragments;
+mod geoip;
+mod handler;
+pub mod hub;
+mod ical;
+mod import;
+pub mod jobs;
+mod jwt;
+mod link_preview;
+pub mod mailer;
+pub mod model;
+mod quick_add;
+pub mod rate_limit;
+mod reminder;
+pub mod repo;
+mod reporting;
+mod route;
+mod sanitize;
+mod serialization;
+mod service;
+mod session;
+pub mod signed_url;
+mod slack;
+mod timing;
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+
+use crate::{
+    cleanup::PurgedRows,
+    config::Config,
+    db::{DbPool, DbPools},
+    events::EventBus,
+    fragments::FragmentCache,
+    geoip::GeoIp,
+    hub::Hub,
+    jobs::JobRunner,
+    jwt::JwtKeys,
+    mailer::Mailer,
+    rate_limit::RateLimiter,
+    repo::{
+        CachedSettingsRepo, CachedUserRepo, NotificationRepo, SettingsRepo, SqlRepo, TodoRepo,
+        UserRepo, WorkspaceRepo,
+    },
+    reporting::RecentErrors,
+    session::Sessions,
+    signed_url::UrlSigner,
+};
+
+pub use route::app;
+
+/// This structure represents the state of the application,
+/// holding the database connection pools (`pool` for the writes and
+/// `read_pool` for the reads), the repositories the handlers
+/// access it through, app config data, the keys of the JWTs and
+/// of the signed URLs,
+/// the mailer, the bus of the
+/// domain events, the rooms of the WebSocket channel, the limiters
+/// of the todo creations and of the login attempts, the store of the
+/// sessions, the last errors logged, the rendered rows of the todos,
+/// the GeoIP database and the rows purged by the cleanup job
+pub struct AppState {
+    pub pool: DbPool,
+    pub read_pool: DbPool,
+    pub users: Arc<dyn UserRepo>,
+    pub settings: Arc<dyn SettingsRepo>,
+    pub workspaces: Arc<dyn WorkspaceRepo>,
+    pub todos: Arc<dyn TodoRepo>,
+    pub notifications: Arc<dyn NotificationRepo>,
+    pub config: Config,
+    pub jwt_keys: JwtKeys,
+    pub url_signer: UrlSigner,
+    pub mailer: Mailer,
+    pub events: EventBus,
+    pub hub: Hub,
+    pub todo_create_limiter: RateLimiter,
+    pub login_limiter: RateLimiter,
+    pub sessions: Sessions,
+    pub recent_errors: RecentErrors,
+    pub fragments: FragmentCache,
+    pub geoip: GeoIp,
+    pub purged: PurgedRows,
+}
+
+impl AppState {
+    pub fn new(pools: DbPools, config: Config) -> Result<Self> {
+        let mailer = Mailer::new(&config)?;
+        let events = EventBus::default();
+        let repo = SqlRepo::new(pools.clone(), events.clone());
+        let sessions = Sessions::new(&config, &pools.writer)?;
+        let geoip = GeoIp::new(&config)?;
+        let todo_create_limiter =
+            RateLimiter::new(config.todo_create_limit, Duration::from_secs(60));
+        let login_limiter = RateLimiter::new(config.login_attempt_limit, Duration::from_secs(60));
+
+        // The users and their settings are looked up on every request, unless cached
+        let (users, settings): (Arc<dyn UserRepo>, Arc<dyn SettingsRepo>) =
+            if config.user_cache_ttl.is_zero() {
+                (repo.clone(), repo.clone())
+            } else {
+                (
+                    CachedUserRepo::new(repo.clone(), config.user_cache_ttl),
+                    CachedSettingsRepo::new(repo.clone(), config.user_cache_ttl),
+                )
+            };
+
+        Ok(Self {
+            pool: pools.writer,
+            read_pool: pools.reader,
+            users,
+            settings,
+            workspaces: repo.clone(),
+            todos: repo.clone(),
+            notifications: repo,
+            jwt_keys: JwtKeys::new(&config.jwt_secret),
+            url_signer: UrlSigner::new(
+                config
+                    .url_signing_key
+                    .as_deref()
+                    .unwrap_or(&config.jwt_secret),
+            ),
+            config,
+            mailer,
+            events,
+            hub: Hub::default(),
+            todo_create_limiter,
+            login_limiter,
+            sessions,
+            recent_errors: RecentErrors::default(),
+            fragments: FragmentCache::default(),
+            geoip,
+            purged: PurgedRows::default(),
+        })
+    }
+}
+
+/// Starts the background jobs and the http server, until the
+/// server is shut down (`Ctrl+C` or `SIGTERM`).
+pub async fn run(config: Config, pools: DbPools) -> Result<()> {
+    // Set up the application state with the provided
+    // database connection pools and app config data
+    let app_state = Arc::new(AppState::new(pools.clone(), config)?);
+
+    events::start_subscribers(&app_state);
+
+    let mut jobs = JobRunner::new()
+        // Email the reminders of todos as they become due
+        .register("reminder_scan", reminder::SCAN_INTERVAL, reminder::scan)
+        // Email the digests of the users who opted in
+        .register("digest_scan", digest::SCAN_INTERVAL, digest::scan)
+        // Delete the tokens and sessions that can't be used anymore
+        .register(
+            "purge_expired",
+            cleanup::PURGE_INTERVAL,
+            cleanup::purge_expired,
+        );
+
+    // Copy the database to `BACKUP_DIR`
+    if app_state.config.backup_dir.is_some() {
+        jobs = jobs.register(
+            "backup",
+            app_state.config.backup_interval,
+            backup::scheduled,
+        );
+    }
+
+    let jobs = jobs.start(app_state.clone());
+
+    // Start the http server
+    let result = route::serve(app_state.clone()).await;
+
+    // Let the running jobs finish before exiting
+    jobs.shutdown().await;
+    pools.close().await;
+
+    result
+}
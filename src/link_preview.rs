@@ -0,0 +1,248 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{header, redirect::Policy, Client, StatusCode, Url};
+use tokio::net::lookup_host;
+
+/// Maximum time spent fetching a page, redirects included.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Only the beginning of the page is read: `<head>` is enough.
+const MAX_BODY_BYTES: usize = 256 * 1024;
+const MAX_REDIRECTS: usize = 3;
+const MAX_TITLE_LEN: usize = 120;
+const MAX_DESCRIPTION_LEN: usize = 255;
+
+/// Title and description of a linked page.
+#[derive(Debug, Default)]
+pub struct LinkPreview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Checks that `url` is an absolute `http(s)` URL.
+pub fn parse_url(url: &str) -> Result<Url> {
+    let url = Url::parse(url.trim()).map_err(|_| anyhow!("the URL is not valid"))?;
+
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        bail!("only http and https URLs can be attached");
+    }
+
+    Ok(url)
+}
+
+/// Fetches the page at `url` and extracts its title and description.
+///
+/// Every hop is resolved beforehand and refused when it points to a
+/// private, loopback or otherwise internal address, and the request is
+/// pinned to the checked address so DNS can't be swapped in between.
+pub async fn fetch(url: &str) -> Result<LinkPreview> {
+    tokio::time::timeout(FETCH_TIMEOUT, fetch_inner(parse_url(url)?))
+        .await
+        .map_err(|_| anyhow!("timed out fetching the link"))?
+}
+
+async fn fetch_inner(mut url: Url) -> Result<LinkPreview> {
+    for _ in 0..=MAX_REDIRECTS {
+        let host = url.host_str().context("URL without host")?.to_string();
+        let addr = resolve_public(&host, url.port_or_known_default().unwrap_or(80)).await?;
+
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .timeout(FETCH_TIMEOUT)
+            .resolve(&host, addr)
+            .user_agent("rust-axum-askama-htmx link preview")
+            .build()?;
+
+        let mut response = client
+            .get(url.clone())
+            .header(header::ACCEPT, "text/html")
+            .send()
+            .await?;
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .context("redirect without location")?;
+            url = parse_url(url.join(location)?.as_str())?;
+            continue;
+        }
+
+        if response.status() != StatusCode::OK {
+            bail!("the link answered with status {}", response.status());
+        }
+
+        let is_html = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_some_and(|content_type| content_type.contains("text/html"));
+        if !is_html {
+            return Ok(LinkPreview::default());
+        }
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_BYTES {
+                body.truncate(MAX_BODY_BYTES);
+                break;
+            }
+        }
+
+        return Ok(parse_html(&String::from_utf8_lossy(&body)));
+    }
+
+    bail!("too many redirects")
+}
+
+/// Resolves `host` and returns the first address, failing if any of
+/// them is not a public one.
+async fn resolve_public(host: &str, port: u16) -> Result<SocketAddr> {
+    let addrs = lookup_host((host, port))
+        .await
+        .with_context(|| format!("could not resolve {}", host))?
+        .collect::<Vec<_>>();
+
+    if addrs.is_empty() {
+        bail!("could not resolve {}", host);
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+        bail!("{} points to a non public address ({})", host, addr.ip());
+    }
+
+    Ok(addrs[0])
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space (100.64.0.0/10) and 0.0.0.0/8
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local (fc00::/7) and link local (fe80::/10)
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Extracts the title and description from the `<head>` of a page,
+/// preferring the Open Graph ones.
+fn parse_html(html: &str) -> LinkPreview {
+    let mut og = LinkPreview::default();
+    let mut title = None;
+    let mut description = None;
+    let lower = html.to_ascii_lowercase();
+
+    let mut rest = 0;
+    while let Some(start) = lower[rest..].find("<meta") {
+        let start = rest + start;
+        let Some(end) = lower[start..].find('>') else {
+            break;
+        };
+        let tag = &html[start..start + end];
+        rest = start + end;
+
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        let content = attribute(tag, "content");
+        match (key.map(|key| key.to_ascii_lowercase()).as_deref(), content) {
+            (Some("og:title"), Some(content)) => og.title = Some(content),
+            (Some("og:description"), Some(content)) => og.description = Some(content),
+            (Some("description"), Some(content)) => description = Some(content),
+            _ => {}
+        }
+    }
+
+    if let Some(start) = lower.find("<title") {
+        if let Some(open_end) = lower[start..].find('>') {
+            let from = start + open_end + 1;
+            if let Some(len) = lower[from..].find("</title") {
+                title = Some(html[from..from + len].to_string());
+            }
+        }
+    }
+
+    LinkPreview {
+        title: clean(og.title.or(title), MAX_TITLE_LEN),
+        description: clean(og.description.or(description), MAX_DESCRIPTION_LEN),
+    }
+}
+
+/// Value of the attribute `name` in a tag (`name="value"` or `name='value'`).
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut from = 0;
+
+    while let Some(pos) = lower[from..].find(name) {
+        let pos = from + pos;
+        from = pos + name.len();
+
+        // Must be a whole attribute name followed by `=`
+        let before = lower[..pos].chars().last();
+        if !matches!(before, Some(c) if c.is_whitespace()) {
+            continue;
+        }
+        let after = lower[from..].trim_start();
+        let Some(value) = after.strip_prefix('=') else {
+            continue;
+        };
+        let value_start = tag.len() - value.trim_start().len();
+        let value = &tag[value_start..];
+
+        let quote = value.chars().next()?;
+        return if quote == '"' || quote == '\'' {
+            value[1..].split(quote).next().map(str::to_string)
+        } else {
+            value.split_whitespace().next().map(str::to_string)
+        };
+    }
+
+    None
+}
+
+/// Decodes the most common entities, collapses whitespace and
+/// truncates to `max` characters.
+fn clean(value: Option<String>, max: usize) -> Option<String> {
+    let value = value?
+        .replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if value.is_empty() {
+        return None;
+    }
+
+    match value.char_indices().nth(max) {
+        Some((index, _)) => Some(format!("{}…", &value[..index])),
+        None => Some(value),
+    }
+}
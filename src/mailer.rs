@@ -0,0 +1,188 @@
+use anyhow::{Context, Result};
+use askama::Template;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use tracing::info;
+
+use crate::config::{Config, MailTransport};
+
+/// Port of the SMTP servers that use TLS from the start (not STARTTLS).
+const SMTPS_PORT: u16 = 465;
+
+/// An email of the application, rendered from a text template
+/// of `templates/email`.
+pub trait Email: Template {
+    fn subject(&self) -> String;
+}
+
+/// Email with the link that verifies the address of a new account.
+#[derive(Template)]
+#[template(path = "email/verification.txt")]
+pub struct VerificationEmail {
+    pub username: String,
+    pub link: String,
+}
+
+impl Email for VerificationEmail {
+    fn subject(&self) -> String {
+        "Verify your email address".to_string()
+    }
+}
+
+/// Email with the link to choose a new password.
+#[derive(Template)]
+#[template(path = "email/password_reset.txt")]
+pub struct PasswordResetEmail {
+    pub username: String,
+    pub link: String,
+    pub valid_minutes: i64,
+}
+
+impl Email for PasswordResetEmail {
+    fn subject(&self) -> String {
+        "Reset your password".to_string()
+    }
+}
+
+/// Email of the reminder of a todo.
+#[derive(Template)]
+#[template(path = "email/reminder.txt")]
+pub struct ReminderEmail {
+    pub username: String,
+    pub title: String,
+    pub description: String,
+    pub link: String,
+}
+
+impl Email for ReminderEmail {
+    fn subject(&self) -> String {
+        format!("Reminder: {}", self.title)
+    }
+}
+
+/// A todo listed in a digest, with its due date in the timezone of the user.
+pub struct DigestItem {
+    pub title: String,
+    pub due: String,
+}
+
+/// Email with the overdue and upcoming todos of the user.
+#[derive(Template)]
+#[template(path = "email/digest.txt")]
+pub struct DigestEmail {
+    pub username: String,
+    /// `daily` or `weekly`.
+    pub period: &'static str,
+    pub overdue: Vec<DigestItem>,
+    pub upcoming: Vec<DigestItem>,
+    pub link: String,
+}
+
+impl Email for DigestEmail {
+    fn subject(&self) -> String {
+        format!(
+            "Your {} digest: {} overdue, {} upcoming",
+            self.period,
+            self.overdue.len(),
+            self.upcoming.len()
+        )
+    }
+}
+
+#[derive(Clone)]
+enum Transport {
+    Smtp(AsyncSmtpTransport<Tokio1Executor>),
+    Log,
+    None,
+}
+
+/// Sends the emails of the application through SMTP or, in
+/// development, only logs them (see `MAIL_TRANSPORT`).
+#[derive(Clone)]
+pub struct Mailer {
+    transport: Transport,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(config: &Config) -> Result<Self> {
+        let transport = match config.mail_transport {
+            MailTransport::Smtp => Transport::Smtp(smtp_transport(config)?),
+            MailTransport::Log => Transport::Log,
+            MailTransport::None => Transport::None,
+        };
+
+        let from = config
+            .mail_from
+            .parse::<Mailbox>()
+            .context("Error: 🔥 invalid MAIL_FROM address!")?;
+
+        Ok(Self { transport, from })
+    }
+
+    /// Renders and sends an email.
+    pub async fn send_email(&self, to: &str, email: &impl Email) -> Result<()> {
+        let body = email.render().context("failed to render email")?;
+
+        self.send(to, &email.subject(), body).await
+    }
+
+    /// Sends a plain text email.
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<()> {
+        let transport = match &self.transport {
+            Transport::Smtp(transport) => transport,
+            Transport::Log => {
+                info!("📧 email to {} (not sent): {}\n{}", to, subject, body);
+                return Ok(());
+            }
+            Transport::None => return Ok(()),
+        };
+
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse::<Mailbox>().context("invalid recipient address")?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .context("failed to build email")?;
+
+        transport
+            .send(email)
+            .await
+            .context("failed to send email")?;
+
+        Ok(())
+    }
+}
+
+/// The SMTP transport of `SMTP_URL`, or else of `SMTP_HOST` and the rest.
+fn smtp_transport(config: &Config) -> Result<AsyncSmtpTransport<Tokio1Executor>> {
+    if let Some(url) = &config.smtp_url {
+        return Ok(AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+            .context("Error: 🔥 invalid SMTP_URL!")?
+            .build());
+    }
+
+    let host = config.smtp_host.as_deref().unwrap_or_default();
+    let port = config.smtp_port.unwrap_or(587);
+
+    let builder = if port == SMTPS_PORT {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+    }
+    .context("Error: 🔥 invalid SMTP_HOST!")?
+    .port(port);
+
+    let builder = match (&config.smtp_username, &config.smtp_password) {
+        (Some(username), Some(password)) => {
+            builder.credentials(Credentials::new(username.clone(), password.clone()))
+        }
+        _ => builder,
+    };
+
+    Ok(builder.build())
+}
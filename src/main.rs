@@ -1,58 +1,71 @@
-mod config;
-mod db;
-mod handler;
-mod model;
-mod route;
-mod serialization;
-mod service;
-
-use std::sync::Arc;
-
 use anyhow::Result;
+use clap::Parser;
 use dotenv::dotenv;
-use model::Todo;
-use sqlx::SqlitePool;
-use tokio::sync::RwLock;
-
-use crate::config::Config;
-
-/// This structure represents the state of the application,
-/// holding a database connection pool and app config data
-pub struct AppState {
-    pub pool: SqlitePool,
-    pub config: Config,
-    pub todos: Vec<Todo>,
-}
+use rust_axum_askama_htmx::{
+    cli::{self, Cli, Command, TodoCommand, UserCommand},
+    config::Config,
+    db, run,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = Cli::parse();
+
     // Load environment variables from the `.env` file
     dotenv().ok();
 
-    // Retrieve the value of the `DATABASE_URL` from .env file
+    // Retrieve the config from the env vars (and the .env and config.toml files)
     let config = Config::init();
+    println!("⚙️  Configuration:\n{}", config.summary());
+
+    // `migrate` applies the migrations even if they are disabled at startup
+    if let Some(Command::Migrate) = cli.command {
+        let config = Config {
+            run_migrations: true,
+            ..config
+        };
+        db::connect(&config).await?.close().await;
+
+        return Ok(());
+    }
 
-    // Connect to `Sqlite` database
-    let pool = db::connect(&config.database_url).await?;
+    // Connect to the database
+    let pools = db::connect(&config).await?;
 
-    let todos: Vec<Todo> = vec![];
+    let result = match cli.command {
+        Some(Command::CreateAdmin {
+            email,
+            username,
+            password,
+        }) => cli::create_admin(email, username, password, &pools.writer).await,
+        Some(Command::Seed { users, todos }) => cli::seed(users, todos, &pools.writer).await,
+        Some(Command::User { command }) => match command {
+            UserCommand::List => cli::list_users(&pools.reader).await,
+            UserCommand::ResetPassword { email, password } => {
+                cli::reset_password(email, password, &pools.writer).await
+            }
+            UserCommand::Promote { email } => cli::promote(email, &pools.writer).await,
+        },
+        Some(Command::Todo {
+            command: TodoCommand::PurgeDeleted,
+        }) => cli::purge_deleted_todos(&pools.writer).await,
+        Some(Command::Serve) | Some(Command::Migrate) | None => {
+            if let Some(todos) = cli.seed_load {
+                cli::seed_load(todos, &pools.writer).await?;
+            }
 
-    // Set up the application state with the provided
-    // database connection pool and app config data
-    let app_state = Arc::new(RwLock::new(AppState {
-        pool,
-        config,
-        todos,
-    }));
+            return run(config, pools).await;
+        }
+    };
 
-    // Start the http server
-    route::serve(app_state).await?;
+    // Closing the pools makes sure every write is committed before exiting
+    pools.close().await;
 
-    Ok(())
+    result
 }
 
 /* HOT RELOADING COMMAND:
-cargo watch -x run -w src -w assets -w templates
+cargo watch -x "run --features dev" -w src -w assets -w templates
 */
 
 /* REFERENCES:
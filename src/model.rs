@@ -1,6 +1,9 @@
-use chrono::NaiveDateTime;
+use std::fmt;
+
+use chrono::{DateTime, Duration, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
 
 use crate::serialization::{deserialize_checkbox, false_fn};
 
@@ -11,10 +14,404 @@ pub struct User {
     pub email: String,
     pub password: String,
     pub username: String,
+    /// When the user opened the link of the verification email.
+    pub email_verified_at: Option<NaiveDateTime>,
+    /// Admins can search the audit log.
+    pub is_admin: bool,
+    /// When the last digest was sent.
+    pub digest_sent_at: Option<NaiveDateTime>,
 }
 
-/// Struct for holding data from the user register form.
+/// Number of todos loaded at once in the list, unless the user chose another.
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+
+/// Page sizes offered in the profile.
+pub const PAGE_SIZES: [i64; 4] = [10, 25, 50, 100];
+
+/// Preferences of a user, from the `user_settings` table. The ones
+/// left unset (`None`) have their default value.
+#[derive(Debug, Default, Clone, Deserialize, FromRow, Serialize)]
+pub struct UserSettings {
+    pub user_id: String,
+    /// Color theme, `None` to follow the system.
+    pub theme: Option<String>,
+    /// IANA timezone, `None` to use the one of the browser.
+    pub timezone: Option<String>,
+    /// Order of the day, month and year in the dates.
+    pub date_order: Option<String>,
+    /// `12h` or `24h` clock.
+    pub clock: Option<String>,
+    /// Number of todos loaded at once in the list.
+    pub page_size: Option<i64>,
+    /// Sort of the todo list when none is chosen (see `TodoFilter::sort`).
+    pub default_sort: Option<String>,
+    /// How often the digest of the todos is emailed, `None` for never.
+    pub digest: Option<String>,
+    /// Spacing of the rows of the todo list, `compact` or `comfortable`.
+    pub density: Option<String>,
+}
+
+impl UserSettings {
+    /// The default settings of a user who never saved any.
+    pub fn new(user_id: &str) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn theme(&self) -> Theme {
+        Theme::from_name(self.theme.as_deref())
+    }
+
+    pub fn date_format(&self) -> DateFormat {
+        DateFormat::from_names(self.date_order.as_deref(), self.clock.as_deref())
+    }
+
+    pub fn digest(&self) -> DigestFrequency {
+        DigestFrequency::from_name(self.digest.as_deref())
+    }
+
+    /// One of `PAGE_SIZES`.
+    pub fn page_size(&self) -> i64 {
+        self.page_size
+            .filter(|size| PAGE_SIZES.contains(size))
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+    }
+
+    /// Whether the rows of the todo list are packed tighter.
+    pub fn compact(&self) -> bool {
+        self.density.as_deref() == Some("compact")
+    }
+
+    /// `""` (newest first), `"oldest"`, `"due"` or `"priority"`.
+    pub fn default_sort(&self) -> &str {
+        self.default_sort
+            .as_deref()
+            .filter(|sort| matches!(*sort, "oldest" | "due" | "priority"))
+            .unwrap_or_default()
+    }
+}
+
+/// What a token sent by email is for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    /// Verifying the email address of a new account.
+    Verification,
+    /// Choosing a new password.
+    PasswordReset,
+}
+
+impl TokenKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Verification => "verification",
+            Self::PasswordReset => "password_reset",
+        }
+    }
+
+    /// How long a token can be used after it is sent.
+    pub fn lifetime(&self) -> chrono::Duration {
+        match self {
+            Self::Verification => chrono::Duration::days(2),
+            Self::PasswordReset => chrono::Duration::minutes(30),
+        }
+    }
+}
+
+/// Authentication event recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuditEvent {
+    Register,
+    LoginSucceeded,
+    LoginFailed,
+    Logout,
+    /// The password was changed with a reset link.
+    PasswordChanged,
+    /// An admin started to use the app as the user.
+    ImpersonationStarted,
+    /// The admin went back to their own account.
+    ImpersonationStopped,
+    /// A request other than a `GET` or `HEAD` made by an admin as the user.
+    ImpersonatedChange,
+}
+
+impl AuditEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::LoginSucceeded => "login_succeeded",
+            Self::LoginFailed => "login_failed",
+            Self::Logout => "logout",
+            Self::PasswordChanged => "password_changed",
+            Self::ImpersonationStarted => "impersonation_started",
+            Self::ImpersonationStopped => "impersonation_stopped",
+            Self::ImpersonatedChange => "impersonated_change",
+        }
+    }
+}
+
+/// Structure that represents an row from the `audit_log` table.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub user_id: Option<String>,
+    pub email: String,
+    pub event: String,
+    pub created_at: NaiveDateTime,
+    pub client_ip: Option<String>,
+    /// Admin who made the event while impersonating the user.
+    pub actor_email: Option<String>,
+}
+
+/// Search of the audit log page, from its query string. The dates
+/// (`YYYY-MM-DD`, in UTC) are inclusive, the empty fields are ignored.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AuditFilter {
+    /// Part of the email, or the id, of the user.
+    #[serde(default)]
+    pub user: String,
+    #[serde(default)]
+    pub from: String,
+    #[serde(default)]
+    pub to: String,
+}
+
+/// Structure that represents an row from the `jobs` table,
+/// shown in the ops dashboard.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct JobStatus {
+    pub name: String,
+    pub next_run_at: NaiveDateTime,
+    pub last_started_at: Option<NaiveDateTime>,
+    pub last_finished_at: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+}
+
+impl JobStatus {
+    /// How long a job may stay past its next run before it's reported
+    /// as late (e.g. no instance of the app is running the jobs).
+    const LATE_AFTER: chrono::Duration = chrono::Duration::minutes(5);
+
+    /// State of the job at `now`: `running`, `late`, `failed` (its last
+    /// run) or `ok`.
+    pub fn health(&self, now: &NaiveDateTime) -> &'static str {
+        let running = match (self.last_started_at, self.last_finished_at) {
+            (Some(started_at), Some(finished_at)) => finished_at < started_at,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        if running {
+            "running"
+        } else if self.next_run_at + Self::LATE_AFTER < *now {
+            "late"
+        } else if self.last_error.is_some() {
+            "failed"
+        } else {
+            "ok"
+        }
+    }
+}
+
+/// Connections of a database pool, shown in the ops dashboard.
+#[derive(Clone, Debug, Default)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub max: u32,
+}
+
+/// Rows of the main tables, shown in the ops dashboard.
+#[derive(Clone, Debug, Default)]
+pub struct OpsCounts {
+    pub users: i64,
+    pub todos: i64,
+    pub open_todos: i64,
+}
+
+/// Color theme of the pages. `System` follows `prefers-color-scheme`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    /// Name of the DaisyUI theme, `None` for the system one.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Self::System => None,
+            Self::Light => Some("light"),
+            Self::Dark => Some("dark"),
+        }
+    }
+
+    pub fn from_name(name: Option<&str>) -> Self {
+        match name {
+            Some("light") => Self::Light,
+            Some("dark") => Self::Dark,
+            _ => Self::System,
+        }
+    }
+}
+
+/// How often the user gets the digest of their overdue and upcoming
+/// todos by email. `Off` unless the user opts in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    #[default]
+    Off,
+    /// Every morning.
+    Daily,
+    /// Every Monday morning.
+    Weekly,
+}
+
+impl DigestFrequency {
+    /// Name saved in the account, `None` for no digest.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Self::Off => None,
+            Self::Daily => Some("daily"),
+            Self::Weekly => Some("weekly"),
+        }
+    }
+
+    pub fn from_name(name: Option<&str>) -> Self {
+        match name {
+            Some("daily") => Self::Daily,
+            Some("weekly") => Self::Weekly,
+            _ => Self::Off,
+        }
+    }
+
+    /// How far ahead the upcoming todos of a digest are looked for.
+    pub fn horizon(&self) -> Duration {
+        match self {
+            Self::Off => Duration::zero(),
+            Self::Daily => Duration::days(1),
+            Self::Weekly => Duration::days(7),
+        }
+    }
+}
+
+/// Order of the parts of the dates shown to the user.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateOrder {
+    /// `17 Jun 2024`
+    #[default]
+    Dmy,
+    /// `Jun 17, 2024`
+    Mdy,
+    /// `2024-06-17`
+    Ymd,
+}
+
+/// Clock of the times shown to the user.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Hash, Deserialize, Serialize)]
+pub enum Clock {
+    /// `14:30`
+    #[default]
+    #[serde(rename = "24h")]
+    H24,
+    /// `02:30 PM`
+    #[serde(rename = "12h")]
+    H12,
+}
+
+/// How the dates are shown to the user.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Hash, Deserialize, Serialize)]
+pub struct DateFormat {
+    pub order: DateOrder,
+    pub clock: Clock,
+}
+
+impl DateFormat {
+    /// Reads the format saved in the account (see `names()`).
+    pub fn from_names(order: Option<&str>, clock: Option<&str>) -> Self {
+        let order = match order {
+            Some("mdy") => DateOrder::Mdy,
+            Some("ymd") => DateOrder::Ymd,
+            _ => DateOrder::Dmy,
+        };
+        let clock = match clock {
+            Some("12h") => Clock::H12,
+            _ => Clock::H24,
+        };
+
+        Self { order, clock }
+    }
+
+    /// Names of the order and the clock, as saved in the account.
+    pub fn names(&self) -> (&'static str, &'static str) {
+        let order = match self.order {
+            DateOrder::Dmy => "dmy",
+            DateOrder::Mdy => "mdy",
+            DateOrder::Ymd => "ymd",
+        };
+        let clock = match self.clock {
+            Clock::H24 => "24h",
+            Clock::H12 => "12h",
+        };
+
+        (order, clock)
+    }
+
+    /// `chrono` format string of a datetime, with the UTC offset at the end.
+    pub fn pattern(&self) -> String {
+        let time = match self.clock {
+            Clock::H24 => "%H:%M",
+            Clock::H12 => "%I:%M %p",
+        };
+
+        format!("{} {} %z", self.date_pattern(), time)
+    }
+
+    /// `chrono` format string of a date.
+    pub fn date_pattern(&self) -> &'static str {
+        match self.order {
+            DateOrder::Dmy => "%d %b %Y",
+            DateOrder::Mdy => "%b %d, %Y",
+            DateOrder::Ymd => "%Y-%m-%d",
+        }
+    }
+}
+
+/// Struct for holding data from the theme toggle.
 #[derive(Debug, Deserialize)]
+pub struct ThemeSchema {
+    pub theme: Theme,
+}
+
+/// Struct for holding data from the profile form.
+#[derive(Debug, Deserialize)]
+pub struct ProfileSchema {
+    /// Empty to use the timezone of the browser.
+    pub timezone: String,
+    pub date_order: DateOrder,
+    pub clock: Clock,
+    #[serde(default)]
+    pub digest: DigestFrequency,
+    /// One of `PAGE_SIZES`, any other for the default one.
+    #[serde(default)]
+    pub page_size: i64,
+    /// Like `TodoFilter::sort`.
+    #[serde(default)]
+    pub default_sort: String,
+    /// `compact`, any other for comfortable rows.
+    #[serde(default)]
+    pub density: String,
+}
+
+/// Struct for holding data from the user register form.
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct RegisterUserSchema {
     pub email: String,
     pub password: String,
@@ -22,22 +419,44 @@ pub struct RegisterUserSchema {
 }
 
 /// Struct for holding data from the user login form.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginUserSchema {
     pub email: String,
     pub password: String,
 }
 
+/// Struct for holding data from the forgotten password form.
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordSchema {
+    pub email: String,
+}
+
+/// Struct for holding data from the password reset form.
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordSchema {
+    pub token: String,
+    pub password: String,
+}
+
 /// Struct for holding data from the JWT.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct TokenClaims {
     pub sub: String,
     pub iat: usize,
     pub exp: usize,
+    /// Id of the admin impersonating the user of `sub`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<String>,
+}
+
+/// Struct for holding data from the impersonation form of the Ops page.
+#[derive(Debug, Deserialize)]
+pub struct ImpersonateSchema {
+    pub email: String,
 }
 
 /// Structure that represents an row from the `todos` table.
-#[derive(Clone, Debug, Default, Deserialize, FromRow, Serialize)]
+#[derive(Clone, Debug, Default, Hash, Deserialize, FromRow, Serialize, ToSchema)]
 pub struct Todo {
     pub id: i64,
     pub created_by: String,
@@ -45,21 +464,640 @@ pub struct Todo {
     pub description: String,
     pub status: bool,
     pub created_at: NaiveDateTime,
+    pub due_at: Option<NaiveDateTime>,
+    pub priority: i64,
+    pub tags: String,
+    pub remind_at: Option<NaiveDateTime>,
+    pub reminder_sent_at: Option<NaiveDateTime>,
+    /// Number of edits, to detect the ones made on an outdated copy.
+    pub version: i64,
+    /// Workspace whose members share the todo.
+    pub workspace_id: i64,
+    /// When the todo was marked as done.
+    pub completed_at: Option<NaiveDateTime>,
+}
+
+impl Todo {
+    /// Returns the tags of the todo (stored space separated).
+    pub fn tag_list(&self) -> Vec<&str> {
+        self.tags.split_whitespace().collect()
+    }
+
+    /// Returns a human readable label for the priority of the todo.
+    pub fn priority_label(&self) -> &'static str {
+        match self.priority {
+            3 => "high",
+            2 => "medium",
+            1 => "low",
+            _ => "",
+        }
+    }
+}
+
+/// Body of the response of the health check endpoint.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthCheckResponse {
+    pub status: String,
+    pub message: String,
+    #[serde(flatten)]
+    pub build: BuildInfo,
+    pub database: DatabaseHealth,
+}
+
+/// Version of the app and the commit it was built from, embedded at
+/// compile time by `build.rs` to tell the deployed versions apart.
+#[derive(Clone, Copy, Debug, Serialize, ToSchema)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Short SHA of the commit.
+    pub git_sha: &'static str,
+    /// RFC 3339 timestamp.
+    pub built_at: &'static str,
+}
+
+impl BuildInfo {
+    pub const CURRENT: Self = Self {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("VERGEN_GIT_SHA"),
+        built_at: env!("VERGEN_BUILD_TIMESTAMP"),
+    };
+}
+
+/// Connections of the database pool of the reads, reported by the health check.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DatabaseHealth {
+    pub reachable: bool,
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
 }
 
 /// Struct for holding data from the todo create form.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TodoSchema {
     pub title: String,
     pub description: String,
+    /// Separated by spaces or commas, with or without `#`.
+    #[serde(default)]
+    pub tags: String,
+    /// Create it even if an open todo has a similar title.
+    #[serde(default)]
+    pub confirmed: bool,
 }
 
-/// Struct for holding data from the todo edit form.
+/// Position in the todo list of a todo, after which the next page starts
+/// (or before which the previous one ends). Written as
+/// `<created_at in µs>_<id>`, followed by `_<rank>` in the lists sorted
+/// by priority or due date.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TodoCursor {
+    pub created_at: NaiveDateTime,
+    pub id: i64,
+    /// The priority, or the due date in µs (`None` when there is none),
+    /// of the todo in the lists sorted by them.
+    pub rank: Option<i64>,
+}
+
+impl TodoCursor {
+    /// The cursor right after `todo`, in the list sorted by `sort`
+    /// (see `TodoFilter::sort`).
+    pub fn after(todo: &Todo, sort: &str) -> Self {
+        let rank = match sort {
+            "priority" => Some(todo.priority),
+            "due" => todo
+                .due_at
+                .map(|due_at| due_at.and_utc().timestamp_micros()),
+            _ => None,
+        };
+
+        Self {
+            created_at: todo.created_at,
+            id: todo.id,
+            rank,
+        }
+    }
+
+    /// Reads a cursor written by `to_string`, `None` if it is malformed.
+    pub fn parse(cursor: &str) -> Option<Self> {
+        let mut parts = cursor.splitn(3, '_');
+        let micros = parts.next()?.parse().ok()?;
+        let created_at = DateTime::from_timestamp_micros(micros)?.naive_utc();
+        let id = parts.next()?.parse().ok()?;
+        let rank = match parts.next() {
+            Some(rank) => Some(rank.parse().ok()?),
+            None => None,
+        };
+
+        Some(Self {
+            created_at,
+            id,
+            rank,
+        })
+    }
+}
+
+impl fmt::Display for TodoCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}_{}",
+            self.created_at.and_utc().timestamp_micros(),
+            self.id
+        )?;
+        match self.rank {
+            Some(rank) => write!(f, "_{}", rank),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where a page of the todo list starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PageStart {
+    /// At the start of the list.
+    #[default]
+    First,
+    /// Right after the cursor, going down the list.
+    After(TodoCursor),
+    /// Right before the cursor, going up the list.
+    Before(TodoCursor),
+}
+
+/// A page of results, with the cursors of the next page (`None` on the
+/// last one) and of the previous one (`None` on the first one).
+#[derive(Clone, Debug, Default)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+/// Search query, tag, status and sort applied to the todo list,
+/// either from the query string or from a saved filter.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TodoFilter {
+    #[serde(default)]
+    pub q: String,
+    #[serde(default)]
+    pub tag: String,
+    /// `""` (all), `"open"` or `"done"`.
+    #[serde(default)]
+    pub status: String,
+    /// `"newest"`, `"oldest"`, `"due"` or `"priority"`. When it's `""`
+    /// the list has the default sort of the user.
+    #[serde(default)]
+    pub sort: String,
+}
+
+impl TodoFilter {
+    /// Returns `true` when no criteria are set (the plain list).
+    pub fn is_empty(&self) -> bool {
+        self.lists_all() && matches!(self.sort.as_str(), "" | "newest")
+    }
+
+    /// Returns `true` when every todo is listed, whatever the sort.
+    pub fn lists_all(&self) -> bool {
+        self.q.trim().is_empty() && self.tag.trim().is_empty() && self.status.is_empty()
+    }
+}
+
+/// Structure that represents an row from the `saved_filters` table.
+#[derive(Clone, Debug, Default, Deserialize, FromRow, Serialize)]
+pub struct SavedFilter {
+    pub id: i64,
+    pub user_id: String,
+    pub name: String,
+    pub query: String,
+    pub tag: String,
+    pub status: String,
+    pub sort: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<&SavedFilter> for TodoFilter {
+    fn from(saved: &SavedFilter) -> Self {
+        Self {
+            q: saved.query.clone(),
+            tag: saved.tag.clone(),
+            status: saved.status.clone(),
+            sort: saved.sort.clone(),
+        }
+    }
+}
+
+/// Struct for holding data from the save filter form.
 #[derive(Debug, Deserialize)]
+pub struct SavedFilterSchema {
+    pub name: String,
+    #[serde(flatten)]
+    pub filter: TodoFilter,
+}
+
+/// Struct for holding data from the quick-add input.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct QuickAddSchema {
+    pub text: String,
+}
+
+/// Struct for holding data from the todo edit form.
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TodoEditSchema {
     pub title: String,
     pub description: String,
     #[serde(default = "false_fn")]
     #[serde(deserialize_with = "deserialize_checkbox")]
     pub status: bool,
+    /// Value of the `datetime-local` input, in the client's timezone.
+    #[serde(default)]
+    pub remind_at: String,
+    /// Version of the todo shown in the form, the edit is refused if it
+    /// has been changed since. Without it the edit always wins.
+    #[serde(default)]
+    pub version: Option<i64>,
+    /// Separated by spaces or commas, with or without `#`.
+    /// Without it the tags are kept.
+    #[serde(default)]
+    pub tags: Option<String>,
+}
+
+/// Struct for holding data from the bulk edit form, sent with
+/// `axum_extra`'s `Form` to read the repeated `id` params. The fields
+/// left empty keep the values of each todo.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct BulkEditSchema {
+    /// Ids of the selected todos.
+    #[serde(default, rename = "id")]
+    pub ids: Vec<i64>,
+    pub status: Option<bool>,
+    /// From 0 (none) to 3 (high).
+    pub priority: Option<i64>,
+    /// Replace the tags, separated by spaces or commas.
+    pub tags: Option<String>,
+    /// Workspace to move the todos to.
+    pub workspace_id: Option<i64>,
+}
+
+/// A previous title/description of a todo, saved on each update.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct TodoVersion {
+    pub id: i64,
+    pub todo_id: i64,
+    pub title: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Name (in the URL) and UID that a CalDAV client gave to a todo it
+/// created. The other todos are served as `<id>.ics`.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct DavResource {
+    pub todo_id: i64,
+    pub name: String,
+    pub uid: String,
+}
+
+/// Changes made by a client while offline, sent to `/api/v1/sync`
+/// once it's back online.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SyncRequest {
+    pub mutations: Vec<SyncMutation>,
+}
+
+/// A change made offline. Its `id`, generated by the client, makes it
+/// safe to send it again: it's only applied the first time.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncMutation {
+    /// Creates a todo, like the todo create form.
+    Create {
+        id: String,
+        title: String,
+        #[serde(default)]
+        description: String,
+    },
+    /// Creates a todo from a text like the quick add input.
+    QuickAdd {
+        id: String,
+        text: String,
+    },
+    /// Edits a todo, failing if it was changed since `version` (when given).
+    Update {
+        id: String,
+        todo: SyncTodoRef,
+        title: String,
+        #[serde(default)]
+        description: String,
+        status: bool,
+        version: Option<i64>,
+    },
+    Toggle {
+        id: String,
+        todo: SyncTodoRef,
+    },
+    Delete {
+        id: String,
+        todo: SyncTodoRef,
+    },
+}
+
+impl SyncMutation {
+    pub fn id(&self) -> &str {
+        match self {
+            Self::Create { id, .. }
+            | Self::QuickAdd { id, .. }
+            | Self::Update { id, .. }
+            | Self::Toggle { id, .. }
+            | Self::Delete { id, .. } => id,
+        }
+    }
+}
+
+/// A todo, by its id or, when it was created offline too,
+/// by the `id` of the change that created it.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum SyncTodoRef {
+    Id(i64),
+    Mutation(String),
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncResponse {
+    /// In the order of the changes.
+    pub results: Vec<SyncResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncResult {
+    /// `id` of the change.
+    pub id: String,
+    pub status: SyncStatus,
+    /// Todo created or changed.
+    pub todo_id: Option<i64>,
+    /// Why it wasn't applied.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    Applied,
+    /// Already applied when it was sent before.
+    Duplicate,
+    /// Can't be applied, e.g. the todo was deleted or changed meanwhile.
+    /// It mustn't be sent again.
+    Rejected,
+    /// Couldn't be applied this time, it must be sent again later.
+    Failed,
+}
+
+/// Changes to the todos of the workspace sent to `/api/v1/todos/batch`,
+/// applied together or not at all.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BatchRequest {
+    pub operations: Vec<BatchOperation>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    Create {
+        title: String,
+        #[serde(default)]
+        description: String,
+        due_at: Option<NaiveDateTime>,
+        /// From 0 (none) to 3 (high).
+        #[serde(default)]
+        priority: i64,
+        /// Separated by spaces.
+        #[serde(default)]
+        tags: String,
+    },
+    /// Edits the fields given, failing if the todo was changed
+    /// since `version` (when given).
+    Update {
+        id: i64,
+        title: Option<String>,
+        description: Option<String>,
+        status: Option<bool>,
+        priority: Option<i64>,
+        tags: Option<String>,
+        version: Option<i64>,
+    },
+    Delete {
+        id: i64,
+    },
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResponse {
+    /// Whether the operations were applied, all of them being otherwise.
+    pub applied: bool,
+    /// In the order of the operations.
+    pub results: Vec<BatchResult>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchResult {
+    pub status: BatchStatus,
+    /// Todo created or changed.
+    pub todo: Option<Todo>,
+    /// Why it failed.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Created,
+    Updated,
+    Deleted,
+    /// The operation that kept the batch from being applied.
+    Failed,
+    /// Not applied since another operation failed.
+    Aborted,
+}
+
+/// A URL attached to a todo, with the preview fetched in background.
+#[derive(Clone, Debug, Default, Hash, FromRow)]
+pub struct TodoLink {
+    pub id: i64,
+    pub todo_id: i64,
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub fetched_at: Option<NaiveDateTime>,
+}
+
+/// Struct for holding data from the attach link form.
+#[derive(Debug, Deserialize)]
+pub struct LinkSchema {
+    pub url: String,
+}
+
+/// A checklist item of a todo.
+#[derive(Clone, Debug, Default, Hash, FromRow)]
+pub struct Subtask {
+    pub id: i64,
+    pub todo_id: i64,
+    pub title: String,
+    pub done: bool,
+}
+
+/// Struct for holding data from the add subtask form.
+#[derive(Debug, Deserialize)]
+pub struct SubtaskSchema {
+    pub title: String,
+}
+
+/// Completion of the checklist of a todo.
+#[derive(Clone, Debug, Default, Hash)]
+pub struct ChecklistProgress {
+    pub todo_id: i64,
+    pub done: i64,
+    pub total: i64,
+    pub percent: i64,
+}
+
+impl ChecklistProgress {
+    pub fn new(todo_id: i64, done: i64, total: i64) -> Self {
+        let percent = if total > 0 { done * 100 / total } else { 0 };
+
+        Self {
+            todo_id,
+            done,
+            total,
+            percent,
+        }
+    }
+}
+
+/// Struct for holding data from the add dependency form.
+#[derive(Debug, Deserialize)]
+pub struct DependencySchema {
+    pub blocked_by: i64,
+}
+
+/// Time tracked on a todo, summed over all its time entries.
+#[derive(Clone, Debug, Default, Hash, FromRow)]
+pub struct TrackedTime {
+    pub todo_id: i64,
+    pub seconds: i64,
+    pub running: bool,
+}
+
+/// Counters shown on the stats page.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct TodoStats {
+    pub total: i64,
+    pub done: i64,
+    pub overdue: i64,
+}
+
+/// A tag of the workspace suggested while typing, with the number
+/// of todos that have it.
+#[derive(Clone, Debug, Default)]
+pub struct TagSuggestion {
+    pub tag: String,
+    pub count: i64,
+}
+
+/// A todo whose reminder is due, joined with its owner.
+#[derive(Debug, FromRow)]
+pub struct DueReminder {
+    pub todo_id: i64,
+    pub user_id: String,
+    pub title: String,
+    pub description: String,
+    pub email: String,
+    pub username: String,
+}
+
+/// A workspace the user is a member of, with the role of the user in it.
+#[derive(Clone, Debug, Default, Deserialize, FromRow, Serialize)]
+pub struct Workspace {
+    pub id: i64,
+    pub name: String,
+    pub role: String,
+}
+
+impl Workspace {
+    /// Owners manage the members of the workspace.
+    pub fn is_owner(&self) -> bool {
+        self.role == "owner"
+    }
+}
+
+/// A member of a workspace, as listed in its settings.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct WorkspaceMember {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+}
+
+/// Struct for holding data from the create workspace form.
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceSchema {
+    pub name: String,
+}
+
+/// Struct for holding data from the add member form.
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceMemberSchema {
+    pub email: String,
+}
+
+/// Struct for holding data from the workspace switcher.
+#[derive(Debug, Deserialize)]
+pub struct SwitchWorkspaceSchema {
+    pub workspace_id: i64,
+}
+
+/// What a notification is about.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationKind {
+    /// The user was added to a workspace.
+    Invite,
+    /// The reminder of a todo is due.
+    Reminder,
+    /// The user was mentioned (`@username`) in a todo.
+    Mention,
+}
+
+impl NotificationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Invite => "invite",
+            Self::Reminder => "reminder",
+            Self::Mention => "mention",
+        }
+    }
+
+    /// Whether the user is notified only once about the same todo,
+    /// e.g. not again on every edit of a todo they are mentioned in.
+    pub fn once_per_todo(&self) -> bool {
+        matches!(self, Self::Mention)
+    }
+}
+
+/// Structure that represents an row from the `notifications` table.
+#[derive(Clone, Debug, Default, FromRow)]
+pub struct Notification {
+    pub id: i64,
+    pub user_id: String,
+    pub kind: String,
+    pub message: String,
+    /// Page the notification leads to.
+    pub link: String,
+    pub todo_id: Option<i64>,
+    pub read_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl Notification {
+    pub fn is_unread(&self) -> bool {
+        self.read_at.is_none()
+    }
 }
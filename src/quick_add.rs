@@ -0,0 +1,293 @@
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+/// Time of day used when a due date is given without an explicit time.
+pub const DEFAULT_DUE_HOUR: u32 = 9;
+
+/// Result of parsing a quick-add line such as
+/// `pay rent tomorrow 5pm #bills !high`.
+#[derive(Debug, Default, PartialEq)]
+pub struct QuickAdd {
+    pub title: String,
+    /// Due date already converted to UTC (as stored in the database).
+    pub due_at: Option<NaiveDateTime>,
+    pub priority: i64,
+    pub tags: Vec<String>,
+}
+
+/// Parses a quick-add line into title, due date, tags and priority.
+/// Relative dates ("today", "tomorrow", "friday", "in 3 days"…) are
+/// resolved in the client's timezone `tz` relative to `now`.
+pub fn parse(input: &str, tz: Tz, now: DateTime<Utc>) -> QuickAdd {
+    let today = now.with_timezone(&tz).date_naive();
+    let words = input.split_whitespace().collect::<Vec<_>>();
+
+    let mut title = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    let mut priority = 0;
+    let mut date: Option<NaiveDate> = None;
+    let mut time: Option<NaiveTime> = None;
+
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        let lower = word.to_lowercase();
+
+        if let Some(tag) = lower.strip_prefix('#') {
+            let tag = tag.trim_matches(|c: char| !c.is_alphanumeric() && c != '-' && c != '_');
+            if !tag.is_empty() {
+                if !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+                i += 1;
+                continue;
+            }
+        }
+
+        if let Some(p) = parse_priority(&lower) {
+            priority = p;
+            i += 1;
+            continue;
+        }
+
+        // "in 3 days", "in 2 weeks". Left in the title past the last date
+        if lower == "in" && i + 2 < words.len() {
+            if let (Ok(n), Some(unit)) = (
+                words[i + 1].parse::<i64>(),
+                parse_unit(&words[i + 2].to_lowercase()),
+            ) {
+                if let Some(d) = n
+                    .checked_mul(unit)
+                    .and_then(Duration::try_days)
+                    .and_then(|days| today.checked_add_signed(days))
+                {
+                    date = Some(d);
+                    i += 3;
+                    continue;
+                }
+            }
+        }
+
+        // "next friday", "on monday", "at 5pm", "by tomorrow"
+        if matches!(lower.as_str(), "next" | "on" | "at" | "by") && i + 1 < words.len() {
+            let next = words[i + 1].to_lowercase();
+            if let Some(d) = parse_date(&next, today) {
+                date = Some(d);
+                i += 2;
+                continue;
+            }
+            if let Some(t) = parse_time(&next) {
+                time = Some(t);
+                i += 2;
+                continue;
+            }
+        }
+
+        if let Some(d) = parse_date(&lower, today) {
+            date = Some(d);
+            if lower == "tonight" && time.is_none() {
+                time = NaiveTime::from_hms_opt(20, 0, 0);
+            }
+            i += 1;
+            continue;
+        }
+
+        if let Some(t) = parse_time(&lower) {
+            time = Some(t);
+            i += 1;
+            continue;
+        }
+
+        title.push(word);
+        i += 1;
+    }
+
+    let local_due = match (date, time) {
+        (Some(d), Some(t)) => Some(d.and_time(t)),
+        (Some(d), None) => d.and_hms_opt(DEFAULT_DUE_HOUR, 0, 0),
+        (None, Some(t)) => {
+            // A bare time refers to the next occurrence of that time
+            let candidate = today.and_time(t);
+            if candidate < now.with_timezone(&tz).naive_local() {
+                Some(candidate + Duration::days(1))
+            } else {
+                Some(candidate)
+            }
+        }
+        (None, None) => None,
+    };
+
+    let due_at = local_due.and_then(|dt| {
+        tz.from_local_datetime(&dt)
+            .earliest()
+            .map(|dt| dt.naive_utc())
+    });
+
+    QuickAdd {
+        title: title.join(" "),
+        due_at,
+        priority,
+        tags,
+    }
+}
+
+/// `!high`, `!medium`, `!low`, `!1`..`!3` or `!!!`-style priorities.
+fn parse_priority(word: &str) -> Option<i64> {
+    let rest = word.strip_prefix('!')?;
+    match rest {
+        "high" | "h" | "3" | "!!" => Some(3),
+        "medium" | "med" | "m" | "2" | "!" => Some(2),
+        "low" | "l" | "1" => Some(1),
+        _ => None,
+    }
+}
+
+/// Number of days represented by a unit word.
+fn parse_unit(word: &str) -> Option<i64> {
+    match word {
+        "day" | "days" => Some(1),
+        "week" | "weeks" => Some(7),
+        _ => None,
+    }
+}
+
+fn parse_date(word: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match word {
+        "today" | "tonight" => return Some(today),
+        "tomorrow" | "tmr" | "tmrw" => return Some(today + Duration::days(1)),
+        _ => {}
+    }
+
+    if let Some(weekday) = parse_weekday(word) {
+        // Always the next occurrence, never today
+        let mut days = (7 + weekday.num_days_from_monday() as i64
+            - today.weekday().num_days_from_monday() as i64)
+            % 7;
+        if days == 0 {
+            days = 7;
+        }
+        return Some(today + Duration::days(days));
+    }
+
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").ok()
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Accepts `5pm`, `5:30pm`, `17:00` and `noon`.
+fn parse_time(word: &str) -> Option<NaiveTime> {
+    if word == "noon" {
+        return NaiveTime::from_hms_opt(12, 0, 0);
+    }
+
+    let (clock, offset) = if let Some(c) = word.strip_suffix("am") {
+        (c, Some(0))
+    } else if let Some(c) = word.strip_suffix("pm") {
+        (c, Some(12))
+    } else {
+        (word, None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        // A bare number is only a time with an am/pm suffix
+        None if offset.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+
+    let hour = match offset {
+        Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday 2024-06-05, 12:00 UTC.
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 5, 12, 0, 0).unwrap()
+    }
+
+    fn due(input: &str) -> Option<NaiveDateTime> {
+        parse(input, Tz::UTC, now()).due_at
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2024, 6, day)?.and_hms_opt(hour, minute, 0)
+    }
+
+    #[test]
+    fn title_tags_and_priority_are_split() {
+        let parsed = parse("pay rent #bills #Bills !high", Tz::UTC, now());
+
+        assert_eq!(parsed.title, "pay rent");
+        assert_eq!(parsed.tags, ["bills"]);
+        assert_eq!(parsed.priority, 3);
+        assert_eq!(parsed.due_at, None);
+        assert_eq!(parse("x !med", Tz::UTC, now()).priority, 2);
+        assert_eq!(parse("x !1", Tz::UTC, now()).priority, 1);
+    }
+
+    #[test]
+    fn relative_days_are_resolved() {
+        assert_eq!(due("call mum today"), at(5, DEFAULT_DUE_HOUR, 0));
+        assert_eq!(due("call mum tonight"), at(5, 20, 0));
+        assert_eq!(due("call mum tomorrow"), at(6, DEFAULT_DUE_HOUR, 0));
+        assert_eq!(due("call mum by tmrw"), at(6, DEFAULT_DUE_HOUR, 0));
+        assert_eq!(due("call mum in 3 days"), at(8, DEFAULT_DUE_HOUR, 0));
+        assert_eq!(due("call mum in 2 weeks"), at(19, DEFAULT_DUE_HOUR, 0));
+    }
+
+    #[test]
+    fn weekdays_are_the_next_ones() {
+        assert_eq!(due("report friday"), at(7, DEFAULT_DUE_HOUR, 0));
+        assert_eq!(due("report on mon"), at(10, DEFAULT_DUE_HOUR, 0));
+        // Never today
+        assert_eq!(due("report next wednesday"), at(12, DEFAULT_DUE_HOUR, 0));
+    }
+
+    #[test]
+    fn dates_and_times_are_combined() {
+        let july_first = NaiveDate::from_ymd_opt(2024, 7, 1).and_then(|d| d.and_hms_opt(17, 30, 0));
+        assert_eq!(due("dentist 2024-07-01 at 5:30pm"), july_first);
+        assert_eq!(due("lunch tomorrow noon"), at(6, 12, 0));
+        assert_eq!(due("standup tomorrow 09:15"), at(6, 9, 15));
+    }
+
+    #[test]
+    fn bare_times_are_the_next_occurrence() {
+        assert_eq!(due("tea at 5pm"), at(5, 17, 0));
+        assert_eq!(due("breakfast 8am"), at(6, 8, 0));
+        // Not a time without am/pm
+        assert_eq!(parse("buy 5 apples", Tz::UTC, now()).title, "buy 5 apples");
+    }
+
+    #[test]
+    fn out_of_range_offsets_are_left_in_the_title() {
+        let parsed = parse("wait in 99999999999999 weeks", Tz::UTC, now());
+        assert_eq!(parsed.title, "wait in 99999999999999 weeks");
+        assert_eq!(parsed.due_at, None);
+
+        let parsed = parse("wait in 9223372036854775807 days", Tz::UTC, now());
+        assert_eq!(parsed.title, "wait in 9223372036854775807 days");
+        assert_eq!(parsed.due_at, None);
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    iter,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Counts the actions of every user (or client address) in a sliding
+/// window, refusing the ones over the limit. The counts are kept in memory, so each
+/// instance of the app enforces the limit on its own.
+pub struct RateLimiter {
+    limit: usize,
+    window: Duration,
+    hits: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl RateLimiter {
+    /// Allows `limit` actions per user every `window`.
+    pub fn new(limit: usize, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records an action of `key` if it is under the limit, otherwise
+    /// returns how long it has to wait until the next one is allowed.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        self.check_many(key, 1)
+    }
+
+    /// Like `check`, for `count` actions at once: either all of them are
+    /// recorded or none is. More than the limit are never allowed, the
+    /// wait is then the whole window.
+    pub fn check_many(&self, key: &str, count: usize) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap();
+
+        // Users without recent actions are forgotten
+        hits.retain(|_, times| {
+            while times
+                .front()
+                .is_some_and(|time| now.duration_since(*time) >= self.window)
+            {
+                times.pop_front();
+            }
+            !times.is_empty()
+        });
+
+        let times = hits.entry(key.to_string()).or_default();
+        if times.len() + count > self.limit {
+            // Until enough of the oldest actions are out of the window
+            let wait = times
+                .get(times.len() + count - self.limit - 1)
+                .map_or(self.window, |time| self.window - now.duration_since(*time));
+            return Err(wait);
+        }
+
+        times.extend(iter::repeat_n(now, count));
+
+        Ok(())
+    }
+
+    /// Like `check`, but without recording an action: how long `key`
+    /// has to wait if it is over the limit.
+    pub fn wait_time(&self, key: &str) -> Option<Duration> {
+        let now = Instant::now();
+        let hits = self.hits.lock().unwrap();
+
+        let recent: Vec<_> = hits
+            .get(key)?
+            .iter()
+            .filter(|time| now.duration_since(**time) < self.window)
+            .collect();
+        if recent.len() < self.limit {
+            return None;
+        }
+
+        recent
+            .first()
+            .map(|first| self.window - now.duration_since(**first))
+    }
+}
@@ -0,0 +1,65 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use tracing::{error, info};
+
+use crate::{
+    events::DomainEvent,
+    mailer::ReminderEmail,
+    service::{claim_reminder, get_due_reminders, release_reminder},
+    AppState,
+};
+
+/// How often the database is scanned for due reminders.
+pub const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Emails the owners of todos whose reminder is due (a background job).
+/// Every reminder is claimed (marked as sent) before the email is sent,
+/// so it is never sent twice; it is released again if sending fails.
+pub async fn scan(state: Arc<AppState>) -> Result<()> {
+    let pool = &state.pool;
+    let mailer = &state.mailer;
+
+    let now = Utc::now().naive_utc();
+
+    let reminders = get_due_reminders(now, &state.read_pool).await?;
+
+    for reminder in reminders {
+        match claim_reminder(reminder.todo_id, now, pool).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                error!("failed to claim reminder #{}: {}", reminder.todo_id, e);
+                continue;
+            }
+        }
+
+        let email = ReminderEmail {
+            username: reminder.username,
+            title: reminder.title.clone(),
+            description: reminder.description,
+            link: format!("{}/todo/list", state.config.app_url),
+        };
+
+        match mailer.send_email(&reminder.email, &email).await {
+            Ok(_) => {
+                info!("reminder sent for todo #{}", reminder.todo_id);
+
+                state.events.publish(DomainEvent::ReminderDue {
+                    user_id: reminder.user_id,
+                    todo_id: reminder.todo_id,
+                    title: reminder.title,
+                });
+            }
+            Err(e) => {
+                error!("failed to send reminder #{}: {:#}", reminder.todo_id, e);
+                if let Err(e) = release_reminder(reminder.todo_id, pool).await {
+                    error!("failed to release reminder #{}: {}", reminder.todo_id, e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1032 @@
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use moka::sync::Cache;
+
+use crate::{
+    db::{DbPool, DbPools},
+    events::EventBus,
+    import::ImportedTodo,
+    model::{
+        AuditEntry, AuditEvent, AuditFilter, BatchOperation, BulkEditSchema, ChecklistProgress,
+        DavResource, Notification, NotificationKind, Page, PageStart, SavedFilter, Subtask,
+        TagSuggestion, Theme, Todo, TodoFilter, TodoLink, TodoStats, TodoVersion, TokenKind,
+        TrackedTime, User, UserSettings, Workspace, WorkspaceMember,
+    },
+    service::{self, BatchOutcome},
+};
+
+/// Storage of the accounts, used by the handlers through `AppState::users`.
+#[async_trait]
+pub trait UserRepo: Send + Sync {
+    /// Creates an account, publishing `UserRegistered`.
+    async fn create_user(&self, email: String, password: String, username: String) -> Result<User>;
+
+    /// The user with that email and password.
+    async fn check_email_password(&self, email: String, password: String) -> Result<User>;
+
+    async fn get_user_by_id(&self, user_id: &str) -> Result<Option<User>, String>;
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>>;
+
+    async fn set_user_password(&self, user_id: &str, password: &str) -> Result<()>;
+
+    async fn set_email_verified(&self, user_id: &str, now: NaiveDateTime) -> Result<()>;
+
+    /// Creates a single use token of the user, valid for the lifetime of its kind.
+    async fn create_user_token(
+        &self,
+        user_id: &str,
+        kind: TokenKind,
+        now: NaiveDateTime,
+    ) -> Result<String>;
+
+    /// Marks a token as used, returning its user if it was valid.
+    async fn use_user_token(
+        &self,
+        token: &str,
+        kind: TokenKind,
+        now: NaiveDateTime,
+    ) -> Result<Option<String>>;
+
+    async fn get_or_create_feed_token(&self, user_id: String) -> Result<String>;
+
+    async fn get_user_by_feed_token(&self, token: &str) -> Result<Option<User>>;
+
+    /// Links the Slack user of the team to the account.
+    async fn link_slack_user(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+        user_id: &str,
+    ) -> Result<()>;
+
+    /// The account the Slack user of the team is linked to.
+    async fn get_user_by_slack_id(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+    ) -> Result<Option<User>>;
+
+    /// Records an authentication event of the account with that email,
+    /// made from the address of `client_ip` (by the admin with the
+    /// email `actor_email` while impersonating the account).
+    async fn add_audit_entry(
+        &self,
+        email: &str,
+        event: AuditEvent,
+        client_ip: Option<IpAddr>,
+        actor_email: Option<&str>,
+    ) -> Result<()>;
+
+    /// Entries of the audit log matching the filter, newest first.
+    async fn search_audit_log(&self, filter: &AuditFilter, limit: i64) -> Result<Vec<AuditEntry>>;
+}
+
+/// Storage of the workspaces and their members, used by the handlers
+/// through `AppState::workspaces`.
+#[async_trait]
+pub trait WorkspaceRepo: Send + Sync {
+    /// Creates a workspace owned by the user.
+    async fn create_workspace(&self, name: String, user_id: String) -> Result<Workspace>;
+
+    /// Workspaces the user is a member of, the ones they own first.
+    async fn get_user_workspaces(&self, user_id: &str) -> Result<Vec<Workspace>>;
+
+    async fn get_workspace_members(&self, workspace_id: i64) -> Result<Vec<WorkspaceMember>>;
+
+    /// Adds the user with that email to the workspace, publishing `MemberAdded`.
+    async fn add_workspace_member(&self, workspace_id: i64, email: String) -> Result<()>;
+
+    /// Removes a member of the workspace, other than its owner.
+    async fn remove_workspace_member(&self, workspace_id: i64, user_id: String) -> Result<()>;
+}
+
+/// Storage of the todos and everything attached to them (filters, timers,
+/// dependencies, links and subtasks), used by the handlers through
+/// `AppState::todos`.
+#[async_trait]
+pub trait TodoRepo: Send + Sync {
+    /// Creates a todo in the workspace, publishing `TodoCreated`.
+    #[allow(clippy::too_many_arguments)]
+    async fn add_todo(
+        &self,
+        created_by: String,
+        workspace_id: i64,
+        title: String,
+        description: String,
+        due_at: Option<NaiveDateTime>,
+        priority: i64,
+        tags: String,
+    ) -> Result<Todo>;
+
+    /// Creates all the todos or none, publishing `TodosImported`.
+    async fn add_imported_todos(
+        &self,
+        created_by: String,
+        workspace_id: i64,
+        todos: Vec<ImportedTodo>,
+    ) -> Result<usize>;
+
+    /// A page of `limit` todos of the workspace in the `sort` of
+    /// `TodoFilter`, newest first by default.
+    async fn get_all_todos(
+        &self,
+        workspace_id: i64,
+        sort: &str,
+        start: PageStart,
+        limit: i64,
+    ) -> Result<Page<Todo>>;
+
+    async fn count_todos(&self, workspace_id: i64) -> Result<i64>;
+
+    async fn get_todo_titles(&self, workspace_id: i64) -> Result<Vec<(i64, String)>>;
+
+    /// Id and title of the open todo of the workspace that looks like
+    /// a duplicate of one titled `title` (see `service::title_similarity`).
+    async fn find_similar_todo(
+        &self,
+        workspace_id: i64,
+        title: &str,
+    ) -> Result<Option<(i64, String)>>;
+
+    async fn get_filtered_todos(&self, workspace_id: i64, filter: &TodoFilter)
+        -> Result<Vec<Todo>>;
+
+    /// The first `limit` todos of the workspace matching the terms of
+    /// a search (see `service::search_terms`), the best matches first.
+    async fn search_todos(
+        &self,
+        workspace_id: i64,
+        terms: &[String],
+        limit: i64,
+    ) -> Result<Vec<Todo>>;
+
+    /// The first `limit` tags of the workspace starting with `prefix`,
+    /// the most used first.
+    async fn suggest_tags(
+        &self,
+        workspace_id: i64,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<TagSuggestion>>;
+
+    async fn get_todo_by_id(&self, todo_id: i64, workspace_id: i64) -> Result<Todo>;
+
+    /// Deletes a todo, publishing `TodoDeleted`.
+    async fn remove_todo(&self, todo_id: i64, workspace_id: i64) -> Result<()>;
+
+    /// Edits a todo, publishing `TodoUpdated`, its tags too when given. Fails
+    /// with `TodoBlockedError` or `TodoConflictError`.
+    #[allow(clippy::too_many_arguments)]
+    async fn update_todo(
+        &self,
+        title: String,
+        description: String,
+        status: bool,
+        remind_at: Option<NaiveDateTime>,
+        tags: Option<String>,
+        todo_id: i64,
+        expected_version: Option<i64>,
+        workspace_id: i64,
+    ) -> Result<Todo>;
+
+    /// Flips the status of a todo, publishing `TodoUpdated`. Fails with
+    /// `TodoBlockedError` when marking as done a blocked todo.
+    async fn toggle_todo(&self, todo_id: i64, workspace_id: i64) -> Result<Todo>;
+
+    /// Edits the selected todos at once, publishing `TodoUpdated` for
+    /// each one (and `TodoDeleted` for the ones moved to another workspace).
+    async fn bulk_update_todos(
+        &self,
+        edit: &BulkEditSchema,
+        workspace_id: i64,
+    ) -> Result<Vec<Todo>>;
+
+    /// Applies all the operations or none, publishing the events
+    /// of the todos created, updated and deleted.
+    async fn apply_todo_batch(
+        &self,
+        created_by: String,
+        workspace_id: i64,
+        operations: &[BatchOperation],
+    ) -> Result<Vec<BatchOutcome>>;
+
+    async fn get_todo_versions(&self, todo_id: i64) -> Result<Vec<TodoVersion>>;
+
+    /// Restores a previous version of a todo, publishing `TodoUpdated`.
+    async fn revert_todo(&self, todo_id: i64, version_id: i64, workspace_id: i64) -> Result<Todo>;
+
+    async fn get_recent_todos(&self, workspace_id: i64, limit: i64) -> Result<Vec<Todo>>;
+
+    /// Names and UIDs of the todos of the workspace created by CalDAV clients.
+    async fn get_dav_resources(&self, workspace_id: i64) -> Result<Vec<DavResource>>;
+
+    async fn add_dav_resource(
+        &self,
+        todo_id: i64,
+        workspace_id: i64,
+        name: String,
+        uid: String,
+    ) -> Result<()>;
+
+    /// Records the id of a change made offline by a client, returning
+    /// `false` if it already was (the change was sent before).
+    async fn claim_sync_mutation(
+        &self,
+        user_id: &str,
+        client_id: &str,
+        now: NaiveDateTime,
+    ) -> Result<bool>;
+
+    /// Todo created or changed by the change of a client, once applied.
+    async fn get_sync_mutation_todo(&self, user_id: &str, client_id: &str) -> Result<Option<i64>>;
+
+    async fn finish_sync_mutation(
+        &self,
+        user_id: &str,
+        client_id: &str,
+        todo_id: i64,
+    ) -> Result<()>;
+
+    /// Gives back the claim of a change that couldn't be applied.
+    async fn release_sync_mutation(&self, user_id: &str, client_id: &str) -> Result<()>;
+
+    async fn get_todo_stats(&self, workspace_id: i64, now: NaiveDateTime) -> Result<TodoStats>;
+
+    async fn add_saved_filter(
+        &self,
+        user_id: String,
+        name: String,
+        filter: TodoFilter,
+    ) -> Result<SavedFilter>;
+
+    async fn get_saved_filters(&self, user_id: String) -> Result<Vec<SavedFilter>>;
+
+    async fn remove_saved_filter(&self, filter_id: i64, user_id: String) -> Result<()>;
+
+    async fn start_timer(&self, todo_id: i64, user_id: String, workspace_id: i64) -> Result<()>;
+
+    async fn stop_timer(&self, todo_id: i64, user_id: String) -> Result<()>;
+
+    async fn get_tracked_times(&self, user_id: String) -> Result<Vec<TrackedTime>>;
+
+    async fn add_dependency(
+        &self,
+        todo_id: i64,
+        blocked_by_id: i64,
+        workspace_id: i64,
+    ) -> Result<()>;
+
+    async fn remove_dependency(
+        &self,
+        todo_id: i64,
+        blocked_by_id: i64,
+        workspace_id: i64,
+    ) -> Result<()>;
+
+    async fn get_blockers(&self, todo_id: i64) -> Result<Vec<Todo>>;
+
+    async fn get_blocker_candidates(&self, todo_id: i64, workspace_id: i64) -> Result<Vec<Todo>>;
+
+    async fn get_blocked_todo_ids(&self, workspace_id: i64) -> Result<Vec<i64>>;
+
+    async fn add_link(&self, todo_id: i64, url: String, workspace_id: i64) -> Result<i64>;
+
+    async fn set_link_preview(
+        &self,
+        link_id: i64,
+        title: Option<String>,
+        description: Option<String>,
+    ) -> Result<()>;
+
+    async fn get_links(&self, workspace_id: i64) -> Result<Vec<TodoLink>>;
+
+    /// Removes a link, returning the id of its Todo.
+    async fn remove_link(&self, link_id: i64, workspace_id: i64) -> Result<i64>;
+
+    async fn add_subtask(&self, todo_id: i64, title: String, workspace_id: i64) -> Result<()>;
+
+    async fn get_subtasks(&self, workspace_id: i64) -> Result<Vec<Subtask>>;
+
+    async fn toggle_subtask(&self, subtask_id: i64, workspace_id: i64) -> Result<Subtask>;
+
+    /// Removes a checklist item, returning the id of its Todo.
+    async fn remove_subtask(&self, subtask_id: i64, workspace_id: i64) -> Result<i64>;
+
+    async fn get_checklist_progress(&self, workspace_id: i64) -> Result<Vec<ChecklistProgress>>;
+
+    async fn get_todo_checklist_progress(&self, todo_id: i64) -> Result<ChecklistProgress>;
+}
+
+/// Storage of the preferences of the users (theme, timezone, date format,
+/// todo list and digest), used by the handlers through `AppState::settings`.
+#[async_trait]
+pub trait SettingsRepo: Send + Sync {
+    /// The settings of the user, the default ones if they never saved any.
+    async fn get_user_settings(&self, user_id: &str) -> Result<UserSettings>;
+
+    async fn save_user_settings(&self, settings: &UserSettings) -> Result<()>;
+
+    /// Saves the theme, leaving the other settings as they are.
+    async fn set_user_theme(&self, user_id: &str, theme: Theme) -> Result<()>;
+}
+
+/// Storage of the notifications of the users, used by the handlers
+/// and the subscribers of the domain events through
+/// `AppState::notifications`.
+#[async_trait]
+pub trait NotificationRepo: Send + Sync {
+    /// Notifies the user, only once per todo for the kinds that say so.
+    async fn add_notification(
+        &self,
+        user_id: &str,
+        kind: NotificationKind,
+        message: &str,
+        link: &str,
+        todo_id: Option<i64>,
+    ) -> Result<()>;
+
+    /// The last `limit` notifications of the user, newest first.
+    async fn get_notifications(&self, user_id: &str, limit: i64) -> Result<Vec<Notification>>;
+
+    async fn count_unread_notifications(&self, user_id: &str) -> Result<i64>;
+
+    /// Marks a notification of the user as read, returning it.
+    async fn mark_notification_read(
+        &self,
+        notification_id: i64,
+        user_id: &str,
+        now: NaiveDateTime,
+    ) -> Result<Notification>;
+
+    async fn mark_all_notifications_read(&self, user_id: &str, now: NaiveDateTime) -> Result<()>;
+}
+
+/// The repositories backed by the database of `DATABASE_URL`,
+/// through the functions of the `service` module. The functions
+/// that only read (`db = "read"`) go through the reader pool.
+#[derive(Clone)]
+pub struct SqlRepo {
+    pool: DbPool,
+    read_pool: DbPool,
+    events: EventBus,
+}
+
+impl SqlRepo {
+    /// The changes are published on `events`.
+    pub fn new(pools: DbPools, events: EventBus) -> Arc<Self> {
+        Arc::new(Self {
+            pool: pools.writer,
+            read_pool: pools.reader,
+            events,
+        })
+    }
+}
+
+#[async_trait]
+impl UserRepo for SqlRepo {
+    async fn create_user(&self, email: String, password: String, username: String) -> Result<User> {
+        service::create_user(email, password, username, &self.events, &self.pool).await
+    }
+
+    async fn check_email_password(&self, email: String, password: String) -> Result<User> {
+        service::check_email_password(email, password, &self.read_pool).await
+    }
+
+    async fn get_user_by_id(&self, user_id: &str) -> Result<Option<User>, String> {
+        service::get_user_by_id(user_id, &self.read_pool).await
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        service::get_user_by_email(email, &self.read_pool).await
+    }
+
+    async fn set_user_password(&self, user_id: &str, password: &str) -> Result<()> {
+        service::set_user_password(user_id, password, &self.pool).await
+    }
+
+    async fn set_email_verified(&self, user_id: &str, now: NaiveDateTime) -> Result<()> {
+        service::set_email_verified(user_id, now, &self.pool).await
+    }
+
+    async fn create_user_token(
+        &self,
+        user_id: &str,
+        kind: TokenKind,
+        now: NaiveDateTime,
+    ) -> Result<String> {
+        service::create_user_token(user_id, kind, now, &self.pool).await
+    }
+
+    async fn use_user_token(
+        &self,
+        token: &str,
+        kind: TokenKind,
+        now: NaiveDateTime,
+    ) -> Result<Option<String>> {
+        service::use_user_token(token, kind, now, &self.pool).await
+    }
+
+    async fn get_or_create_feed_token(&self, user_id: String) -> Result<String> {
+        service::get_or_create_feed_token(user_id, &self.pool).await
+    }
+
+    async fn get_user_by_feed_token(&self, token: &str) -> Result<Option<User>> {
+        service::get_user_by_feed_token(token, &self.read_pool).await
+    }
+
+    async fn link_slack_user(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+        user_id: &str,
+    ) -> Result<()> {
+        service::link_slack_user(team_id, slack_user_id, user_id, &self.pool).await
+    }
+
+    async fn get_user_by_slack_id(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+    ) -> Result<Option<User>> {
+        service::get_user_by_slack_id(team_id, slack_user_id, &self.read_pool).await
+    }
+
+    async fn add_audit_entry(
+        &self,
+        email: &str,
+        event: AuditEvent,
+        client_ip: Option<IpAddr>,
+        actor_email: Option<&str>,
+    ) -> Result<()> {
+        service::add_audit_entry(email, event, client_ip, actor_email, &self.pool).await
+    }
+
+    async fn search_audit_log(&self, filter: &AuditFilter, limit: i64) -> Result<Vec<AuditEntry>> {
+        service::search_audit_log(filter, limit, &self.read_pool).await
+    }
+}
+
+#[async_trait]
+impl WorkspaceRepo for SqlRepo {
+    async fn create_workspace(&self, name: String, user_id: String) -> Result<Workspace> {
+        service::create_workspace(name, user_id, &self.pool).await
+    }
+
+    async fn get_user_workspaces(&self, user_id: &str) -> Result<Vec<Workspace>> {
+        service::get_user_workspaces(user_id, &self.read_pool).await
+    }
+
+    async fn get_workspace_members(&self, workspace_id: i64) -> Result<Vec<WorkspaceMember>> {
+        service::get_workspace_members(workspace_id, &self.read_pool).await
+    }
+
+    async fn add_workspace_member(&self, workspace_id: i64, email: String) -> Result<()> {
+        service::add_workspace_member(workspace_id, email, &self.events, &self.pool).await
+    }
+
+    async fn remove_workspace_member(&self, workspace_id: i64, user_id: String) -> Result<()> {
+        service::remove_workspace_member(workspace_id, user_id, &self.pool).await
+    }
+}
+
+#[async_trait]
+impl TodoRepo for SqlRepo {
+    #[allow(clippy::too_many_arguments)]
+    async fn add_todo(
+        &self,
+        created_by: String,
+        workspace_id: i64,
+        title: String,
+        description: String,
+        due_at: Option<NaiveDateTime>,
+        priority: i64,
+        tags: String,
+    ) -> Result<Todo> {
+        service::add_todo(
+            created_by,
+            workspace_id,
+            title,
+            description,
+            due_at,
+            priority,
+            tags,
+            &self.events,
+            &self.pool,
+        )
+        .await
+    }
+
+    async fn add_imported_todos(
+        &self,
+        created_by: String,
+        workspace_id: i64,
+        todos: Vec<ImportedTodo>,
+    ) -> Result<usize> {
+        service::add_imported_todos(created_by, workspace_id, todos, &self.events, &self.pool).await
+    }
+
+    async fn get_all_todos(
+        &self,
+        workspace_id: i64,
+        sort: &str,
+        start: PageStart,
+        limit: i64,
+    ) -> Result<Page<Todo>> {
+        service::get_all_todos(workspace_id, sort, start, limit, &self.read_pool).await
+    }
+
+    async fn count_todos(&self, workspace_id: i64) -> Result<i64> {
+        service::count_todos(workspace_id, &self.read_pool).await
+    }
+
+    async fn get_todo_titles(&self, workspace_id: i64) -> Result<Vec<(i64, String)>> {
+        service::get_todo_titles(workspace_id, &self.read_pool).await
+    }
+
+    async fn find_similar_todo(
+        &self,
+        workspace_id: i64,
+        title: &str,
+    ) -> Result<Option<(i64, String)>> {
+        service::find_similar_todo(workspace_id, title, &self.read_pool).await
+    }
+
+    async fn get_filtered_todos(
+        &self,
+        workspace_id: i64,
+        filter: &TodoFilter,
+    ) -> Result<Vec<Todo>> {
+        service::get_filtered_todos(workspace_id, filter, &self.read_pool).await
+    }
+
+    async fn search_todos(
+        &self,
+        workspace_id: i64,
+        terms: &[String],
+        limit: i64,
+    ) -> Result<Vec<Todo>> {
+        service::search_todos(workspace_id, terms, limit, &self.read_pool).await
+    }
+
+    async fn suggest_tags(
+        &self,
+        workspace_id: i64,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<TagSuggestion>> {
+        service::suggest_tags(workspace_id, prefix, limit, &self.read_pool).await
+    }
+
+    async fn get_todo_by_id(&self, todo_id: i64, workspace_id: i64) -> Result<Todo> {
+        service::get_todo_by_id(todo_id, workspace_id, &self.read_pool).await
+    }
+
+    async fn remove_todo(&self, todo_id: i64, workspace_id: i64) -> Result<()> {
+        service::remove_todo(todo_id, workspace_id, &self.events, &self.pool).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn update_todo(
+        &self,
+        title: String,
+        description: String,
+        status: bool,
+        remind_at: Option<NaiveDateTime>,
+        tags: Option<String>,
+        todo_id: i64,
+        expected_version: Option<i64>,
+        workspace_id: i64,
+    ) -> Result<Todo> {
+        service::update_todo(
+            title,
+            description,
+            status,
+            remind_at,
+            tags,
+            todo_id,
+            expected_version,
+            workspace_id,
+            &self.events,
+            &self.pool,
+        )
+        .await
+    }
+
+    async fn toggle_todo(&self, todo_id: i64, workspace_id: i64) -> Result<Todo> {
+        service::toggle_todo(todo_id, workspace_id, &self.events, &self.pool).await
+    }
+
+    async fn bulk_update_todos(
+        &self,
+        edit: &BulkEditSchema,
+        workspace_id: i64,
+    ) -> Result<Vec<Todo>> {
+        service::bulk_update_todos(edit, workspace_id, &self.events, &self.pool).await
+    }
+
+    async fn apply_todo_batch(
+        &self,
+        created_by: String,
+        workspace_id: i64,
+        operations: &[BatchOperation],
+    ) -> Result<Vec<BatchOutcome>> {
+        service::apply_todo_batch(
+            created_by,
+            workspace_id,
+            operations,
+            &self.events,
+            &self.pool,
+        )
+        .await
+    }
+
+    async fn get_todo_versions(&self, todo_id: i64) -> Result<Vec<TodoVersion>> {
+        service::get_todo_versions(todo_id, &self.read_pool).await
+    }
+
+    async fn revert_todo(&self, todo_id: i64, version_id: i64, workspace_id: i64) -> Result<Todo> {
+        service::revert_todo(todo_id, version_id, workspace_id, &self.events, &self.pool).await
+    }
+
+    async fn get_recent_todos(&self, workspace_id: i64, limit: i64) -> Result<Vec<Todo>> {
+        service::get_recent_todos(workspace_id, limit, &self.read_pool).await
+    }
+
+    async fn get_dav_resources(&self, workspace_id: i64) -> Result<Vec<DavResource>> {
+        service::get_dav_resources(workspace_id, &self.read_pool).await
+    }
+
+    async fn add_dav_resource(
+        &self,
+        todo_id: i64,
+        workspace_id: i64,
+        name: String,
+        uid: String,
+    ) -> Result<()> {
+        service::add_dav_resource(todo_id, workspace_id, name, uid, &self.pool).await
+    }
+
+    async fn claim_sync_mutation(
+        &self,
+        user_id: &str,
+        client_id: &str,
+        now: NaiveDateTime,
+    ) -> Result<bool> {
+        service::claim_sync_mutation(user_id, client_id, now, &self.pool).await
+    }
+
+    async fn get_sync_mutation_todo(&self, user_id: &str, client_id: &str) -> Result<Option<i64>> {
+        // From the writer, as the changes of the same batch refer
+        // to the todos created by the previous ones
+        service::get_sync_mutation_todo(user_id, client_id, &self.pool).await
+    }
+
+    async fn finish_sync_mutation(
+        &self,
+        user_id: &str,
+        client_id: &str,
+        todo_id: i64,
+    ) -> Result<()> {
+        service::finish_sync_mutation(user_id, client_id, todo_id, &self.pool).await
+    }
+
+    async fn release_sync_mutation(&self, user_id: &str, client_id: &str) -> Result<()> {
+        service::release_sync_mutation(user_id, client_id, &self.pool).await
+    }
+
+    async fn get_todo_stats(&self, workspace_id: i64, now: NaiveDateTime) -> Result<TodoStats> {
+        service::get_todo_stats(workspace_id, now, &self.read_pool).await
+    }
+
+    async fn add_saved_filter(
+        &self,
+        user_id: String,
+        name: String,
+        filter: TodoFilter,
+    ) -> Result<SavedFilter> {
+        service::add_saved_filter(user_id, name, filter, &self.pool).await
+    }
+
+    async fn get_saved_filters(&self, user_id: String) -> Result<Vec<SavedFilter>> {
+        service::get_saved_filters(user_id, &self.read_pool).await
+    }
+
+    async fn remove_saved_filter(&self, filter_id: i64, user_id: String) -> Result<()> {
+        service::remove_saved_filter(filter_id, user_id, &self.pool).await
+    }
+
+    async fn start_timer(&self, todo_id: i64, user_id: String, workspace_id: i64) -> Result<()> {
+        service::start_timer(todo_id, user_id, workspace_id, &self.pool).await
+    }
+
+    async fn stop_timer(&self, todo_id: i64, user_id: String) -> Result<()> {
+        service::stop_timer(todo_id, user_id, &self.pool).await
+    }
+
+    async fn get_tracked_times(&self, user_id: String) -> Result<Vec<TrackedTime>> {
+        service::get_tracked_times(user_id, &self.read_pool).await
+    }
+
+    async fn add_dependency(
+        &self,
+        todo_id: i64,
+        blocked_by_id: i64,
+        workspace_id: i64,
+    ) -> Result<()> {
+        service::add_dependency(todo_id, blocked_by_id, workspace_id, &self.pool).await
+    }
+
+    async fn remove_dependency(
+        &self,
+        todo_id: i64,
+        blocked_by_id: i64,
+        workspace_id: i64,
+    ) -> Result<()> {
+        service::remove_dependency(todo_id, blocked_by_id, workspace_id, &self.pool).await
+    }
+
+    async fn get_blockers(&self, todo_id: i64) -> Result<Vec<Todo>> {
+        service::get_blockers(todo_id, &self.read_pool).await
+    }
+
+    async fn get_blocker_candidates(&self, todo_id: i64, workspace_id: i64) -> Result<Vec<Todo>> {
+        service::get_blocker_candidates(todo_id, workspace_id, &self.read_pool).await
+    }
+
+    async fn get_blocked_todo_ids(&self, workspace_id: i64) -> Result<Vec<i64>> {
+        service::get_blocked_todo_ids(workspace_id, &self.read_pool).await
+    }
+
+    async fn add_link(&self, todo_id: i64, url: String, workspace_id: i64) -> Result<i64> {
+        service::add_link(todo_id, url, workspace_id, &self.pool).await
+    }
+
+    async fn set_link_preview(
+        &self,
+        link_id: i64,
+        title: Option<String>,
+        description: Option<String>,
+    ) -> Result<()> {
+        service::set_link_preview(link_id, title, description, &self.pool).await
+    }
+
+    async fn get_links(&self, workspace_id: i64) -> Result<Vec<TodoLink>> {
+        service::get_links(workspace_id, &self.read_pool).await
+    }
+
+    async fn remove_link(&self, link_id: i64, workspace_id: i64) -> Result<i64> {
+        service::remove_link(link_id, workspace_id, &self.pool).await
+    }
+
+    async fn add_subtask(&self, todo_id: i64, title: String, workspace_id: i64) -> Result<()> {
+        service::add_subtask(todo_id, title, workspace_id, &self.pool).await
+    }
+
+    async fn get_subtasks(&self, workspace_id: i64) -> Result<Vec<Subtask>> {
+        service::get_subtasks(workspace_id, &self.read_pool).await
+    }
+
+    async fn toggle_subtask(&self, subtask_id: i64, workspace_id: i64) -> Result<Subtask> {
+        service::toggle_subtask(subtask_id, workspace_id, &self.pool).await
+    }
+
+    async fn remove_subtask(&self, subtask_id: i64, workspace_id: i64) -> Result<i64> {
+        service::remove_subtask(subtask_id, workspace_id, &self.pool).await
+    }
+
+    async fn get_checklist_progress(&self, workspace_id: i64) -> Result<Vec<ChecklistProgress>> {
+        service::get_checklist_progress(workspace_id, &self.read_pool).await
+    }
+
+    async fn get_todo_checklist_progress(&self, todo_id: i64) -> Result<ChecklistProgress> {
+        service::get_todo_checklist_progress(todo_id, &self.read_pool).await
+    }
+}
+
+#[async_trait]
+impl SettingsRepo for SqlRepo {
+    async fn get_user_settings(&self, user_id: &str) -> Result<UserSettings> {
+        service::get_user_settings(user_id, &self.read_pool).await
+    }
+
+    async fn save_user_settings(&self, settings: &UserSettings) -> Result<()> {
+        service::save_user_settings(settings, &self.pool).await
+    }
+
+    async fn set_user_theme(&self, user_id: &str, theme: Theme) -> Result<()> {
+        service::set_user_theme(user_id, theme, &self.pool).await
+    }
+}
+
+#[async_trait]
+impl NotificationRepo for SqlRepo {
+    async fn add_notification(
+        &self,
+        user_id: &str,
+        kind: NotificationKind,
+        message: &str,
+        link: &str,
+        todo_id: Option<i64>,
+    ) -> Result<()> {
+        service::add_notification(user_id, kind, message, link, todo_id, &self.pool).await
+    }
+
+    async fn get_notifications(&self, user_id: &str, limit: i64) -> Result<Vec<Notification>> {
+        service::get_notifications(user_id, limit, &self.read_pool).await
+    }
+
+    async fn count_unread_notifications(&self, user_id: &str) -> Result<i64> {
+        service::count_unread_notifications(user_id, &self.read_pool).await
+    }
+
+    async fn mark_notification_read(
+        &self,
+        notification_id: i64,
+        user_id: &str,
+        now: NaiveDateTime,
+    ) -> Result<Notification> {
+        service::mark_notification_read(notification_id, user_id, now, &self.pool).await
+    }
+
+    async fn mark_all_notifications_read(&self, user_id: &str, now: NaiveDateTime) -> Result<()> {
+        service::mark_all_notifications_read(user_id, now, &self.pool).await
+    }
+}
+
+/// Most users kept by `CachedUserRepo`.
+const USER_CACHE_CAPACITY: u64 = 10_000;
+
+/// Keeps the users read by `get_user_by_id` (the authentication of every
+/// request) for `USER_CACHE_SECONDS`, forgetting them as soon as they
+/// change through this repository. The changes made by other instances
+/// of the app are seen once the cached user expires.
+pub struct CachedUserRepo {
+    inner: Arc<dyn UserRepo>,
+    users: Cache<String, User>,
+}
+
+impl CachedUserRepo {
+    pub fn new(inner: Arc<dyn UserRepo>, ttl: Duration) -> Arc<Self> {
+        let users = Cache::builder()
+            .max_capacity(USER_CACHE_CAPACITY)
+            .time_to_live(ttl)
+            .build();
+
+        Arc::new(Self { inner, users })
+    }
+}
+
+#[async_trait]
+impl UserRepo for CachedUserRepo {
+    async fn create_user(&self, email: String, password: String, username: String) -> Result<User> {
+        self.inner.create_user(email, password, username).await
+    }
+
+    async fn check_email_password(&self, email: String, password: String) -> Result<User> {
+        self.inner.check_email_password(email, password).await
+    }
+
+    async fn get_user_by_id(&self, user_id: &str) -> Result<Option<User>, String> {
+        if let Some(user) = self.users.get(user_id) {
+            return Ok(Some(user));
+        }
+
+        let user = self.inner.get_user_by_id(user_id).await?;
+        if let Some(user) = &user {
+            self.users.insert(user_id.to_string(), user.clone());
+        }
+
+        Ok(user)
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_email(email).await
+    }
+
+    async fn set_user_password(&self, user_id: &str, password: &str) -> Result<()> {
+        let result = self.inner.set_user_password(user_id, password).await;
+        self.users.invalidate(user_id);
+
+        result
+    }
+
+    async fn set_email_verified(&self, user_id: &str, now: NaiveDateTime) -> Result<()> {
+        let result = self.inner.set_email_verified(user_id, now).await;
+        self.users.invalidate(user_id);
+
+        result
+    }
+
+    async fn create_user_token(
+        &self,
+        user_id: &str,
+        kind: TokenKind,
+        now: NaiveDateTime,
+    ) -> Result<String> {
+        self.inner.create_user_token(user_id, kind, now).await
+    }
+
+    async fn use_user_token(
+        &self,
+        token: &str,
+        kind: TokenKind,
+        now: NaiveDateTime,
+    ) -> Result<Option<String>> {
+        self.inner.use_user_token(token, kind, now).await
+    }
+
+    async fn get_or_create_feed_token(&self, user_id: String) -> Result<String> {
+        self.inner.get_or_create_feed_token(user_id).await
+    }
+
+    async fn get_user_by_feed_token(&self, token: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_feed_token(token).await
+    }
+
+    async fn link_slack_user(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+        user_id: &str,
+    ) -> Result<()> {
+        self.inner
+            .link_slack_user(team_id, slack_user_id, user_id)
+            .await
+    }
+
+    async fn get_user_by_slack_id(
+        &self,
+        team_id: &str,
+        slack_user_id: &str,
+    ) -> Result<Option<User>> {
+        self.inner
+            .get_user_by_slack_id(team_id, slack_user_id)
+            .await
+    }
+
+    async fn add_audit_entry(
+        &self,
+        email: &str,
+        event: AuditEvent,
+        client_ip: Option<IpAddr>,
+        actor_email: Option<&str>,
+    ) -> Result<()> {
+        self.inner
+            .add_audit_entry(email, event, client_ip, actor_email)
+            .await
+    }
+
+    async fn search_audit_log(&self, filter: &AuditFilter, limit: i64) -> Result<Vec<AuditEntry>> {
+        self.inner.search_audit_log(filter, limit).await
+    }
+}
+
+/// Keeps the settings read by `get_user_settings` (loaded on every
+/// request) like `CachedUserRepo` keeps the users.
+pub struct CachedSettingsRepo {
+    inner: Arc<dyn SettingsRepo>,
+    settings: Cache<String, UserSettings>,
+}
+
+impl CachedSettingsRepo {
+    pub fn new(inner: Arc<dyn SettingsRepo>, ttl: Duration) -> Arc<Self> {
+        let settings = Cache::builder()
+            .max_capacity(USER_CACHE_CAPACITY)
+            .time_to_live(ttl)
+            .build();
+
+        Arc::new(Self { inner, settings })
+    }
+}
+
+#[async_trait]
+impl SettingsRepo for CachedSettingsRepo {
+    async fn get_user_settings(&self, user_id: &str) -> Result<UserSettings> {
+        if let Some(settings) = self.settings.get(user_id) {
+            return Ok(settings);
+        }
+
+        let settings = self.inner.get_user_settings(user_id).await?;
+        self.settings.insert(user_id.to_string(), settings.clone());
+
+        Ok(settings)
+    }
+
+    async fn save_user_settings(&self, settings: &UserSettings) -> Result<()> {
+        let result = self.inner.save_user_settings(settings).await;
+        self.settings.invalidate(&settings.user_id);
+
+        result
+    }
+
+    async fn set_user_theme(&self, user_id: &str, theme: Theme) -> Result<()> {
+        let result = self.inner.set_user_theme(user_id, theme).await;
+        self.settings.invalidate(user_id);
+
+        result
+    }
+}
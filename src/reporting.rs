@@ -0,0 +1,224 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context as _, Result};
+use chrono::{NaiveDateTime, Utc};
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use uuid::Uuid;
+
+/// Errors waiting to be sent; more are dropped while it is full.
+const QUEUE_CAPACITY: usize = 64;
+
+/// Time given to Sentry to accept a report.
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors kept for the ops dashboard; the oldest are dropped first.
+const RECENT_ERRORS_CAPACITY: usize = 50;
+
+/// Tracing layer that reports the events logged with the `error` level
+/// (panics, template render failures, database errors…) to Sentry, or
+/// any service with the same API, along with the fields of their request
+/// span (route, request id, user id…).
+pub struct ErrorReportLayer {
+    sender: mpsc::Sender<Value>,
+}
+
+/// Reports the errors to the project of `dsn`
+/// (`https://<public key>@<host>/<project id>`).
+/// It must be called within the Tokio runtime.
+pub fn layer(dsn: &str) -> Result<ErrorReportLayer> {
+    let dsn = Url::parse(dsn).context("SENTRY_DSN must be a valid DSN")?;
+    let public_key = dsn.username().to_string();
+    let project_id = dsn
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| anyhow!("SENTRY_DSN must end with the id of the project"))?;
+
+    let mut endpoint = dsn.clone();
+    endpoint.set_username("").unwrap();
+    endpoint.set_path(&format!("api/{}/envelope/", project_id));
+
+    let auth = format!(
+        "Sentry sentry_version=7, sentry_key={}, sentry_client={}/{}",
+        public_key,
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+    tokio::spawn(send_reports(receiver, endpoint, auth));
+
+    Ok(ErrorReportLayer { sender })
+}
+
+/// Sends the reports one by one. Failures are only printed,
+/// as logging them would report them again.
+async fn send_reports(mut receiver: mpsc::Receiver<Value>, endpoint: Url, auth: String) {
+    let client = Client::builder().timeout(SEND_TIMEOUT).build().unwrap();
+
+    while let Some(event) = receiver.recv().await {
+        // An envelope with a single event: its header, the item header and the item
+        let envelope = format!(
+            "{}\n{}\n{}",
+            json!({ "event_id": event["event_id"] }),
+            json!({ "type": "event" }),
+            event
+        );
+
+        let result = client
+            .post(endpoint.clone())
+            .header("X-Sentry-Auth", &auth)
+            .header("Content-Type", "application/x-sentry-envelope")
+            .body(envelope)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+
+        if let Err(e) = result {
+            eprintln!("failed to report an error to Sentry: {}", e);
+        }
+    }
+}
+
+/// Fields recorded on a span, kept in its extensions.
+#[derive(Default)]
+struct SpanFields(BTreeMap<String, String>);
+
+/// Collects the fields of a span or an event as strings.
+struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if !value.is_empty() {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl<S> Layer<S> for ErrorReportLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut fields = SpanFields::default();
+        attrs.record(&mut FieldVisitor(&mut fields.0));
+        span.extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut FieldVisitor(&mut fields.0));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+
+        let mut fields = BTreeMap::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let message = fields.remove("message").unwrap_or_default();
+
+        // The fields of the spans around the event, the innermost last
+        let mut tags = BTreeMap::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                    tags.extend(span_fields.0.clone());
+                }
+            }
+        }
+
+        let mut report = json!({
+            "event_id": Uuid::new_v4().simple().to_string(),
+            "timestamp": Utc::now().timestamp(),
+            "platform": "other",
+            "level": "error",
+            "logger": event.metadata().target(),
+            "release": concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION")),
+            "message": { "formatted": message },
+            "tags": tags,
+            "extra": fields,
+        });
+        if let Some(user_id) = tags.get("user_id") {
+            report["user"] = json!({ "id": user_id });
+        }
+
+        // Never blocks the request that logged the error
+        let _ = self.sender.try_send(report);
+    }
+}
+
+/// An error logged by the app, as shown in the ops dashboard.
+#[derive(Clone, Debug)]
+pub struct RecentError {
+    pub logged_at: NaiveDateTime,
+    pub target: String,
+    pub message: String,
+}
+
+/// Tracing layer that keeps the last events logged with the `error`
+/// level in memory, so the admins can see them without a Sentry
+/// project. Every instance of the app keeps its own.
+#[derive(Clone, Default)]
+pub struct RecentErrors(Arc<Mutex<VecDeque<RecentError>>>);
+
+impl RecentErrors {
+    /// The errors kept, the newest first.
+    pub fn list(&self) -> Vec<RecentError> {
+        let errors = self.0.lock().unwrap();
+
+        errors.iter().rev().cloned().collect()
+    }
+}
+
+impl<S> Layer<S> for RecentErrors
+where
+    S: Subscriber,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+
+        let mut fields = BTreeMap::new();
+        event.record(&mut FieldVisitor(&mut fields));
+
+        let mut errors = self.0.lock().unwrap();
+        if errors.len() == RECENT_ERRORS_CAPACITY {
+            errors.pop_front();
+        }
+        errors.push_back(RecentError {
+            logged_at: Utc::now().naive_utc(),
+            target: event.metadata().target().to_string(),
+            message: fields.remove("message").unwrap_or_default(),
+        });
+    }
+}
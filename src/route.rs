@@ -1,113 +1,751 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+#[cfg(unix)]
+use std::{
+    path::{Path, PathBuf},
+    pin::pin,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
-    middleware::from_fn_with_state,
-    routing::{delete, get, post},
+    error_handling::HandleErrorLayer,
+    extract::{DefaultBodyLimit, Host, MatchedPath, Request},
+    http::{header, uri::Authority, HeaderName, Uri},
+    middleware::{self, from_fn_with_state},
+    response::Redirect,
+    routing::{any, delete, get, patch, post},
     Router,
 };
 use axum_messages::MessagesManagerLayer;
-use tokio::sync::RwLock;
-use tower_http::{services::ServeDir, trace::TraceLayer};
-use tower_sessions::{MemoryStore, SessionManagerLayer};
-use tracing::info;
+use axum_server::tls_rustls::RustlsConfig;
+#[cfg(unix)]
+use hyper::{body::Incoming, service::service_fn};
+#[cfg(unix)]
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{sync::watch, task::JoinSet};
+#[cfg(unix)]
+use tower::Service;
+use tower::{
+    limit::ConcurrencyLimitLayer, load_shed::LoadShedLayer, timeout::TimeoutLayer, ServiceBuilder,
+};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    cors::{AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tower_sessions::SessionManagerLayer;
+#[cfg(unix)]
+use tracing::{debug, warn};
+use tracing::{error, field::Empty, info, info_span, Span};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 use crate::{
+    assets,
+    config::{Config, ListenAddress, LogFormat},
     handler::{
-        auth_middleware, handler_404, health_checker_handler, home_handler, login_page_handler,
-        login_user_handler, logout_handler, register_page_handler, register_user_handler,
-        todo_add_handler, todo_create_handler, todo_delete_handler, todo_edit_handler,
-        todo_list_handler, todo_patch_handler,
+        audit_log_handler, auth_middleware, backup_handler, client_ip_middleware,
+        conditional_get_middleware, dav_auth_middleware, dav_calendar_handler, dav_home_handler,
+        dav_todo_handler, feed_handler, feed_link_handler, filter_delete_handler,
+        filter_save_handler, forgot_password_handler, forgot_password_page_handler, handle_panic,
+        handle_timeout_error, handler_404, health_checker_handler, home_handler,
+        htmx_error_middleware, impersonate_handler, impersonate_stop_handler,
+        import_confirm_handler, import_page_handler, import_preview_handler,
+        legacy_delete_redirect_handler, legacy_edit_redirect_handler, link_add_handler,
+        link_delete_handler, login_limit_middleware, login_page_handler, login_user_handler,
+        logout_handler, method_not_allowed_middleware, method_override_middleware, metrics_handler,
+        notification_bell_handler, notification_read_handler, notifications_page_handler,
+        notifications_read_all_handler, ops_handler, profile_page_handler, profile_update_handler,
+        register_page_handler, register_user_handler, request_id_middleware,
+        reset_password_handler, reset_password_page_handler, security_headers_middleware,
+        signed_url_middleware, slack_command_handler, slack_link_handler,
+        slack_signature_middleware, subtask_add_handler, subtask_delete_handler,
+        subtask_toggle_handler, sync_handler, tag_suggest_handler, theme_handler, theme_middleware,
+        todo_add_handler, todo_api_list_handler, todo_batch_handler, todo_bulk_edit_handler,
+        todo_bulk_handler, todo_create_handler, todo_create_limit_middleware, todo_delete_handler,
+        todo_dependency_add_handler, todo_dependency_remove_handler, todo_edit_handler,
+        todo_export_handler, todo_list_handler, todo_list_page_handler, todo_patch_handler,
+        todo_quick_add_handler, todo_revert_handler, todo_search_live_handler, todo_stats_handler,
+        todo_timer_start_handler, todo_timer_stop_handler, todo_toggle_handler,
+        verify_email_handler, workspace_create_handler, workspace_member_add_handler,
+        workspace_member_remove_handler, workspace_page_handler, workspace_switch_handler,
+        ws_handler, ApiDoc, REQUEST_ID_HEADER, TOTAL_COUNT_HEADER, WORKSPACE_HEADER,
     },
-    AppState,
+    reporting, timing, AppState,
 };
 
+/// Room for the other fields and the headers of the parts of
+/// the upload forms, besides the file.
+const UPLOAD_FORM_OVERHEAD: usize = 64 * 1024;
+
 /// This function serves as the entry point for running the Axum web server.
 /// It takes a PostgreSQL connection pool (`PgPool`) as input,
 /// sets up the application state,
 /// creates the API routes using the provided application state,
-/// binds the server to a specific port,
-/// and starts serving incoming connections.
-pub async fn serve(app_state: Arc<RwLock<AppState>>) -> Result<()> {
+/// binds the server to the addresses of the config (`LISTEN`, or else
+/// its host and port), and starts serving incoming connections.
+pub async fn serve(app_state: Arc<AppState>) -> Result<()> {
+    let config = app_state.config.clone();
+
+    // The JSON logs include the fields of the request span
+    // (route, user id…) so they can be ingested as they are
+    let (pretty_layer, json_layer) = match config.log_format {
+        LogFormat::Pretty => (Some(tracing_subscriber::fmt::layer()), None),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(false),
+            ),
+        ),
+    };
+
+    // Errors are reported to Sentry when `SENTRY_DSN` is set
+    let report_layer = config
+        .sentry_dsn
+        .as_deref()
+        .map(reporting::layer)
+        .transpose()?;
+
+    let slow_service_layer = timing::layer(config.slow_query_threshold);
+
+    // The last errors are shown in the ops dashboard
+    let recent_errors_layer = app_state.recent_errors.clone();
+
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "rust_axum_askama_htmx=debug,tower_http=debug".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(pretty_layer)
+        .with(json_layer)
+        .with(report_layer)
+        .with(slow_service_layer)
+        .with(recent_errors_layer)
         .init();
 
     info!("initializing router…");
 
     // Create the router using the application state
-    let app = create_router(app_state);
+    let app = app(app_state);
+    let shutdown = Shutdown::listen();
+
+    // Every address is served by its own task
+    let mut servers = JoinSet::new();
+
+    // Serve over HTTPS when a certificate is configured
+    if let (Some(cert), Some(key)) = (&config.tls_cert, &config.tls_key) {
+        let tls_config = RustlsConfig::from_pem_file(cert, key)
+            .await
+            .context("Error: 🔥 unable to load the TLS certificate!")?;
+
+        if let Some(http_port) = config.http_redirect_port {
+            let http_address = SocketAddr::new(config.host, http_port);
+            let listener = tokio::net::TcpListener::bind(http_address)
+                .await
+                .with_context(|| format!("Error: 🔥 failed to bind to {}!", http_address))?;
+
+            // To the port of the first address
+            let https_port = config
+                .listen
+                .iter()
+                .find_map(|address| match address {
+                    ListenAddress::Tcp(address) => Some(address.port()),
+                    ListenAddress::Unix(_) => None,
+                })
+                .unwrap_or(config.port);
+
+            info!("redirecting http://{} to https", http_address);
+
+            tokio::spawn(redirect_to_https(listener, https_port));
+        }
+
+        for address in &config.listen {
+            // The config rejects Unix sockets along with TLS
+            let ListenAddress::Tcp(address) = *address else {
+                continue;
+            };
+
+            // In-flight requests can't take longer than the longest timeout
+            let handle = axum_server::Handle::new();
+            let grace_period = config.longest_request_timeout();
+            tokio::spawn({
+                let handle = handle.clone();
+                let shutdown = shutdown.clone();
+                async move {
+                    shutdown.wait().await;
+                    handle.graceful_shutdown(Some(grace_period));
+                }
+            });
+
+            info!(
+                "🚀 router initialized, now listening on https://{}",
+                address
+            );
+
+            let server = axum_server::bind_rustls(address, tls_config.clone())
+                .handle(handle)
+                .serve(
+                    app.clone()
+                        .into_make_service_with_connect_info::<SocketAddr>(),
+                );
+            servers.spawn(async move { Ok(server.await?) });
+        }
+    } else {
+        for address in &config.listen {
+            match address {
+                ListenAddress::Tcp(address) => {
+                    // Bind the server to the configured address and port
+                    let listener = tokio::net::TcpListener::bind(address)
+                        .await
+                        .with_context(|| format!("Error: 🔥 failed to bind to {}!", address))?;
+
+                    // The handlers get the address of the client
+                    let service = app
+                        .clone()
+                        .into_make_service_with_connect_info::<SocketAddr>();
+                    let server = axum::serve(listener, service)
+                        .with_graceful_shutdown(shutdown.clone().wait());
+                    servers.spawn(async move { Ok(server.await?) });
+                }
+                #[cfg(unix)]
+                ListenAddress::Unix(path) => {
+                    let listener = bind_unix(path, config.unix_socket_mode)?;
+
+                    servers.spawn(serve_unix(
+                        listener,
+                        path.clone(),
+                        app.clone(),
+                        shutdown.clone(),
+                        config.longest_request_timeout(),
+                    ));
+                }
+                #[cfg(not(unix))]
+                ListenAddress::Unix(_) => {
+                    anyhow::bail!("Unix sockets are only supported on Unix systems")
+                }
+            }
+
+            info!("🚀 router initialized, now listening on {}", address);
+        }
+    }
+
+    // Until every server has stopped, or one of them fails
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
+
+    Ok(())
+}
 
-    let port = 8082_u16;
+/// Completes once the server is shutting down, for each of its listeners.
+#[derive(Clone)]
+struct Shutdown(watch::Receiver<bool>);
 
-    // Bind the server to the specified address and port
-    let address = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
-        .await
-        .unwrap();
+impl Shutdown {
+    /// Starts listening for the shutdown signals.
+    fn listen() -> Self {
+        let (sender, receiver) = watch::channel(false);
 
-    info!("🚀 router initialized, now listening on port {}", port);
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            let _ = sender.send(true);
+        });
 
-    // Start serving incoming connections
-    axum::serve(address, app.into_make_service()).await?;
+        Self(receiver)
+    }
+
+    async fn wait(mut self) {
+        let _ = self.0.wait_for(|shutting_down| *shutting_down).await;
+    }
+}
+
+/// Completes on `Ctrl+C` or `SIGTERM`, to stop the server gracefully:
+/// it stops accepting connections and waits for the in-flight requests.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    info!("shutting down…");
+}
+
+/// Binds the Unix socket at `path`, replacing the one left behind by
+/// a previous run, and gives it the permissions of `mode`.
+#[cfg(unix)]
+fn bind_unix(path: &Path, mode: u32) -> Result<UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("Error: 🔥 failed to remove {}!", path.display()))?,
+        Ok(_) => anyhow::bail!(
+            "Error: 🔥 {} already exists and isn't a socket!",
+            path.display()
+        ),
+        Err(_) => {}
+    }
+
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Error: 🔥 failed to bind to unix:{}!", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Error: 🔥 failed to set the mode of {}!", path.display()))?;
+
+    Ok(listener)
+}
+
+/// Serves the connections of a Unix socket until the server shuts down,
+/// then lets the in-flight requests finish (for up to `grace_period`)
+/// and removes the socket file.
+#[cfg(unix)]
+async fn serve_unix(
+    listener: UnixListener,
+    path: PathBuf,
+    app: Router,
+    shutdown: Shutdown,
+    grace_period: Duration,
+) -> Result<()> {
+    let mut connections = JoinSet::new();
+    let mut shutting_down = pin!(shutdown.clone().wait());
+
+    loop {
+        let socket = tokio::select! {
+            result = listener.accept() => match result {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    warn!("failed to accept a connection on unix:{}: {}", path.display(), e);
+                    continue;
+                }
+            },
+            _ = &mut shutting_down => break,
+        };
+
+        let app = app.clone();
+        let shutdown = shutdown.clone();
+        connections.spawn(async move {
+            let service = service_fn(move |request: Request<Incoming>| app.clone().call(request));
+            let builder = auto::Builder::new(TokioExecutor::new());
+            let mut connection =
+                pin!(builder.serve_connection_with_upgrades(TokioIo::new(socket), service));
+            let mut shutting_down = pin!(shutdown.wait());
+            let mut closing = false;
+
+            // Closes the connection once its request is answered on shutdown
+            loop {
+                tokio::select! {
+                    result = connection.as_mut() => {
+                        if let Err(e) = result {
+                            debug!("unix socket connection failed: {}", e);
+                        }
+                        break;
+                    }
+                    _ = &mut shutting_down, if !closing => {
+                        connection.as_mut().graceful_shutdown();
+                        closing = true;
+                    }
+                }
+            }
+        });
+    }
+
+    // Stop accepting connections before waiting for the open ones
+    drop(listener);
+    if let Err(e) = std::fs::remove_file(&path) {
+        warn!("failed to remove unix:{}: {}", path.display(), e);
+    }
+
+    let open_connections = async { while connections.join_next().await.is_some() {} };
+    let _ = tokio::time::timeout(grace_period, open_connections).await;
 
     Ok(())
 }
 
+/// Redirects every request of the plain HTTP listener
+/// to the same path on the HTTPS port.
+async fn redirect_to_https(listener: tokio::net::TcpListener, https_port: u16) {
+    let redirect = move |Host(host): Host, uri: Uri| async move {
+        // Drop the port of the HTTP listener from the Host header
+        let host = host
+            .parse::<Authority>()
+            .map(|authority| authority.host().to_string())
+            .unwrap_or(host);
+        let path = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+
+        Redirect::permanent(&format!("https://{}:{}{}", host, https_port, path))
+    };
+
+    if let Err(e) = axum::serve(listener, Router::new().fallback(redirect)).await {
+        error!("http redirect server failed: {}", e);
+    }
+}
+
+/// Routes only built with the `dev` feature.
+fn dev_routes() -> Router<Arc<AppState>> {
+    let router = Router::new();
+
+    #[cfg(feature = "dev")]
+    let router = router.route("/dev/live-reload", get(crate::handler::live_reload_handler));
+
+    router
+}
+
+/// CORS policy of the `/api` routes, from the allowed origins
+/// and methods of the config (`*` allows any origin).
+fn cors_layer(config: &Config) -> CorsLayer {
+    let origins = &config.cors_allowed_origins;
+
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().map(|origin| origin.parse().unwrap()))
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(config.cors_allowed_methods.clone())
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            header::IF_NONE_MATCH,
+            HeaderName::from_static(WORKSPACE_HEADER),
+        ])
+        .expose_headers([
+            header::ETAG,
+            header::LINK,
+            HeaderName::from_static(TOTAL_COUNT_HEADER),
+        ])
+}
+
+/// Span of a request, the parent of every log it makes. `client_ip` is
+/// recorded by the client IP middleware, `user_id` once the user is known
+/// (see `record_user_id`), and `impersonator_id` (the admin using the app
+/// as the user) and `workspace_id` by the auth middleware, so the logs can
+/// be filtered per user when looking into a report.
+fn request_span(req: &Request) -> Span {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or_default();
+
+    info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        route,
+        request_id,
+        client_ip = Empty,
+        user_id = Empty,
+        impersonator_id = Empty,
+        workspace_id = Empty,
+    )
+}
+
 /// This function defines the API routes for the application.
 /// It takes the application state as input and sets up
 /// the routes for handling different HTTP methods and endpoints.
-fn create_router(app_state: Arc<RwLock<AppState>>) -> Router {
-    // Setup session store for flash messages & globals flags
-    let session_store = MemoryStore::default();
-    let session_layer = SessionManagerLayer::new(session_store).with_secure(false);
+pub fn app(app_state: Arc<AppState>) -> Router {
+    // Setup session store for flash messages & globals flags,
+    // kept where `SESSION_STORE` says
+    let session_layer = SessionManagerLayer::new(app_state.sessions.clone()).with_secure(false);
+
+    let request_timeout = app_state.config.request_timeout;
+
+    // Routes that create todos, limited per user (`TODO_CREATE_LIMIT`)
+    let creation_routes = Router::new()
+        .route("/create", post(todo_add_handler))
+        .route("/todo/quick-add", post(todo_quick_add_handler))
+        .route_layer(from_fn_with_state(
+            app_state.clone(),
+            todo_create_limit_middleware,
+        ));
+
+    // Lists answered with a 304 when the client already has them
+    let list_routes = Router::new()
+        .route("/todo/list", get(todo_list_handler))
+        .route("/todo/list/page", get(todo_list_page_handler))
+        .route_layer(middleware::from_fn(conditional_get_middleware));
+
+    // Routes slower than the others, each with its own timeout instead of
+    // `REQUEST_TIMEOUT` (counting the authentication, as that one does).
+    // Past `HEAVY_ROUTE_CONCURRENCY` requests running at once, the next
+    // ones are turned away rather than left to pile up
+    let timeouts = app_state.config.route_timeouts;
+    let concurrency = app_state.config.heavy_route_concurrency;
+    let heavy_routes = |routes: Router<Arc<AppState>>, timeout: Duration| {
+        routes
+            .route_layer(from_fn_with_state(app_state.clone(), auth_middleware))
+            .route_layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(handle_timeout_error))
+                    .layer(LoadShedLayer::new())
+                    .layer(ConcurrencyLimitLayer::new(concurrency))
+                    .layer(TimeoutLayer::new(timeout)),
+            )
+    };
+    let export_routes = heavy_routes(
+        Router::new().route("/todo/export.md", get(todo_export_handler)),
+        timeouts.export,
+    );
+    let search_routes = heavy_routes(
+        Router::new().route("/todo/search-live", get(todo_search_live_handler)),
+        timeouts.search,
+    );
+    let import_routes = heavy_routes(
+        Router::new()
+            .route(
+                "/settings/import",
+                get(import_page_handler).post(import_confirm_handler),
+            )
+            // The form has little more than the file
+            .route(
+                "/settings/import/preview",
+                post(import_preview_handler).layer(DefaultBodyLimit::max(
+                    app_state.config.max_upload_size + UPLOAD_FORM_OVERHEAD,
+                )),
+            ),
+        timeouts.import,
+    );
+
+    // Routes that require a logged-in user. The auth middleware is
+    // applied to the whole group, so no route can be left unprotected
+    let protected_routes = Router::new()
+        .route("/logout", post(logout_handler))
+        .route("/create", get(todo_create_handler))
+        .route(
+            "/todo/dependencies",
+            post(todo_dependency_add_handler).delete(todo_dependency_remove_handler),
+        )
+        .route("/todo/timer/start", post(todo_timer_start_handler))
+        .route("/todo/timer/stop", post(todo_timer_stop_handler))
+        .route("/todo/stats", get(todo_stats_handler))
+        .route("/tags/suggest", get(tag_suggest_handler))
+        .route(
+            "/todo/bulk",
+            get(todo_bulk_edit_handler).post(todo_bulk_handler),
+        )
+        .route("/todo/:id", patch(todo_patch_handler))
+        .route("/todo/:id/edit", get(todo_edit_handler))
+        .route("/todo/:id/toggle", patch(todo_toggle_handler))
+        .route("/todo/:id/delete", delete(todo_delete_handler))
+        .route(
+            "/todo/links",
+            post(link_add_handler).delete(link_delete_handler),
+        )
+        .route(
+            "/todo/subtasks",
+            post(subtask_add_handler)
+                .patch(subtask_toggle_handler)
+                .delete(subtask_delete_handler),
+        )
+        .route("/todo/revert", post(todo_revert_handler))
+        // The URLs of the todos used to take their id in the query string
+        .route(
+            "/edit",
+            get(legacy_edit_redirect_handler).patch(legacy_edit_redirect_handler),
+        )
+        .route("/delete", delete(legacy_delete_redirect_handler))
+        .route(
+            "/settings/profile",
+            get(profile_page_handler).post(profile_update_handler),
+        )
+        .route("/settings/workspace", get(workspace_page_handler))
+        .route("/admin/audit", get(audit_log_handler))
+        .route("/admin/ops", get(ops_handler))
+        .route("/admin/backup", post(backup_handler))
+        .route("/admin/impersonate", post(impersonate_handler))
+        .route("/admin/impersonate/stop", post(impersonate_stop_handler))
+        .route("/workspaces", post(workspace_create_handler))
+        .route("/workspaces/switch", post(workspace_switch_handler))
+        .route(
+            "/workspaces/members",
+            post(workspace_member_add_handler).delete(workspace_member_remove_handler),
+        )
+        .route("/notifications", get(notifications_page_handler))
+        .route(
+            "/notifications/read-all",
+            post(notifications_read_all_handler),
+        )
+        .route("/notifications/:id/read", post(notification_read_handler))
+        .route("/feed", get(feed_link_handler))
+        // Sent by the Slack command to the users it doesn't know yet
+        .route(
+            "/integrations/slack/link/:team_id/:user_id",
+            get(slack_link_handler)
+                .route_layer(from_fn_with_state(app_state.clone(), signed_url_middleware)),
+        )
+        .route(
+            "/filters",
+            post(filter_save_handler).delete(filter_delete_handler),
+        )
+        .route("/ws", get(ws_handler))
+        .merge(creation_routes)
+        .merge(list_routes)
+        .route_layer(from_fn_with_state(app_state.clone(), auth_middleware));
 
-    // Get the current directory for serving assets
-    let assets_path = std::env::current_dir().unwrap();
+    // Logins, limited per client address (`LOGIN_ATTEMPT_LIMIT`)
+    let login_routes = Router::new()
+        .route("/login", post(login_user_handler))
+        .route_layer(from_fn_with_state(
+            app_state.clone(),
+            login_limit_middleware,
+        ));
+
+    // CalDAV endpoint, where the clients log in with the email
+    // and password of the user on every request
+    let dav_routes = Router::new()
+        .route("/dav", any(dav_home_handler))
+        .route("/dav/", any(dav_home_handler))
+        .route("/dav/:workspace_id", any(dav_calendar_handler))
+        .route("/dav/:workspace_id/", any(dav_calendar_handler))
+        .route("/dav/:workspace_id/:file_name", any(dav_todo_handler))
+        .route_layer(from_fn_with_state(app_state.clone(), dav_auth_middleware));
+
+    // Routes meant for other clients than the pages of the app,
+    // the only ones that can be called from other origins
+    let api_routes = Router::new()
+        // The changes made offline, sent by the service worker
+        .route(
+            "/api/v1/sync",
+            post(sync_handler).route_layer(from_fn_with_state(app_state.clone(), auth_middleware)),
+        )
+        // The todos a page at a time, with the links of the others,
+        // answered with a 304 when the client already has the page
+        .route(
+            "/api/v1/todos",
+            get(todo_api_list_handler)
+                .route_layer(from_fn_with_state(app_state.clone(), auth_middleware))
+                .route_layer(middleware::from_fn(conditional_get_middleware)),
+        )
+        // Up to 100 changes at once, applied together or not at all
+        .route(
+            "/api/v1/todos/batch",
+            post(todo_batch_handler)
+                .route_layer(from_fn_with_state(app_state.clone(), auth_middleware)),
+        )
+        .merge(SwaggerUi::new("/api/docs").url("/api/openapi.json", ApiDoc::openapi()))
+        .layer(cors_layer(&app_state.config));
+
+    let client_ip_layer = from_fn_with_state(app_state.clone(), client_ip_middleware);
 
     // General router of our application
-    Router::new()
+    let router = Router::new()
         .route("/", get(home_handler))
         .route(
             "/register",
             get(register_page_handler).post(register_user_handler),
         )
-        .route("/login", get(login_page_handler).post(login_user_handler))
+        .route("/login", get(login_page_handler))
+        .merge(login_routes)
+        .route("/verify-email", get(verify_email_handler))
         .route(
-            "/todo/list",
-            get(todo_list_handler)
-                .route_layer(from_fn_with_state(app_state.clone(), auth_middleware)),
+            "/forgot-password",
+            get(forgot_password_page_handler).post(forgot_password_handler),
         )
         .route(
-            "/logout",
-            post(logout_handler)
-                .route_layer(from_fn_with_state(app_state.clone(), auth_middleware)),
+            "/reset-password",
+            get(reset_password_page_handler).post(reset_password_handler),
         )
+        .route("/theme", post(theme_handler))
+        // Polled by the navbar, it stops the polling once logged out
+        .route("/notifications/bell", get(notification_bell_handler))
+        // The secret token in the signed URL authenticates feed readers
         .route(
-            "/create",
-            get(todo_create_handler)
-                .post(todo_add_handler)
-                .route_layer(from_fn_with_state(app_state.clone(), auth_middleware)),
+            "/feed/:file_name",
+            get(feed_handler)
+                .route_layer(from_fn_with_state(app_state.clone(), signed_url_middleware)),
         )
+        .merge(protected_routes)
+        // Authenticated by the signature of Slack
         .route(
-            "/edit",
-            get(todo_edit_handler)
-                .patch(todo_patch_handler)
-                .route_layer(from_fn_with_state(app_state.clone(), auth_middleware)),
+            "/integrations/slack/command",
+            post(slack_command_handler).route_layer(from_fn_with_state(
+                app_state.clone(),
+                slack_signature_middleware,
+            )),
+        )
+        .merge(dav_routes)
+        // Where the CalDAV clients look for the endpoint (RFC 6764)
+        .route(
+            "/.well-known/caldav",
+            any(|| async { Redirect::permanent("/dav/") }),
         )
-        .route("/delete", delete(todo_delete_handler))
         .route("/healthchecker", get(health_checker_handler))
-        .nest_service(
-            "/assets",
-            ServeDir::new(format!("{}/assets", assets_path.to_str().unwrap())), // Serve static assets
+        // Scraped by Prometheus, with the `METRICS_TOKEN` if set
+        .route("/metrics", get(metrics_handler))
+        .merge(api_routes)
+        .merge(dev_routes())
+        // Serve static assets
+        .nest_service("/assets", assets::service())
+        .route("/sw.js", get(assets::service_worker))
+        .fallback(handler_404) // Add a Fallback service for handling unknown paths
+        // Applied to the routes above only, the heavy ones have their own
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_timeout_error))
+                .layer(TimeoutLayer::new(request_timeout)),
         )
+        .merge(export_routes)
+        .merge(search_routes)
+        .merge(import_routes)
         .with_state(app_state)
-        .fallback(handler_404) // Add a Fallback service for handling unknown paths
+        .layer(CatchPanicLayer::custom(handle_panic))
+        .layer(middleware::from_fn(method_not_allowed_middleware))
+        .layer(middleware::from_fn(htmx_error_middleware))
+        .layer(middleware::from_fn(theme_middleware))
+        .layer(middleware::from_fn(security_headers_middleware))
         .layer(MessagesManagerLayer)
         .layer(session_layer)
-        .layer(TraceLayer::new_for_http())
+        .layer(middleware::from_fn(request_id_middleware))
+        .layer(client_ip_layer)
+        .layer(TraceLayer::new_for_http().make_span_with(request_span))
+        // Every request gets an id (unless it already has one),
+        // which is also returned in the `x-request-id` header
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            REQUEST_ID_HEADER,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(REQUEST_ID_HEADER),
+            MakeRequestUuid,
+        ));
+
+    // The method of the forms sent without JavaScript
+    // is overridden before the routes are matched
+    Router::new().fallback_service(
+        ServiceBuilder::new()
+            .layer(middleware::from_fn(method_override_middleware))
+            .service(router),
+    )
 }
@@ -0,0 +1,36 @@
+/// Strict policy for the plain text entered by users (titles, descriptions):
+/// HTML tags and comments are removed and so are the control characters
+/// other than new lines and tabs. The templates escape everything they
+/// render, this makes sure no markup is stored in the first place.
+///
+/// A `<` that doesn't start a tag (as in `1 < 2`) is kept.
+pub fn plain_text(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '<' if chars.peek().is_some_and(|next| {
+                next.is_ascii_alphabetic() || matches!(next, '/' | '!' | '?')
+            }) =>
+            {
+                // Skip the whole tag, or the rest of the text if it isn't closed
+                for c in chars.by_ref() {
+                    if c == '>' {
+                        break;
+                    }
+                }
+            }
+            '\n' | '\t' => output.push(c),
+            c if c.is_control() => {}
+            c => output.push(c),
+        }
+    }
+
+    output.trim().to_string()
+}
+
+/// Cuts a text to at most `max` characters.
+pub fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
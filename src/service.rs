@@ -1,46 +1,75 @@
-use std::result::Result::Ok;
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    result::Result::Ok,
+};
 
 use anyhow::{anyhow, bail, Result};
 use argon2::{
     password_hash::{rand_core::OsRng, SaltString},
     Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
 };
-use sqlx::{query, query_as, query_scalar, SqlitePool};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sqlx::{query, query_as, query_scalar, QueryBuilder, Transaction};
+use tracing::instrument;
 use uuid::Uuid;
 
-use crate::model::{Todo, User};
+use crate::{
+    db::{Db, DbPool},
+    events::{DomainEvent, EventBus},
+    import::ImportedTodo,
+    model::{
+        AuditEntry, AuditEvent, AuditFilter, BatchOperation, BulkEditSchema, ChecklistProgress,
+        DavResource, DueReminder, JobStatus, Notification, NotificationKind, OpsCounts, Page,
+        PageStart, SavedFilter, Subtask, TagSuggestion, Theme, Todo, TodoCursor, TodoFilter,
+        TodoLink, TodoStats, TodoVersion, TokenKind, TrackedTime, User, UserSettings, Workspace,
+        WorkspaceMember,
+    },
+    sanitize::plain_text,
+};
+
+/// Case-insensitive `LIKE` operator of the database.
+#[cfg(feature = "sqlite")]
+const LIKE: &str = "LIKE";
+#[cfg(feature = "postgres")]
+const LIKE: &str = "ILIKE";
+
+/// Least similarity of a title to the one of an open todo for
+/// `find_similar_todo` to take them for the same task.
+pub const SIMILAR_TITLE_THRESHOLD: f64 = 0.6;
+
+/// Runs a trivial query to check that the database is reachable.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn ping_database(pool: &DbPool) -> Result<()> {
+    query("SELECT 1")
+        .execute(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
 
+#[instrument(skip_all, fields(db = "write"))]
 pub async fn create_user(
     email: String,
     password: String,
     username: String,
-    pool: &SqlitePool,
+    events: &EventBus,
+    pool: &DbPool,
 ) -> Result<User> {
-    // Check if the email is already in use
-    let user_exists: Option<bool> =
-        query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)")
-            .bind(email.to_ascii_lowercase())
-            .fetch_one(pool)
-            .await?;
-
-    if let Some(exists) = user_exists {
-        if exists {
-            // Err(anyhow!("the email is already in use."))?;
-            bail!("the email is already in use.");
-            // Equivalent to:
-            // return Err(anyhow!("the email is already in use."));
-            // ↓↓ SEE NOTE-01 BELOW ↓↓
-        }
-    }
-
-    let salt = SaltString::generate(&mut OsRng);
-    let hashed_password = Argon2::default()
-        .hash_password(password.as_bytes(), &salt)
-        .map_err(|e| anyhow!("failed to hash password: {}", e))
-        .map(|hash| hash.to_string())?;
+    let email = email.to_ascii_lowercase();
+    let hashed_password = hash_password(&password)?;
 
     let uuid = Uuid::new_v4().to_string();
 
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    // The unique index of the emails rejects the ones already in use,
+    // even when two registrations with the same email run at once
     let user = query_as!(
         User,
         "INSERT INTO users (id,email,password,username) VALUES ($1, $2, $3, $4) RETURNING *",
@@ -49,18 +78,144 @@ pub async fn create_user(
         hashed_password,
         username
     )
-    .fetch_one(pool)
+    .fetch_one(&mut *tx)
     .await
-    .map_err(|e| anyhow!("database error: {}", e))?;
+    .map_err(|e| match e {
+        sqlx::Error::Database(e) if e.is_unique_violation() => {
+            anyhow!("the email is already in use.")
+        }
+        e => anyhow!("database error: {}", e),
+    })?;
+
+    // Every user starts with a workspace of their own
+    let name = format!("{}'s workspace", user.username);
+    insert_workspace(&name, &user.id, &mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    events.publish(DomainEvent::UserRegistered {
+        user_id: user.id.clone(),
+        email: user.email.clone(),
+        username: user.username.clone(),
+    });
 
     Ok(user)
 }
 
-pub async fn check_email_password(
-    email: String,
-    password: String,
-    pool: &SqlitePool,
-) -> Result<User> {
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow!("failed to hash password: {}", e))
+        .map(|hash| hash.to_string())
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn set_user_password(user_id: &str, password: &str, pool: &DbPool) -> Result<()> {
+    let hashed_password = hash_password(password)?;
+
+    query!(
+        "UPDATE users SET password = $1 WHERE id = $2",
+        hashed_password,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn set_email_verified(user_id: &str, now: NaiveDateTime, pool: &DbPool) -> Result<()> {
+    query!(
+        "UPDATE users SET email_verified_at = $1 WHERE id = $2 AND email_verified_at IS NULL",
+        now,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Creates a random single-use token of the user, for the links sent by email.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn create_user_token(
+    user_id: &str,
+    kind: TokenKind,
+    now: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<String> {
+    let token: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(43)
+        .map(char::from)
+        .collect();
+    let kind_name = kind.name();
+    let expires_at = now + kind.lifetime();
+
+    query!(
+        "INSERT INTO user_tokens (token,user_id,kind,expires_at) VALUES ($1, $2, $3, $4)",
+        token,
+        user_id,
+        kind_name,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(token)
+}
+
+/// Marks a token as used, returning its user if it was valid
+/// (of that kind, not used yet and not expired).
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn use_user_token(
+    token: &str,
+    kind: TokenKind,
+    now: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<Option<String>> {
+    let kind_name = kind.name();
+
+    query_scalar!(
+        "UPDATE user_tokens SET used_at = $1
+        WHERE token = $2 AND kind = $3 AND used_at IS NULL AND expires_at > $4
+        RETURNING user_id",
+        now,
+        token,
+        kind_name,
+        now
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))
+}
+
+/// Deletes the tokens that can't be used anymore (used or expired),
+/// returning how many were deleted.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn delete_stale_user_tokens(now: NaiveDateTime, pool: &DbPool) -> Result<u64> {
+    let rows_affected = query!(
+        "DELETE FROM user_tokens WHERE used_at IS NOT NULL OR expires_at <= $1",
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    Ok(rows_affected)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn check_email_password(email: String, password: String, pool: &DbPool) -> Result<User> {
     let email = email.to_ascii_lowercase();
     let user = query_as!(User, "SELECT * FROM users WHERE email = $1", email)
         .fetch_optional(pool)
@@ -82,93 +237,2536 @@ pub async fn check_email_password(
     Ok(user)
 }
 
-pub async fn get_user_by_id(user_id: &str, pool: &SqlitePool) -> Result<Option<User>, String> {
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_user_by_id(user_id: &str, pool: &DbPool) -> Result<Option<User>, String> {
     query_as!(User, "SELECT * FROM users WHERE id = $1", user_id)
         .fetch_optional(pool)
         .await
         .map_err(|e| format!("error fetching user from database: {}", e))
 }
 
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_user_by_email(email: &str, pool: &DbPool) -> Result<Option<User>> {
+    let email = email.to_ascii_lowercase();
+    let user = query_as!(User, "SELECT * FROM users WHERE email = $1", email)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(user)
+}
+
+/// All the users, by email.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_all_users(pool: &DbPool) -> Result<Vec<User>> {
+    let users = query_as!(User, "SELECT * FROM users ORDER BY email")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(users)
+}
+
+/// The settings of the user, the default ones if they never saved any.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_user_settings(user_id: &str, pool: &DbPool) -> Result<UserSettings> {
+    let settings = query_as!(
+        UserSettings,
+        "SELECT * FROM user_settings WHERE user_id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(settings.unwrap_or_else(|| UserSettings::new(user_id)))
+}
+
+/// Saves all the settings of the user.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn save_user_settings(settings: &UserSettings, pool: &DbPool) -> Result<()> {
+    query!(
+        "INSERT INTO user_settings
+        (user_id, theme, timezone, date_order, clock, page_size, default_sort, digest, density)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (user_id) DO UPDATE SET theme = excluded.theme,
+        timezone = excluded.timezone, date_order = excluded.date_order, clock = excluded.clock,
+        page_size = excluded.page_size, default_sort = excluded.default_sort,
+        digest = excluded.digest, density = excluded.density",
+        settings.user_id,
+        settings.theme,
+        settings.timezone,
+        settings.date_order,
+        settings.clock,
+        settings.page_size,
+        settings.default_sort,
+        settings.digest,
+        settings.density
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Saves the theme of the user, leaving the other settings as they are.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn set_user_theme(user_id: &str, theme: Theme, pool: &DbPool) -> Result<()> {
+    let name = theme.name();
+
+    query!(
+        "INSERT INTO user_settings (user_id, theme) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET theme = excluded.theme",
+        user_id,
+        name
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Creates a workspace owned by the user, returning its id.
+async fn insert_workspace(name: &str, user_id: &str, tx: &mut Transaction<'_, Db>) -> Result<i64> {
+    let workspace_id = query_scalar!(
+        r#"INSERT INTO workspaces (name,created_by) VALUES ($1, $2) RETURNING id AS "id!: i64""#,
+        name,
+        user_id
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    query!(
+        "INSERT INTO workspace_members (workspace_id,user_id,role) VALUES ($1, $2, 'owner')",
+        workspace_id,
+        user_id
+    )
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(workspace_id)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn create_workspace(name: String, user_id: String, pool: &DbPool) -> Result<Workspace> {
+    let name = plain_text(&name);
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let id = insert_workspace(&name, &user_id, &mut tx).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(Workspace {
+        id,
+        name,
+        role: "owner".to_string(),
+    })
+}
+
+/// Workspaces the user is a member of, the ones they own first.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_user_workspaces(user_id: &str, pool: &DbPool) -> Result<Vec<Workspace>> {
+    let workspaces = query_as!(
+        Workspace,
+        r#"SELECT workspaces.id AS "id!", workspaces.name, workspace_members.role
+        FROM workspaces
+        JOIN workspace_members ON workspace_members.workspace_id = workspaces.id
+        WHERE workspace_members.user_id = $1
+        ORDER BY CASE workspace_members.role WHEN 'owner' THEN 0 ELSE 1 END, workspaces.id"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(workspaces)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_workspace_members(
+    workspace_id: i64,
+    pool: &DbPool,
+) -> Result<Vec<WorkspaceMember>> {
+    let members = query_as!(
+        WorkspaceMember,
+        "SELECT users.id AS user_id, users.username, users.email, workspace_members.role
+        FROM workspace_members
+        JOIN users ON users.id = workspace_members.user_id
+        WHERE workspace_members.workspace_id = $1
+        ORDER BY workspace_members.created_at, users.username",
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(members)
+}
+
+/// Adds the user with that email to the workspace.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_workspace_member(
+    workspace_id: i64,
+    email: String,
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<()> {
+    let user = get_user_by_email(&email, pool)
+        .await?
+        .ok_or_else(|| anyhow!("there is no account with that email."))?;
+
+    let rows_affected = query!(
+        "INSERT INTO workspace_members (workspace_id,user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        workspace_id,
+        user.id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!("{} is already a member of the workspace.", user.email);
+    }
+
+    events.publish(DomainEvent::MemberAdded {
+        workspace_id,
+        user_id: user.id,
+    });
+
+    Ok(())
+}
+
+/// Removes a member of the workspace. Owners can't be removed.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn remove_workspace_member(
+    workspace_id: i64,
+    user_id: String,
+    pool: &DbPool,
+) -> Result<()> {
+    let rows_affected = query!(
+        "DELETE FROM workspace_members WHERE workspace_id = $1 AND user_id = $2 AND role != 'owner'",
+        workspace_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!("Member with ID: {} not found", user_id);
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(db = "write"))]
 pub async fn add_todo(
     created_by: String,
+    workspace_id: i64,
     title: String,
     description: String,
-    pool: &SqlitePool,
+    due_at: Option<NaiveDateTime>,
+    priority: i64,
+    tags: String,
+    events: &EventBus,
+    pool: &DbPool,
 ) -> Result<Todo> {
+    let title = plain_text(&title);
+    let description = plain_text(&description);
+
     let todo = query_as!(
         Todo,
-        "INSERT INTO todos (created_by,title,description) VALUES($1, $2, $3) RETURNING *",
+        "INSERT INTO todos (created_by,workspace_id,title,description,due_at,priority,tags) VALUES($1, $2, $3, $4, $5, $6, $7) RETURNING *",
         created_by,
+        workspace_id,
         title,
         description,
+        due_at,
+        priority,
+        tags,
     )
     .fetch_one(pool)
     .await
     .map_err(|e| anyhow!("database error: {}", e))?;
 
+    events.publish(DomainEvent::TodoCreated { todo: todo.clone() });
+
     Ok(todo)
 }
 
-pub async fn get_all_todos(created_by: String, pool: &SqlitePool) -> Result<Vec<Todo>> {
-    let todos = query_as!(
-        Todo,
-        "SELECT * FROM todos WHERE created_by = ? ORDER BY created_at DESC",
-        created_by
-    )
-    .fetch_all(pool)
-    .await
-    .map_err(|e| anyhow!("database error: {}", e))?;
+/// Creates all the imported todos in a single transaction,
+/// so either every todo is created or none is.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_imported_todos(
+    created_by: String,
+    workspace_id: i64,
+    todos: Vec<ImportedTodo>,
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<usize> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
 
-    Ok(todos)
-}
+    let count = todos.len();
 
-pub async fn get_todo_by_id(todo_id: i64, pool: &SqlitePool) -> Result<Todo> {
-    let todo = query_as!(Todo, "SELECT * FROM todos WHERE id = $1", todo_id)
-        .fetch_optional(pool)
+    for todo in todos {
+        let title = plain_text(&todo.title);
+        let description = plain_text(&todo.description);
+
+        query!(
+            "INSERT INTO todos (created_by,workspace_id,title,description,status,due_at,priority,tags,completed_at)
+            VALUES($1, $2, $3, $4, $5, $6, $7, $8, CASE WHEN $5 THEN CURRENT_TIMESTAMP END)",
+            created_by,
+            workspace_id,
+            title,
+            description,
+            todo.status,
+            todo.due_at,
+            todo.priority,
+            todo.tags,
+        )
+        .execute(&mut *tx)
         .await
-        .map_err(|e| anyhow!("database error: {}.", e))?
-        .ok_or_else(|| anyhow!("todo does not exist in the database."))?;
+        .map_err(|e| anyhow!("database error: {}", e))?;
+    }
 
-    Ok(todo)
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    events.publish(DomainEvent::TodosImported {
+        user_id: created_by,
+        count,
+    });
+
+    Ok(count)
 }
 
-pub async fn remove_todo(todo_id: i64, pool: &SqlitePool) -> Result<()> {
-    let rows_affected = query!("DELETE FROM todos WHERE id = $1", todo_id)
-        .execute(pool)
+/// A page of `limit` todos of the workspace in the `sort` of `TodoFilter`
+/// (newest first by default), starting at `start`. The plain todo list
+/// and the JSON API page through it.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_all_todos(
+    workspace_id: i64,
+    sort: &str,
+    start: PageStart,
+    limit: i64,
+    pool: &DbPool,
+) -> Result<Page<Todo>> {
+    let mut builder = QueryBuilder::<Db>::new("SELECT * FROM todos WHERE workspace_id = ");
+    builder.push_bind(workspace_id);
+
+    // Read up the list from the cursor, then put back in order
+    let backwards = matches!(start, PageStart::Before(_));
+    if let PageStart::After(cursor) | PageStart::Before(cursor) = start {
+        push_past_cursor(&mut builder, sort, cursor, backwards);
+    }
+
+    builder.push(" ORDER BY ");
+    let mut order = builder.separated(", ");
+    for (column, descending) in sort_columns(sort) {
+        let direction = if *descending != backwards {
+            "DESC"
+        } else {
+            "ASC"
+        };
+        order.push(format_args!("{} {}", column, direction));
+    }
+
+    // One more than the page, to know whether there is another one
+    let mut todos = builder
+        .push(" LIMIT ")
+        .push_bind(limit + 1)
+        .build_query_as::<Todo>()
+        .fetch_all(pool)
         .await
-        .unwrap()
-        .rows_affected();
+        .map_err(|e| anyhow!("database error: {}", e))?;
 
-    if rows_affected == 0 {
-        bail!(format!("Todo with ID: {} not found", todo_id));
+    let more = todos.len() as i64 > limit;
+    todos.truncate(limit as usize);
+    if backwards {
+        todos.reverse();
     }
 
-    Ok(())
+    let (has_next, has_prev) = match start {
+        PageStart::First => (more, false),
+        PageStart::After(_) => (more, true),
+        PageStart::Before(_) => (true, more),
+    };
+    let next_cursor = todos
+        .last()
+        .filter(|_| has_next)
+        .map(|todo| TodoCursor::after(todo, sort).to_string());
+    let prev_cursor = todos
+        .first()
+        .filter(|_| has_prev)
+        .map(|todo| TodoCursor::after(todo, sort).to_string());
+
+    Ok(Page {
+        items: todos,
+        next_cursor,
+        prev_cursor,
+    })
 }
 
-pub async fn update_todo(
-    title: String,
-    description: String,
-    status: bool,
-    todo_id: i64,
-    pool: &SqlitePool,
-) -> Result<()> {
-    let rows_affected = query!(
-        "UPDATE todos SET title = $1, description = $2, status = $3 WHERE id = $4",
-        title,
-        description,
-        status,
-        todo_id
+/// Columns the todo list is sorted by (see `TodoFilter::sort`), and
+/// whether they are descending. The id tells apart the todos created
+/// at the same time.
+fn sort_columns(sort: &str) -> &'static [(&'static str, bool)] {
+    match sort {
+        "oldest" => &[("created_at", false), ("id", false)],
+        "due" => &[
+            ("(due_at IS NULL)", false),
+            ("due_at", false),
+            ("created_at", true),
+            ("id", true),
+        ],
+        "priority" => &[("priority", true), ("created_at", true), ("id", true)],
+        _ => &[("created_at", true), ("id", true)],
+    }
+}
+
+/// Value of one of the `sort_columns` at a cursor.
+enum SortValue {
+    Flag(bool),
+    Time(NaiveDateTime),
+    Number(i64),
+}
+
+/// Adds the condition of the todos that come after `cursor` in the list
+/// sorted by `sort` (before it when going `backwards`), comparing the
+/// `sort_columns` in turn.
+fn push_past_cursor(
+    builder: &mut QueryBuilder<'_, Db>,
+    sort: &str,
+    cursor: TodoCursor,
+    backwards: bool,
+) {
+    let due_at = cursor
+        .rank
+        .and_then(DateTime::from_timestamp_micros)
+        .map(|due_at| due_at.naive_utc());
+    let values = match sort {
+        // Without a due date the cursor is among the last todos,
+        // whose `due_at` is the same, so it isn't compared
+        "due" => vec![
+            Some(SortValue::Flag(due_at.is_none())),
+            due_at.map(SortValue::Time),
+        ],
+        "priority" => vec![Some(SortValue::Number(cursor.rank.unwrap_or_default()))],
+        _ => Vec::new(),
+    };
+    let keys: Vec<(&str, bool, SortValue)> = sort_columns(sort)
+        .iter()
+        .zip(values.into_iter().chain([
+            Some(SortValue::Time(cursor.created_at)),
+            Some(SortValue::Number(cursor.id)),
+        ]))
+        .filter_map(|(&(column, descending), value)| Some((column, descending, value?)))
+        .collect();
+
+    // (a past the cursor) OR (a equal AND b past it) OR …
+    builder.push(" AND (");
+    for (index, (column, descending, value)) in keys.iter().enumerate() {
+        if index > 0 {
+            builder.push(" OR ");
+        }
+        builder.push("(");
+        for (column, _, value) in &keys[..index] {
+            builder.push(format_args!("{} = ", column));
+            push_sort_value(builder, value);
+            builder.push(" AND ");
+        }
+        let past = if *descending != backwards { "<" } else { ">" };
+        builder.push(format_args!("{} {} ", column, past));
+        push_sort_value(builder, value);
+        builder.push(")");
+    }
+    builder.push(")");
+}
+
+fn push_sort_value(builder: &mut QueryBuilder<'_, Db>, value: &SortValue) {
+    match *value {
+        SortValue::Flag(flag) => builder.push_bind(flag),
+        SortValue::Time(time) => builder.push_bind(time),
+        SortValue::Number(number) => builder.push_bind(number),
+    };
+}
+
+/// Number of todos of the workspace.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn count_todos(workspace_id: i64, pool: &DbPool) -> Result<i64> {
+    let count = query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM todos WHERE workspace_id = $1"#,
+        workspace_id
     )
-    .execute(pool)
+    .fetch_one(pool)
     .await
-    .unwrap()
-    .rows_affected();
+    .map_err(|e| anyhow!("database error: {}", e))?;
 
-    if rows_affected == 0 {
-        bail!(format!("Todo with ID: {} not found", todo_id));
+    Ok(count)
+}
+
+/// Deletes the todos whose workspace no longer exists (the database
+/// doesn't check it, see the `workspaces` migration), along with all
+/// their rows. Returns how many were deleted.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn purge_orphaned_todos(pool: &DbPool) -> Result<u64> {
+    let rows_affected =
+        query!("DELETE FROM todos WHERE workspace_id NOT IN (SELECT id FROM workspaces)")
+            .execute(pool)
+            .await
+            .map_err(|e| anyhow!("database error: {}", e))?
+            .rows_affected();
+
+    Ok(rows_affected)
+}
+
+/// Ids and titles of all the todos of the workspace.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_todo_titles(workspace_id: i64, pool: &DbPool) -> Result<Vec<(i64, String)>> {
+    let titles = query!(
+        r#"SELECT id AS "id!", title FROM todos WHERE workspace_id = $1"#,
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .into_iter()
+    .map(|row| (row.id, row.title))
+    .collect();
+
+    Ok(titles)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_filtered_todos(
+    workspace_id: i64,
+    filter: &TodoFilter,
+    pool: &DbPool,
+) -> Result<Vec<Todo>> {
+    let mut builder = QueryBuilder::<Db>::new("SELECT * FROM todos WHERE workspace_id = ");
+    builder.push_bind(workspace_id);
+
+    let q = filter.q.trim();
+    if !q.is_empty() {
+        let pattern = format!("%{}%", escape_like(q));
+        builder
+            .push(format_args!(" AND (title {} ", LIKE))
+            .push_bind(pattern.clone())
+            .push(format_args!(" ESCAPE '\\' OR description {} ", LIKE))
+            .push_bind(pattern)
+            .push(" ESCAPE '\\')");
     }
 
+    let tag = filter.tag.trim().trim_start_matches('#').to_lowercase();
+    if !tag.is_empty() {
+        builder
+            .push(" AND (' ' || tags || ' ') LIKE ")
+            .push_bind(format!("% {} %", escape_like(&tag)))
+            .push(" ESCAPE '\\'");
+    }
+
+    match filter.status.as_str() {
+        "open" => {
+            builder.push(" AND status = FALSE");
+        }
+        "done" => {
+            builder.push(" AND status = TRUE");
+        }
+        _ => {}
+    }
+
+    builder.push(match filter.sort.as_str() {
+        "oldest" => " ORDER BY created_at ASC",
+        "due" => " ORDER BY due_at IS NULL, due_at ASC, created_at DESC",
+        "priority" => " ORDER BY priority DESC, created_at DESC",
+        _ => " ORDER BY created_at DESC",
+    });
+
+    let todos = builder
+        .build_query_as::<Todo>()
+        .fetch_all(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(todos)
+}
+
+/// Escapes the wildcards of `LIKE` (with `ESCAPE '\'`), which
+/// can be part of what is searched, e.g. a tag.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Words of a search, split like the full-text index splits the todos.
+pub fn search_terms(q: &str) -> Vec<String> {
+    q.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Similarity of two titles from 0 to 1, as the share of trigrams
+/// they have in common (like `pg_trgm`), ignoring case and punctuation.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    fn trigrams(title: &str) -> HashSet<[char; 3]> {
+        search_terms(title)
+            .iter()
+            .flat_map(|word| {
+                let padded: Vec<char> = format!("  {word} ").chars().collect();
+                padded
+                    .windows(3)
+                    .map(|w| [w[0], w[1], w[2]])
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// The open todo of the workspace whose title is the most similar to
+/// `title`, if any is at least `SIMILAR_TITLE_THRESHOLD` similar.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn find_similar_todo(
+    workspace_id: i64,
+    title: &str,
+    pool: &DbPool,
+) -> Result<Option<(i64, String)>> {
+    let open = query!(
+        r#"SELECT id AS "id!", title FROM todos WHERE workspace_id = $1 AND status = FALSE"#,
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let similar = open
+        .into_iter()
+        .map(|row| (title_similarity(title, &row.title), row.id, row.title))
+        .filter(|(similarity, ..)| *similarity >= SIMILAR_TITLE_THRESHOLD)
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, id, title)| (id, title));
+
+    Ok(similar)
+}
+
+/// The first `limit` todos of the workspace with a word starting with
+/// each of the terms, the best matches first. It goes through the
+/// full-text index, unlike the `q` of the filters.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn search_todos(
+    workspace_id: i64,
+    terms: &[String],
+    limit: i64,
+    pool: &DbPool,
+) -> Result<Vec<Todo>> {
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Quoted, so that no term is read as an operator (`AND`, `NEAR`…)
+    #[cfg(feature = "sqlite")]
+    let mut builder = {
+        let query: Vec<_> = terms.iter().map(|term| format!("\"{}\"*", term)).collect();
+
+        let mut builder = QueryBuilder::<Db>::new(
+            "SELECT todos.* FROM todos_fts JOIN todos ON todos.id = todos_fts.rowid
+            WHERE todos_fts MATCH ",
+        );
+        builder
+            .push_bind(query.join(" "))
+            .push(" AND todos.workspace_id = ")
+            .push_bind(workspace_id)
+            .push(" ORDER BY todos_fts.rank");
+        builder
+    };
+
+    // The expression of the index, `todos_search_idx`
+    #[cfg(feature = "postgres")]
+    let mut builder = {
+        let query: Vec<_> = terms.iter().map(|term| format!("{}:*", term)).collect();
+
+        let mut builder =
+            QueryBuilder::<Db>::new("SELECT todos.* FROM todos, to_tsquery('simple', ");
+        builder
+            .push_bind(query.join(" & "))
+            .push(
+                ") AS query WHERE to_tsvector('simple', title || ' ' || description) @@ query
+                AND workspace_id = ",
+            )
+            .push_bind(workspace_id)
+            .push(
+                " ORDER BY ts_rank(to_tsvector('simple', title || ' ' || description), query) DESC",
+            );
+        builder
+    };
+
+    builder.push(", created_at DESC LIMIT ").push_bind(limit);
+
+    let todos = builder
+        .build_query_as::<Todo>()
+        .fetch_all(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(todos)
+}
+
+/// The first `limit` tags of the workspace starting with `prefix`, the
+/// ones of more todos first. Only the todos with such a tag are read.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn suggest_tags(
+    workspace_id: i64,
+    prefix: &str,
+    limit: usize,
+    pool: &DbPool,
+) -> Result<Vec<TagSuggestion>> {
+    let prefix = prefix.trim().trim_start_matches('#').to_lowercase();
+    let pattern = format!("% {}%", escape_like(&prefix));
+
+    let rows = query_scalar!(
+        "SELECT tags FROM todos WHERE workspace_id = $1 AND (' ' || tags) LIKE $2 ESCAPE '\\'",
+        workspace_id,
+        pattern
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let mut counts: HashMap<String, i64> = HashMap::new();
+    for tags in &rows {
+        for tag in tags
+            .split_whitespace()
+            .filter(|tag| tag.starts_with(&prefix))
+        {
+            *counts.entry(tag.to_string()).or_default() += 1;
+        }
+    }
+
+    let mut suggestions: Vec<_> = counts
+        .into_iter()
+        .map(|(tag, count)| TagSuggestion { tag, count })
+        .collect();
+    suggestions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    suggestions.truncate(limit);
+
+    Ok(suggestions)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_todo_by_id(todo_id: i64, workspace_id: i64, pool: &DbPool) -> Result<Todo> {
+    let todo = query_as!(
+        Todo,
+        "SELECT * FROM todos WHERE id = $1 AND workspace_id = $2",
+        todo_id,
+        workspace_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}.", e))?
+    .ok_or_else(|| anyhow!("todo does not exist in the database."))?;
+
+    Ok(todo)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn remove_todo(
+    todo_id: i64,
+    workspace_id: i64,
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<()> {
+    let rows_affected = query!(
+        "DELETE FROM todos WHERE id = $1 AND workspace_id = $2",
+        todo_id,
+        workspace_id
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!(format!("Todo with ID: {} not found", todo_id));
+    }
+
+    events.publish(DomainEvent::TodoDeleted {
+        workspace_id,
+        todo_id,
+    });
+
+    Ok(())
+}
+
+/// Error returned by `update_todo` and `toggle_todo` when trying to mark as done
+/// a todo that still has open blockers.
+#[derive(Debug)]
+pub struct TodoBlockedError {
+    pub open_blockers: i64,
+}
+
+impl std::fmt::Display for TodoBlockedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "this task is blocked by {} open task(s), complete them first.",
+            self.open_blockers
+        )
+    }
+}
+
+impl std::error::Error for TodoBlockedError {}
+
+/// Error returned by `update_todo` when the todo has been changed
+/// since the version that was edited.
+#[derive(Debug)]
+pub struct TodoConflictError;
+
+impl std::fmt::Display for TodoConflictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "this task has been changed since you opened it, your changes were not saved."
+        )
+    }
+}
+
+impl std::error::Error for TodoConflictError {}
+
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn update_todo(
+    title: String,
+    description: String,
+    status: bool,
+    remind_at: Option<NaiveDateTime>,
+    tags: Option<String>,
+    todo_id: i64,
+    expected_version: Option<i64>,
+    workspace_id: i64,
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<Todo> {
+    let title = plain_text(&title);
+    let description = plain_text(&description);
+
+    if status {
+        check_open_blockers(todo_id, workspace_id, pool).await?;
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let version = query_scalar!(
+        "SELECT version FROM todos WHERE id = $1 AND workspace_id = $2",
+        todo_id,
+        workspace_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .ok_or_else(|| anyhow!("Todo with ID: {} not found", todo_id))?;
+
+    if expected_version.is_some_and(|expected| expected != version) {
+        return Err(TodoConflictError.into());
+    }
+
+    // Keep the previous title/description when they change
+    query!(
+        "INSERT INTO todo_versions (todo_id, title, description)
+        SELECT id, title, description FROM todos
+        WHERE id = $1 AND workspace_id = $2 AND (title != $3 OR description != $4)",
+        todo_id,
+        workspace_id,
+        title,
+        description
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    // A new reminder time must be sent again. The version is checked
+    // again, in case another edit was saved since it was read. Without
+    // tags the current ones are kept
+    let rows_affected = query!(
+        "UPDATE todos SET title = $1, description = $2, status = $3,
+        completed_at = CASE WHEN $3 THEN COALESCE(completed_at, CURRENT_TIMESTAMP) END,
+        reminder_sent_at = CASE WHEN remind_at IS NOT DISTINCT FROM $4 THEN reminder_sent_at ELSE NULL END,
+        remind_at = $4, tags = COALESCE($7, tags), version = version + 1 WHERE id = $5 AND version = $6",
+        title,
+        description,
+        status,
+        remind_at,
+        todo_id,
+        version,
+        tags
+    )
+    .execute(&mut *tx)
+    .await
+    .unwrap()
+    .rows_affected();
+
+    if rows_affected == 0 {
+        return Err(TodoConflictError.into());
+    }
+
+    let todo = query_as!(Todo, "SELECT * FROM todos WHERE id = $1", todo_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    events.publish(DomainEvent::TodoUpdated { todo: todo.clone() });
+
+    Ok(todo)
+}
+
+/// Applies the fields set in `edit` to all the selected todos of the
+/// workspace, in one transaction: none is changed if one of them is
+/// missing or still blocked. Moved todos are deleted from the lists
+/// of the workspace.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn bulk_update_todos(
+    edit: &BulkEditSchema,
+    workspace_id: i64,
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<Vec<Todo>> {
+    if edit.status == Some(true) {
+        for &todo_id in &edit.ids {
+            check_open_blockers(todo_id, workspace_id, pool).await?;
+        }
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let mut todos = Vec::with_capacity(edit.ids.len());
+    for &todo_id in &edit.ids {
+        let rows_affected = query!(
+            "UPDATE todos SET status = COALESCE($1, status),
+            completed_at = CASE WHEN COALESCE($1, status) THEN COALESCE(completed_at, CURRENT_TIMESTAMP) END,
+            priority = COALESCE($2, priority), tags = COALESCE($3, tags),
+            workspace_id = COALESCE($4, workspace_id), version = version + 1
+            WHERE id = $5 AND workspace_id = $6",
+            edit.status,
+            edit.priority,
+            edit.tags,
+            edit.workspace_id,
+            todo_id,
+            workspace_id
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            bail!("Todo with ID: {} not found", todo_id);
+        }
+
+        let todo = query_as!(Todo, "SELECT * FROM todos WHERE id = $1", todo_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| anyhow!("database error: {}", e))?;
+
+        todos.push(todo);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    for todo in &todos {
+        if todo.workspace_id != workspace_id {
+            events.publish(DomainEvent::TodoDeleted {
+                workspace_id,
+                todo_id: todo.id,
+            });
+        }
+        events.publish(DomainEvent::TodoUpdated { todo: todo.clone() });
+    }
+
+    Ok(todos)
+}
+
+/// What an operation of `apply_todo_batch` did.
+#[derive(Debug)]
+pub enum BatchOutcome {
+    Created(Todo),
+    Updated(Todo),
+    Deleted(i64),
+}
+
+/// Error returned by `apply_todo_batch` when one of the operations
+/// failed, none of them being applied.
+#[derive(Debug)]
+pub struct BatchError {
+    /// Position of the operation in the batch.
+    pub index: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for BatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "operation {} failed: {}", self.index, self.reason)
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// Applies the create, update and delete operations to the todos of the
+/// workspace in order, in one transaction: none is applied if one of them
+/// fails. The events are published once they are all saved.
+#[instrument(skip_all, fields(db = "write", operations = operations.len()))]
+pub async fn apply_todo_batch(
+    created_by: String,
+    workspace_id: i64,
+    operations: &[BatchOperation],
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<Vec<BatchOutcome>> {
+    let failed = |index: usize| {
+        move |e: anyhow::Error| BatchError {
+            index,
+            reason: e.to_string(),
+        }
+    };
+
+    // Checked before the transaction, as it holds the connection
+    for (index, operation) in operations.iter().enumerate() {
+        if let BatchOperation::Update {
+            id,
+            status: Some(true),
+            ..
+        } = operation
+        {
+            check_open_blockers(*id, workspace_id, pool)
+                .await
+                .map_err(failed(index))?;
+        }
+    }
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let mut outcomes = Vec::with_capacity(operations.len());
+    for (index, operation) in operations.iter().enumerate() {
+        let outcome = apply_batch_operation(operation, &created_by, workspace_id, &mut tx)
+            .await
+            .map_err(failed(index))?;
+        outcomes.push(outcome);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    for outcome in &outcomes {
+        events.publish(match outcome {
+            BatchOutcome::Created(todo) => DomainEvent::TodoCreated { todo: todo.clone() },
+            BatchOutcome::Updated(todo) => DomainEvent::TodoUpdated { todo: todo.clone() },
+            BatchOutcome::Deleted(todo_id) => DomainEvent::TodoDeleted {
+                workspace_id,
+                todo_id: *todo_id,
+            },
+        });
+    }
+
+    Ok(outcomes)
+}
+
+/// Applies an operation of `apply_todo_batch` within its transaction.
+async fn apply_batch_operation(
+    operation: &BatchOperation,
+    created_by: &str,
+    workspace_id: i64,
+    tx: &mut Transaction<'_, Db>,
+) -> Result<BatchOutcome> {
+    match operation {
+        BatchOperation::Create {
+            title,
+            description,
+            due_at,
+            priority,
+            tags,
+        } => {
+            let title = plain_text(title);
+            let description = plain_text(description);
+
+            let todo = query_as!(
+                Todo,
+                "INSERT INTO todos (created_by,workspace_id,title,description,due_at,priority,tags) VALUES($1, $2, $3, $4, $5, $6, $7) RETURNING *",
+                created_by,
+                workspace_id,
+                title,
+                description,
+                due_at,
+                priority,
+                tags,
+            )
+            .fetch_one(&mut **tx)
+            .await
+            .map_err(|e| anyhow!("database error: {}", e))?;
+
+            Ok(BatchOutcome::Created(todo))
+        }
+        BatchOperation::Update {
+            id,
+            title,
+            description,
+            status,
+            priority,
+            tags,
+            version: expected_version,
+        } => {
+            let title = title.as_deref().map(plain_text);
+            let description = description.as_deref().map(plain_text);
+
+            let version = query_scalar!(
+                "SELECT version FROM todos WHERE id = $1 AND workspace_id = $2",
+                id,
+                workspace_id
+            )
+            .fetch_optional(&mut **tx)
+            .await
+            .map_err(|e| anyhow!("database error: {}", e))?
+            .ok_or_else(|| anyhow!("Todo with ID: {} not found", id))?;
+
+            if expected_version.is_some_and(|expected| expected != version) {
+                return Err(TodoConflictError.into());
+            }
+
+            // Keep the previous title/description when they change
+            query!(
+                "INSERT INTO todo_versions (todo_id, title, description)
+                SELECT id, title, description FROM todos
+                WHERE id = $1 AND (title != COALESCE($2, title) OR description != COALESCE($3, description))",
+                id,
+                title,
+                description
+            )
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| anyhow!("database error: {}", e))?;
+
+            query!(
+                "UPDATE todos SET title = COALESCE($1, title), description = COALESCE($2, description),
+                status = COALESCE($3, status),
+                completed_at = CASE WHEN COALESCE($3, status) THEN COALESCE(completed_at, CURRENT_TIMESTAMP) END,
+                priority = COALESCE($4, priority), tags = COALESCE($5, tags), version = version + 1
+                WHERE id = $6",
+                title,
+                description,
+                status,
+                priority,
+                tags,
+                id
+            )
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| anyhow!("database error: {}", e))?;
+
+            let todo = query_as!(Todo, "SELECT * FROM todos WHERE id = $1", id)
+                .fetch_one(&mut **tx)
+                .await
+                .map_err(|e| anyhow!("database error: {}", e))?;
+
+            Ok(BatchOutcome::Updated(todo))
+        }
+        BatchOperation::Delete { id } => {
+            let rows_affected = query!(
+                "DELETE FROM todos WHERE id = $1 AND workspace_id = $2",
+                id,
+                workspace_id
+            )
+            .execute(&mut **tx)
+            .await
+            .map_err(|e| anyhow!("database error: {}", e))?
+            .rows_affected();
+
+            if rows_affected == 0 {
+                bail!("Todo with ID: {} not found", id);
+            }
+
+            Ok(BatchOutcome::Deleted(*id))
+        }
+    }
+}
+
+/// Marks an open todo as done, or a done one as open again,
+/// leaving the rest of it as it is.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn toggle_todo(
+    todo_id: i64,
+    workspace_id: i64,
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<Todo> {
+    let status = query_scalar!(
+        "SELECT status FROM todos WHERE id = $1 AND workspace_id = $2",
+        todo_id,
+        workspace_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .ok_or_else(|| anyhow!("Todo with ID: {} not found", todo_id))?;
+
+    if !status {
+        check_open_blockers(todo_id, workspace_id, pool).await?;
+    }
+
+    // Concurrent edits of the modal are refused, as the version changes
+    query!(
+        "UPDATE todos SET status = NOT status,
+        completed_at = CASE WHEN status THEN NULL ELSE CURRENT_TIMESTAMP END,
+        version = version + 1 WHERE id = $1 AND workspace_id = $2",
+        todo_id,
+        workspace_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let todo = query_as!(Todo, "SELECT * FROM todos WHERE id = $1", todo_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    events.publish(DomainEvent::TodoUpdated { todo: todo.clone() });
+
+    Ok(todo)
+}
+
+/// Fails with `TodoBlockedError` if the todo is blocked by open todos.
+async fn check_open_blockers(todo_id: i64, workspace_id: i64, pool: &DbPool) -> Result<()> {
+    let open_blockers = query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM todo_dependencies
+        JOIN todos ON todos.id = todo_dependencies.blocked_by_id
+        WHERE todo_dependencies.todo_id = $1 AND todos.status = FALSE
+        AND todos.workspace_id = $2"#,
+        todo_id,
+        workspace_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    if open_blockers > 0 {
+        return Err(TodoBlockedError { open_blockers }.into());
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_todo_versions(todo_id: i64, pool: &DbPool) -> Result<Vec<TodoVersion>> {
+    let versions = query_as!(
+        TodoVersion,
+        "SELECT * FROM todo_versions WHERE todo_id = $1 ORDER BY created_at DESC, id DESC",
+        todo_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(versions)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn revert_todo(
+    todo_id: i64,
+    version_id: i64,
+    workspace_id: i64,
+    events: &EventBus,
+    pool: &DbPool,
+) -> Result<Todo> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let version = query_as!(
+        TodoVersion,
+        "SELECT todo_versions.* FROM todo_versions
+        JOIN todos ON todos.id = todo_versions.todo_id
+        WHERE todo_versions.id = $1 AND todo_id = $2 AND todos.workspace_id = $3",
+        version_id,
+        todo_id,
+        workspace_id
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .ok_or(anyhow!("Version with ID: {} not found", version_id))?;
+
+    // The current values become a version too, so a revert can be undone
+    query!(
+        "INSERT INTO todo_versions (todo_id, title, description)
+        SELECT id, title, description FROM todos WHERE id = $1",
+        todo_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    query!(
+        "UPDATE todos SET title = $1, description = $2, version = version + 1 WHERE id = $3",
+        version.title,
+        version.description,
+        todo_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let todo = query_as!(Todo, "SELECT * FROM todos WHERE id = $1", todo_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    events.publish(DomainEvent::TodoUpdated { todo: todo.clone() });
+
+    Ok(todo)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_recent_todos(workspace_id: i64, limit: i64, pool: &DbPool) -> Result<Vec<Todo>> {
+    let todos = query_as!(
+        Todo,
+        r#"SELECT id AS "id!", created_by, title, description, status, created_at,
+        due_at, priority, tags, remind_at, reminder_sent_at, version, workspace_id, completed_at
+        FROM todos WHERE workspace_id = $1 ORDER BY created_at DESC LIMIT $2"#,
+        workspace_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(todos)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_dav_resources(workspace_id: i64, pool: &DbPool) -> Result<Vec<DavResource>> {
+    let resources = query_as!(
+        DavResource,
+        "SELECT todo_id, name, uid FROM dav_resources WHERE workspace_id = $1",
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(resources)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_dav_resource(
+    todo_id: i64,
+    workspace_id: i64,
+    name: String,
+    uid: String,
+    pool: &DbPool,
+) -> Result<()> {
+    query!(
+        "INSERT INTO dav_resources (todo_id,workspace_id,name,uid) VALUES($1, $2, $3, $4)",
+        todo_id,
+        workspace_id,
+        name,
+        uid
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Records the id that a client gave to a change it made offline,
+/// returning `false` if it already was, i.e. the change was sent before.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn claim_sync_mutation(
+    user_id: &str,
+    client_id: &str,
+    now: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<bool> {
+    let rows_affected = query!(
+        "INSERT INTO sync_mutations (user_id,client_id,created_at) VALUES($1, $2, $3)
+        ON CONFLICT DO NOTHING",
+        user_id,
+        client_id,
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    Ok(rows_affected == 1)
+}
+
+/// Todo created or changed by the change of a client, `None`
+/// until it has been applied.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_sync_mutation_todo(
+    user_id: &str,
+    client_id: &str,
+    pool: &DbPool,
+) -> Result<Option<i64>> {
+    let row = query!(
+        "SELECT todo_id FROM sync_mutations WHERE user_id = $1 AND client_id = $2",
+        user_id,
+        client_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(row.and_then(|row| row.todo_id))
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn finish_sync_mutation(
+    user_id: &str,
+    client_id: &str,
+    todo_id: i64,
+    pool: &DbPool,
+) -> Result<()> {
+    query!(
+        "UPDATE sync_mutations SET todo_id = $1 WHERE user_id = $2 AND client_id = $3",
+        todo_id,
+        user_id,
+        client_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Gives back the claim of a change that couldn't be applied,
+/// so it can be sent again.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn release_sync_mutation(user_id: &str, client_id: &str, pool: &DbPool) -> Result<()> {
+    query!(
+        "DELETE FROM sync_mutations WHERE user_id = $1 AND client_id = $2",
+        user_id,
+        client_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Deletes the ids of the changes of the clients recorded before `before`,
+/// which won't be sent again.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn delete_stale_sync_mutations(before: NaiveDateTime, pool: &DbPool) -> Result<u64> {
+    let rows_affected = query!("DELETE FROM sync_mutations WHERE created_at < $1", before)
+        .execute(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?
+        .rows_affected();
+
+    Ok(rows_affected)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn get_or_create_feed_token(user_id: String, pool: &DbPool) -> Result<String> {
+    let token = Uuid::new_v4().simple().to_string();
+
+    // Keeps the existing token if the user already has one
+    query!(
+        "INSERT INTO feed_tokens (user_id,token) VALUES($1, $2) ON CONFLICT(user_id) DO NOTHING",
+        user_id,
+        token
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let token = query_scalar!("SELECT token FROM feed_tokens WHERE user_id = $1", user_id)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(token)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_user_by_feed_token(token: &str, pool: &DbPool) -> Result<Option<User>> {
+    let user = query_as!(
+        User,
+        "SELECT users.* FROM users JOIN feed_tokens ON feed_tokens.user_id = users.id WHERE feed_tokens.token = $1",
+        token
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(user)
+}
+
+/// Links a Slack user to the account, replacing the account it was linked to.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn link_slack_user(
+    team_id: &str,
+    slack_user_id: &str,
+    user_id: &str,
+    pool: &DbPool,
+) -> Result<()> {
+    query!(
+        "INSERT INTO slack_links (team_id,slack_user_id,user_id) VALUES ($1, $2, $3)
+        ON CONFLICT (team_id, slack_user_id) DO UPDATE SET user_id = excluded.user_id",
+        team_id,
+        slack_user_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_user_by_slack_id(
+    team_id: &str,
+    slack_user_id: &str,
+    pool: &DbPool,
+) -> Result<Option<User>> {
+    let user = query_as!(
+        User,
+        "SELECT users.* FROM users JOIN slack_links ON slack_links.user_id = users.id
+        WHERE slack_links.team_id = $1 AND slack_links.slack_user_id = $2",
+        team_id,
+        slack_user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(user)
+}
+
+/// Adds an authentication event to the audit log, with the account
+/// that has the email (none for the failed logins of unknown emails),
+/// the address of the client, if known, and the admin impersonating
+/// the account, if any.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_audit_entry(
+    email: &str,
+    event: AuditEvent,
+    client_ip: Option<IpAddr>,
+    actor_email: Option<&str>,
+    pool: &DbPool,
+) -> Result<()> {
+    let email = email.to_ascii_lowercase();
+    let event = event.name();
+    let client_ip = client_ip.map(|ip| ip.to_string());
+
+    query!(
+        "INSERT INTO audit_log (user_id,email,event,client_ip,actor_email)
+        VALUES ((SELECT id FROM users WHERE email = $1), $1, $2, $3, $4)",
+        email,
+        event,
+        client_ip,
+        actor_email
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// The last `limit` entries of the audit log matching the filter, newest first.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn search_audit_log(
+    filter: &AuditFilter,
+    limit: i64,
+    pool: &DbPool,
+) -> Result<Vec<AuditEntry>> {
+    let mut builder = QueryBuilder::<Db>::new("SELECT * FROM audit_log WHERE 1 = 1");
+
+    let user = filter.user.trim();
+    if !user.is_empty() {
+        builder
+            .push(" AND (user_id = ")
+            .push_bind(user.to_string())
+            .push(format_args!(" OR email {} ", LIKE))
+            .push_bind(format!("%{}%", user))
+            .push(")");
+    }
+
+    // The dates are inclusive, the range ends at the start of the next day
+    if let Some(from) = parse_date(&filter.from)? {
+        builder.push(" AND created_at >= ").push_bind(from);
+    }
+    if let Some(to) = parse_date(&filter.to)? {
+        builder
+            .push(" AND created_at < ")
+            .push_bind(to + chrono::Duration::days(1));
+    }
+
+    builder
+        .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(limit);
+
+    let entries = builder
+        .build_query_as::<AuditEntry>()
+        .fetch_all(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(entries)
+}
+
+/// Start of the day of a `YYYY-MM-DD` date, `None` if empty.
+fn parse_date(date: &str) -> Result<Option<NaiveDateTime>> {
+    let date = date.trim();
+    if date.is_empty() {
+        return Ok(None);
+    }
+
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| anyhow!("invalid date {}, expected YYYY-MM-DD.", date))?;
+
+    Ok(date.and_hms_opt(0, 0, 0))
+}
+
+/// Lets the user search the audit log.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn set_user_admin(user_id: &str, pool: &DbPool) -> Result<()> {
+    query!("UPDATE users SET is_admin = TRUE WHERE id = $1", user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_saved_filter(
+    user_id: String,
+    name: String,
+    filter: TodoFilter,
+    pool: &DbPool,
+) -> Result<SavedFilter> {
+    let saved_filter = query_as!(
+        SavedFilter,
+        "INSERT INTO saved_filters (user_id,name,query,tag,status,sort) VALUES($1, $2, $3, $4, $5, $6) RETURNING *",
+        user_id,
+        name,
+        filter.q,
+        filter.tag,
+        filter.status,
+        filter.sort,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(saved_filter)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_saved_filters(user_id: String, pool: &DbPool) -> Result<Vec<SavedFilter>> {
+    let saved_filters = query_as!(
+        SavedFilter,
+        r#"SELECT id AS "id!", user_id, name, query, tag, status, sort, created_at
+        FROM saved_filters WHERE user_id = $1 ORDER BY name ASC"#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(saved_filters)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn remove_saved_filter(filter_id: i64, user_id: String, pool: &DbPool) -> Result<()> {
+    let rows_affected = query!(
+        "DELETE FROM saved_filters WHERE id = $1 AND user_id = $2",
+        filter_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!(format!("Filter with ID: {} not found", filter_id));
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_due_reminders(now: NaiveDateTime, pool: &DbPool) -> Result<Vec<DueReminder>> {
+    let reminders = query_as!(
+        DueReminder,
+        "SELECT todos.id AS todo_id, users.id AS user_id, todos.title, todos.description, users.email, users.username
+        FROM todos JOIN users ON users.id = todos.created_by
+        WHERE todos.remind_at <= $1 AND todos.reminder_sent_at IS NULL AND todos.status = FALSE",
+        now
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(reminders)
+}
+
+/// Marks the reminder as sent, returning `false` if it already was.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn claim_reminder(todo_id: i64, now: NaiveDateTime, pool: &DbPool) -> Result<bool> {
+    let rows_affected = query!(
+        "UPDATE todos SET reminder_sent_at = $1 WHERE id = $2 AND reminder_sent_at IS NULL",
+        now,
+        todo_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    Ok(rows_affected == 1)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn release_reminder(todo_id: i64, pool: &DbPool) -> Result<()> {
+    query!(
+        "UPDATE todos SET reminder_sent_at = NULL WHERE id = $1",
+        todo_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Users who get the digest of their todos by email, with their settings.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_digest_recipients(pool: &DbPool) -> Result<Vec<(User, UserSettings)>> {
+    let mut settings: HashMap<String, UserSettings> = query_as!(
+        UserSettings,
+        "SELECT * FROM user_settings WHERE digest IS NOT NULL"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .into_iter()
+    .map(|settings| (settings.user_id.clone(), settings))
+    .collect();
+
+    let users = query_as!(
+        User,
+        "SELECT * FROM users WHERE id IN (SELECT user_id FROM user_settings WHERE digest IS NOT NULL)"
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    // A user who opted in between the two queries waits for the next scan
+    let recipients = users
+        .into_iter()
+        .filter_map(|user| {
+            let settings = settings.remove(&user.id)?;
+            Some((user, settings))
+        })
+        .collect();
+
+    Ok(recipients)
+}
+
+/// Open todos of the workspaces of the user due before `until`
+/// (overdue included), the earliest first.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_digest_todos(
+    user_id: &str,
+    until: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<Vec<Todo>> {
+    let todos = query_as!(
+        Todo,
+        r#"SELECT todos.id AS "id!", created_by, title, description, status, todos.created_at,
+        due_at, priority, tags, remind_at, reminder_sent_at, version, todos.workspace_id, completed_at FROM todos
+        JOIN workspace_members ON workspace_members.workspace_id = todos.workspace_id
+        WHERE workspace_members.user_id = $1 AND todos.status = FALSE AND todos.due_at <= $2
+        ORDER BY todos.due_at, todos.id"#,
+        user_id,
+        until
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(todos)
+}
+
+/// Marks the digest of the user as sent, returning `false` if
+/// one was already sent after `since`.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn claim_digest(
+    user_id: &str,
+    now: NaiveDateTime,
+    since: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<bool> {
+    let rows_affected = query!(
+        "UPDATE users SET digest_sent_at = $1
+        WHERE id = $2 AND (digest_sent_at IS NULL OR digest_sent_at < $3)",
+        now,
+        user_id,
+        since
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    Ok(rows_affected == 1)
+}
+
+/// Gives back the claim of a digest that couldn't be sent.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn release_digest(
+    user_id: &str,
+    sent_at: Option<NaiveDateTime>,
+    pool: &DbPool,
+) -> Result<()> {
+    query!(
+        "UPDATE users SET digest_sent_at = $1 WHERE id = $2",
+        sent_at,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// Adds a background job to the `jobs` table, due right away.
+/// Jobs already there keep their schedule.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn register_job(name: &str, now: NaiveDateTime, pool: &DbPool) -> Result<()> {
+    query!(
+        "INSERT INTO jobs (name, next_run_at) VALUES ($1, $2) ON CONFLICT (name) DO NOTHING",
+        name,
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// When a background job is due next.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_job_next_run(name: &str, pool: &DbPool) -> Result<NaiveDateTime> {
+    query_scalar!("SELECT next_run_at FROM jobs WHERE name = $1", name)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))
+}
+
+/// Moves a due job to its next run, returning `false` if it isn't due
+/// (e.g. another instance of the app already claimed this run).
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn claim_job(
+    name: &str,
+    now: NaiveDateTime,
+    next_run_at: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<bool> {
+    let rows_affected = query!(
+        "UPDATE jobs SET next_run_at = $1, last_started_at = $2 WHERE name = $3 AND next_run_at <= $4",
+        next_run_at,
+        now,
+        name,
+        now
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    Ok(rows_affected == 1)
+}
+
+/// Records the end of a run of a job, with its error if it failed.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn finish_job(
+    name: &str,
+    finished_at: NaiveDateTime,
+    error: Option<String>,
+    pool: &DbPool,
+) -> Result<()> {
+    query!(
+        "UPDATE jobs SET last_finished_at = $1, last_error = $2 WHERE name = $3",
+        finished_at,
+        error,
+        name
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// The background jobs with their last runs, for the ops dashboard.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_jobs(pool: &DbPool) -> Result<Vec<JobStatus>> {
+    query_as!(JobStatus, "SELECT * FROM jobs ORDER BY name")
+        .fetch_all(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))
+}
+
+/// How many users and todos (all and still open) there are.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_ops_counts(pool: &DbPool) -> Result<OpsCounts> {
+    query_as!(
+        OpsCounts,
+        r#"SELECT (SELECT COUNT(*) FROM users) AS "users!: i64",
+        (SELECT COUNT(*) FROM todos) AS "todos!: i64",
+        (SELECT COUNT(*) FROM todos WHERE NOT status) AS "open_todos!: i64""#
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))
+}
+
+/// How many sessions kept in the database haven't expired at `now`.
+#[cfg(feature = "sqlite")]
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn count_active_sessions(now: DateTime<Utc>, pool: &DbPool) -> Result<i64> {
+    let now = now.timestamp();
+
+    query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM tower_sessions WHERE expiry_date > $1"#,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))
+}
+
+/// How many sessions kept in the database haven't expired at `now`.
+#[cfg(feature = "postgres")]
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn count_active_sessions(now: DateTime<Utc>, pool: &DbPool) -> Result<i64> {
+    query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM tower_sessions.session WHERE expiry_date > $1"#,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))
+}
+
+/// Starts a timer of the user on a todo of the workspace,
+/// stopping any other running timer of the user.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn start_timer(
+    todo_id: i64,
+    user_id: String,
+    workspace_id: i64,
+    pool: &DbPool,
+) -> Result<()> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    query!(
+        "UPDATE time_entries SET ended_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND ended_at IS NULL",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    let rows_affected = query!(
+        "INSERT INTO time_entries (todo_id,user_id) SELECT id, $2 FROM todos WHERE id = $1 AND workspace_id = $3",
+        todo_id,
+        user_id,
+        workspace_id
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!(format!("Todo with ID: {} not found", todo_id));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn stop_timer(todo_id: i64, user_id: String, pool: &DbPool) -> Result<()> {
+    let rows_affected = query!(
+        "UPDATE time_entries SET ended_at = CURRENT_TIMESTAMP WHERE todo_id = $1 AND user_id = $2 AND ended_at IS NULL",
+        todo_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!(format!(
+            "There is no running timer for the todo with ID: {}",
+            todo_id
+        ));
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_tracked_times(user_id: String, pool: &DbPool) -> Result<Vec<TrackedTime>> {
+    #[cfg(feature = "sqlite")]
+    let tracked_times = query_as!(
+        TrackedTime,
+        r#"SELECT todo_id AS "todo_id!",
+        SUM(unixepoch(COALESCE(ended_at, CURRENT_TIMESTAMP)) - unixepoch(started_at)) AS "seconds!: i64",
+        MAX(ended_at IS NULL) AS "running!: bool"
+        FROM time_entries WHERE user_id = $1 GROUP BY todo_id"#,
+        user_id
+    );
+    #[cfg(feature = "postgres")]
+    let tracked_times = query_as!(
+        TrackedTime,
+        r#"SELECT todo_id AS "todo_id!",
+        SUM(EXTRACT(EPOCH FROM COALESCE(ended_at, LOCALTIMESTAMP) - started_at))::BIGINT AS "seconds!: i64",
+        BOOL_OR(ended_at IS NULL) AS "running!: bool"
+        FROM time_entries WHERE user_id = $1 GROUP BY todo_id"#,
+        user_id
+    );
+
+    let tracked_times = tracked_times
+        .fetch_all(pool)
+        .await
+        .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(tracked_times)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_todo_stats(
+    workspace_id: i64,
+    now: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<TodoStats> {
+    let stats = query_as!(
+        TodoStats,
+        r#"SELECT COUNT(*) AS "total!: i64",
+        COALESCE(SUM(CASE WHEN status THEN 1 ELSE 0 END), 0) AS "done!: i64",
+        COALESCE(SUM(CASE WHEN NOT status AND due_at < $2 THEN 1 ELSE 0 END), 0) AS "overdue!: i64"
+        FROM todos WHERE workspace_id = $1"#,
+        workspace_id,
+        now
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(stats)
+}
+
+/// Marks `todo_id` as blocked by `blocked_by_id`; both todos must belong
+/// to the workspace and the new dependency must not create a cycle.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_dependency(
+    todo_id: i64,
+    blocked_by_id: i64,
+    workspace_id: i64,
+    pool: &DbPool,
+) -> Result<()> {
+    if todo_id == blocked_by_id {
+        bail!("a task cannot be blocked by itself.");
+    }
+
+    let owned = query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM todos WHERE id IN ($1, $2) AND workspace_id = $3"#,
+        todo_id,
+        blocked_by_id,
+        workspace_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    if owned != 2 {
+        bail!("todo does not exist in the database.");
+    }
+
+    // Is `todo_id` already (transitively) blocking `blocked_by_id`?
+    let creates_cycle = query_scalar!(
+        r#"WITH RECURSIVE blockers(id) AS (
+            SELECT blocked_by_id FROM todo_dependencies WHERE todo_id = $1
+            UNION
+            SELECT todo_dependencies.blocked_by_id FROM todo_dependencies
+            JOIN blockers ON todo_dependencies.todo_id = blockers.id
+        )
+        SELECT EXISTS(SELECT 1 FROM blockers WHERE id = $2) AS "exists!: bool""#,
+        blocked_by_id,
+        todo_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    if creates_cycle {
+        bail!("that dependency would create a cycle.");
+    }
+
+    query!(
+        "INSERT INTO todo_dependencies (todo_id,blocked_by_id) VALUES($1, $2) ON CONFLICT DO NOTHING",
+        todo_id,
+        blocked_by_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn remove_dependency(
+    todo_id: i64,
+    blocked_by_id: i64,
+    workspace_id: i64,
+    pool: &DbPool,
+) -> Result<()> {
+    let rows_affected = query!(
+        "DELETE FROM todo_dependencies WHERE todo_id = $1 AND blocked_by_id = $2
+        AND todo_id IN (SELECT id FROM todos WHERE workspace_id = $3)",
+        todo_id,
+        blocked_by_id,
+        workspace_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!(format!("Dependency of todo with ID: {} not found", todo_id));
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_blockers(todo_id: i64, pool: &DbPool) -> Result<Vec<Todo>> {
+    let todos = query_as!(
+        Todo,
+        "SELECT todos.* FROM todos
+        JOIN todo_dependencies ON todo_dependencies.blocked_by_id = todos.id
+        WHERE todo_dependencies.todo_id = $1 ORDER BY todos.id",
+        todo_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(todos)
+}
+
+/// Todos of the workspace that can still be added as blockers of a todo.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_blocker_candidates(
+    todo_id: i64,
+    workspace_id: i64,
+    pool: &DbPool,
+) -> Result<Vec<Todo>> {
+    let todos = query_as!(
+        Todo,
+        r#"SELECT todos.id AS "id!", created_by, title, description, status, created_at,
+        due_at, priority, tags, remind_at, reminder_sent_at, version, workspace_id, completed_at FROM todos
+        LEFT JOIN todo_dependencies ON todo_dependencies.blocked_by_id = todos.id
+            AND todo_dependencies.todo_id = $2
+        WHERE todos.workspace_id = $1 AND todos.id != $2 AND todo_dependencies.todo_id IS NULL
+        ORDER BY todos.created_at DESC, todos.id DESC"#,
+        workspace_id,
+        todo_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(todos)
+}
+
+/// Ids of the workspace's todos that have at least one open blocker.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_blocked_todo_ids(workspace_id: i64, pool: &DbPool) -> Result<Vec<i64>> {
+    let ids = query_scalar!(
+        "SELECT DISTINCT todo_dependencies.todo_id FROM todo_dependencies
+        JOIN todos AS blocked ON blocked.id = todo_dependencies.todo_id
+        JOIN todos AS blocker ON blocker.id = todo_dependencies.blocked_by_id
+        WHERE blocked.workspace_id = $1 AND blocker.status = FALSE",
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(ids)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_link(todo_id: i64, url: String, workspace_id: i64, pool: &DbPool) -> Result<i64> {
+    let id = query_scalar!(
+        "INSERT INTO todo_links (todo_id, url)
+        SELECT id, $2 FROM todos WHERE id = $1 AND workspace_id = $3 RETURNING id",
+        todo_id,
+        url,
+        workspace_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .ok_or(anyhow!("Todo with ID: {} not found", todo_id))?;
+
+    Ok(id)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn set_link_preview(
+    link_id: i64,
+    title: Option<String>,
+    description: Option<String>,
+    pool: &DbPool,
+) -> Result<()> {
+    query!(
+        "UPDATE todo_links SET title = $1, description = $2, fetched_at = CURRENT_TIMESTAMP
+        WHERE id = $3",
+        title,
+        description,
+        link_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_links(workspace_id: i64, pool: &DbPool) -> Result<Vec<TodoLink>> {
+    let links = query_as!(
+        TodoLink,
+        "SELECT todo_links.id, todo_id, url, todo_links.title, todo_links.description, fetched_at
+        FROM todo_links
+        JOIN todos ON todos.id = todo_links.todo_id
+        WHERE todos.workspace_id = $1 ORDER BY todo_links.id",
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(links)
+}
+
+/// Removes a link, returning the id of its Todo.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn remove_link(link_id: i64, workspace_id: i64, pool: &DbPool) -> Result<i64> {
+    query_scalar!(
+        "DELETE FROM todo_links WHERE id = $1
+        AND todo_id IN (SELECT id FROM todos WHERE workspace_id = $2)
+        RETURNING todo_id",
+        link_id,
+        workspace_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .ok_or_else(|| anyhow!("Link with ID: {} not found", link_id))
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_subtask(
+    todo_id: i64,
+    title: String,
+    workspace_id: i64,
+    pool: &DbPool,
+) -> Result<()> {
+    let title = plain_text(&title);
+
+    let rows_affected = query!(
+        "INSERT INTO todo_subtasks (todo_id, title)
+        SELECT id, $2 FROM todos WHERE id = $1 AND workspace_id = $3",
+        todo_id,
+        title,
+        workspace_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .rows_affected();
+
+    if rows_affected == 0 {
+        bail!(format!("Todo with ID: {} not found", todo_id));
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_subtasks(workspace_id: i64, pool: &DbPool) -> Result<Vec<Subtask>> {
+    let subtasks = query_as!(
+        Subtask,
+        "SELECT todo_subtasks.* FROM todo_subtasks
+        JOIN todos ON todos.id = todo_subtasks.todo_id
+        WHERE todos.workspace_id = $1 ORDER BY todo_subtasks.id",
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(subtasks)
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn toggle_subtask(subtask_id: i64, workspace_id: i64, pool: &DbPool) -> Result<Subtask> {
+    let subtask = query_as!(
+        Subtask,
+        r#"UPDATE todo_subtasks SET done = NOT done WHERE id = $1
+        AND todo_id IN (SELECT id FROM todos WHERE workspace_id = $2)
+        RETURNING id AS "id!", todo_id AS "todo_id!", title AS "title!", done AS "done!""#,
+        subtask_id,
+        workspace_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .ok_or(anyhow!("Subtask with ID: {} not found", subtask_id))?;
+
+    Ok(subtask)
+}
+
+/// Removes a checklist item, returning the id of its Todo.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn remove_subtask(subtask_id: i64, workspace_id: i64, pool: &DbPool) -> Result<i64> {
+    query_scalar!(
+        "DELETE FROM todo_subtasks WHERE id = $1
+        AND todo_id IN (SELECT id FROM todos WHERE workspace_id = $2)
+        RETURNING todo_id",
+        subtask_id,
+        workspace_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?
+    .ok_or_else(|| anyhow!("Subtask with ID: {} not found", subtask_id))
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_checklist_progress(
+    workspace_id: i64,
+    pool: &DbPool,
+) -> Result<Vec<ChecklistProgress>> {
+    let rows = query!(
+        r#"SELECT todo_id AS "todo_id!",
+        SUM(CASE WHEN done THEN 1 ELSE 0 END) AS "done!: i64", COUNT(*) AS "total!: i64"
+        FROM todo_subtasks JOIN todos ON todos.id = todo_subtasks.todo_id
+        WHERE todos.workspace_id = $1 GROUP BY todo_id"#,
+        workspace_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ChecklistProgress::new(row.todo_id, row.done, row.total))
+        .collect())
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_todo_checklist_progress(todo_id: i64, pool: &DbPool) -> Result<ChecklistProgress> {
+    let row = query!(
+        r#"SELECT COALESCE(SUM(CASE WHEN done THEN 1 ELSE 0 END), 0) AS "done!: i64",
+        COUNT(*) AS "total!: i64"
+        FROM todo_subtasks WHERE todo_id = $1"#,
+        todo_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(ChecklistProgress::new(todo_id, row.done, row.total))
+}
+
+/// Notifies the user. The kinds sent once per todo (mentions) are
+/// skipped if the user was already notified about that todo.
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn add_notification(
+    user_id: &str,
+    kind: NotificationKind,
+    message: &str,
+    link: &str,
+    todo_id: Option<i64>,
+    pool: &DbPool,
+) -> Result<()> {
+    let once = kind.once_per_todo() && todo_id.is_some();
+    let kind = kind.name();
+
+    query!(
+        "INSERT INTO notifications (user_id,kind,message,link,todo_id)
+        SELECT $1, $2, $3, $4, $5
+        WHERE NOT $6 OR NOT EXISTS (
+            SELECT 1 FROM notifications WHERE user_id = $1 AND kind = $2 AND todo_id = $5
+        )",
+        user_id,
+        kind,
+        message,
+        link,
+        todo_id,
+        once
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(())
+}
+
+/// The last `limit` notifications of the user, newest first.
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn get_notifications(
+    user_id: &str,
+    limit: i64,
+    pool: &DbPool,
+) -> Result<Vec<Notification>> {
+    let notifications = query_as!(
+        Notification,
+        "SELECT * FROM notifications WHERE user_id = $1 ORDER BY created_at DESC, id DESC LIMIT $2",
+        user_id,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(notifications)
+}
+
+#[instrument(skip_all, fields(db = "read"))]
+pub async fn count_unread_notifications(user_id: &str, pool: &DbPool) -> Result<i64> {
+    let count = query_scalar!(
+        r#"SELECT COUNT(*) AS "count!: i64" FROM notifications WHERE user_id = $1 AND read_at IS NULL"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    Ok(count)
+}
+
+/// Marks a notification of the user as read, returning it (for its link).
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn mark_notification_read(
+    notification_id: i64,
+    user_id: &str,
+    now: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<Notification> {
+    let notification = query_as!(
+        Notification,
+        r#"UPDATE notifications SET read_at = COALESCE(read_at, $1)
+        WHERE id = $2 AND user_id = $3
+        RETURNING id AS "id!", user_id, kind, message, link, todo_id, read_at, created_at"#,
+        now,
+        notification_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
+    notification.ok_or_else(|| anyhow!("Notification with ID: {} not found", notification_id))
+}
+
+#[instrument(skip_all, fields(db = "write"))]
+pub async fn mark_all_notifications_read(
+    user_id: &str,
+    now: NaiveDateTime,
+    pool: &DbPool,
+) -> Result<()> {
+    query!(
+        "UPDATE notifications SET read_at = $1 WHERE user_id = $2 AND read_at IS NULL",
+        now,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| anyhow!("database error: {}", e))?;
+
     Ok(())
 }
 
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tower_sessions::{
+    session::{Id, Record},
+    session_store, MemoryStore, SessionStore,
+};
+#[cfg(feature = "postgres")]
+use tower_sessions_sqlx_store::PostgresStore as DatabaseStore;
+#[cfg(feature = "sqlite")]
+use tower_sessions_sqlx_store::SqliteStore as DatabaseStore;
+
+use crate::{
+    config::{self, Config},
+    db::DbPool,
+};
+
+/// Connections to Redis shared by the requests.
+#[cfg(feature = "redis")]
+const REDIS_POOL_SIZE: usize = 6;
+
+/// The store of the sessions selected with `SESSION_STORE`.
+#[derive(Clone, Debug)]
+pub struct Sessions(Arc<dyn SessionStore>);
+
+impl Sessions {
+    pub fn new(config: &Config, pool: &DbPool) -> Result<Self> {
+        let store: Arc<dyn SessionStore> = match config.session_store {
+            config::SessionStore::Memory => Arc::new(MemoryStore::default()),
+            config::SessionStore::Database => Arc::new(database_store(pool)),
+            config::SessionStore::Redis => redis_store(config)?,
+        };
+
+        Ok(Self(store))
+    }
+}
+
+#[async_trait]
+impl SessionStore for Sessions {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        self.0.create(record).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        self.0.save(record).await
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        self.0.load(session_id).await
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        self.0.delete(session_id).await
+    }
+}
+
+/// The sessions kept in the database of the app. Their table
+/// is created by the migrations.
+pub fn database_store(pool: &DbPool) -> DatabaseStore {
+    DatabaseStore::new(pool.clone())
+}
+
+/// The sessions kept in Redis, which expires them by itself.
+#[cfg(feature = "redis")]
+fn redis_store(config: &Config) -> Result<Arc<dyn SessionStore>> {
+    use anyhow::Context;
+    use tower_sessions_redis_store::{fred::prelude::*, RedisStore};
+
+    let url = config.redis_url.as_deref().unwrap_or_default();
+    let redis_config = RedisConfig::from_url(url).context("Error: 🔥 invalid Redis URL!")?;
+    let pool = RedisPool::new(
+        redis_config,
+        None,
+        None,
+        Some(ReconnectPolicy::default()),
+        REDIS_POOL_SIZE,
+    )
+    .context("Error: 🔥 failed to set up the Redis connections!")?;
+
+    // Connects in the background, reconnecting whenever a connection drops
+    pool.connect();
+
+    Ok(Arc::new(RedisStore::new(pool)))
+}
+
+#[cfg(not(feature = "redis"))]
+fn redis_store(_config: &Config) -> Result<Arc<dyn SessionStore>> {
+    anyhow::bail!("the app must be built with the `redis` feature to keep the sessions in Redis")
+}
@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use tracing::{span, warn, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// Target of the spans of the service functions.
+const SERVICE_TARGET: &str = concat!(env!("CARGO_CRATE_NAME"), "::service");
+
+/// Tracing layer that logs a warning when a service function takes
+/// longer than `SLOW_QUERY_MS`, counting the time spent waiting for
+/// a connection of the pool and for the database.
+pub struct SlowServiceLayer {
+    threshold: Duration,
+}
+
+/// Logs the service functions slower than `threshold`.
+pub fn layer(threshold: Duration) -> SlowServiceLayer {
+    SlowServiceLayer { threshold }
+}
+
+/// When the span of a service function was created.
+struct StartedAt(Instant);
+
+impl<S> Layer<S> for SlowServiceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+
+        if span.metadata().target() == SERVICE_TARGET {
+            span.extensions_mut().insert(StartedAt(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        let Some(elapsed) = span
+            .extensions()
+            .get::<StartedAt>()
+            .map(|started_at| started_at.0.elapsed())
+        else {
+            return;
+        };
+
+        if elapsed > self.threshold {
+            warn!(
+                elapsed_ms = elapsed.as_millis() as u64,
+                "slow service function {}",
+                span.name()
+            );
+        }
+    }
+}
@@ -15,7 +15,10 @@ module.exports = {
     require("daisyui")
   ],
   daisyui: {
-    themes: ["dark"]
+    // Without a `data-theme` (the "System" choice) the
+    // `prefers-color-scheme` of the browser picks one of these
+    themes: ["light", "dark"],
+    darkTheme: "dark"
   }
 }
 
@@ -0,0 +1,278 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, Response, StatusCode},
+    Router,
+};
+use rust_axum_askama_htmx::{app, config::Config, model::BuildInfo};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use common::{body_text, create_todo, register_and_login, send, setup, setup_state_with};
+
+async fn preflight(app: &Router, uri: &str, origin: &str) -> Response<Body> {
+    let request = Request::options(uri)
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+        .body(Body::empty())
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn api_routes_allow_the_configured_origins() {
+    let app = setup().await;
+
+    let response = preflight(&app, "/api/openapi.json", "https://app.example.com").await;
+    assert_eq!(
+        response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://app.example.com"
+    );
+
+    let response = preflight(&app, "/api/openapi.json", "https://evil.example.com").await;
+    assert!(!response
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn pages_do_not_allow_other_origins() {
+    let app = setup().await;
+
+    let response = preflight(&app, "/todo/list", "https://app.example.com").await;
+    assert!(!response
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+/// The path of the link of the `Link` header with the given `rel`.
+fn link(response: &Response<Body>, rel: &str) -> Option<String> {
+    let links = response.headers().get(header::LINK)?.to_str().unwrap();
+
+    links.split(", ").find_map(|link| {
+        let (url, link_rel) = link.split_once("; ")?;
+        (link_rel == format!("rel=\"{}\"", rel)).then(|| {
+            url.trim_matches(['<', '>'])
+                .trim_start_matches("http://localhost")
+                .to_string()
+        })
+    })
+}
+
+async fn titles(response: Response<Body>) -> Vec<String> {
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&body_text(response).await).unwrap();
+
+    todos
+        .iter()
+        .map(|todo| todo["title"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn todos_are_paged_with_link_headers() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    for title in ["Report", "Groceries", "Run"] {
+        create_todo(&app, &token, title).await;
+    }
+
+    let response = send(&app, "GET", "/api/v1/todos?limit=2", Some(&token), None).await;
+    assert_eq!(response.headers()["x-total-count"], "3");
+    assert!(link(&response, "prev").is_none());
+    let next = link(&response, "next").unwrap();
+    assert_eq!(titles(response).await, ["Run", "Groceries"]);
+
+    let response = send(&app, "GET", &next, Some(&token), None).await;
+    assert!(link(&response, "next").is_none());
+    let prev = link(&response, "prev").unwrap();
+    assert_eq!(titles(response).await, ["Report"]);
+
+    let response = send(&app, "GET", &prev, Some(&token), None).await;
+    assert!(link(&response, "prev").is_none());
+    assert!(link(&response, "next").is_some());
+    assert_eq!(titles(response).await, ["Run", "Groceries"]);
+
+    let response = send(&app, "GET", "/api/v1/todos?after=oops", Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // A page the client already has isn't sent again
+    let response = send(&app, "GET", "/api/v1/todos", Some(&token), None).await;
+    let etag = response.headers()[header::ETAG].clone();
+    let request = Request::get("/api/v1/todos")
+        .header(header::COOKIE, format!("token={}", token))
+        .header(header::IF_NONE_MATCH, etag.clone())
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[header::ETAG], etag);
+    assert!(body_text(response).await.is_empty());
+
+    create_todo(&app, &token, "Laundry").await;
+    let request = Request::get("/api/v1/todos")
+        .header(header::COOKIE, format!("token={}", token))
+        .header(header::IF_NONE_MATCH, etag)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(titles(response).await[0], "Laundry");
+}
+
+/// Sends a batch of operations, returning the status and the body.
+async fn batch(app: &Router, token: &str, operations: Value) -> (StatusCode, Value) {
+    let request = Request::post("/api/v1/todos/batch")
+        .header(header::COOKIE, format!("token={}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "operations": operations }).to_string()))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = serde_json::from_str(&body_text(response).await).unwrap();
+
+    (status, body)
+}
+
+#[tokio::test]
+async fn todo_batches_are_applied_together_or_not_at_all() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    let rent = create_todo(&app, &token, "Rent").await;
+    let milk = create_todo(&app, &token, "Milk").await;
+
+    let (status, body) = batch(
+        &app,
+        &token,
+        json!([
+            { "op": "create", "title": "Report", "priority": 3 },
+            { "op": "update", "id": rent, "title": "Pay the rent", "status": true },
+            { "op": "delete", "id": milk },
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["applied"], true);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results[0]["status"], "created");
+    assert_eq!(results[0]["todo"]["priority"], 3);
+    assert_eq!(results[1]["status"], "updated");
+    assert_eq!(results[1]["todo"]["status"], true);
+    assert_eq!(results[2]["status"], "deleted");
+
+    let response = send(&app, "GET", "/api/v1/todos", Some(&token), None).await;
+    assert_eq!(titles(response).await, ["Report", "Pay the rent"]);
+
+    // The todo deleted above makes the whole batch fail
+    let (status, body) = batch(
+        &app,
+        &token,
+        json!([
+            { "op": "create", "title": "Groceries" },
+            { "op": "delete", "id": milk },
+        ]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["applied"], false);
+    assert_eq!(body["results"][0]["status"], "aborted");
+    assert_eq!(body["results"][1]["status"], "failed");
+
+    let response = send(&app, "GET", "/api/v1/todos", Some(&token), None).await;
+    assert_eq!(titles(response).await, ["Report", "Pay the rent"]);
+
+    // Invalid operations are rejected before anything is written
+    let (status, body) = batch(&app, &token, json!([{ "op": "create", "title": " " }])).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["results"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("title"));
+}
+
+#[tokio::test]
+async fn todo_batches_count_towards_the_create_limit_at_once() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    let creates = |count: usize| {
+        (0..count)
+            .map(|i| json!({ "op": "create", "title": format!("Task {}", i) }))
+            .collect::<Vec<_>>()
+    };
+
+    // Refused batches don't use the limit (5 todos a minute in the tests)
+    let mut operations = creates(2);
+    operations.push(json!({ "op": "create", "title": " " }));
+    let (status, _) = batch(&app, &token, json!(operations)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let (status, body) = batch(&app, &token, json!(creates(6))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["results"][4]["status"], "aborted");
+    assert_eq!(body["results"][5]["status"], "failed");
+    assert!(body["results"][5]["error"]
+        .as_str()
+        .unwrap()
+        .contains("up to 5 tasks"));
+
+    // The tags are stored as the forms store them
+    let mut operations = creates(4);
+    operations.push(json!({ "op": "create", "title": "Report", "tags": "#Work, Q3  report" }));
+    let (status, body) = batch(&app, &token, json!(operations)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["results"][4]["todo"]["tags"], "work q3 report");
+
+    let (status, body) = batch(&app, &token, json!(creates(1))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(body["results"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("creating tasks too fast"));
+}
+
+#[tokio::test]
+async fn pool_gauges_are_exported_to_prometheus() {
+    let open = setup().await;
+
+    let response = send(&open, "GET", "/metrics", None, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_text(response).await;
+    assert!(body.contains("# TYPE todo_db_pool_in_use_connections gauge"));
+    assert!(body.contains("todo_db_pool_max_connections{pool=\"write\"} 1"));
+
+    // Only for the scraper once there is a token
+    let config = Config {
+        metrics_token: Some("scraper-token".to_string()),
+        ..common::config()
+    };
+    let app = app(setup_state_with(config).await);
+    let response = send(&app, "GET", "/metrics", None, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let request = Request::get("/metrics")
+        .header(header::AUTHORIZATION, "Bearer scraper-token")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_build_is_reported_by_the_health_check_and_the_footer() {
+    let app = setup().await;
+    let build = BuildInfo::CURRENT;
+
+    let response = send(&app, "GET", "/healthchecker", None, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(body["git_sha"], build.git_sha);
+    assert_eq!(body["built_at"], build.built_at);
+
+    let body = body_text(send(&app, "GET", "/", None, None).await).await;
+    assert!(body.contains(&format!("v{} ({})", build.version, build.git_sha)));
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+
+use common::{body_text, send, setup};
+
+/// The value of the first `attribute="…"` after `from` in the page.
+fn attribute<'a>(body: &'a str, from: &str, attribute: &str) -> &'a str {
+    let start = body.find(from).unwrap();
+    let pattern = format!("{}=\"", attribute);
+    let value_start = start + body[start..].find(&pattern).unwrap() + pattern.len();
+
+    &body[value_start..value_start + body[value_start..].find('"').unwrap()]
+}
+
+#[tokio::test]
+async fn asset_urls_are_versioned_and_cached() {
+    let app = setup().await;
+
+    let body = body_text(send(&app, "GET", "/", None, None).await).await;
+    let start = body.find("/assets/js/htmx.min.js?v=").unwrap();
+    let url = &body[start..body[start..].find('"').unwrap() + start];
+
+    let response = send(&app, "GET", url, None, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[header::CACHE_CONTROL]
+        .to_str()
+        .unwrap()
+        .contains("immutable"));
+    let etag = response.headers()[header::ETAG].clone();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::get("/assets/js/htmx.min.js")
+                .header(header::IF_NONE_MATCH, etag)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+}
+
+#[tokio::test]
+async fn pages_reload_on_restart_only_in_development() {
+    let app = setup().await;
+
+    let body = body_text(send(&app, "GET", "/", None, None).await).await;
+    let response = send(&app, "GET", "/dev/live-reload", None, None).await;
+
+    if cfg!(feature = "dev") {
+        assert!(body.contains("/dev/live-reload"));
+        assert_eq!(response.status(), StatusCode::OK);
+    } else {
+        assert!(!body.contains("/dev/live-reload"));
+        assert!(body_text(response).await.contains("Resource not found"));
+    }
+}
+
+#[tokio::test]
+async fn pages_only_run_their_own_scripts() {
+    let app = setup().await;
+
+    let response = send(&app, "GET", "/", None, None).await;
+    let policy = response.headers()[header::CONTENT_SECURITY_POLICY]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = body_text(response).await;
+
+    // The inline scripts carry the nonce of the policy, new on every request
+    let nonce = attribute(&body, "<script nonce", "nonce");
+    assert!(policy.contains(&format!("script-src 'self' 'nonce-{}'", nonce)));
+    assert!(!body.contains("onclick="));
+
+    let response = send(&app, "GET", "/", None, None).await;
+    let body_again = body_text(response).await;
+    assert_ne!(attribute(&body_again, "<script nonce", "nonce"), nonce);
+
+    // The bundled scripts are checked against their hash
+    let integrity = attribute(&body, "/assets/js/htmx.min.js", "integrity");
+    let script = send(&app, "GET", "/assets/js/htmx.min.js", None, None).await;
+    let hash = Sha256::digest(body_text(script).await.as_bytes());
+    assert_eq!(integrity, format!("sha256-{}", STANDARD.encode(hash)));
+}
+
+#[tokio::test]
+async fn the_app_can_be_installed() {
+    let app = setup().await;
+
+    let body = body_text(send(&app, "GET", "/", None, None).await).await;
+    let manifest = attribute(&body, "<link rel=\"manifest\"", "href");
+    let response = send(&app, "GET", manifest, None, None).await;
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/manifest+json"
+    );
+    assert!(body_text(response)
+        .await
+        .contains("\"start_url\": \"/todo/list\""));
+
+    // Served from the root, so it controls every page
+    let response = send(&app, "GET", "/sw.js", None, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+    assert!(body_text(response).await.contains("/api/v1/sync"));
+}
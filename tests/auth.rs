@@ -0,0 +1,444 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use rust_axum_askama_htmx::{app, cli, config::Config};
+use tower::ServiceExt;
+
+use common::{
+    body_text, create_todo, login, register, register_and_login, send, setup, setup_state,
+    setup_state_with, token_cookie,
+};
+
+#[tokio::test]
+async fn register_and_login_sets_the_token_cookie() {
+    let app = setup().await;
+
+    register(&app, "alice@example.com", "secret123").await;
+
+    let response = login(&app, "alice@example.com", "secret123").await;
+
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/todo/list");
+    assert!(token_cookie(&response).is_some());
+}
+
+#[tokio::test]
+async fn login_with_a_wrong_password_is_rejected() {
+    let app = setup().await;
+
+    register(&app, "alice@example.com", "secret123").await;
+
+    let response = login(&app, "alice@example.com", "wrong").await;
+
+    assert_eq!(response.headers()[header::LOCATION], "/login");
+    assert!(token_cookie(&response).is_none());
+}
+
+#[tokio::test]
+async fn email_can_only_be_registered_once() {
+    let app = setup().await;
+
+    register(&app, "alice@example.com", "secret123").await;
+
+    for email in ["alice@example.com", "Alice@Example.com"] {
+        let form = format!("email={}&password=other&username=other", email);
+        let response = send(&app, "POST", "/register", None, Some(&form)).await;
+
+        assert_eq!(response.headers()[header::LOCATION], "/register");
+    }
+}
+
+#[tokio::test]
+async fn protected_routes_require_a_valid_token() {
+    let app = setup().await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", None, None).await).await;
+    assert!(body.contains("You are not logged in"));
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some("bogus"), None).await).await;
+    assert!(body.contains("Invalid token"));
+}
+
+#[tokio::test]
+async fn theme_is_saved_in_the_account() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(!body.contains("data-theme"));
+
+    let response = send(&app, "POST", "/theme", Some(&token), Some("theme=dark")).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains(r#"data-theme="dark""#));
+}
+
+#[tokio::test]
+async fn login_works_without_the_timezone_header() {
+    let app = setup().await;
+
+    register(&app, "alice@example.com", "secret123").await;
+
+    let request = Request::post("/login")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from("email=alice@example.com&password=secret123"))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert!(token_cookie(&response).is_some());
+}
+
+#[tokio::test]
+async fn pages_show_the_title_and_the_logged_in_user() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(body.contains("<title>Todo List | Profile</title>"));
+    assert!(body.contains("tester &lt;alice@example.com&gt;"));
+}
+
+#[tokio::test]
+async fn date_preferences_are_saved_in_the_profile() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let form = "timezone=Europe/Madrid&date_order=ymd&clock=12h";
+    let response = send(&app, "POST", "/settings/profile", Some(&token), Some(form)).await;
+    assert_eq!(response.headers()[header::LOCATION], "/settings/profile");
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(body.contains(r#"<option value="Europe/Madrid"  selected >"#));
+
+    let form = "timezone=Mars/Olympus&date_order=dmy&clock=24h";
+    send(&app, "POST", "/settings/profile", Some(&token), Some(form)).await;
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(body.contains(r#"<option value="Europe/Madrid"  selected >"#));
+    assert!(body.contains(r#"<option value="ymd"  selected >"#));
+}
+
+#[tokio::test]
+async fn password_can_be_reset_with_the_emailed_link() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+
+    register(&app, "alice@example.com", "secret123").await;
+
+    // Unknown emails get the same answer
+    for email in ["alice@example.com", "nobody@example.com"] {
+        let form = format!("email={}", email);
+        let response = send(&app, "POST", "/forgot-password", None, Some(&form)).await;
+        assert_eq!(response.headers()[header::LOCATION], "/login");
+    }
+
+    let token: String =
+        sqlx::query_scalar("SELECT token FROM user_tokens WHERE kind = 'password_reset'")
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+
+    let form = format!("token={}&password=newsecret", token);
+    let response = send(&app, "POST", "/reset-password", None, Some(&form)).await;
+    assert_eq!(response.headers()[header::LOCATION], "/login");
+
+    assert!(token_cookie(&login(&app, "alice@example.com", "secret123").await).is_none());
+    assert!(token_cookie(&login(&app, "alice@example.com", "newsecret").await).is_some());
+
+    // The link can only be used once
+    let form = format!("token={}&password=othersecret", token);
+    let response = send(&app, "POST", "/reset-password", None, Some(&form)).await;
+    assert_eq!(response.headers()[header::LOCATION], "/forgot-password");
+}
+
+#[tokio::test]
+async fn email_is_verified_with_the_emailed_link() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(body.contains("not verified"));
+
+    // The email is sent by a subscriber of the registration event
+    let mut verification = None;
+    for _ in 0..50 {
+        verification = sqlx::query_scalar::<_, String>(
+            "SELECT token FROM user_tokens WHERE kind = 'verification'",
+        )
+        .fetch_optional(&state.pool)
+        .await
+        .unwrap();
+        if verification.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let verification = verification.expect("no verification token was created");
+
+    let uri = format!("/verify-email?token={}", verification);
+    let response = send(&app, "GET", &uri, None, None).await;
+    assert_eq!(response.headers()[header::LOCATION], "/login");
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(!body.contains("not verified"));
+}
+
+#[tokio::test]
+async fn admins_can_search_the_audit_log() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+
+    let alice = register_and_login(&app, "alice@example.com").await;
+    login(&app, "alice@example.com", "wrong").await;
+    login(&app, "nobody@example.com", "wrong").await;
+    let admin = register_and_login(&app, "admin@example.com").await;
+
+    let response = send(&app, "GET", "/admin/audit", Some(&alice), None).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE email = 'admin@example.com'")
+        .execute(&state.pool)
+        .await
+        .unwrap();
+
+    let uri = "/admin/audit?user=alice&from=2000-01-01&to=2999-12-31";
+    let body = body_text(send(&app, "GET", uri, Some(&admin), None).await).await;
+    assert_eq!(body.matches("alice@example.com").count(), 3);
+    assert!(body.contains("register"));
+    assert!(body.contains("login_succeeded"));
+    assert!(body.contains("login_failed"));
+    assert!(!body.contains("admin@example.com"));
+
+    // Failed logins of unknown emails are recorded too
+    let body =
+        body_text(send(&app, "GET", "/admin/audit?user=nobody", Some(&admin), None).await).await;
+    assert!(body.contains("nobody@example.com"));
+
+    let uri = "/admin/audit?user=alice&to=2000-01-01";
+    let body = body_text(send(&app, "GET", uri, Some(&admin), None).await).await;
+    assert!(body.contains("No events match the search"));
+}
+
+/// Sends a failed login from `peer`, through the proxies of `forwarded_for`.
+async fn login_from(app: &Router, peer: &str, forwarded_for: &str) -> StatusCode {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/login")
+        .extension(ConnectInfo(peer.parse::<SocketAddr>().unwrap()))
+        .header("x-forwarded-for", forwarded_for)
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from("email=alice@example.com&password=wrong"))
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn logins_are_limited_per_client_address() {
+    let state = setup_state_with(Config {
+        trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        ..common::config()
+    })
+    .await;
+    let app = app(state.clone());
+
+    // The address before the trusted proxies, not the made up one before it
+    for _ in 0..5 {
+        let status = login_from(&app, "10.0.0.1:1234", "1.1.1.1, 2.2.2.2, 10.0.0.2").await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+    }
+    let status = login_from(&app, "10.0.0.1:1234", "3.3.3.3, 2.2.2.2").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    // Other clients aren't affected, and untrusted peers can't pretend to be others
+    let status = login_from(&app, "10.0.0.1:1234", "3.3.3.3").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+    let status = login_from(&app, "4.4.4.4:1234", "3.3.3.3").await;
+    assert_eq!(status, StatusCode::SEE_OTHER);
+
+    let client_ips: Vec<String> = sqlx::query_scalar("SELECT client_ip FROM audit_log")
+        .fetch_all(&state.pool)
+        .await
+        .unwrap();
+    assert_eq!(client_ips.iter().filter(|ip| *ip == "2.2.2.2").count(), 5);
+    assert!(client_ips.contains(&"3.3.3.3".to_string()));
+    assert!(client_ips.contains(&"4.4.4.4".to_string()));
+}
+
+#[tokio::test]
+async fn admins_can_see_the_ops_dashboard() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+
+    let alice = register_and_login(&app, "alice@example.com").await;
+    create_todo(&app, &alice, "First+task").await;
+    create_todo(&app, &alice, "Second+task").await;
+    let admin = register_and_login(&app, "admin@example.com").await;
+
+    let response = send(&app, "GET", "/admin/ops", Some(&alice), None).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE email = 'admin@example.com'")
+        .execute(&state.pool)
+        .await
+        .unwrap();
+
+    let response = send(&app, "GET", "/admin/ops", Some(&admin), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_text(response).await;
+    assert!(body.contains(r#"<div class="stat-value text-2xl">2</div>"#));
+    assert!(body.contains("2 open"));
+    assert!(body.contains("No errors since the app started"));
+}
+
+#[tokio::test]
+async fn users_are_cached_until_they_change() {
+    let state = setup_state_with(Config {
+        user_cache_ttl: Duration::from_secs(60),
+        ..common::config()
+    })
+    .await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(body.contains("tester"));
+    assert!(body.contains(r#"value="50"  selected"#));
+
+    sqlx::query("UPDATE users SET username = 'renamed' WHERE email = 'alice@example.com'")
+        .execute(&state.pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO user_settings (user_id, page_size)
+        SELECT id, 25 FROM users WHERE email = 'alice@example.com'",
+    )
+    .execute(&state.pool)
+    .await
+    .unwrap();
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(!body.contains("renamed"));
+    assert!(body.contains(r#"value="50"  selected"#));
+
+    // Saving the profile forgets the cached settings
+    let form = "timezone=&date_order=dmy&clock=24h&page_size=100";
+    send(&app, "POST", "/settings/profile", Some(&token), Some(form)).await;
+
+    let body = body_text(send(&app, "GET", "/settings/profile", Some(&token), None).await).await;
+    assert!(body.contains(r#"value="100"  selected"#));
+}
+
+#[tokio::test]
+async fn operators_manage_the_accounts_from_the_cli() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    register(&app, "alice@example.com", "secret123").await;
+
+    cli::reset_password(
+        "Alice@example.com".to_string(),
+        Some("newsecret456".to_string()),
+        &state.pool,
+    )
+    .await
+    .unwrap();
+    let response = login(&app, "alice@example.com", "secret123").await;
+    assert!(token_cookie(&response).is_none());
+    let response = login(&app, "alice@example.com", "newsecret456").await;
+    let token = token_cookie(&response).unwrap();
+
+    let response = send(&app, "GET", "/admin/audit", Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    cli::promote("alice@example.com".to_string(), &state.pool)
+        .await
+        .unwrap();
+    let response = send(&app, "GET", "/admin/audit", Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    assert!(cli::promote("bob@example.com".to_string(), &state.pool)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn admins_can_use_the_app_as_another_user() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let alice = register_and_login(&app, "alice@example.com").await;
+    create_todo(&app, &alice, "Alice+task").await;
+    let admin = register_and_login(&app, "admin@example.com").await;
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE email = 'admin@example.com'")
+        .execute(&state.pool)
+        .await
+        .unwrap();
+
+    let form = "email=admin%40example.com";
+    let response = send(&app, "POST", "/admin/impersonate", Some(&alice), Some(form)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let form = "email=alice%40example.com";
+    let response = send(&app, "POST", "/admin/impersonate", Some(&admin), Some(form)).await;
+    assert_eq!(response.headers()[header::LOCATION], "/todo/list");
+    let impersonating = token_cookie(&response).unwrap();
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&impersonating), None).await).await;
+    assert!(body.contains("Alice task"));
+    assert!(body.contains("impersonation-banner"));
+    create_todo(&app, &impersonating, "Admin+task").await;
+
+    let response = send(
+        &app,
+        "POST",
+        "/admin/impersonate/stop",
+        Some(&impersonating),
+        None,
+    )
+    .await;
+    assert_eq!(response.headers()[header::LOCATION], "/admin/ops");
+    let admin = token_cookie(&response).unwrap();
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&admin), None).await).await;
+    assert!(!body.contains("Alice task"));
+    assert!(!body.contains("impersonation-banner"));
+
+    let entries: Vec<(String, String, Option<String>)> = sqlx::query_as(
+        "SELECT event, email, actor_email FROM audit_log
+        WHERE event LIKE 'impersonation%' ORDER BY id",
+    )
+    .fetch_all(&state.pool)
+    .await
+    .unwrap();
+    let actor = Some("admin@example.com".to_string());
+    assert_eq!(
+        entries,
+        vec![
+            (
+                "impersonation_started".to_string(),
+                "alice@example.com".to_string(),
+                actor.clone()
+            ),
+            (
+                "impersonation_stopped".to_string(),
+                "alice@example.com".to_string(),
+                actor
+            ),
+        ]
+    );
+
+    // The changes made as the user are in their audit log
+    let uri = "/admin/audit?user=alice%40example.com";
+    let body = body_text(send(&app, "GET", uri, Some(&admin), None).await).await;
+    assert!(body.contains("impersonated_change"));
+    assert!(body.contains("by admin@example.com"));
+}
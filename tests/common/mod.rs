@@ -0,0 +1,200 @@
+#![allow(dead_code)]
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Method, Request, Response, StatusCode},
+    Router,
+};
+use rust_axum_askama_htmx::{
+    app,
+    config::{
+        Config, ListenAddress, LogFormat, MailTransport, RouteTimeouts, SessionStore, TextLimits,
+    },
+    db::{self, DbPools},
+    events, AppState,
+};
+use sqlx::sqlite::SqlitePoolOptions;
+use tower::ServiceExt;
+
+/// Builds the router on top of a migrated in-memory database.
+pub async fn setup() -> Router {
+    app(setup_state().await)
+}
+
+/// Builds the state of the app on top of a migrated in-memory database.
+pub async fn setup_state() -> Arc<AppState> {
+    setup_state_with(config()).await
+}
+
+/// Same as `setup_state`, with another config.
+pub async fn setup_state_with(config: Config) -> Arc<AppState> {
+    // A single connection that is never closed, as every
+    // connection to `sqlite::memory:` is a different database
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+
+    db::migrate(&pool).await.unwrap();
+
+    let state = Arc::new(AppState::new(DbPools::single(pool), config).unwrap());
+    events::start_subscribers(&state);
+
+    state
+}
+
+/// The config of the tests, on an in-memory database.
+pub fn config() -> Config {
+    Config {
+        host: "127.0.0.1".parse().unwrap(),
+        port: 0,
+        listen: vec![ListenAddress::Tcp("127.0.0.1:0".parse().unwrap())],
+        unix_socket_mode: 0o660,
+        tls_cert: None,
+        tls_key: None,
+        http_redirect_port: None,
+        request_timeout: Duration::from_secs(30),
+        route_timeouts: RouteTimeouts::default(),
+        heavy_route_concurrency: 4,
+        trusted_proxies: Vec::new(),
+        cors_allowed_origins: vec!["https://app.example.com".to_string()],
+        cors_allowed_methods: vec![Method::GET, Method::POST],
+        database_url: "sqlite::memory:".to_string(),
+        run_migrations: true,
+        db_connect_max_wait: Duration::ZERO,
+        db_max_connections: 10,
+        db_acquire_timeout: Duration::from_secs(30),
+        db_idle_timeout: Duration::from_secs(600),
+        slow_query_threshold: Duration::from_millis(250),
+        sqlite_busy_timeout: Duration::from_millis(5000),
+        jwt_secret: "test_secret".to_string(),
+        jwt_expires_in: Duration::from_secs(60 * 60),
+        jwt_maxage: 60,
+        url_signing_key: None,
+        feed_link_ttl: Duration::from_secs(60 * 60),
+        app_url: "http://localhost".to_string(),
+        mail_transport: MailTransport::None,
+        smtp_url: None,
+        smtp_host: None,
+        smtp_port: None,
+        smtp_username: None,
+        smtp_password: None,
+        mail_from: "Todo List <noreply@localhost>".to_string(),
+        log_format: LogFormat::Pretty,
+        session_store: SessionStore::Memory,
+        redis_url: None,
+        sentry_dsn: None,
+        slack_signing_secret: None,
+        backup_dir: None,
+        backup_interval: Duration::from_secs(24 * 60 * 60),
+        backup_keep: 7,
+        text_limits: TextLimits::default(),
+        max_upload_size: 2048 * 1024,
+        todo_create_limit: 5,
+        login_attempt_limit: 5,
+        // Most tests change the users in the database directly
+        user_cache_ttl: Duration::ZERO,
+        geoip_database: None,
+        metrics_token: None,
+    }
+}
+
+/// Sends a request to the router, with the `token` cookie if given.
+pub async fn send(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    token: Option<&str>,
+    form: Option<&str>,
+) -> Response<Body> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("x-timezone", "UTC");
+
+    if let Some(token) = token {
+        request = request.header(header::COOKIE, format!("token={}", token));
+    }
+
+    let body = match form {
+        Some(form) => {
+            request = request.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+            Body::from(form.to_string())
+        }
+        None => Body::empty(),
+    };
+
+    app.clone()
+        .oneshot(request.body(body).unwrap())
+        .await
+        .unwrap()
+}
+
+pub async fn body_text(response: Response<Body>) -> String {
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+/// Events sent in the `HX-Trigger` header, by name.
+pub fn hx_trigger(response: &Response<Body>) -> serde_json::Value {
+    let header = response.headers()["hx-trigger"].to_str().unwrap();
+
+    serde_json::from_str(header).unwrap()
+}
+
+/// Extracts the value of the `token` cookie set by the login.
+pub fn token_cookie(response: &Response<Body>) -> Option<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .find_map(|cookie| cookie.strip_prefix("token="))
+        .and_then(|cookie| cookie.split(';').next())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+}
+
+pub async fn register(app: &Router, email: &str, password: &str) {
+    let form = format!("email={}&password={}&username=tester", email, password);
+    let response = send(app, "POST", "/register", None, Some(&form)).await;
+
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/login");
+}
+
+pub async fn login(app: &Router, email: &str, password: &str) -> Response<Body> {
+    let form = format!("email={}&password={}", email, password);
+
+    send(app, "POST", "/login", None, Some(&form)).await
+}
+
+/// Registers a new user and returns its JWT.
+pub async fn register_and_login(app: &Router, email: &str) -> String {
+    register(app, email, "secret123").await;
+
+    let response = login(app, email, "secret123").await;
+
+    token_cookie(&response).expect("the login must set the token cookie")
+}
+
+/// Creates a todo and returns its id, taken from the returned row.
+pub async fn create_todo(app: &Router, token: &str, title: &str) -> i64 {
+    let form = format!("title={}&description=test", title);
+    let response = send(app, "POST", "/create", Some(token), Some(&form)).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(hx_trigger(&response).get("todoCreated").is_some());
+
+    let body = body_text(response).await;
+    let start = body.find("id=\"todo-").expect("the row of the todo") + "id=\"todo-".len();
+    let end = start + body[start..].find('"').unwrap();
+
+    body[start..end].parse().unwrap()
+}
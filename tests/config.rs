@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use rust_axum_askama_htmx::config::Config;
+use uuid::Uuid;
+
+// A single test, as the env vars are shared by the tests of the binary
+#[test]
+fn config_is_read_from_the_env_vars_and_the_files() {
+    let path = std::env::temp_dir().join(format!("config-{}.toml", Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        r#"
+        DATABASE_URL = "sqlite://todos.db"
+        JWT_SECRET = "a_secret_long_enough_for_the_tests"
+        JWT_EXPIRED_IN = "12h"
+        JWT_MAXAGE = 720
+        PORT = 9000
+        cors_allowed_methods = ["GET", "POST"]
+        "#,
+    )
+    .unwrap();
+    std::env::set_var("CONFIG_FILE", &path);
+    std::env::set_var("PORT", "9001");
+
+    let config = Config::init();
+    assert_eq!(config.port, 9001);
+    assert_eq!(config.jwt_expires_in, Duration::from_secs(12 * 60 * 60));
+    assert_eq!(config.jwt_maxage, 720);
+    assert_eq!(config.cors_allowed_methods.len(), 2);
+
+    let summary = config.summary();
+    assert!(summary.contains("JWT_SECRET = ***"));
+    assert!(!summary.contains("a_secret_long_enough_for_the_tests"));
+
+    // The secrets can be read from files, which take precedence over the config file
+    let secret_path = std::env::temp_dir().join(format!("jwt-secret-{}", Uuid::new_v4()));
+    std::fs::write(
+        &secret_path,
+        "a_secret_from_a_file_long_enough_for_the_tests\n",
+    )
+    .unwrap();
+    std::env::set_var("JWT_SECRET_FILE", &secret_path);
+
+    let config = Config::init();
+    assert_eq!(
+        config.jwt_secret,
+        "a_secret_from_a_file_long_enough_for_the_tests"
+    );
+
+    // The secret must be long enough
+    std::env::set_var("JWT_SECRET", "short");
+    assert!(std::panic::catch_unwind(Config::init).is_err());
+
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&secret_path).unwrap();
+}
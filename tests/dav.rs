@@ -0,0 +1,190 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, Response, StatusCode},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rust_axum_askama_htmx::app;
+use tower::ServiceExt;
+
+use common::{body_text, create_todo, register_and_login, send, setup_state};
+
+/// Sends a request of a CalDAV client logged in as `email`.
+async fn dav(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    email: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> Response<Body> {
+    let credentials = STANDARD.encode(format!("{}:secret123", email));
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Basic {}", credentials));
+
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+
+    app.clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap()
+}
+
+/// Href of the first calendar of the user, e.g. `/dav/1/`.
+async fn calendar_href(app: &Router, email: &str) -> String {
+    let response = dav(app, "PROPFIND", "/dav/", email, &[("depth", "1")], "").await;
+    assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+
+    body_text(response)
+        .await
+        .split("<d:href>")
+        .filter_map(|part| part.split_once("</d:href>"))
+        .map(|(href, _)| href.to_string())
+        .find(|href| href != "/dav/")
+        .expect("the calendar of the workspace")
+}
+
+fn vtodo(uid: &str, summary: &str, status: &str) -> String {
+    format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\nBEGIN:VTODO\r\n\
+        UID:{}\r\nDTSTAMP:20240708T090000Z\r\nSUMMARY:{}\r\nDUE;VALUE=DATE:20240710\r\n\
+        CATEGORIES:Family,Phone calls\r\nSTATUS:{}\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\n\
+        DESCRIPTION:Reminder\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nEND:VTODO\r\nEND:VCALENDAR\r\n",
+        uid, summary, status
+    )
+}
+
+#[tokio::test]
+async fn caldav_clients_sync_the_todos() {
+    let app = app(setup_state().await);
+    let token = register_and_login(&app, "alice@example.com").await;
+    let todo_id = create_todo(&app, &token, "Pay rent").await;
+    let calendar = calendar_href(&app, "alice@example.com").await;
+
+    // The todos of the app are listed with their data
+    let response = dav(
+        &app,
+        "REPORT",
+        &calendar,
+        "alice@example.com",
+        &[("depth", "1")],
+        r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"/>"#,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+    let body = body_text(response).await;
+    assert!(body.contains(&format!("{}{}.ics", calendar, todo_id)));
+    assert!(body.contains("SUMMARY:Pay rent"));
+    assert!(body.contains("STATUS:NEEDS-ACTION"));
+
+    // A todo created by the client is found under its own name
+    let uri = format!("{}call-mom.ics", calendar);
+    let response = dav(
+        &app,
+        "PUT",
+        &uri,
+        "alice@example.com",
+        &[("if-none-match", "*")],
+        &vtodo("call-mom-uid", "Call mom", "NEEDS-ACTION"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let response = dav(&app, "GET", &uri, "alice@example.com", &[], "").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = body_text(response).await;
+    assert!(body.contains("UID:call-mom-uid"));
+    assert!(body.contains("SUMMARY:Call mom"));
+    assert!(body.contains("DUE:20240710T090000Z"));
+    assert!(body.contains("CATEGORIES:family,phone-calls"));
+
+    let response = send(&app, "GET", "/todo/list", Some(&token), None).await;
+    assert!(body_text(response).await.contains("Call mom"));
+
+    // Completed from the client
+    let response = dav(
+        &app,
+        "PUT",
+        &uri,
+        "alice@example.com",
+        &[("if-match", &etag)],
+        &vtodo("call-mom-uid", "Call mom", "COMPLETED"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = dav(&app, "GET", &uri, "alice@example.com", &[], "").await;
+    let new_etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(body_text(response).await.contains("STATUS:COMPLETED"));
+
+    // A client that hasn't seen the change can't overwrite nor delete it
+    let response = dav(
+        &app,
+        "PUT",
+        &uri,
+        "alice@example.com",
+        &[("if-match", &etag)],
+        &vtodo("call-mom-uid", "Call dad", "NEEDS-ACTION"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = dav(
+        &app,
+        "DELETE",
+        &uri,
+        "alice@example.com",
+        &[("if-match", &etag)],
+        "",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = dav(
+        &app,
+        "DELETE",
+        &uri,
+        "alice@example.com",
+        &[("if-match", &new_etag)],
+        "",
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = dav(&app, "GET", &uri, "alice@example.com", &[], "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn caldav_requires_the_credentials_of_a_member() {
+    let app = app(setup_state().await);
+    register_and_login(&app, "alice@example.com").await;
+    register_and_login(&app, "bob@example.com").await;
+    let calendar = calendar_href(&app, "alice@example.com").await;
+
+    let response = send(&app, "PROPFIND", &calendar, None, None).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().contains_key(header::WWW_AUTHENTICATE));
+
+    let response = dav(&app, "PROPFIND", &calendar, "nobody@example.com", &[], "").await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Bob isn't a member of the workspace of Alice
+    let response = dav(&app, "PROPFIND", &calendar, "bob@example.com", &[], "").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
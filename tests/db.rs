@@ -0,0 +1,108 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use rust_axum_askama_htmx::{config::Config, db};
+use uuid::Uuid;
+
+/// A config on a new database file.
+fn file_config() -> (Config, PathBuf) {
+    let path = std::env::temp_dir().join(format!("todos-{}.db", Uuid::new_v4()));
+    let config = Config {
+        database_url: format!("sqlite://{}", path.display()),
+        ..common::config()
+    };
+
+    (config, path)
+}
+
+/// Deletes the database file and the ones of its WAL journal.
+fn remove_database(path: &Path) {
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+#[tokio::test]
+async fn sqlite_databases_are_opened_in_wal_mode() {
+    let (config, path) = file_config();
+    let pools = db::connect(&config).await.unwrap();
+    let pool = &pools.writer;
+
+    let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert_eq!(journal_mode, "wal");
+
+    let busy_timeout: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert_eq!(busy_timeout, 5000);
+
+    let foreign_keys: bool = sqlx::query_scalar("PRAGMA foreign_keys")
+        .fetch_one(pool)
+        .await
+        .unwrap();
+    assert!(foreign_keys);
+
+    pools.close().await;
+    remove_database(&path);
+}
+
+#[tokio::test]
+async fn sqlite_writes_go_through_a_single_connection() {
+    let (config, path) = file_config();
+    let pools = db::connect(&config).await.unwrap();
+
+    assert_eq!(pools.writer.options().get_max_connections(), 1);
+    assert_eq!(pools.reader.options().get_max_connections(), 10);
+
+    sqlx::query("INSERT INTO jobs (name, next_run_at) VALUES ('test', CURRENT_TIMESTAMP)")
+        .execute(&pools.writer)
+        .await
+        .unwrap();
+
+    // The reader sees the writes, but can't write
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM jobs")
+        .fetch_one(&pools.reader)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    let result = sqlx::query("DELETE FROM jobs").execute(&pools.reader).await;
+    assert!(result.is_err());
+
+    pools.close().await;
+    remove_database(&path);
+}
+
+#[tokio::test]
+async fn pools_are_tuned_by_the_config() {
+    let (config, path) = file_config();
+    let config = Config {
+        db_max_connections: 3,
+        db_acquire_timeout: Duration::from_secs(2),
+        db_idle_timeout: Duration::from_secs(60),
+        ..config
+    };
+    let pools = db::connect(&config).await.unwrap();
+
+    let options = pools.reader.options();
+    assert_eq!(options.get_max_connections(), 3);
+    assert_eq!(options.get_acquire_timeout(), Duration::from_secs(2));
+    assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(60)));
+    assert_eq!(
+        pools.writer.options().get_acquire_timeout(),
+        Duration::from_secs(2)
+    );
+
+    pools.close().await;
+    remove_database(&path);
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use rust_axum_askama_htmx::{
+    app,
+    config::{Config, RouteTimeouts},
+};
+use tower::ServiceExt;
+
+use common::{body_text, register_and_login, send, setup, setup_state_with};
+
+#[tokio::test]
+async fn unknown_paths_get_the_404_page() {
+    let app = setup().await;
+
+    let body = body_text(send(&app, "GET", "/nothing-here", None, None).await).await;
+    assert!(body.contains("Resource not found"));
+}
+
+#[tokio::test]
+async fn protected_pages_link_to_the_login_when_logged_out() {
+    let app = setup().await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", None, None).await).await;
+    assert!(body.contains("Status Unauthorized"));
+    assert!(body.contains(r#"href="/login""#));
+    assert!(body.contains("Go Login Page"));
+}
+
+#[tokio::test]
+async fn wrong_methods_get_the_405_page() {
+    let app = setup().await;
+
+    let response = send(&app, "DELETE", "/login", None, None).await;
+
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert!(response.headers().contains_key(header::ALLOW));
+    assert!(body_text(response).await.contains("Method not allowed"));
+}
+
+#[tokio::test]
+async fn htmx_requests_get_errors_as_a_toast() {
+    let app = setup().await;
+
+    let request = Request::delete("/todo/1/delete")
+        .header("hx-request", "true")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    assert_eq!(response.headers()["hx-retarget"], "#toasts");
+    let body = body_text(response).await;
+    assert!(body.contains("Error 401"));
+    assert!(!body.contains("<html"));
+}
+
+#[tokio::test]
+async fn heavy_routes_have_their_own_timeouts() {
+    let state = setup_state_with(Config {
+        route_timeouts: RouteTimeouts {
+            search: Duration::from_millis(100),
+            ..RouteTimeouts::default()
+        },
+        ..common::config()
+    })
+    .await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "timeouts@example.com").await;
+
+    // Holding the only connection of the database, the requests wait for it
+    let connection = state.pool.acquire().await.unwrap();
+    let export = tokio::spawn({
+        let app = app.clone();
+        let token = token.clone();
+        async move { send(&app, "GET", "/todo/export.md", Some(&token), None).await }
+    });
+
+    let response = send(&app, "GET", "/todo/search-live?q=rent", Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+    assert!(body_text(response)
+        .await
+        .contains("The request took too long to complete"));
+
+    // The export, with a longer timeout, is still waiting
+    drop(connection);
+    assert_eq!(export.await.unwrap().status(), StatusCode::OK);
+}
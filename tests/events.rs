@@ -0,0 +1,44 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::time::Duration;
+
+use rust_axum_askama_htmx::{app, events::DomainEvent};
+use tokio::sync::broadcast::Receiver;
+
+use common::{create_todo, register_and_login, send, setup_state};
+
+/// Waits for the next event published on the bus.
+async fn next_event(events: &mut Receiver<DomainEvent>) -> DomainEvent {
+    tokio::time::timeout(Duration::from_secs(1), events.recv())
+        .await
+        .expect("no event was published")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn services_publish_the_changes_of_the_todos() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let mut events = state.events.subscribe();
+
+    let token = register_and_login(&app, "alice@example.com").await;
+    assert!(matches!(
+        next_event(&mut events).await,
+        DomainEvent::UserRegistered { email, .. } if email == "alice@example.com"
+    ));
+
+    let id = create_todo(&app, &token, "Buy milk").await;
+    assert!(matches!(
+        next_event(&mut events).await,
+        DomainEvent::TodoCreated { todo } if todo.id == id
+    ));
+
+    let uri = format!("/todo/{}/delete", id);
+    send(&app, "DELETE", &uri, Some(&token), None).await;
+    assert!(matches!(
+        next_event(&mut events).await,
+        DomainEvent::TodoDeleted { todo_id, .. } if todo_id == id
+    ));
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::http::{header, StatusCode};
+
+use common::{body_text, register_and_login, send, setup};
+
+#[tokio::test]
+async fn feed_links_are_signed() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let response = send(&app, "GET", "/feed", Some(&token), None).await;
+    let url = response.headers()[header::LOCATION]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(url.contains("?expires="));
+
+    // Feed readers have no session, the signed URL is enough
+    let response = send(&app, "GET", &url, None, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_text(response).await.contains("<feed"));
+
+    let (path, query) = url.split_once('?').unwrap();
+    let response = send(&app, "GET", path, None, None).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // The signature only holds for its path and expiry
+    let tampered = query.replace("expires=", "expires=9");
+    let response = send(&app, "GET", &format!("{}?{}", path, tampered), None, None).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send(
+        &app,
+        "GET",
+        &format!("/feed/other.atom?{}", query),
+        None,
+        None,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
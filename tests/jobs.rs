@@ -0,0 +1,229 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use chrono::NaiveDateTime;
+use rust_axum_askama_htmx::{
+    app, backup, cleanup, config::Config, db, digest, jobs::JobRunner, AppState,
+};
+use sqlx::sqlite::SqlitePoolOptions;
+use uuid::Uuid;
+
+use common::{body_text, create_todo, register_and_login, send, setup_state};
+
+/// Starts a job counting its runs, and stops it once it ran or after a while.
+async fn run_counter(state: &Arc<AppState>, runs: &Arc<AtomicUsize>) {
+    let counter = runs.clone();
+    let before = runs.load(Ordering::SeqCst);
+
+    let jobs = JobRunner::new()
+        .register("counter", Duration::from_secs(3600), move |_| {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        })
+        .start(state.clone());
+
+    for _ in 0..20 {
+        if runs.load(Ordering::SeqCst) > before {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+
+    jobs.shutdown().await;
+}
+
+#[tokio::test]
+async fn jobs_are_not_run_again_before_they_are_due() {
+    let state = setup_state().await;
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    run_counter(&state, &runs).await;
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    // As after a restart, the schedule is kept in the database
+    run_counter(&state, &runs).await;
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn used_and_expired_tokens_are_purged() {
+    let state = setup_state().await;
+    register_and_login(&app(state.clone()), "alice@example.com").await;
+
+    for (token, expires_at, used_at) in [
+        ("expired", "2000-01-01 00:00:00", None),
+        ("used", "2999-01-01 00:00:00", Some("2000-01-01 00:00:00")),
+        ("valid", "2999-01-01 00:00:00", None),
+    ] {
+        sqlx::query(
+            "INSERT INTO user_tokens (token, user_id, kind, expires_at, used_at)
+            SELECT $1, id, 'verification', $2, $3 FROM users WHERE email = 'alice@example.com'",
+        )
+        .bind(token)
+        .bind(expires_at)
+        .bind(used_at)
+        .execute(&state.pool)
+        .await
+        .unwrap();
+    }
+
+    cleanup::purge_expired(state.clone()).await.unwrap();
+
+    let tokens: Vec<String> = sqlx::query_scalar("SELECT token FROM user_tokens")
+        .fetch_all(&state.pool)
+        .await
+        .unwrap();
+    // The verification token sent at the registration is still valid
+    assert!(tokens.contains(&"valid".to_string()));
+    assert!(!tokens.contains(&"expired".to_string()));
+    assert!(!tokens.contains(&"used".to_string()));
+
+    let response = send(&app(state), "GET", "/metrics", None, None).await;
+    let body = body_text(response).await;
+    assert!(body.contains("# TYPE todo_purged_rows_total counter"));
+    assert!(body.contains("todo_purged_rows_total{table=\"user_tokens\"} 2"));
+    assert!(body.contains("todo_purged_rows_total{table=\"sync_mutations\"} 0"));
+}
+
+#[tokio::test]
+async fn backups_keep_the_last_copies() {
+    // `VACUUM INTO` doesn't copy in-memory databases
+    let dir = std::env::temp_dir().join(format!("backups-{}", Uuid::new_v4()));
+    let config = Config {
+        database_url: format!("sqlite://{}/todos.db", dir.display()),
+        backup_dir: Some(dir.join("backups")),
+        backup_keep: 2,
+        ..common::config()
+    };
+    std::fs::create_dir_all(&dir).unwrap();
+    let pools = db::connect(&config).await.unwrap();
+    let state = Arc::new(AppState::new(pools.clone(), config).unwrap());
+    let app = app(state.clone());
+    let alice = register_and_login(&app, "alice@example.com").await;
+    let admin = register_and_login(&app, "admin@example.com").await;
+
+    backup::scheduled(state.clone()).await.unwrap();
+    backup::scheduled(state.clone()).await.unwrap();
+
+    let response = send(&app, "POST", "/admin/backup", Some(&alice), None).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    sqlx::query("UPDATE users SET is_admin = TRUE WHERE email = 'admin@example.com'")
+        .execute(&state.pool)
+        .await
+        .unwrap();
+
+    let response = send(&app, "POST", "/admin/backup", Some(&admin), None).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let backups = backup::list_backups(&dir.join("backups")).await.unwrap();
+    assert_eq!(backups.len(), 2);
+
+    // The copy is a database with the data of the app
+    let copy = SqlitePoolOptions::new()
+        .connect(&format!(
+            "sqlite://{}/backups/{}",
+            dir.display(),
+            backups[0].name
+        ))
+        .await
+        .unwrap();
+    let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+        .fetch_one(&copy)
+        .await
+        .unwrap();
+    assert_eq!(users, 2);
+
+    copy.close().await;
+    pools.close().await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// When the last digest of the user was sent.
+async fn digest_sent_at(state: &AppState, email: &str) -> Option<NaiveDateTime> {
+    sqlx::query_scalar("SELECT digest_sent_at FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_one(&state.pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn digests_are_sent_once_per_period() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let monday = |time: &str| {
+        NaiveDateTime::parse_from_str(&format!("2024-07-08 {}", time), "%Y-%m-%d %H:%M:%S").unwrap()
+    };
+
+    for (email, digest) in [
+        ("alice@example.com", "daily"),
+        ("bob@example.com", "weekly"),
+        ("carol@example.com", "off"),
+    ] {
+        let token = register_and_login(&app, email).await;
+        let form = format!("timezone=&date_order=dmy&clock=24h&digest={}", digest);
+        send(&app, "POST", "/settings/profile", Some(&token), Some(&form)).await;
+
+        let id = create_todo(&app, &token, "Pay+rent").await;
+        sqlx::query("UPDATE todos SET due_at = $1 WHERE id = $2")
+            .bind(monday("08:00:00"))
+            .bind(id)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+    }
+
+    // Too early, even for the daily digest
+    digest::send_due(state.clone(), monday("06:00:00"))
+        .await
+        .unwrap();
+    assert_eq!(digest_sent_at(&state, "alice@example.com").await, None);
+
+    digest::send_due(state.clone(), monday("09:00:00"))
+        .await
+        .unwrap();
+    assert_eq!(
+        digest_sent_at(&state, "alice@example.com").await,
+        Some(monday("09:00:00"))
+    );
+    assert_eq!(
+        digest_sent_at(&state, "bob@example.com").await,
+        Some(monday("09:00:00"))
+    );
+    assert_eq!(digest_sent_at(&state, "carol@example.com").await, None);
+
+    // Not again the same day, and the weekly one only on Mondays
+    digest::send_due(state.clone(), monday("18:00:00"))
+        .await
+        .unwrap();
+    assert_eq!(
+        digest_sent_at(&state, "alice@example.com").await,
+        Some(monday("09:00:00"))
+    );
+
+    let tuesday =
+        NaiveDateTime::parse_from_str("2024-07-09 09:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    digest::send_due(state.clone(), tuesday).await.unwrap();
+    assert_eq!(
+        digest_sent_at(&state, "alice@example.com").await,
+        Some(tuesday)
+    );
+    assert_eq!(
+        digest_sent_at(&state, "bob@example.com").await,
+        Some(monday("09:00:00"))
+    );
+}
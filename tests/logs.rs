@@ -0,0 +1,101 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use rust_axum_askama_htmx::app;
+use serde_json::Value;
+use tower::ServiceExt;
+use tracing_subscriber::fmt::MakeWriter;
+
+use common::{register_and_login, setup_state};
+
+/// The JSON lines logged by the tests.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Logs {
+    fn lines(&self) -> Vec<Value> {
+        let logs = self.0.lock().unwrap();
+
+        String::from_utf8_lossy(&logs)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+impl Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Logs {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn request_logs_have_the_user_id() {
+    let logs = Logs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_current_span(true)
+        .with_writer(logs.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+    let user_id: String = sqlx::query_scalar("SELECT id FROM users")
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+
+    // Even when the request fails after the user is known
+    let request = Request::builder()
+        .uri("/todo/list")
+        .header("x-request-id", "the-request")
+        .header("x-workspace", "999")
+        .header(header::COOKIE, format!("token={}", token))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let lines = logs.lines();
+    let span = lines
+        .iter()
+        .filter(|line| line["span"]["request_id"] == "the-request")
+        .map(|line| &line["span"])
+        .next_back()
+        .expect("the request must be logged");
+    assert_eq!(span["name"], "request");
+    assert_eq!(span["route"], "/todo/list");
+    assert_eq!(span["user_id"], user_id.as_str());
+
+    // The logins are logged with the user too
+    let logged_in = lines.iter().any(|line| {
+        line["span"]["route"] == "/login" && line["span"]["user_id"] == user_id.as_str()
+    });
+    assert!(logged_in);
+}
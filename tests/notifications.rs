@@ -0,0 +1,88 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use std::time::Duration;
+
+use axum::{
+    http::{header, StatusCode},
+    Router,
+};
+use rust_axum_askama_htmx::app;
+
+use common::{body_text, create_todo, register_and_login, send, setup_state};
+
+/// Polls the bell of the user until it shows `unread` notifications,
+/// as they are added by a subscriber of the domain events.
+async fn wait_for_unread(app: &Router, token: &str, unread: usize) -> String {
+    let badge = format!(">{}</span>", unread);
+
+    for _ in 0..50 {
+        let response = send(app, "GET", "/notifications/bell", Some(token), None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_text(response).await;
+        if body.contains(&badge) || (unread == 0 && !body.contains("badge")) {
+            return body;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    panic!("the bell never showed {} unread notifications", unread);
+}
+
+#[tokio::test]
+async fn members_are_notified_of_invites_and_mentions() {
+    let app = app(setup_state().await);
+    let alice = register_and_login(&app, "alice@example.com").await;
+    let bob = register_and_login(&app, "bob@example.com").await;
+
+    wait_for_unread(&app, &bob, 0).await;
+
+    let response = send(
+        &app,
+        "POST",
+        "/workspaces/members",
+        Some(&alice),
+        Some("email=bob@example.com"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    wait_for_unread(&app, &bob, 1).await;
+
+    // Every test user is named "tester"; the author isn't notified
+    create_todo(&app, &alice, "Ask+@tester").await;
+    wait_for_unread(&app, &bob, 2).await;
+    wait_for_unread(&app, &alice, 0).await;
+
+    let body = body_text(send(&app, "GET", "/notifications", Some(&bob), None).await).await;
+    assert!(body.contains("You were added to the workspace"));
+    assert!(body.contains("You were mentioned in"));
+
+    // The newest first: the mention
+    let id: i64 = body
+        .split("/notifications/")
+        .find_map(|rest| rest.split('/').next()?.parse().ok())
+        .expect("a notification");
+    let uri = format!("/notifications/{}/read", id);
+    let response = send(&app, "POST", &uri, Some(&bob), None).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/todo/list");
+    wait_for_unread(&app, &bob, 1).await;
+
+    // Nobody else can read them
+    let response = send(&app, "POST", &uri, Some(&alice), None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = send(&app, "POST", "/notifications/read-all", Some(&bob), None).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    wait_for_unread(&app, &bob, 0).await;
+}
+
+#[tokio::test]
+async fn the_bell_stops_polling_once_logged_out() {
+    let app = app(setup_state().await);
+
+    let response = send(&app, "GET", "/notifications/bell", None, None).await;
+    assert_eq!(response.status().as_u16(), 286);
+}
@@ -0,0 +1,54 @@
+#![cfg(all(feature = "sqlite", unix))]
+
+mod common;
+
+use std::{os::unix::fs::PermissionsExt, time::Duration};
+
+use rust_axum_askama_htmx::{
+    config::{Config, ListenAddress},
+    db, run,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn the_server_listens_on_unix_sockets() {
+    let dir = std::env::temp_dir().join(format!("server-{}", Uuid::new_v4()));
+    std::fs::create_dir(&dir).unwrap();
+    let socket_path = dir.join("todos.sock");
+
+    let config = Config {
+        database_url: format!("sqlite://{}", dir.join("todos.db").display()),
+        listen: vec![
+            ListenAddress::Unix(socket_path.clone()),
+            ListenAddress::Tcp("127.0.0.1:0".parse().unwrap()),
+        ],
+        unix_socket_mode: 0o600,
+        ..common::config()
+    };
+    let pools = db::connect(&config).await.unwrap();
+    tokio::spawn(run(config, pools));
+
+    while !socket_path.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let mode = std::fs::metadata(&socket_path)
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let mut stream = UnixStream::connect(&socket_path).await.unwrap();
+    stream
+        .write_all(b"GET /healthchecker HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
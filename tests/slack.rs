@@ -0,0 +1,101 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, Response, StatusCode},
+    Router,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rust_axum_askama_htmx::{app, config::Config};
+use sha2::Sha256;
+use tower::ServiceExt;
+
+use common::{body_text, register_and_login, send, setup_state_with};
+
+const SECRET: &str = "slack_signing_secret";
+
+/// Posts a slash command signed as Slack would, `age` seconds ago.
+async fn command(app: &Router, text: &str, age: i64) -> Response<Body> {
+    let body = format!("team_id=T1&user_id=U1&command=%2Ftodo&text={}", text);
+    let timestamp = (Utc::now().timestamp() - age).to_string();
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+    let signature = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/integrations/slack/command")
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header("x-slack-request-timestamp", timestamp)
+        .header("x-slack-signature", format!("v0={}", signature))
+        .body(Body::from(body))
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn slack_users_manage_the_todos_of_their_linked_account() {
+    let app = app(setup_state_with(Config {
+        slack_signing_secret: Some(SECRET.to_string()),
+        ..common::config()
+    })
+    .await);
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    // Not signed by Slack
+    let form = "team_id=T1&user_id=U1&text=list";
+    let response = send(
+        &app,
+        "POST",
+        "/integrations/slack/command",
+        None,
+        Some(form),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Replayed
+    let response = command(&app, "list", 3600).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // Unknown Slack users get a link to their account
+    let response = command(&app, "list", 0).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_text(response).await;
+    let start = body.find("/integrations/slack/link/T1/U1?").unwrap();
+    let link = &body[start..start + body[start..].find('|').unwrap()];
+
+    let response = send(&app, "GET", link, Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/settings/profile");
+
+    let response = command(&app, "add+Milk+%26+eggs+%23shop", 0).await;
+    assert!(body_text(response)
+        .await
+        .contains("Added *Milk &amp; eggs*"));
+
+    let body = body_text(command(&app, "list", 0).await).await;
+    assert!(body.contains("\"response_type\":\"ephemeral\""));
+    assert!(body.contains("Milk &amp; eggs"));
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Milk &amp; eggs"));
+}
+
+#[tokio::test]
+async fn the_slack_command_is_disabled_without_a_secret() {
+    let app = app(common::setup_state().await);
+
+    let response = command(&app, "list", 0).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
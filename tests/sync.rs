@@ -0,0 +1,110 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use rust_axum_askama_htmx::app;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use common::{body_text, create_todo, register_and_login, send, setup_state};
+
+/// Sends the changes queued offline, returning the status of each one.
+async fn sync(app: &Router, token: &str, mutations: &Value) -> Vec<Value> {
+    let request = Request::post("/api/v1/sync")
+        .header(header::COOKIE, format!("token={}", token))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json!({ "mutations": mutations }).to_string()))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    body["results"].as_array().unwrap().clone()
+}
+
+fn statuses(results: &[Value]) -> Vec<&str> {
+    results
+        .iter()
+        .map(|result| result["status"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn offline_changes_are_applied_once() {
+    let app = app(setup_state().await);
+    let token = register_and_login(&app, "alice@example.com").await;
+    let todo_id = create_todo(&app, &token, "Pay rent").await;
+
+    let mutations = json!([
+        { "type": "quick_add", "id": "c1", "text": "Buy milk #shopping" },
+        // Marks as done the todo created by the previous change
+        { "type": "toggle", "id": "c2", "todo": "c1" },
+        { "type": "update", "id": "c3", "todo": todo_id, "title": "Pay the rent", "status": false },
+        { "type": "create", "id": "c4", "title": "   " },
+        { "type": "delete", "id": "c5", "todo": 999 },
+    ]);
+
+    let results = sync(&app, &token, &mutations).await;
+    assert_eq!(
+        statuses(&results),
+        ["applied", "applied", "applied", "rejected", "rejected"]
+    );
+    let created_id = results[0]["todo_id"].as_i64().unwrap();
+    assert_eq!(results[1]["todo_id"], created_id);
+    assert_eq!(results[2]["todo_id"], todo_id);
+
+    // Sent again, e.g. after the response was lost
+    let results = sync(&app, &token, &mutations).await;
+    assert_eq!(
+        statuses(&results),
+        [
+            "duplicate",
+            "duplicate",
+            "duplicate",
+            "rejected",
+            "rejected"
+        ]
+    );
+    assert_eq!(results[0]["todo_id"], created_id);
+
+    let response = send(&app, "GET", "/todo/list?status=done", Some(&token), None).await;
+    let body = body_text(response).await;
+    assert_eq!(body.matches("Buy milk").count(), 1);
+    assert!(body.contains("#shopping"));
+
+    let response = send(&app, "GET", "/todo/list", Some(&token), None).await;
+    assert!(body_text(response).await.contains("Pay the rent"));
+}
+
+#[tokio::test]
+async fn offline_changes_need_a_logged_in_user() {
+    let app = app(setup_state().await);
+    let token = register_and_login(&app, "alice@example.com").await;
+    let other = register_and_login(&app, "bob@example.com").await;
+    let todo_id = create_todo(&app, &token, "Pay rent").await;
+
+    let request = Request::post("/api/v1/sync")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(r#"{"mutations":[]}"#))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert!(body_text(response).await.contains("You are not logged in"));
+
+    // Bob can't change the todos of the workspace of Alice
+    let results = sync(
+        &app,
+        &other,
+        &json!([{ "type": "delete", "id": "c1", "todo": todo_id }]),
+    )
+    .await;
+    assert_eq!(statuses(&results), ["rejected"]);
+
+    let response = send(&app, "GET", "/todo/list", Some(&token), None).await;
+    assert!(body_text(response).await.contains("Pay rent"));
+}
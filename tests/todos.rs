@@ -0,0 +1,876 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, Response, StatusCode},
+    Router,
+};
+use rust_axum_askama_htmx::{app, config::Config};
+use tower::ServiceExt;
+
+use common::{
+    body_text, create_todo, hx_trigger, register_and_login, send, setup, setup_state,
+    setup_state_with,
+};
+
+#[tokio::test]
+async fn todo_crud() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let id = create_todo(&app, &token, "Buy+milk").await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Buy milk"));
+
+    let response = send(
+        &app,
+        "PATCH",
+        &format!("/todo/{}", id),
+        Some(&token),
+        Some("title=Buy+oat+milk&description=test&status=on"),
+    )
+    .await;
+    assert!(hx_trigger(&response).get("todoUpdated").is_some());
+    let body = body_text(response).await;
+    assert!(body.contains("Buy oat milk"));
+    assert!(body.contains("Mark as open"));
+
+    let response = send(
+        &app,
+        "DELETE",
+        &format!("/todo/{}/delete", id),
+        Some(&token),
+        None,
+    )
+    .await;
+    let trigger = hx_trigger(&response);
+    assert!(trigger.get("todoDeleted").is_some());
+    assert_eq!(trigger["toast"]["text"], "Task successfully deleted!!");
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(!body.contains("Buy oat milk"));
+}
+
+#[tokio::test]
+async fn toggle_marks_todos_as_done_and_open() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let id = create_todo(&app, &token, "Buy+milk").await;
+    let uri = format!("/todo/{}/toggle", id);
+
+    let response = send(&app, "PATCH", &uri, Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        hx_trigger(&response)["toast"]["text"],
+        "Task marked as done!!"
+    );
+    assert!(body_text(response).await.contains("Mark as open"));
+
+    let completed_at: Option<String> =
+        sqlx::query_scalar("SELECT completed_at FROM todos WHERE id = $1")
+            .bind(id)
+            .fetch_one(&state.pool)
+            .await
+            .unwrap();
+    assert!(completed_at.is_some());
+
+    let response = send(&app, "PATCH", &uri, Some(&token), None).await;
+    assert_eq!(
+        hx_trigger(&response)["toast"]["text"],
+        "Task marked as open!!"
+    );
+    assert!(body_text(response).await.contains("Mark as done"));
+
+    let response = send(&app, "PATCH", "/todo/999/toggle", Some(&token), None).await;
+    assert!(response.headers().contains_key("hx-retarget"));
+}
+
+#[tokio::test]
+async fn old_todo_urls_are_redirected() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let response = send(&app, "GET", "/edit?id=7", Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.headers()[header::LOCATION], "/todo/7/edit");
+
+    let response = send(&app, "PATCH", "/edit?id=7", Some(&token), Some("title=x")).await;
+    assert_eq!(response.headers()[header::LOCATION], "/todo/7");
+
+    let response = send(&app, "DELETE", "/delete?id=7", Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.headers()[header::LOCATION], "/todo/7/delete");
+}
+
+#[tokio::test]
+async fn forms_without_javascript_override_the_method() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    let id = create_todo(&app, &token, "Buy+milk").await;
+
+    let response = send(
+        &app,
+        "POST",
+        &format!("/todo/{}/delete", id),
+        Some(&token),
+        Some("_method=DELETE"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/todo/list");
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(!body.contains("Buy milk"));
+}
+
+#[tokio::test]
+async fn todo_without_title_is_rejected() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let response = send(
+        &app,
+        "POST",
+        "/create",
+        Some(&token),
+        Some("title=+&description=Keep+me"),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["hx-retarget"], "#modal");
+    let body = body_text(response).await;
+    assert!(body.contains("You must enter a title"));
+    assert!(body.contains("Keep me"));
+}
+
+#[tokio::test]
+async fn users_cannot_see_todos_of_others() {
+    let app = setup().await;
+    let alice = register_and_login(&app, "alice@example.com").await;
+    let bob = register_and_login(&app, "bob@example.com").await;
+
+    create_todo(&app, &alice, "Alice+secret").await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&bob), None).await).await;
+    assert!(!body.contains("Alice secret"));
+}
+
+#[tokio::test]
+async fn users_cannot_edit_or_delete_todos_of_others() {
+    let app = setup().await;
+    let alice = register_and_login(&app, "alice@example.com").await;
+    let bob = register_and_login(&app, "bob@example.com").await;
+
+    let id = create_todo(&app, &alice, "Alice+todo").await;
+
+    let body =
+        body_text(send(&app, "GET", &format!("/todo/{}/edit", id), Some(&bob), None).await).await;
+    assert!(!body.contains("Alice todo"));
+
+    let response = send(
+        &app,
+        "PATCH",
+        &format!("/todo/{}", id),
+        Some(&bob),
+        Some("title=Hacked&description="),
+    )
+    .await;
+    assert!(body_text(response).await.contains("Error 404"));
+
+    let response = send(
+        &app,
+        "POST",
+        &format!("/todo/revert?id={}&version=1", id),
+        Some(&bob),
+        None,
+    )
+    .await;
+    assert!(body_text(response).await.contains("Error 404"));
+
+    let response = send(
+        &app,
+        "DELETE",
+        &format!("/todo/{}/delete", id),
+        Some(&bob),
+        None,
+    )
+    .await;
+    assert!(body_text(response).await.contains("Error 404"));
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&alice), None).await).await;
+    assert!(body.contains("Alice todo"));
+    assert!(!body.contains("Hacked"));
+}
+
+#[tokio::test]
+async fn invalid_edit_shows_the_errors_in_the_modal() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let id = create_todo(&app, &token, "Buy+milk").await;
+
+    let response = send(
+        &app,
+        "PATCH",
+        &format!("/todo/{}", id),
+        Some(&token),
+        Some("title=&description=Oat+milk"),
+    )
+    .await;
+
+    assert_eq!(response.headers()["hx-retarget"], "#modal");
+    let body = body_text(response).await;
+    assert!(body.contains(&format!("Update Task #{}", id)));
+    assert!(body.contains("You must enter a title"));
+    assert!(body.contains("Oat milk"));
+}
+
+#[tokio::test]
+async fn markup_is_removed_from_todos() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let form = "title=%3Cb%3EBold%3C%2Fb%3E+task&description=%3Cscript%3Ealert(1)%3C%2Fscript%3E";
+    send(&app, "POST", "/create", Some(&token), Some(form)).await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Bold task"));
+    assert!(!body.contains("&lt;b&gt;"));
+    assert!(!body.contains("&lt;script"));
+}
+
+#[tokio::test]
+async fn descriptions_are_rendered_as_markdown() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let form = "title=Read&description=**Chapter+one**+of+[the+book](https://example.com)\
+                +and+[this](javascript:alert(1))";
+    send(&app, "POST", "/create", Some(&token), Some(form)).await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("<strong>Chapter one</strong>"));
+    assert!(body.contains(r#"<a href="https://example.com">the book</a>"#));
+    assert!(!body.contains("javascript:"));
+}
+
+#[tokio::test]
+async fn due_dates_follow_the_date_preferences() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let form = "timezone=Asia/Tokyo&date_order=ymd&clock=12h";
+    send(&app, "POST", "/settings/profile", Some(&token), Some(form)).await;
+
+    let form = "text=Pay+rent+2030-03-01+5pm";
+    let response = send(&app, "POST", "/todo/quick-add", Some(&token), Some(form)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Due: 2030-03-01 05:00 PM +0900"));
+}
+
+#[tokio::test]
+async fn todo_creation_is_rate_limited_per_user() {
+    let app = setup().await;
+    let alice = register_and_login(&app, "alice@example.com").await;
+    let bob = register_and_login(&app, "bob@example.com").await;
+
+    // The tests allow 5 creations per minute
+    for title in ["Spam", "Eggs", "Ham", "Beans", "Toast"] {
+        create_todo(&app, &alice, title).await;
+    }
+
+    let response = send(
+        &app,
+        "POST",
+        "/todo/quick-add",
+        Some(&alice),
+        Some("text=Spam"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().contains_key("retry-after"));
+    assert!(body_text(response)
+        .await
+        .contains("creating tasks too fast"));
+
+    // Other users aren't affected
+    create_todo(&app, &bob, "Not+spam").await;
+}
+
+#[tokio::test]
+async fn todo_list_is_loaded_a_page_at_a_time() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    // Created in the same second, so the pages are split by id
+    for i in 1..=60 {
+        sqlx::query(
+            "INSERT INTO todos (created_by, workspace_id, title, description)
+            SELECT users.id, workspace_members.workspace_id, $1, '' FROM users
+            JOIN workspace_members ON workspace_members.user_id = users.id
+            WHERE email = 'alice@example.com'",
+        )
+        .bind(format!("Task {}", i))
+        .execute(&state.pool)
+        .await
+        .unwrap();
+    }
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    // 50 todos and the row that loads the next page
+    assert_eq!(body.matches("<tr id=\"todo-").count(), 51);
+    assert!(body.contains("Task 60"));
+    assert!(!body.contains("Task 10"));
+
+    let start = body.find("/todo/list/page?after=").unwrap();
+    let end = start + body[start..].find('"').unwrap();
+    let next = &body[start..end];
+
+    let body = body_text(send(&app, "GET", next, Some(&token), None).await).await;
+    assert_eq!(body.matches("<tr id=\"todo-").count(), 10);
+    assert!(body.contains("Task 10"));
+    assert!(!body.contains("Task 11"));
+    assert!(!body.contains("todo-more"));
+    assert!(!body.contains("table-xs"));
+
+    // Smaller pages, with compact rows
+    let form = "timezone=&date_order=dmy&clock=24h&page_size=25&density=compact";
+    send(&app, "POST", "/settings/profile", Some(&token), Some(form)).await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert_eq!(body.matches("<tr id=\"todo-").count(), 26);
+    assert!(body.contains("table-xs"));
+
+    let start = body.find("/todo/list/page?after=").unwrap();
+    let end = start + body[start..].find('"').unwrap();
+    let body = body_text(send(&app, "GET", &body[start..end], Some(&token), None).await).await;
+    assert_eq!(body.matches("<tr id=\"todo-").count(), 26);
+    assert!(body.contains("Task 35"));
+    assert!(!body.contains("Task 36"));
+
+    // The pages follow the default sort of the user
+    sqlx::query("UPDATE todos SET priority = 3 WHERE title IN ('Task 1', 'Task 2')")
+        .execute(&state.pool)
+        .await
+        .unwrap();
+    let form = "timezone=&date_order=dmy&clock=24h&page_size=25&default_sort=priority";
+    send(&app, "POST", "/settings/profile", Some(&token), Some(form)).await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert_eq!(body.matches("<tr id=\"todo-").count(), 26);
+    assert!(body.find("Task 2\n").unwrap() < body.find("Task 60").unwrap());
+    assert!(body.contains("Task 38"));
+    assert!(!body.contains("Task 37"));
+
+    let start = body.find("/todo/list/page?after=").unwrap();
+    let end = start + body[start..].find('"').unwrap();
+    let next = body[start..end].replace("&amp;", "&");
+    assert!(next.ends_with("&sort=priority"));
+    let body = body_text(send(&app, "GET", &next, Some(&token), None).await).await;
+    assert_eq!(body.matches("<tr id=\"todo-").count(), 26);
+    assert!(body.contains("Task 37"));
+    assert!(body.contains("Task 13"));
+    assert!(!body.contains("Task 38"));
+    assert!(!body.contains("Task 12"));
+}
+
+#[tokio::test]
+async fn edits_of_an_outdated_todo_are_refused() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    let id = create_todo(&app, &token, "Buy+milk").await;
+    let uri = format!("/todo/{}", id);
+
+    // Two modals opened on the first version
+    let form = "title=Buy+oat+milk&description=test&version=0";
+    let response = send(&app, "PATCH", &uri, Some(&token), Some(form)).await;
+    assert!(hx_trigger(&response).get("todoUpdated").is_some());
+
+    let form = "title=Buy+soy+milk&description=test&version=0";
+    let response = send(&app, "PATCH", &uri, Some(&token), Some(form)).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(response.headers()["hx-retarget"], "#modal");
+    let body = body_text(response).await;
+    assert!(body.contains("Buy oat milk"));
+    assert!(body.contains("name=\"version\" value=\"1\""));
+
+    // The newer version can be edited
+    let form = "title=Buy+soy+milk&description=test&version=1";
+    let response = send(&app, "PATCH", &uri, Some(&token), Some(form)).await;
+    assert!(hx_trigger(&response).get("todoUpdated").is_some());
+}
+
+#[tokio::test]
+async fn actions_on_a_todo_return_its_row_with_a_toast() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    let id = create_todo(&app, &token, "Write+report").await;
+
+    let uri = format!("/todo/timer/start?id={}", id);
+    let response = send(&app, "POST", &uri, Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let trigger = hx_trigger(&response);
+    assert_eq!(trigger["toast"]["level"], "success");
+    assert_eq!(trigger["toast"]["text"], "Timer started!!");
+    let body = body_text(response).await;
+    assert!(body.contains(&format!("<tr id=\"todo-{}\"", id)));
+    assert!(body.contains("(running)"));
+
+    let uri = format!("/todo/subtasks?id={}", id);
+    let response = send(&app, "POST", &uri, Some(&token), Some("title=Draft")).await;
+    assert_eq!(
+        hx_trigger(&response)["toast"]["text"],
+        "Subtask added successfully!!"
+    );
+    assert!(body_text(response).await.contains("Draft"));
+}
+
+/// Uploads an export file to the import preview.
+async fn upload_export(app: &Router, token: &str, file_name: &str, data: &[u8]) -> Response<Body> {
+    let mut body = format!(
+        "--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: text/csv\r\n\r\n",
+        file_name
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/settings/import/preview")
+        .header(header::COOKIE, format!("token={}", token))
+        .header(
+            header::CONTENT_TYPE,
+            "multipart/form-data; boundary=boundary",
+        )
+        .body(Body::from(body))
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn imports_only_accept_small_text_files() {
+    let state = setup_state_with(Config {
+        max_upload_size: 1024,
+        ..common::config()
+    })
+    .await;
+    let app = app(state);
+    let token = register_and_login(&app, "alice@example.com").await;
+
+    let response = upload_export(&app, &token, "tasks.csv", b"TYPE,CONTENT\ntask,Buy milk\n").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_text(response).await.contains("Buy milk"));
+
+    // A PNG, whatever its name says
+    let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    let response = upload_export(&app, &token, "tasks.csv", png).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/settings/import");
+
+    let large = format!("TYPE,CONTENT\n{}", "task,Buy milk\n".repeat(100));
+    let response = upload_export(&app, &token, "tasks.csv", large.as_bytes()).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(response.headers()[header::LOCATION], "/settings/import");
+}
+
+/// Gets the todo list, with the `ETag` of a previous response.
+async fn get_list(app: &Router, token: &str, etag: Option<&str>) -> Response<Body> {
+    let mut request = Request::builder()
+        .uri("/todo/list")
+        .header("x-timezone", "UTC")
+        .header(header::COOKIE, format!("token={}", token));
+    if let Some(etag) = etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+
+    app.clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn unchanged_lists_are_not_sent_again() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    create_todo(&app, &token, "Buy+milk").await;
+
+    // The first visit shows the login message
+    get_list(&app, &token, None).await;
+    let response = get_list(&app, &token, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let response = get_list(&app, &token, Some(&etag)).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert!(body_text(response).await.is_empty());
+
+    create_todo(&app, &token, "Buy+bread").await;
+
+    let response = get_list(&app, &token, Some(&etag)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers()[header::ETAG], etag.as_str());
+    assert!(body_text(response).await.contains("Buy bread"));
+}
+
+#[tokio::test]
+async fn cached_rows_follow_the_changes() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+    let id = create_todo(&app, &token, "Write+report").await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Write report"));
+
+    // Subtasks don't go through the domain events of the todos
+    let uri = format!("/todo/subtasks?id={}", id);
+    send(&app, "POST", &uri, Some(&token), Some("title=Draft")).await;
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Draft"));
+
+    // Neither do the changes made to the database directly
+    sqlx::query("UPDATE todos SET title = 'Write summary' WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .unwrap();
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Write summary"));
+    assert!(!body.contains("Write report"));
+}
+
+#[tokio::test]
+async fn todos_are_exported_as_a_markdown_checklist() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    create_todo(&app, &token, "Pay+*rent*").await;
+    let id = create_todo(&app, &token, "Write+report").await;
+
+    let uri = format!("/todo/subtasks?id={}", id);
+    send(&app, "POST", &uri, Some(&token), Some("title=Draft")).await;
+    send(
+        &app,
+        "PATCH",
+        &format!("/todo/{}/toggle", id),
+        Some(&token),
+        None,
+    )
+    .await;
+
+    let response = send(&app, "GET", "/todo/export.md", Some(&token), None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers()[header::CONTENT_DISPOSITION]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment; filename="));
+    let body = body_text(response).await;
+    assert!(body.contains("## To do (1)\n\n- [ ] Pay \\*rent\\*\n"));
+    assert!(body.contains("## Done (1)\n\n- [x] Write report — done "));
+    assert!(body.contains("  - [ ] Draft\n"));
+
+    // Only the todos of the filter of the list
+    let response = send(
+        &app,
+        "GET",
+        "/todo/export.md?status=open",
+        Some(&token),
+        None,
+    )
+    .await;
+    let body = body_text(response).await;
+    assert!(body.contains("Pay \\*rent\\*"));
+    assert!(!body.contains("Write report"));
+}
+
+/// The items of the search as you type.
+async fn search_live(app: &Router, token: &str, q: &str) -> String {
+    let uri = format!("/todo/search-live?q={}", q);
+    body_text(send(app, "GET", &uri, Some(token), None).await).await
+}
+
+#[tokio::test]
+async fn todos_are_searched_as_you_type() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    create_todo(&app, &token, "Pay+rent").await;
+    let id = create_todo(&app, &token, "Rent+a+car").await;
+    create_todo(&app, &token, "Buy+parental+gifts").await;
+
+    // Only the words that start with the terms match
+    let body = search_live(&app, &token, "REN").await;
+    assert_eq!(body.matches("<li>").count(), 2);
+    assert!(body.contains(">Ren</mark>t a car"));
+    assert!(
+        body.contains("Pay <mark class=\"bg-warning text-warning-content rounded-sm\">ren</mark>t")
+    );
+    assert!(!body.contains("parental"));
+
+    assert!(search_live(&app, &token, "ren+car")
+        .await
+        .contains(&format!("#{}", id)));
+    assert!(search_live(&app, &token, "bike")
+        .await
+        .contains("No tasks found"));
+    assert!(!search_live(&app, &token, "+%22*").await.contains("<li"));
+
+    // The index follows the changes of the todos
+    send(
+        &app,
+        "PATCH",
+        &format!("/todo/{}", id),
+        Some(&token),
+        Some("title=Book+a+bike&description="),
+    )
+    .await;
+    assert!(search_live(&app, &token, "bike").await.contains("Book a"));
+    assert_eq!(
+        search_live(&app, &token, "rent")
+            .await
+            .matches("<li>")
+            .count(),
+        1
+    );
+
+    let other = register_and_login(&app, "bob@example.com").await;
+    let response = send(&app, "GET", "/todo/search-live?q=rent", Some(&other), None).await;
+    assert!(!body_text(response).await.contains("Pay"));
+}
+
+/// The options of the datalist of the tags input.
+async fn suggest_tags(app: &Router, token: &str, q: &str) -> String {
+    let uri = format!("/tags/suggest?q={}", q);
+    body_text(send(app, "GET", &uri, Some(token), None).await).await
+}
+
+#[tokio::test]
+async fn the_list_is_filtered_by_the_exact_tag_and_words() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    for (title, tags) in [
+        ("Report", "q3_report"),
+        ("Groceries", "q3-report"),
+        ("Sale+100%25+off", ""),
+        ("Sale+1000+off", ""),
+    ] {
+        let form = format!("title={}&description=test&tags={}", title, tags);
+        send(&app, "POST", "/create", Some(&token), Some(&form)).await;
+    }
+
+    // The wildcards of the searches are taken as they are
+    let uri = "/todo/list?tag=q3_report";
+    let body = body_text(send(&app, "GET", uri, Some(&token), None).await).await;
+    assert!(body.contains("Report"));
+    assert!(!body.contains("Groceries"));
+
+    let uri = "/todo/list?q=100%25";
+    let body = body_text(send(&app, "GET", uri, Some(&token), None).await).await;
+    assert!(body.contains("Sale 100% off"));
+    assert!(!body.contains("Sale 1000 off"));
+}
+
+#[tokio::test]
+async fn tags_are_suggested_by_usage() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    for (title, tags) in [
+        ("Report", "%23Work+urgent"),
+        ("Groceries", "work,home"),
+        ("Run", "workout"),
+    ] {
+        let form = format!("title={}&description=test&tags={}", title, tags);
+        let response = send(&app, "POST", "/create", Some(&token), Some(&form)).await;
+        assert!(hx_trigger(&response).get("todoCreated").is_some());
+    }
+
+    let body = suggest_tags(&app, &token, "WO").await;
+    let work = body.find("value=\"work\"").expect("the most used tag");
+    let workout = body.find("value=\"workout\"").expect("the other tag");
+    assert!(work < workout);
+    assert!(body.contains("#work (2)"));
+    assert!(!body.contains("urgent"));
+
+    // Only the last tag is completed, the ones typed are left out
+    let body = suggest_tags(&app, &token, "work+").await;
+    assert!(body.contains("value=\"work urgent\""));
+    assert!(body.contains("value=\"work home\""));
+    assert!(!body.contains("value=\"work work\""));
+    assert!(!suggest_tags(&app, &token, "%25").await.contains("<option"));
+
+    // The edit modal changes the tags, the forms without them keep them
+    let id = create_todo(&app, &token, "Errands").await;
+    send(
+        &app,
+        "PATCH",
+        &format!("/todo/{}", id),
+        Some(&token),
+        Some("title=Errands&description=test&tags=%23Shopping+list"),
+    )
+    .await;
+    send(
+        &app,
+        "PATCH",
+        &format!("/todo/{}", id),
+        Some(&token),
+        Some("title=Errands&description=edited"),
+    )
+    .await;
+    assert!(suggest_tags(&app, &token, "sh")
+        .await
+        .contains("value=\"shopping\""));
+
+    let other = register_and_login(&app, "bob@example.com").await;
+    assert!(!suggest_tags(&app, &other, "w").await.contains("<option"));
+}
+
+#[tokio::test]
+async fn the_list_has_the_default_sort_of_the_user() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    let form = "text=Urgent+task+!high";
+    send(&app, "POST", "/todo/quick-add", Some(&token), Some(form)).await;
+    create_todo(&app, &token, "Later+task").await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.find("Later task").unwrap() < body.find("Urgent task").unwrap());
+
+    let form = "timezone=&date_order=dmy&clock=24h&default_sort=priority";
+    send(&app, "POST", "/settings/profile", Some(&token), Some(form)).await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.find("Urgent task").unwrap() < body.find("Later task").unwrap());
+
+    // The one chosen in the list wins
+    let uri = "/todo/list?sort=newest";
+    let body = body_text(send(&app, "GET", uri, Some(&token), None).await).await;
+    assert!(body.find("Later task").unwrap() < body.find("Urgent task").unwrap());
+}
+
+#[tokio::test]
+async fn todos_show_how_long_ago_they_were_created() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+    let id = create_todo(&app, &token, "Buy+milk").await;
+    create_todo(&app, &token, "Pay+rent").await;
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert_eq!(body.matches("Created just now").count(), 2);
+
+    let three_days_ago = chrono::Utc::now().naive_utc() - chrono::Duration::days(3);
+    let long_ago = chrono::NaiveDate::from_ymd_opt(2024, 6, 17)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap();
+    for (created_at, title) in [(three_days_ago, "Buy milk"), (long_ago, "Pay rent")] {
+        sqlx::query("UPDATE todos SET created_at = $1 WHERE title = $2")
+            .bind(created_at)
+            .bind(title)
+            .execute(&state.pool)
+            .await
+            .unwrap();
+    }
+
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&token), None).await).await;
+    assert!(body.contains("Created 3 days ago"));
+    assert!(body.contains("Created 17 Jun 2024"));
+
+    let uri = format!("/todo/{}/edit", id);
+    let body = body_text(send(&app, "GET", &uri, Some(&token), None).await).await;
+    assert!(body.contains("3 days ago"));
+}
+
+#[tokio::test]
+async fn similar_open_todos_are_confirmed_before_creating() {
+    let app = setup().await;
+    let token = register_and_login(&app, "alice@example.com").await;
+    create_todo(&app, &token, "Buy+milk").await;
+
+    let form = "title=buy+Milk!&description=test";
+    let response = send(&app, "POST", "/create", Some(&token), Some(form)).await;
+    assert!(!response.headers().contains_key("hx-trigger"));
+    let body = body_text(response).await;
+    assert!(body.contains("You already have the task \"Buy milk\""));
+    assert!(!body.contains("id=\"todo-"));
+
+    // Different enough
+    create_todo(&app, &token, "Buy+bread").await;
+
+    let form = "title=buy+Milk!&description=test&confirmed=true";
+    let response = send(&app, "POST", "/create", Some(&token), Some(form)).await;
+    assert!(hx_trigger(&response).get("todoCreated").is_some());
+}
+
+#[tokio::test]
+async fn bulk_edit_updates_all_the_selected_todos() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let token = register_and_login(&app, "alice@example.com").await;
+    let milk = create_todo(&app, &token, "Buy+milk").await;
+    let bread = create_todo(&app, &token, "Buy+bread").await;
+    let eggs = create_todo(&app, &token, "Buy+eggs").await;
+
+    let form = format!(
+        "id={}&id={}&status=true&priority=3&tags=%23Shop&workspace_id=",
+        milk, bread
+    );
+    let response = send(&app, "POST", "/todo/bulk", Some(&token), Some(&form)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        hx_trigger(&response)["toast"]["text"],
+        "2 task(s) updated!!"
+    );
+    let body = body_text(response).await;
+    assert!(body.contains(&format!("id=\"todo-{}\"", milk)));
+    assert!(!body.contains(&format!("id=\"todo-{}\"", eggs)));
+
+    let rows: Vec<(i64, bool, i64, String)> =
+        sqlx::query_as("SELECT id, status, priority, tags FROM todos ORDER BY id")
+            .fetch_all(&state.pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            (milk, true, 3, "shop".to_string()),
+            (bread, true, 3, "shop".to_string()),
+            (eggs, false, 0, String::new()),
+        ]
+    );
+
+    // Nothing selected
+    let response = send(
+        &app,
+        "POST",
+        "/todo/bulk",
+        Some(&token),
+        Some("status=true"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // One missing id leaves the others as they were
+    let form = format!("id={}&id=999&priority=1", eggs);
+    let response = send(&app, "POST", "/todo/bulk", Some(&token), Some(&form)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let priority: i64 = sqlx::query_scalar("SELECT priority FROM todos WHERE id = $1")
+        .bind(eggs)
+        .fetch_one(&state.pool)
+        .await
+        .unwrap();
+    assert_eq!(priority, 0);
+}
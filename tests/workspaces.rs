@@ -0,0 +1,111 @@
+#![cfg(feature = "sqlite")]
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, Response, StatusCode},
+    Router,
+};
+use rust_axum_askama_htmx::{app, AppState};
+use tower::ServiceExt;
+
+use common::{body_text, create_todo, register_and_login, send, setup_state};
+
+/// Sends a `GET` request in the workspace given by the `X-Workspace` header.
+async fn get_in_workspace(
+    app: &Router,
+    uri: &str,
+    token: &str,
+    workspace_id: i64,
+) -> Response<Body> {
+    let request = Request::builder()
+        .uri(uri)
+        .header("x-timezone", "UTC")
+        .header("x-workspace", workspace_id.to_string())
+        .header(header::COOKIE, format!("token={}", token))
+        .body(Body::empty())
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap()
+}
+
+/// Returns the id of the workspace created with the account of `email`.
+async fn personal_workspace(state: &AppState, email: &str) -> i64 {
+    sqlx::query_scalar(
+        "SELECT workspaces.id FROM workspaces
+        JOIN users ON users.id = workspaces.created_by
+        WHERE users.email = $1",
+    )
+    .bind(email)
+    .fetch_one(&state.pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn members_share_the_todos_of_the_workspace() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let alice = register_and_login(&app, "alice@example.com").await;
+    let bob = register_and_login(&app, "bob@example.com").await;
+    let workspace_id = personal_workspace(&state, "alice@example.com").await;
+
+    create_todo(&app, &alice, "Shared+task").await;
+
+    let response = get_in_workspace(&app, "/todo/list", &bob, workspace_id).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send(
+        &app,
+        "POST",
+        "/workspaces/members",
+        Some(&alice),
+        Some("email=bob@example.com"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let response = get_in_workspace(&app, "/todo/list", &bob, workspace_id).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body_text(response).await.contains("Shared task"));
+
+    // Bob's own workspace is still the default one
+    let body = body_text(send(&app, "GET", "/todo/list", Some(&bob), None).await).await;
+    assert!(!body.contains("Shared task"));
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_the_members() {
+    let state = setup_state().await;
+    let app = app(state.clone());
+    let alice = register_and_login(&app, "alice@example.com").await;
+    let bob = register_and_login(&app, "bob@example.com").await;
+    register_and_login(&app, "carol@example.com").await;
+    let workspace_id = personal_workspace(&state, "alice@example.com").await;
+
+    send(
+        &app,
+        "POST",
+        "/workspaces/members",
+        Some(&alice),
+        Some("email=bob@example.com"),
+    )
+    .await;
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/workspaces/members")
+        .header("x-workspace", workspace_id.to_string())
+        .header(header::COOKIE, format!("token={}", bob))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from("email=carol@example.com"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let body =
+        body_text(get_in_workspace(&app, "/settings/workspace", &alice, workspace_id).await).await;
+    assert!(body.contains("bob@example.com"));
+    assert!(!body.contains("carol@example.com"));
+}